@@ -0,0 +1,78 @@
+use std::sync::{Mutex, OnceLock};
+
+use tauri::{command, AppHandle, Emitter};
+
+// Last accent reported to the frontend, for change detection
+fn last_accent() -> &'static Mutex<Option<Option<String>>> {
+    static LAST: OnceLock<Mutex<Option<Option<String>>>> = OnceLock::new();
+    LAST.get_or_init(|| Mutex::new(None))
+}
+
+/// The OS accent color as `#rrggbb`, or null on platforms without one (Linux)
+#[command]
+pub fn system_accent_color() -> Option<String> {
+    let accent = detect();
+    *last_accent().lock().unwrap() = Some(accent.clone());
+    accent
+}
+
+/// Emit `accent-color-changed` if the accent changed since it was last read.
+/// Called when the window regains focus.
+pub fn check_for_change(app: &AppHandle) {
+    let current = detect();
+    let mut last = last_accent().lock().unwrap();
+    if last.as_ref().is_some_and(|last| *last != current) {
+        let _ = app.emit("accent-color-changed", &current);
+    }
+    *last = Some(current);
+}
+
+#[cfg(target_os = "macos")]
+fn detect() -> Option<String> {
+    use objc2_app_kit::{NSColor, NSColorSpace};
+
+    let color =
+        NSColor::controlAccentColor().colorUsingColorSpace(&NSColorSpace::sRGBColorSpace())?;
+    let channel = |value: f64| (value.clamp(0.0, 1.0) * 255.0).round() as u8;
+    Some(format!(
+        "#{:02x}{:02x}{:02x}",
+        channel(color.redComponent()),
+        channel(color.greenComponent()),
+        channel(color.blueComponent())
+    ))
+}
+
+#[cfg(target_os = "windows")]
+fn detect() -> Option<String> {
+    use windows_sys::Win32::System::Registry::{RegGetValueW, HKEY_CURRENT_USER, RRF_RT_REG_DWORD};
+
+    let wide = |s: &str| s.encode_utf16().chain(Some(0)).collect::<Vec<u16>>();
+
+    // "Accent color" in Settings > Personalization > Colors, stored as 0xAABBGGRR
+    let key = wide(r"Software\Microsoft\Windows\DWM");
+    let value = wide("AccentColor");
+    let mut color = 0u32;
+    let mut size = std::mem::size_of::<u32>() as u32;
+    let found = unsafe {
+        RegGetValueW(
+            HKEY_CURRENT_USER,
+            key.as_ptr(),
+            value.as_ptr(),
+            RRF_RT_REG_DWORD,
+            std::ptr::null_mut(),
+            &mut color as *mut u32 as *mut _,
+            &mut size,
+        )
+    } == 0;
+    if !found {
+        return None;
+    }
+
+    let [red, green, blue, _alpha] = color.to_le_bytes();
+    Some(format!("#{:02x}{:02x}{:02x}", red, green, blue))
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+fn detect() -> Option<String> {
+    None
+}
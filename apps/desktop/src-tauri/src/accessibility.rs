@@ -0,0 +1,283 @@
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, OnceLock};
+#[cfg(target_os = "linux")]
+use std::thread;
+
+use serde::Serialize;
+use tauri::{command, AppHandle, Emitter, Manager, WebviewWindow};
+
+use crate::settings;
+use crate::window::main_window;
+
+const HIGH_CONTRAST_KEY: &str = "accessibility.high_contrast";
+
+// gsettings schema and key for each setting read on Linux
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+const MOTION: (&str, &str) = ("org.gnome.desktop.interface", "enable-animations");
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+const CONTRAST: (&str, &str) = ("org.gnome.desktop.a11y.interface", "high-contrast");
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AccessibilityPrefs {
+    pub reduce_motion: bool,
+    pub reduce_transparency: bool,
+    pub high_contrast: bool,
+}
+
+// Last prefs reported to the frontend, for change detection
+fn last_prefs() -> &'static Mutex<Option<AccessibilityPrefs>> {
+    static LAST: OnceLock<Mutex<Option<AccessibilityPrefs>>> = OnceLock::new();
+    LAST.get_or_init(|| Mutex::new(None))
+}
+
+/// The OS reduce-motion, reduce-transparency and high-contrast settings.
+/// Each flag is false when the platform doesn't expose it.
+#[command]
+pub fn accessibility_prefs() -> AccessibilityPrefs {
+    let prefs = detect();
+    *last_prefs().lock().unwrap() = Some(prefs);
+    prefs
+}
+
+/// Emit `accessibility-changed` if the OS settings changed since they were last read.
+/// Called when the window regains focus, and by `watch_prefs`.
+pub fn check_for_change(app: &AppHandle) {
+    let current = detect();
+    let mut last = last_prefs().lock().unwrap();
+    let previous = last.replace(current);
+    drop(last);
+    let Some(previous) = previous.filter(|previous| *previous != current) else {
+        return;
+    };
+    let _ = app.emit("accessibility-changed", current);
+
+    // Follow the OS high-contrast setting unless the user picked one explicitly
+    let follows_os = settings::get::<bool>(app, HIGH_CONTRAST_KEY).is_none();
+    if follows_os && previous.high_contrast != current.high_contrast {
+        apply_high_contrast(app, current.high_contrast);
+        let _ = app.emit("high-contrast-changed", current.high_contrast);
+    }
+}
+
+/// Force the high-contrast palette on or off, or pass null to follow the OS setting.
+/// Emits `high-contrast-changed` with the effective value and returns it.
+///
+/// The palette is switched with a `data-contrast="high"` attribute on the root element, which
+/// sits alongside the theme's `light`/`dark` class so both compose in CSS.
+#[command]
+pub fn set_high_contrast(app: AppHandle, enabled: Option<bool>) -> Result<bool, String> {
+    match enabled {
+        Some(enabled) => settings::set(&app, HIGH_CONTRAST_KEY, enabled)?,
+        None => settings::delete(&app, HIGH_CONTRAST_KEY)?,
+    }
+    let effective = high_contrast_enabled(&app);
+    apply_high_contrast(&app, effective);
+    let _ = app.emit("high-contrast-changed", effective);
+    Ok(effective)
+}
+
+/// The user's high-contrast choice, falling back to the OS setting
+pub fn high_contrast_enabled(app: &AppHandle) -> bool {
+    settings::get(app, HIGH_CONTRAST_KEY).unwrap_or_else(|| detect().high_contrast)
+}
+
+/// Re-apply high contrast after the page reloads
+pub fn restore_high_contrast(window: &WebviewWindow) {
+    if high_contrast_enabled(window.app_handle()) {
+        set_contrast_attribute(window, true);
+    }
+}
+
+fn apply_high_contrast(app: &AppHandle, enabled: bool) {
+    if let Ok(window) = main_window(app) {
+        set_contrast_attribute(&window, enabled);
+    }
+
+    #[cfg(desktop)]
+    {
+        let item = crate::menu::find_item(app, "high_contrast");
+        if let Some(item) = item.as_ref().and_then(|item| item.as_check_menuitem()) {
+            let _ = item.set_checked(enabled);
+        }
+    }
+}
+
+fn set_contrast_attribute(window: &WebviewWindow, enabled: bool) {
+    let script = if enabled {
+        r#"document.documentElement.dataset.contrast = "high""#
+    } else {
+        "delete document.documentElement.dataset.contrast"
+    };
+    if let Err(e) = window.eval(script) {
+        log::warn!("Failed to apply high contrast: {}", e);
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn detect() -> AccessibilityPrefs {
+    let workspace = objc2_app_kit::NSWorkspace::sharedWorkspace();
+    AccessibilityPrefs {
+        reduce_motion: workspace.accessibilityDisplayShouldReduceMotion(),
+        reduce_transparency: workspace.accessibilityDisplayShouldReduceTransparency(),
+        high_contrast: workspace.accessibilityDisplayShouldIncreaseContrast(),
+    }
+}
+
+#[cfg(target_os = "windows")]
+fn detect() -> AccessibilityPrefs {
+    use windows_sys::Win32::System::Registry::{RegGetValueW, HKEY_CURRENT_USER, RRF_RT_REG_DWORD};
+    use windows_sys::Win32::UI::Accessibility::{HCF_HIGHCONTRASTON, HIGHCONTRASTW};
+    use windows_sys::Win32::UI::WindowsAndMessaging::{
+        SystemParametersInfoW, SPI_GETCLIENTAREAANIMATION, SPI_GETHIGHCONTRAST,
+    };
+
+    let wide = |s: &str| s.encode_utf16().chain(Some(0)).collect::<Vec<u16>>();
+
+    let mut animations = 1i32;
+    let animations_ok = unsafe {
+        SystemParametersInfoW(
+            SPI_GETCLIENTAREAANIMATION,
+            0,
+            &mut animations as *mut i32 as *mut _,
+            0,
+        )
+    } != 0;
+
+    let mut contrast = HIGHCONTRASTW {
+        cbSize: std::mem::size_of::<HIGHCONTRASTW>() as u32,
+        ..Default::default()
+    };
+    let contrast_ok = unsafe {
+        SystemParametersInfoW(
+            SPI_GETHIGHCONTRAST,
+            contrast.cbSize,
+            &mut contrast as *mut HIGHCONTRASTW as *mut _,
+            0,
+        )
+    } != 0;
+
+    // "Transparency effects" in Settings > Personalization > Colors
+    let key = wide(r"Software\Microsoft\Windows\CurrentVersion\Themes\Personalize");
+    let value = wide("EnableTransparency");
+    let mut transparency = 1u32;
+    let mut size = std::mem::size_of::<u32>() as u32;
+    let transparency_ok = unsafe {
+        RegGetValueW(
+            HKEY_CURRENT_USER,
+            key.as_ptr(),
+            value.as_ptr(),
+            RRF_RT_REG_DWORD,
+            std::ptr::null_mut(),
+            &mut transparency as *mut u32 as *mut _,
+            &mut size,
+        )
+    } == 0;
+
+    AccessibilityPrefs {
+        reduce_motion: animations_ok && animations == 0,
+        reduce_transparency: transparency_ok && transparency == 0,
+        high_contrast: contrast_ok && contrast.dwFlags & HCF_HIGHCONTRASTON != 0,
+    }
+}
+
+// The gsettings values as last read, which `watch_prefs` keeps current while the desktop
+// portal reports changes; without it they're read again each time
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+fn cached_prefs() -> &'static Mutex<Option<AccessibilityPrefs>> {
+    static CACHED: OnceLock<Mutex<Option<AccessibilityPrefs>>> = OnceLock::new();
+    CACHED.get_or_init(|| Mutex::new(None))
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+static WATCHING: AtomicBool = AtomicBool::new(false);
+
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+fn detect() -> AccessibilityPrefs {
+    let mut cached = cached_prefs().lock().unwrap();
+    match *cached {
+        Some(prefs) if WATCHING.load(Ordering::SeqCst) => prefs,
+        _ => *cached.insert(read_gsettings()),
+    }
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+fn read_gsettings() -> AccessibilityPrefs {
+    // GNOME and most GTK desktops expose these through gsettings
+    let gsetting = |schema: &str, key: &str| {
+        std::process::Command::new("gsettings")
+            .args(["get", schema, key])
+            .output()
+            .ok()
+            .filter(|output| output.status.success())
+            .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
+    };
+    AccessibilityPrefs {
+        reduce_motion: gsetting(MOTION.0, MOTION.1).is_some_and(|v| v == "false"),
+        reduce_transparency: false,
+        high_contrast: gsetting(CONTRAST.0, CONTRAST.1).is_some_and(|v| v == "true"),
+    }
+}
+
+/// Pick up OS setting changes as they happen, so the focus handler doesn't have to run
+/// gsettings every time the window is focused
+#[cfg(target_os = "linux")]
+pub fn watch_prefs(app: &AppHandle) {
+    let app = app.clone();
+    thread::spawn(move || {
+        if let Err(e) = watch_portal(&app) {
+            log::info!("Not watching for accessibility changes: {}", e);
+        }
+        WATCHING.store(false, Ordering::SeqCst);
+    });
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn watch_prefs(_app: &AppHandle) {}
+
+// The desktop portal relays gsettings changes on GNOME and other GTK desktops
+#[cfg(target_os = "linux")]
+fn watch_portal(app: &AppHandle) -> zbus::Result<()> {
+    use zbus::blocking::{Connection, MessageIterator};
+    use zbus::message::Type;
+    use zbus::zvariant::OwnedValue;
+    use zbus::MatchRule;
+
+    const PORTAL: &str = "org.freedesktop.portal.Desktop";
+    const SETTINGS: &str = "org.freedesktop.portal.Settings";
+
+    let session = Connection::session()?;
+    let rule = MatchRule::builder()
+        .msg_type(Type::Signal)
+        .sender(PORTAL)?
+        .interface(SETTINGS)?
+        .member("SettingChanged")?
+        .build();
+    let signals = MessageIterator::for_match_rule(rule, &session, None)?;
+    // Subscribing works with no portal running, so make sure it's there to send anything
+    session.call_method(
+        Some(PORTAL),
+        "/org/freedesktop/portal/desktop",
+        Some(SETTINGS),
+        "Read",
+        &CONTRAST,
+    )?;
+    // Read after subscribing so a change in between isn't missed
+    *cached_prefs().lock().unwrap() = Some(read_gsettings());
+    WATCHING.store(true, Ordering::SeqCst);
+
+    for message in signals {
+        let message = message?;
+        let Ok((namespace, key, _)) = message.body().deserialize::<(String, String, OwnedValue)>()
+        else {
+            continue;
+        };
+        let setting = (namespace.as_str(), key.as_str());
+        if setting == MOTION || setting == CONTRAST {
+            *cached_prefs().lock().unwrap() = Some(read_gsettings());
+            check_for_change(app);
+        }
+    }
+    Ok(())
+}
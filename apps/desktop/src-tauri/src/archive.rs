@@ -0,0 +1,526 @@
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+
+use serde::Serialize;
+use tauri::{command, AppHandle, Emitter};
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+/// Most bytes of files one archive may hold, before compression
+const MAX_TOTAL_SIZE: u64 = 10 * 1024 * 1024 * 1024;
+
+/// Most entries extracted from one archive
+const MAX_ENTRIES: usize = 10_000;
+
+/// Files whose contents are already compressed are stored as they are
+const STORED_EXTENSIONS: &[&str] = &[
+    "7z", "avif", "gif", "gz", "heic", "jpeg", "jpg", "m4a", "mkv", "mov", "mp3", "mp4", "ogg",
+    "opus", "png", "rar", "webm", "webp", "zip",
+];
+
+// Cancel flags of the archives being written, by output path
+fn running() -> &'static Mutex<HashMap<PathBuf, Arc<AtomicBool>>> {
+    static RUNNING: OnceLock<Mutex<HashMap<PathBuf, Arc<AtomicBool>>>> = OnceLock::new();
+    RUNNING.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ZipProgress<'a> {
+    output: &'a Path,
+    written: u64,
+    total: u64,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ExtractProgress<'a> {
+    path: &'a Path,
+    extracted: u64,
+    /// None for tar.gz, whose size is only known once it's all read
+    total: Option<u64>,
+}
+
+// A file to add and its name in the archive
+struct Entry {
+    source: PathBuf,
+    name: String,
+    size: u64,
+}
+
+/// Write files and folders (with everything in them) into a zip at `output`, e.g. to
+/// export a channel's attachments, emitting `zip-progress` as it goes. Files that would
+/// share a name get ` (1)`, ` (2)` and so on. Symlinks are skipped. Fails without
+/// writing anything if the files add up to more than 10 GB. Returns `output`.
+///
+/// `cancel_zip` with the same output stops it; nothing is left behind.
+#[command]
+pub async fn zip_paths(
+    app: AppHandle,
+    paths: Vec<PathBuf>,
+    output: PathBuf,
+) -> Result<PathBuf, String> {
+    let cancel = Arc::new(AtomicBool::new(false));
+    {
+        let mut running = running().lock().unwrap();
+        if running.contains_key(&output) {
+            return Err(format!("{} is already being written", output.display()));
+        }
+        running.insert(output.clone(), cancel.clone());
+    }
+
+    let result = {
+        let output = output.clone();
+        tauri::async_runtime::spawn_blocking(move || write_zip(&app, &paths, &output, &cancel))
+            .await
+            .map_err(|e| e.to_string())
+            .and_then(|result| result)
+    };
+    running().lock().unwrap().remove(&output);
+    result.map(|_| output)
+}
+
+/// Stop a running `zip_paths`. Returns false if nothing is being written to `output`.
+#[command]
+pub fn cancel_zip(output: PathBuf) -> bool {
+    match running().lock().unwrap().get(&output) {
+        Some(cancel) => {
+            cancel.store(true, Ordering::Relaxed);
+            true
+        }
+        None => false,
+    }
+}
+
+/// Extract a zip or tar.gz (told apart by their contents) into `dest`, emitting
+/// `extract-progress`, and return the files extracted. Entries that would land outside
+/// `dest`, links and existing files are refused, as are archives of more than 10,000
+/// entries or 10 GB; nothing is left behind when extraction fails.
+#[command]
+pub async fn extract_archive(
+    app: AppHandle,
+    path: PathBuf,
+    dest: PathBuf,
+) -> Result<Vec<PathBuf>, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let mut magic = [0; 4];
+        File::open(&path)
+            .and_then(|mut file| file.read_exact(&mut magic))
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        fs::create_dir_all(&dest)
+            .map_err(|e| format!("Failed to create {}: {}", dest.display(), e))?;
+
+        let mut extractor = Extractor {
+            app: &app,
+            archive: &path,
+            dest: &dest,
+            total: None,
+            extracted: 0,
+            entries: 0,
+            created: Vec::new(),
+        };
+        let result = match magic {
+            [b'P', b'K', 3, 4] => extract_zip(&mut extractor),
+            [0x1f, 0x8b, _, _] => extract_tar_gz(&mut extractor),
+            _ => Err(format!("{} is not a zip or tar.gz archive", path.display())),
+        };
+        match result {
+            Ok(()) => Ok(extractor.files()),
+            Err(e) => {
+                extractor.remove_created();
+                Err(e)
+            }
+        }
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+fn extract_zip(extractor: &mut Extractor) -> Result<(), String> {
+    let file = File::open(extractor.archive).map_err(|e| e.to_string())?;
+    let mut zip =
+        ZipArchive::new(BufReader::new(file)).map_err(|e| format!("Invalid zip archive: {}", e))?;
+    // The sizes entries claim are only trusted for rejecting early; writing checks too
+    let mut claimed = 0u64;
+    for index in 0..zip.len() {
+        let entry = zip.by_index_raw(index).map_err(|e| e.to_string())?;
+        claimed = claimed.saturating_add(entry.size());
+    }
+    check_limits(zip.len(), claimed)?;
+    extractor.total = Some(claimed);
+
+    for index in 0..zip.len() {
+        let mut entry = zip.by_index(index).map_err(|e| e.to_string())?;
+        let name = PathBuf::from(entry.name());
+        if entry.is_dir() {
+            extractor.dir(&name)?;
+        } else if entry.is_symlink() {
+            log::warn!("Skipping link {} in archive", name.display());
+        } else {
+            extractor.file(&name, &mut entry)?;
+        }
+    }
+    Ok(())
+}
+
+fn extract_tar_gz(extractor: &mut Extractor) -> Result<(), String> {
+    let file = File::open(extractor.archive).map_err(|e| e.to_string())?;
+    let mut tar = tar::Archive::new(flate2::read::GzDecoder::new(BufReader::new(file)));
+    let entries = tar
+        .entries()
+        .map_err(|e| format!("Invalid tar.gz archive: {}", e))?;
+    for entry in entries {
+        let mut entry = entry.map_err(|e| format!("Invalid tar.gz archive: {}", e))?;
+        let name = entry
+            .path()
+            .map_err(|e| format!("Invalid tar.gz archive: {}", e))?
+            .into_owned();
+        match entry.header().entry_type() {
+            tar::EntryType::Directory => extractor.dir(&name)?,
+            tar::EntryType::Regular | tar::EntryType::Continuous => {
+                extractor.file(&name, &mut entry)?
+            }
+            // Links could point anywhere, and the rest aren't files the user wants
+            other => log::warn!("Skipping {:?} entry {} in archive", other, name.display()),
+        }
+    }
+    Ok(())
+}
+
+// Writes entries under `dest`, keeping count against the limits and of what it created
+// so a failed extraction can be undone
+struct Extractor<'a> {
+    app: &'a AppHandle,
+    archive: &'a Path,
+    dest: &'a Path,
+    total: Option<u64>,
+    extracted: u64,
+    entries: usize,
+    /// Files and folders in the order they were created, each with whether it's a file
+    created: Vec<(PathBuf, bool)>,
+}
+
+impl Extractor<'_> {
+    fn dir(&mut self, name: &Path) -> Result<(), String> {
+        self.entries += 1;
+        check_limits(self.entries, self.extracted)?;
+        let path = resolve(self.dest, name)?;
+        self.create_dirs(&path)
+    }
+
+    fn file(&mut self, name: &Path, contents: &mut dyn Read) -> Result<(), String> {
+        self.entries += 1;
+        check_limits(self.entries, self.extracted)?;
+        let path = resolve(self.dest, name)?;
+        if let Some(parent) = path.parent() {
+            self.create_dirs(parent)?;
+        }
+        let file = File::create_new(&path)
+            .map_err(|e| format!("Failed to create {}: {}", path.display(), e))?;
+        self.created.push((path.clone(), true));
+
+        let mut file = BufWriter::new(file);
+        let mut buffer = vec![0; 1024 * 1024];
+        loop {
+            let read = contents
+                .read(&mut buffer)
+                .map_err(|e| format!("Failed to extract {}: {}", name.display(), e))?;
+            if read == 0 {
+                break;
+            }
+            self.extracted += read as u64;
+            check_limits(self.entries, self.extracted)?;
+            file.write_all(&buffer[..read])
+                .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+            let progress = ExtractProgress {
+                path: self.archive,
+                extracted: self.extracted,
+                total: self.total,
+            };
+            let _ = self.app.emit("extract-progress", progress);
+        }
+        file.flush()
+            .map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+    }
+
+    fn create_dirs(&mut self, path: &Path) -> Result<(), String> {
+        let missing: Vec<_> = path
+            .ancestors()
+            .take_while(|ancestor| !ancestor.exists())
+            .map(Path::to_path_buf)
+            .collect();
+        for dir in missing.into_iter().rev() {
+            fs::create_dir(&dir)
+                .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+            self.created.push((dir, false));
+        }
+        Ok(())
+    }
+
+    fn files(self) -> Vec<PathBuf> {
+        self.created
+            .into_iter()
+            .filter(|(_, is_file)| *is_file)
+            .map(|(path, _)| path)
+            .collect()
+    }
+
+    fn remove_created(&self) {
+        for (path, is_file) in self.created.iter().rev() {
+            let _ = match is_file {
+                true => fs::remove_file(path),
+                false => fs::remove_dir(path),
+            };
+        }
+    }
+}
+
+fn check_limits(entries: usize, size: u64) -> Result<(), String> {
+    if entries > MAX_ENTRIES {
+        return Err(format!("The archive has more than {} entries", MAX_ENTRIES));
+    }
+    if size > MAX_TOTAL_SIZE {
+        return Err(format!(
+            "The archive extracts to more than {} GB",
+            MAX_TOTAL_SIZE / 1024 / 1024 / 1024
+        ));
+    }
+    Ok(())
+}
+
+// Only plain relative names are accepted, so nothing can escape `dest`
+fn resolve(dest: &Path, name: &Path) -> Result<PathBuf, String> {
+    let mut path = dest.to_path_buf();
+    for component in name.components() {
+        match component {
+            Component::Normal(part) => path.push(part),
+            Component::CurDir => {}
+            _ => {
+                return Err(format!(
+                    "The archive has an entry outside the folder: {}",
+                    name.display()
+                ))
+            }
+        }
+    }
+    if path == dest {
+        return Err("The archive has an entry without a name".to_string());
+    }
+    Ok(path)
+}
+
+fn write_zip(
+    app: &AppHandle,
+    paths: &[PathBuf],
+    output: &Path,
+    cancel: &AtomicBool,
+) -> Result<(), String> {
+    let mut entries = Vec::new();
+    let mut names = HashSet::new();
+    for path in paths {
+        let name = file_name(path);
+        collect(path, &name, &mut entries, &mut names)?;
+    }
+    let total: u64 = entries.iter().map(|entry| entry.size).sum();
+    if total > MAX_TOTAL_SIZE {
+        return Err(format!(
+            "The files add up to more than the {} GB an archive can hold",
+            MAX_TOTAL_SIZE / 1024 / 1024 / 1024
+        ));
+    }
+
+    // Written next to the output and moved into place once complete
+    let mut partial = output.as_os_str().to_owned();
+    partial.push(".part");
+    let partial = PathBuf::from(partial);
+    let result = write_entries(app, &entries, &partial, output, total, cancel)
+        .and_then(|_| fs::rename(&partial, output).map_err(|e| e.to_string()));
+    if result.is_err() {
+        let _ = fs::remove_file(&partial);
+    }
+    result
+}
+
+fn write_entries(
+    app: &AppHandle,
+    entries: &[Entry],
+    partial: &Path,
+    output: &Path,
+    total: u64,
+    cancel: &AtomicBool,
+) -> Result<(), String> {
+    let file = File::create(partial)
+        .map_err(|e| format!("Failed to create {}: {}", output.display(), e))?;
+    let mut zip = ZipWriter::new(BufWriter::new(file));
+    let mut written = 0;
+    let mut buffer = vec![0; 1024 * 1024];
+    for entry in entries {
+        let method = match stored(&entry.source) {
+            true => CompressionMethod::Stored,
+            false => CompressionMethod::Deflated,
+        };
+        let options = SimpleFileOptions::default()
+            .compression_method(method)
+            .large_file(entry.size >= u32::MAX as u64);
+        zip.start_file(entry.name.as_str(), options)
+            .map_err(|e| e.to_string())?;
+
+        let mut source = File::open(&entry.source)
+            .map_err(|e| format!("Failed to open {}: {}", entry.source.display(), e))?;
+        loop {
+            if cancel.load(Ordering::Relaxed) {
+                return Err("cancelled".to_string());
+            }
+            let read = source
+                .read(&mut buffer)
+                .map_err(|e| format!("Failed to read {}: {}", entry.source.display(), e))?;
+            if read == 0 {
+                break;
+            }
+            zip.write_all(&buffer[..read]).map_err(|e| e.to_string())?;
+            written += read as u64;
+            let progress = ZipProgress {
+                output,
+                written,
+                total,
+            };
+            let _ = app.emit("zip-progress", progress);
+        }
+    }
+    zip.finish()
+        .and_then(|mut writer| writer.flush().map_err(Into::into))
+        .map_err(|e| e.to_string())
+}
+
+// Adds a file, or a folder's files under its name, renaming on clashes
+fn collect(
+    path: &Path,
+    name: &str,
+    entries: &mut Vec<Entry>,
+    names: &mut HashSet<String>,
+) -> Result<(), String> {
+    let metadata = fs::symlink_metadata(path)
+        .map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+    if metadata.is_dir() {
+        let mut children: Vec<_> = fs::read_dir(path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?
+            .flatten()
+            .map(|child| child.path())
+            .collect();
+        children.sort();
+        let folder = unique_name(name, names, true);
+        for child in children {
+            let child_name = format!("{}/{}", folder, file_name(&child));
+            collect(&child, &child_name, entries, names)?;
+        }
+    } else if metadata.is_file() {
+        entries.push(Entry {
+            source: path.to_path_buf(),
+            name: unique_name(name, names, false),
+            size: metadata.len(),
+        });
+    }
+    Ok(())
+}
+
+// Names compare case-insensitively, since the archive may be extracted on a
+// case-insensitive file system
+fn unique_name(name: &str, names: &mut HashSet<String>, folder: bool) -> String {
+    let (stem, extension) = match name.rfind('.') {
+        Some(dot) if !folder && dot > name.rfind('/').map_or(0, |slash| slash + 1) => {
+            name.split_at(dot)
+        }
+        _ => (name, ""),
+    };
+    let mut candidate = name.to_string();
+    let mut copy = 1;
+    while !names.insert(candidate.to_lowercase()) {
+        candidate = format!("{} ({}){}", stem, copy, extension);
+        copy += 1;
+    }
+    candidate
+}
+
+// Names that aren't valid Unicode are kept as close as possible
+fn file_name(path: &Path) -> String {
+    match path.file_name() {
+        Some(name) => name.to_string_lossy().into_owned(),
+        None => "file".to_string(),
+    }
+}
+
+fn stored(path: &Path) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| {
+            STORED_EXTENSIONS.contains(&extension.to_ascii_lowercase().as_str())
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_resolve_inside_dest() {
+        let dest = Path::new("/tmp/dest");
+        assert_eq!(
+            resolve(dest, Path::new("a/b.txt")),
+            Ok(dest.join("a").join("b.txt"))
+        );
+        assert_eq!(resolve(dest, Path::new("./a")), Ok(dest.join("a")));
+        // Backslashes are only separators on Windows; elsewhere they're part of the name
+        #[cfg(not(windows))]
+        assert_eq!(
+            resolve(dest, Path::new("..\\escape")),
+            Ok(dest.join("..\\escape"))
+        );
+    }
+
+    #[test]
+    fn names_escaping_dest_are_refused() {
+        let dest = Path::new("/tmp/dest");
+        for name in [
+            "../escape",
+            "a/../../escape",
+            // A folder entry followed by a name going back up through it
+            "link/../../escape",
+            "a/b/../../../escape",
+            "/etc/passwd",
+            "/",
+            "",
+            ".",
+        ] {
+            assert!(resolve(dest, Path::new(name)).is_err(), "{:?}", name);
+        }
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn windows_prefixes_are_refused() {
+        let dest = Path::new(r"C:\dest");
+        for name in [
+            r"..\escape",
+            r"a\..\..\escape",
+            r"C:\Windows\win.ini",
+            r"C:escape",
+            r"\Windows\win.ini",
+            r"\\server\share\file.txt",
+            r"\\?\C:\file.txt",
+        ] {
+            assert!(resolve(dest, Path::new(name)).is_err(), "{:?}", name);
+        }
+    }
+
+    #[test]
+    fn limits_allow_exactly_the_maximum() {
+        assert!(check_limits(MAX_ENTRIES, MAX_TOTAL_SIZE).is_ok());
+        assert!(check_limits(0, 0).is_ok());
+        assert!(check_limits(MAX_ENTRIES + 1, 0).is_err());
+        assert!(check_limits(0, MAX_TOTAL_SIZE + 1).is_err());
+    }
+}
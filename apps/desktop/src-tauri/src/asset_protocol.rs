@@ -0,0 +1,422 @@
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Component, Path, PathBuf};
+
+use tauri::http::{header, Request, Response, StatusCode};
+use tauri::AppHandle;
+
+use crate::cache;
+
+/// Serves the attachment cache: `hazel-asset://localhost/<path>` on macOS and Linux,
+/// `http://hazel-asset.localhost/<path>` on Windows
+pub const SCHEME: &str = "hazel-asset";
+
+/// Cache subdirectory the protocol serves
+const ROOT: &str = "attachments";
+
+/// Most of a file sent in one response, since bodies are held in memory whole. Players
+/// ask for the rest with further range requests.
+const MAX_CHUNK: u64 = 4 * 1024 * 1024;
+
+/// Answer a `hazel-asset` request with the file it names, or part of it for range requests
+/// and files over `MAX_CHUNK`
+pub fn handle(app: &AppHandle, request: &Request<Vec<u8>>) -> Response<Vec<u8>> {
+    let Ok(root) = cache::dir(app, ROOT) else {
+        return error(StatusCode::INTERNAL_SERVER_ERROR);
+    };
+    let path = match resolve(&root, request.uri().path()) {
+        Ok(path) => path,
+        Err(status) => return error(status),
+    };
+    let range = request
+        .headers()
+        .get(header::RANGE)
+        .and_then(|value| value.to_str().ok());
+    serve(&path, range).unwrap_or_else(|_| error(StatusCode::INTERNAL_SERVER_ERROR))
+}
+
+// Map the URL path into the root, refusing anything that could step outside it
+fn resolve(root: &Path, url_path: &str) -> Result<PathBuf, StatusCode> {
+    let decoded = urlencoding::decode(url_path).map_err(|_| StatusCode::BAD_REQUEST)?;
+    let relative = Path::new(decoded.trim_start_matches('/'));
+    if !relative
+        .components()
+        .all(|component| matches!(component, Component::Normal(_)))
+    {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    // Symlinks inside the cache could still point elsewhere
+    let root = root.canonicalize().map_err(|_| StatusCode::NOT_FOUND)?;
+    let path = root
+        .join(relative)
+        .canonicalize()
+        .map_err(|_| StatusCode::NOT_FOUND)?;
+    if !path.starts_with(&root) {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    if !path.is_file() {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    Ok(path)
+}
+
+// What to send back for a request's `Range` header
+#[derive(Debug, PartialEq)]
+enum ByteRange {
+    Full,
+    /// Inclusive start and end offsets
+    Partial(u64, u64),
+    Unsatisfiable,
+}
+
+fn serve(path: &Path, range: Option<&str>) -> std::io::Result<Response<Vec<u8>>> {
+    let mut file = File::open(path)?;
+    let size = file.metadata()?.len();
+    let builder = Response::builder()
+        .header(header::CONTENT_TYPE, mime_type(path))
+        .header(header::ACCEPT_RANGES, "bytes");
+
+    // Anything bigger than a chunk goes out a chunk at a time, as RFC 9110 lets a server
+    // send less than the range asked for
+    let (start, end) = match range.map_or(ByteRange::Full, |range| parse_range(range, size)) {
+        ByteRange::Full if size <= MAX_CHUNK => {
+            let mut body = Vec::with_capacity(size as usize);
+            file.read_to_end(&mut body)?;
+            return Ok(builder
+                .status(StatusCode::OK)
+                .header(header::CONTENT_LENGTH, body.len())
+                .body(body)
+                .unwrap());
+        }
+        ByteRange::Full => (0, size - 1),
+        ByteRange::Partial(start, end) => (start, end),
+        ByteRange::Unsatisfiable => {
+            return Ok(builder
+                .status(StatusCode::RANGE_NOT_SATISFIABLE)
+                .header(header::CONTENT_RANGE, format!("bytes */{}", size))
+                .header(header::CONTENT_LENGTH, 0)
+                .body(Vec::new())
+                .unwrap())
+        }
+    };
+    let end = end.min(start + MAX_CHUNK - 1);
+    let mut body = vec![0; (end - start + 1) as usize];
+    file.seek(SeekFrom::Start(start))?;
+    file.read_exact(&mut body)?;
+    Ok(builder
+        .status(StatusCode::PARTIAL_CONTENT)
+        .header(
+            header::CONTENT_RANGE,
+            format!("bytes {}-{}/{}", start, end, size),
+        )
+        .header(header::CONTENT_LENGTH, body.len())
+        .body(body)
+        .unwrap())
+}
+
+// A single `bytes=<start>-[<end>]` or `bytes=-<suffix length>` range, clamped to the file.
+// Multiple ranges and headers that don't parse are treated as no range at all, which
+// RFC 9110 allows; ranges starting past the end can't be served at all.
+fn parse_range(range: &str, size: u64) -> ByteRange {
+    let Some(spec) = range.trim().strip_prefix("bytes=") else {
+        return ByteRange::Full;
+    };
+    if spec.contains(',') {
+        return ByteRange::Full;
+    }
+    let Some((start, end)) = spec.split_once('-') else {
+        return ByteRange::Full;
+    };
+
+    let (start, end) = match (start.trim(), end.trim()) {
+        ("", "") => return ByteRange::Full,
+        ("", suffix) => match suffix.parse::<u64>() {
+            Ok(0) => return ByteRange::Unsatisfiable,
+            Ok(suffix) => (size.saturating_sub(suffix), None),
+            Err(_) => return ByteRange::Full,
+        },
+        (start, end) => {
+            let Ok(start) = start.parse::<u64>() else {
+                return ByteRange::Full;
+            };
+            let end = match end {
+                "" => None,
+                end => match end.parse::<u64>() {
+                    Ok(end) if end >= start => Some(end),
+                    _ => return ByteRange::Full,
+                },
+            };
+            (start, end)
+        }
+    };
+
+    if start >= size {
+        return ByteRange::Unsatisfiable;
+    }
+    let last = size - 1;
+    ByteRange::Partial(start, end.map_or(last, |end| end.min(last)))
+}
+
+fn mime_type(path: &Path) -> &'static str {
+    let extension = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(str::to_ascii_lowercase);
+    match extension.as_deref() {
+        Some("png") => "image/png",
+        Some("jpg" | "jpeg") => "image/jpeg",
+        Some("gif") => "image/gif",
+        Some("webp") => "image/webp",
+        Some("svg") => "image/svg+xml",
+        Some("avif") => "image/avif",
+        Some("mp4" | "m4v") => "video/mp4",
+        Some("webm") => "video/webm",
+        Some("mov") => "video/quicktime",
+        Some("mp3") => "audio/mpeg",
+        Some("m4a") => "audio/mp4",
+        Some("ogg" | "oga") => "audio/ogg",
+        Some("wav") => "audio/wav",
+        Some("flac") => "audio/flac",
+        Some("pdf") => "application/pdf",
+        Some("txt" | "log") => "text/plain; charset=utf-8",
+        Some("json") => "application/json",
+        _ => "application/octet-stream",
+    }
+}
+
+fn error(status: StatusCode) -> Response<Vec<u8>> {
+    Response::builder().status(status).body(Vec::new()).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    // A fresh cache root holding `file.txt` and `nested/clip.mp4`, with `secret.txt` beside it
+    fn fixture(name: &str) -> (PathBuf, PathBuf) {
+        let dir = std::env::temp_dir().join(format!(
+            "hazel-asset-protocol-{}-{}",
+            std::process::id(),
+            name
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        let root = dir.join("attachments");
+        fs::create_dir_all(root.join("nested")).unwrap();
+        fs::write(root.join("file.txt"), "0123456789").unwrap();
+        fs::write(root.join("nested").join("clip.mp4"), "mp4").unwrap();
+        fs::write(dir.join("secret.txt"), "secret").unwrap();
+        (dir, root)
+    }
+
+    #[test]
+    fn files_in_the_root_resolve() {
+        let (dir, root) = fixture("resolve");
+        let root_canonical = root.canonicalize().unwrap();
+        assert_eq!(
+            resolve(&root, "/file.txt"),
+            Ok(root_canonical.join("file.txt"))
+        );
+        assert_eq!(
+            resolve(&root, "/nested%2Fclip.mp4"),
+            Ok(root_canonical.join("nested").join("clip.mp4"))
+        );
+        assert_eq!(resolve(&root, "/missing.txt"), Err(StatusCode::NOT_FOUND));
+        assert_eq!(resolve(&root, "/nested"), Err(StatusCode::NOT_FOUND));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn paths_outside_the_root_are_refused() {
+        let (dir, root) = fixture("traversal");
+        for path in [
+            "/../secret.txt",
+            "/nested/../../secret.txt",
+            // Encoded dots and separators
+            "/%2e%2e/secret.txt",
+            "/..%2Fsecret.txt",
+            "/nested%2F..%2F..%2Fsecret.txt",
+            "/%2E%2E%2Fsecret.txt",
+        ] {
+            assert_eq!(resolve(&root, path), Err(StatusCode::FORBIDDEN), "{}", path);
+        }
+        // Leading slashes are dropped, so an absolute path stays inside the root
+        let absolute = format!("/{}", dir.join("secret.txt").display());
+        assert_eq!(resolve(&root, &absolute), Err(StatusCode::NOT_FOUND));
+        assert_eq!(resolve(&root, "/%FF"), Err(StatusCode::BAD_REQUEST));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn windows_paths_outside_the_root_are_refused() {
+        let (dir, root) = fixture("windows-traversal");
+        for path in [
+            "/..%5Csecret.txt",
+            "/nested%5C..%5C..%5Csecret.txt",
+            "/C:%5CWindows%5Cwin.ini",
+            "/C:/Windows/win.ini",
+            "/%5C%5Cserver%5Cshare%5Cfile.txt",
+        ] {
+            assert_eq!(resolve(&root, path), Err(StatusCode::FORBIDDEN), "{}", path);
+        }
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinks_out_of_the_root_are_refused() {
+        let (dir, root) = fixture("symlink");
+        std::os::unix::fs::symlink(dir.join("secret.txt"), root.join("link.txt")).unwrap();
+        assert_eq!(resolve(&root, "/link.txt"), Err(StatusCode::FORBIDDEN));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn whole_and_partial_responses() {
+        let (dir, root) = fixture("serve");
+        let path = root.join("file.txt");
+        let header = |response: &Response<Vec<u8>>, name| {
+            response
+                .headers()
+                .get(name)
+                .map(|value| value.to_str().unwrap().to_string())
+        };
+
+        let full = serve(&path, None).unwrap();
+        assert_eq!(full.status(), StatusCode::OK);
+        assert_eq!(full.body(), b"0123456789");
+        assert_eq!(header(&full, header::CONTENT_LENGTH).as_deref(), Some("10"));
+        assert_eq!(
+            header(&full, header::ACCEPT_RANGES).as_deref(),
+            Some("bytes")
+        );
+        assert_eq!(
+            header(&full, header::CONTENT_TYPE).as_deref(),
+            Some("text/plain; charset=utf-8")
+        );
+
+        let partial = serve(&path, Some("bytes=2-5")).unwrap();
+        assert_eq!(partial.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(partial.body(), b"2345");
+        assert_eq!(
+            header(&partial, header::CONTENT_RANGE).as_deref(),
+            Some("bytes 2-5/10")
+        );
+        assert_eq!(
+            header(&partial, header::CONTENT_LENGTH).as_deref(),
+            Some("4")
+        );
+
+        let suffix = serve(&path, Some("bytes=-3")).unwrap();
+        assert_eq!(suffix.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(suffix.body(), b"789");
+
+        let unsatisfiable = serve(&path, Some("bytes=10-")).unwrap();
+        assert_eq!(unsatisfiable.status(), StatusCode::RANGE_NOT_SATISFIABLE);
+        assert!(unsatisfiable.body().is_empty());
+        assert_eq!(
+            header(&unsatisfiable, header::CONTENT_RANGE).as_deref(),
+            Some("bytes */10")
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn large_files_are_sent_a_chunk_at_a_time() {
+        let (dir, root) = fixture("chunks");
+        let path = root.join("large.mp4");
+        let size = MAX_CHUNK + 10;
+        fs::write(&path, vec![7; size as usize]).unwrap();
+        let content_range = |response: &Response<Vec<u8>>| {
+            response.headers()[header::CONTENT_RANGE]
+                .to_str()
+                .unwrap()
+                .to_string()
+        };
+
+        for range in [
+            None,
+            Some("bytes=0-"),
+            Some(&*format!("bytes=0-{}", size - 1)),
+        ] {
+            let response = serve(&path, range).unwrap();
+            assert_eq!(
+                response.status(),
+                StatusCode::PARTIAL_CONTENT,
+                "{:?}",
+                range
+            );
+            assert_eq!(response.body().len() as u64, MAX_CHUNK);
+            assert_eq!(
+                content_range(&response),
+                format!("bytes 0-{}/{}", MAX_CHUNK - 1, size)
+            );
+        }
+        let rest = serve(&path, Some(&format!("bytes={}-", MAX_CHUNK))).unwrap();
+        assert_eq!(rest.body().len(), 10);
+        assert_eq!(
+            content_range(&rest),
+            format!("bytes {}-{}/{}", MAX_CHUNK, size - 1, size)
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn open_ended_and_suffix_ranges() {
+        assert_eq!(parse_range("bytes=0-", 100), ByteRange::Partial(0, 99));
+        assert_eq!(parse_range("bytes=40-", 100), ByteRange::Partial(40, 99));
+        assert_eq!(
+            parse_range("bytes=-500", 1000),
+            ByteRange::Partial(500, 999)
+        );
+        // A suffix longer than the file is the whole file
+        assert_eq!(parse_range("bytes=-500", 100), ByteRange::Partial(0, 99));
+        assert_eq!(parse_range("bytes=-0", 100), ByteRange::Unsatisfiable);
+    }
+
+    #[test]
+    fn closed_ranges() {
+        assert_eq!(parse_range("bytes=0-0", 100), ByteRange::Partial(0, 0));
+        assert_eq!(parse_range("bytes=10-19", 100), ByteRange::Partial(10, 19));
+        assert_eq!(
+            parse_range(" bytes= 10 - 19 ", 100),
+            ByteRange::Partial(10, 19)
+        );
+        // The end is clamped to the file
+        assert_eq!(parse_range("bytes=50-500", 100), ByteRange::Partial(50, 99));
+        // Start after end is invalid, so the whole file is sent
+        assert_eq!(parse_range("bytes=20-10", 100), ByteRange::Full);
+    }
+
+    #[test]
+    fn ranges_past_the_end() {
+        assert_eq!(parse_range("bytes=100-", 100), ByteRange::Unsatisfiable);
+        assert_eq!(parse_range("bytes=200-300", 100), ByteRange::Unsatisfiable);
+        assert_eq!(parse_range("bytes=0-", 0), ByteRange::Unsatisfiable);
+        assert_eq!(parse_range("bytes=-10", 0), ByteRange::Unsatisfiable);
+    }
+
+    #[test]
+    fn multiple_and_garbage_ranges_get_the_whole_file() {
+        for range in [
+            "bytes=0-1,5-6",
+            "bytes=0-1, 5-",
+            "",
+            "bytes=",
+            "bytes=-",
+            "bytes=abc",
+            "bytes=a-b",
+            "bytes=1-x",
+            "bytes=-x",
+            "bytes=--5",
+            "bytes=18446744073709551616-",
+            "items=0-1",
+            "0-1",
+        ] {
+            assert_eq!(parse_range(range, 100), ByteRange::Full, "{:?}", range);
+        }
+    }
+}
@@ -0,0 +1,132 @@
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Sender};
+use std::sync::OnceLock;
+use std::thread;
+
+use cpal::traits::{DeviceTrait, HostTrait};
+use rodio::{Decoder, OutputStream, OutputStreamHandle, Sink};
+
+/// File extensions accepted for custom sounds
+pub const SUPPORTED_EXTENSIONS: &[&str] = &["wav", "mp3", "ogg", "oga", "flac", "aiff"];
+
+enum AudioCommand {
+    Play(PathBuf, Sender<Result<(), String>>),
+    Stop,
+    /// Output device by name, or the system default
+    SetOutput(Option<String>),
+}
+
+// rodio's output stream isn't Send, so a single thread owns it and plays whatever it's sent
+fn audio_thread() -> &'static Sender<AudioCommand> {
+    static SENDER: OnceLock<Sender<AudioCommand>> = OnceLock::new();
+    SENDER.get_or_init(|| {
+        let (tx, rx) = mpsc::channel::<AudioCommand>();
+        thread::spawn(move || {
+            let mut device: Option<String> = None;
+            let mut output: Option<(OutputStream, OutputStreamHandle)> = None;
+            let mut current: Option<Sink> = None;
+            for command in rx {
+                match command {
+                    AudioCommand::Play(path, reply) => {
+                        if let Some(sink) = current.take() {
+                            sink.stop();
+                        }
+                        let result = open_output(&mut output, device.as_deref())
+                            .and_then(|handle| start_playback(handle, &path))
+                            .map(|sink| current = Some(sink));
+                        let _ = reply.send(result);
+                    }
+                    AudioCommand::Stop => {
+                        if let Some(sink) = current.take() {
+                            sink.stop();
+                        }
+                    }
+                    AudioCommand::SetOutput(name) => {
+                        if let Some(sink) = current.take() {
+                            sink.stop();
+                        }
+                        output = None;
+                        device = name;
+                    }
+                }
+            }
+        });
+        tx
+    })
+}
+
+fn open_output<'a>(
+    output: &'a mut Option<(OutputStream, OutputStreamHandle)>,
+    device: Option<&str>,
+) -> Result<&'a OutputStreamHandle, String> {
+    if output.is_none() {
+        let chosen = device.and_then(|name| {
+            let found = cpal::default_host()
+                .output_devices()
+                .ok()?
+                .find(|candidate| candidate.name().is_ok_and(|candidate| candidate == name));
+            if found.is_none() {
+                log::warn!("Audio output \"{}\" is gone, using the default", name);
+            }
+            found
+        });
+        let opened = match chosen {
+            Some(device) => OutputStream::try_from_device(&device),
+            None => OutputStream::try_default(),
+        };
+        *output = Some(opened.map_err(|e| e.to_string())?);
+    }
+    Ok(&output.as_ref().unwrap().1)
+}
+
+fn start_playback(handle: &OutputStreamHandle, path: &Path) -> Result<Sink, String> {
+    let source = decode(path)?;
+    let sink = Sink::try_new(handle).map_err(|e| e.to_string())?;
+    sink.append(source);
+    Ok(sink)
+}
+
+fn decode(path: &Path) -> Result<Decoder<BufReader<File>>, String> {
+    let file = File::open(path).map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+    Decoder::new(BufReader::new(file))
+        .map_err(|e| format!("Unsupported audio file {}: {}", path.display(), e))
+}
+
+/// Check that a file exists and decodes as audio
+pub fn validate_sound_file(path: &Path) -> Result<(), String> {
+    let extension = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_ascii_lowercase());
+    if !extension.is_some_and(|e| SUPPORTED_EXTENSIONS.contains(&e.as_str())) {
+        return Err(format!(
+            "Unsupported audio file {}, expected one of: {}",
+            path.display(),
+            SUPPORTED_EXTENSIONS.join(", ")
+        ));
+    }
+    decode(path).map(|_| ())
+}
+
+/// Play a sound file once, replacing anything already playing
+pub fn play_file(path: &Path) -> Result<(), String> {
+    let (reply_tx, reply_rx) = mpsc::channel();
+    audio_thread()
+        .send(AudioCommand::Play(path.to_path_buf(), reply_tx))
+        .map_err(|_| "Audio thread is not running".to_string())?;
+    reply_rx
+        .recv()
+        .map_err(|_| "Audio thread is not running".to_string())?
+}
+
+/// Stop whatever is playing
+pub fn stop() {
+    let _ = audio_thread().send(AudioCommand::Stop);
+}
+
+/// Play sounds on the output device with this name from now on, or the system default
+pub fn set_output_device(name: Option<String>) {
+    let _ = audio_thread().send(AudioCommand::SetOutput(name));
+}
@@ -0,0 +1,152 @@
+use std::sync::{Mutex, OnceLock};
+use std::thread;
+use std::time::Duration;
+
+use cpal::traits::{DeviceTrait, HostTrait};
+use cpal::Device;
+use serde::Serialize;
+use tauri::{command, AppHandle, Emitter};
+
+use crate::{audio, settings};
+
+/// How often the device list is checked for changes
+const POLL_INTERVAL: Duration = Duration::from_secs(3);
+
+const OUTPUT_KEY: &str = "audio.output_device";
+
+// Output device sounds are routed to right now, None for the system default
+fn active_output() -> &'static Mutex<Option<String>> {
+    static ACTIVE: OnceLock<Mutex<Option<String>>> = OnceLock::new();
+    ACTIVE.get_or_init(|| Mutex::new(None))
+}
+
+#[derive(Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AudioDevice {
+    /// Also the device's id, since cpal has no stable ones
+    name: String,
+    is_default: bool,
+}
+
+#[derive(Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AudioDevices {
+    inputs: Vec<AudioDevice>,
+    outputs: Vec<AudioDevice>,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct AudioOutputChanged<'a> {
+    device_id: Option<&'a str>,
+    /// The chosen device is unplugged, so sounds went back to the default
+    fallback: bool,
+}
+
+/// Microphones and speakers the system offers, for a device picker. Either list is
+/// empty when there are no devices of that kind or the audio system can't be reached.
+#[command]
+pub async fn list_audio_devices() -> Result<AudioDevices, String> {
+    tauri::async_runtime::spawn_blocking(current_devices)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Play Hazel's own sounds (notifications, previews) on the output with this id, or the
+/// system default with None; emits `audio-output-changed`. The choice is kept across
+/// launches. While the device is unplugged sounds fall back to the default, and they
+/// move back once it returns.
+#[command]
+pub fn set_audio_output(app: AppHandle, device_id: Option<String>) -> Result<(), String> {
+    match &device_id {
+        Some(id) => {
+            if !current_devices()
+                .outputs
+                .iter()
+                .any(|device| &device.name == id)
+            {
+                return Err(format!("No audio output named \"{}\"", id));
+            }
+            settings::set(&app, OUTPUT_KEY, id)?;
+        }
+        None => settings::delete(&app, OUTPUT_KEY)?,
+    }
+    route_output(&app, device_id, false);
+    Ok(())
+}
+
+/// Emit `audio-devices-changed` with the new lists when a device is plugged in or
+/// removed, or the default changes, and move sounds on and off the chosen output to
+/// match. cpal has no hotplug events, so this polls.
+pub fn watch_devices(app: &AppHandle) {
+    let app = app.clone();
+    thread::spawn(move || {
+        let mut last = current_devices();
+        sync_output(&app, &last);
+        loop {
+            thread::sleep(POLL_INTERVAL);
+            let devices = current_devices();
+            if devices != last {
+                let _ = app.emit("audio-devices-changed", &devices);
+                sync_output(&app, &devices);
+                last = devices;
+            }
+        }
+    });
+}
+
+// Route to the chosen output if it's plugged in, the default otherwise
+fn sync_output(app: &AppHandle, devices: &AudioDevices) {
+    let Some(wanted) = settings::get::<String>(app, OUTPUT_KEY) else {
+        return;
+    };
+    if devices.outputs.iter().any(|device| device.name == wanted) {
+        route_output(app, Some(wanted), false);
+    } else if route_output(app, None, true) {
+        log::warn!("Audio output \"{}\" is gone, using the default", wanted);
+    }
+}
+
+// Returns whether the routing changed
+fn route_output(app: &AppHandle, device: Option<String>, fallback: bool) -> bool {
+    let mut active = active_output().lock().unwrap();
+    if *active == device {
+        return false;
+    }
+    audio::set_output_device(device.clone());
+    let _ = app.emit(
+        "audio-output-changed",
+        AudioOutputChanged {
+            device_id: device.as_deref(),
+            fallback,
+        },
+    );
+    *active = device;
+    true
+}
+
+fn current_devices() -> AudioDevices {
+    let host = cpal::default_host();
+    AudioDevices {
+        inputs: describe(
+            host.input_devices().into_iter().flatten(),
+            host.default_input_device(),
+        ),
+        outputs: describe(
+            host.output_devices().into_iter().flatten(),
+            host.default_output_device(),
+        ),
+    }
+}
+
+// Devices whose names can't be read are skipped, since they can't be picked either
+fn describe(devices: impl Iterator<Item = Device>, default: Option<Device>) -> Vec<AudioDevice> {
+    let default_name = default.and_then(|device| device.name().ok());
+    devices
+        .filter_map(|device| device.name().ok())
+        .map(|name| AudioDevice {
+            is_default: default_name.as_ref() == Some(&name),
+            name,
+        })
+        .collect()
+}
@@ -0,0 +1,397 @@
+use std::io::Read;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::thread;
+
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::OsRng;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tauri::{command, AppHandle, Emitter};
+use tiny_http::{Header, Method, Request, Response, Server};
+
+use crate::{lock, settings};
+
+const ENABLED_KEY: &str = "automation.enabled";
+
+/// Keychain account the API token is kept under
+const TOKEN_ACCOUNT: &str = "local-api-token";
+
+/// Ports the API listens on, the first one free; below the OAuth callback range
+const PORT_MIN: u16 = 17800;
+const PORT_MAX: u16 = 17899;
+
+/// Largest request body accepted
+const MAX_BODY_SIZE: u64 = 64 * 1024;
+
+/// Longest message the API will send
+const MAX_MESSAGE_CHARS: usize = 10_000;
+
+// The running server; dropping it closes the socket
+fn server() -> &'static Mutex<Option<Running>> {
+    static SERVER: OnceLock<Mutex<Option<Running>>> = OnceLock::new();
+    SERVER.get_or_init(|| Mutex::new(None))
+}
+
+struct Running {
+    server: Arc<Server>,
+    port: u16,
+}
+
+// The token once it's been read from the keychain, so requests don't each go there
+fn cached_token() -> &'static Mutex<Option<String>> {
+    static TOKEN: OnceLock<Mutex<Option<String>>> = OnceLock::new();
+    TOKEN.get_or_init(|| Mutex::new(None))
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SendMessage {
+    channel_id: String,
+    content: String,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct AutomationSendMessage {
+    channel_id: String,
+    content: String,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SetStatus {
+    status: String,
+    message: Option<String>,
+}
+
+/// Turn the local automation API on or off, returning the port it listens on; it's off
+/// until turned on, and stays as set across launches. Turning it on creates the token
+/// (see `get_local_api_token`) if there isn't one. The API listens on 127.0.0.1 only, on
+/// the first free port from 17800 to 17899, and every request needs
+/// `Authorization: Bearer <token>`. Requests from web pages (with an `Origin` header) are
+/// refused, and so is everything while the app is locked. Bodies are JSON, at most 64 KB.
+///
+/// - `GET /v1/ping` answers `{ "ok": true, "version": "<app version>" }`
+/// - `POST /v1/messages` with `{ "channelId", "content" }` emits `automation-send-message`
+///   with the same fields for the frontend to send
+/// - `POST /v1/status` with `{ "status", "message"? }` emits `automation-set-status`
+///   with the same fields
+///
+/// Accepted requests answer 202 with `{ "ok": true }`; errors answer
+/// `{ "error": "<reason>" }` with 400, 401, 403, 404, 413 or 423 (locked).
+#[command]
+pub async fn set_automation_api(app: AppHandle, enabled: bool) -> Result<Option<u16>, String> {
+    if !enabled {
+        stop();
+        settings::delete(&app, ENABLED_KEY)?;
+        return Ok(None);
+    }
+    tauri::async_runtime::spawn_blocking({
+        let app = app.clone();
+        move || token(&app)
+    })
+    .await
+    .map_err(|e| e.to_string())??;
+    settings::set(&app, ENABLED_KEY, true)?;
+    start(&app).map(Some)
+}
+
+/// The port the API listens on, or None when it's off
+#[command]
+pub fn automation_api_port() -> Option<u16> {
+    Some(server().lock().unwrap().as_ref()?.port)
+}
+
+/// The token scripts send to the API, generated the first time and kept in the keychain
+#[command]
+pub async fn get_local_api_token(app: AppHandle) -> Result<String, String> {
+    tauri::async_runtime::spawn_blocking(move || token(&app))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+/// Replace the token, so scripts holding the old one are shut out, and return the new one
+#[command]
+pub async fn rotate_local_api_token(app: AppHandle) -> Result<String, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let token = generate_token();
+        keychain_entry(&app.config().identifier)?
+            .set_password(&token)
+            .map_err(|e| format!("Failed to save the token to the keychain: {}", e))?;
+        *cached_token().lock().unwrap() = Some(token.clone());
+        Ok(token)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// Delete the token from the keychain, if there is one. Takes the app's identifier so it
+/// can run before the app is built.
+pub fn forget_token(identifier: &str) -> Result<(), String> {
+    *cached_token().lock().unwrap() = None;
+    match keychain_entry(identifier)?.delete_credential() {
+        Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// Start the API if it was on when the app last quit
+pub fn restore_automation_api(app: &AppHandle) {
+    if settings::get::<bool>(app, ENABLED_KEY).unwrap_or(false) {
+        if let Err(e) = start(app) {
+            log::warn!("Failed to start the automation API: {}", e);
+        }
+    }
+}
+
+fn start(app: &AppHandle) -> Result<u16, String> {
+    let mut running = server().lock().unwrap();
+    if let Some(running) = running.as_ref() {
+        return Ok(running.port);
+    }
+    let (server, port) = (PORT_MIN..=PORT_MAX)
+        .find_map(|port| {
+            Server::http(format!("127.0.0.1:{}", port))
+                .ok()
+                .map(|server| (Arc::new(server), port))
+        })
+        .ok_or("No available ports in range 17800-17899")?;
+    let app = app.clone();
+    let listening = server.clone();
+    thread::spawn(move || {
+        for request in listening.incoming_requests() {
+            handle(&app, port, request);
+        }
+    });
+    *running = Some(Running { server, port });
+    Ok(port)
+}
+
+// A token is made the first time it's needed
+fn token(app: &AppHandle) -> Result<String, String> {
+    let mut cached = cached_token().lock().unwrap();
+    if let Some(token) = cached.as_ref() {
+        return Ok(token.clone());
+    }
+    let entry = keychain_entry(&app.config().identifier)?;
+    let token = match entry.get_password() {
+        Ok(token) => token,
+        Err(keyring::Error::NoEntry) => {
+            let token = generate_token();
+            entry
+                .set_password(&token)
+                .map_err(|e| format!("Failed to save the token to the keychain: {}", e))?;
+            token
+        }
+        Err(e) => return Err(format!("Failed to read the token from the keychain: {}", e)),
+    };
+    *cached = Some(token.clone());
+    Ok(token)
+}
+
+fn keychain_entry(identifier: &str) -> Result<keyring::Entry, String> {
+    keyring::Entry::new(identifier, TOKEN_ACCOUNT).map_err(|e| e.to_string())
+}
+
+fn stop() {
+    if let Some(running) = server().lock().unwrap().take() {
+        running.server.unblock();
+    }
+}
+
+fn handle(app: &AppHandle, port: u16, mut request: Request) {
+    let (status, body) = match route(app, port, &mut request) {
+        Ok(answer) => answer,
+        Err((status, error)) => (status, json!({ "error": error })),
+    };
+    let response = Response::from_string(body.to_string())
+        .with_status_code(status)
+        .with_header(Header::from_bytes("Content-Type", "application/json").unwrap());
+    let _ = request.respond(response);
+}
+
+fn route(
+    app: &AppHandle,
+    port: u16,
+    request: &mut Request,
+) -> Result<(u16, Value), (u16, &'static str)> {
+    let header = |name: &'static str| {
+        request
+            .headers()
+            .iter()
+            .find(|header| header.field.equiv(name))
+            .map(|header| header.value.as_str().to_string())
+    };
+    check_origin(port, header("Host").as_deref(), header("Origin").as_deref())?;
+    let token = token(app).map_err(|e| {
+        log::warn!("The automation API can't check tokens: {}", e);
+        (401, "Missing or wrong token")
+    })?;
+    check_token(header("Authorization").as_deref(), &token)?;
+    if lock::is_app_locked() {
+        return Err((423, "The app is locked"));
+    }
+
+    match (request.method(), request.url()) {
+        (Method::Get, "/v1/ping") => Ok((
+            200,
+            json!({ "ok": true, "version": app.package_info().version.to_string() }),
+        )),
+        (Method::Post, "/v1/messages") => {
+            let message: SendMessage = read_json(request)?;
+            let content = message.content.trim();
+            if message.channel_id.trim().is_empty() || content.is_empty() {
+                return Err((400, "channelId and content can't be empty"));
+            }
+            if content.chars().count() > MAX_MESSAGE_CHARS {
+                return Err((413, "The message is too long"));
+            }
+            let send = AutomationSendMessage {
+                channel_id: message.channel_id.trim().to_string(),
+                content: content.to_string(),
+            };
+            let _ = app.emit("automation-send-message", send);
+            Ok((202, json!({ "ok": true })))
+        }
+        (Method::Post, "/v1/status") => {
+            let status: SetStatus = read_json(request)?;
+            if status.status.trim().is_empty() {
+                return Err((400, "status can't be empty"));
+            }
+            let _ = app.emit("automation-set-status", status);
+            Ok((202, json!({ "ok": true })))
+        }
+        _ => Err((404, "No such endpoint")),
+    }
+}
+
+// Browsers send an Origin, and a page's name may point at 127.0.0.1 but is still
+// named in Host; scripts send neither
+fn check_origin(
+    port: u16,
+    host: Option<&str>,
+    origin: Option<&str>,
+) -> Result<(), (u16, &'static str)> {
+    let host_ok = host.is_some_and(|host| {
+        host == format!("127.0.0.1:{}", port) || host == format!("localhost:{}", port)
+    });
+    if origin.is_some() || !host_ok {
+        return Err((403, "Requests from web pages aren't allowed"));
+    }
+    Ok(())
+}
+
+fn check_token(authorization: Option<&str>, token: &str) -> Result<(), (u16, &'static str)> {
+    let authorized = authorization
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .is_some_and(|given| same(given, token));
+    if !authorized {
+        return Err((401, "Missing or wrong token"));
+    }
+    Ok(())
+}
+
+fn read_json<T: for<'de> Deserialize<'de>>(
+    request: &mut Request,
+) -> Result<T, (u16, &'static str)> {
+    if request
+        .body_length()
+        .is_some_and(|length| length as u64 > MAX_BODY_SIZE)
+    {
+        return Err((413, "The body is too large"));
+    }
+    let mut body = Vec::new();
+    request
+        .as_reader()
+        .take(MAX_BODY_SIZE + 1)
+        .read_to_end(&mut body)
+        .map_err(|_| (400, "Failed to read body"))?;
+    if body.len() as u64 > MAX_BODY_SIZE {
+        return Err((413, "The body is too large"));
+    }
+    serde_json::from_slice(&body).map_err(|_| (400, "Invalid JSON"))
+}
+
+// Compares every byte, so how long it takes doesn't give away how much of a guess is right
+fn same(a: &str, b: &str) -> bool {
+    a.len() == b.len()
+        && a.bytes()
+            .zip(b.bytes())
+            .fold(0, |difference, (a, b)| difference | (a ^ b))
+            == 0
+}
+
+fn generate_token() -> String {
+    let mut random = [0; 32];
+    OsRng.fill_bytes(&mut random);
+    random.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TOKEN: &str = "4f3c2a1b";
+
+    #[test]
+    fn right_token_is_accepted() {
+        assert_eq!(check_token(Some("Bearer 4f3c2a1b"), TOKEN), Ok(()));
+    }
+
+    #[test]
+    fn wrong_or_missing_token_is_rejected() {
+        let rejected = Err((401, "Missing or wrong token"));
+        // Wrong, of the same length
+        assert_eq!(check_token(Some("Bearer 4f3c2a1c"), TOKEN), rejected);
+        // Missing
+        assert_eq!(check_token(None, TOKEN), rejected);
+        assert_eq!(check_token(Some("Bearer "), TOKEN), rejected);
+        // Not a bearer token
+        assert_eq!(check_token(Some("4f3c2a1b"), TOKEN), rejected);
+        assert_eq!(check_token(Some("Basic 4f3c2a1b"), TOKEN), rejected);
+        // Different lengths, including the token with more after it
+        assert_eq!(check_token(Some("Bearer 4f3c"), TOKEN), rejected);
+        assert_eq!(check_token(Some("Bearer 4f3c2a1b00"), TOKEN), rejected);
+    }
+
+    #[test]
+    fn same_compares_whole_strings() {
+        assert!(same("abc", "abc"));
+        assert!(same("", ""));
+        assert!(!same("abc", "abd"));
+        assert!(!same("abc", "ab"));
+        assert!(!same("ab", "abc"));
+    }
+
+    #[test]
+    fn only_local_hosts_are_accepted() {
+        assert_eq!(check_origin(4100, Some("127.0.0.1:4100"), None), Ok(()));
+        assert_eq!(check_origin(4100, Some("localhost:4100"), None), Ok(()));
+
+        let rejected = Err((403, "Requests from web pages aren't allowed"));
+        assert_eq!(check_origin(4100, None, None), rejected);
+        assert_eq!(check_origin(4100, Some("127.0.0.1:4101"), None), rejected);
+        assert_eq!(check_origin(4100, Some("127.0.0.1"), None), rejected);
+        assert_eq!(
+            check_origin(4100, Some("evil.example:4100"), None),
+            rejected
+        );
+        assert_eq!(
+            check_origin(4100, Some("127.0.0.1.evil.example:4100"), None),
+            rejected
+        );
+    }
+
+    #[test]
+    fn requests_with_an_origin_are_rejected() {
+        let rejected = Err((403, "Requests from web pages aren't allowed"));
+        assert_eq!(
+            check_origin(4100, Some("127.0.0.1:4100"), Some("https://evil.example")),
+            rejected
+        );
+        assert_eq!(
+            check_origin(4100, Some("localhost:4100"), Some("null")),
+            rejected
+        );
+    }
+}
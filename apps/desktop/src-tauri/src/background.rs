@@ -0,0 +1,118 @@
+use std::fs;
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+
+use base64::Engine;
+use image::codecs::jpeg::JpegEncoder;
+use tauri::{command, AppHandle, Manager, WebviewWindow};
+
+use crate::{data_dir, settings};
+
+const BACKGROUND_KEY: &str = "appearance.background_image";
+
+/// Scaled copy of the chosen image, in the data directory
+const BACKGROUND_FILE: &str = "background.jpg";
+
+/// Images are scaled down to fit these bounds, which cover a large screen
+const MAX_WIDTH: u32 = 2560;
+const MAX_HEIGHT: u32 = 1600;
+
+const JPEG_QUALITY: u8 = 85;
+
+/// CSS variable on the root element holding the image as `url(...)`
+const CSS_VARIABLE: &str = "--hazel-background-image";
+
+/// Use an image (PNG, JPEG or WebP) as the background behind the titlebar and sidebar.
+/// A scaled copy is kept, so the original can be moved or deleted afterwards. Every window
+/// gets it as the `--hazel-background-image` CSS variable, including after reloads.
+#[command]
+pub async fn set_background_image(app: AppHandle, path: PathBuf) -> Result<(), String> {
+    let target = data_dir::data_dir(&app)?.join(BACKGROUND_FILE);
+    tauri::async_runtime::spawn_blocking(move || scale_image(&path, &target))
+        .await
+        .map_err(|e| e.to_string())??;
+
+    settings::set(&app, BACKGROUND_KEY, true)?;
+    for window in app.webview_windows().values() {
+        restore_background_image(window);
+    }
+    Ok(())
+}
+
+/// Go back to the default background
+#[command]
+pub fn clear_background_image(app: AppHandle) -> Result<(), String> {
+    settings::delete(&app, BACKGROUND_KEY)?;
+    if let Ok(dir) = data_dir::data_dir(&app) {
+        let _ = fs::remove_file(dir.join(BACKGROUND_FILE));
+    }
+    for window in app.webview_windows().values() {
+        set_css_variable(window, None);
+    }
+    Ok(())
+}
+
+/// Re-apply the background image after the page reloads
+pub fn restore_background_image(window: &WebviewWindow) {
+    let app = window.app_handle();
+    if !settings::get::<bool>(app, BACKGROUND_KEY).unwrap_or(false) {
+        return;
+    }
+    let Ok(dir) = data_dir::data_dir(app) else {
+        return;
+    };
+    match fs::read(dir.join(BACKGROUND_FILE)) {
+        Ok(bytes) => {
+            let encoded = base64::engine::general_purpose::STANDARD.encode(bytes);
+            set_css_variable(window, Some(&format!("data:image/jpeg;base64,{}", encoded)));
+        }
+        Err(e) => log::warn!("Failed to read the background image: {}", e),
+    }
+}
+
+fn scale_image(path: &Path, target: &Path) -> Result<(), String> {
+    if !path.is_file() {
+        return Err(format!("{} doesn't exist", path.display()));
+    }
+    let image = image::ImageReader::open(path)
+        .and_then(|reader| reader.with_guessed_format())
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?
+        .decode()
+        .map_err(|e| format!("{} is not a supported image: {}", path.display(), e))?;
+
+    let image = if image.width() > MAX_WIDTH || image.height() > MAX_HEIGHT {
+        image.resize(MAX_WIDTH, MAX_HEIGHT, image::imageops::FilterType::Triangle)
+    } else {
+        image
+    };
+    let mut jpeg = Vec::new();
+    image
+        .to_rgb8()
+        .write_with_encoder(JpegEncoder::new_with_quality(
+            &mut Cursor::new(&mut jpeg),
+            JPEG_QUALITY,
+        ))
+        .map_err(|e| e.to_string())?;
+
+    if let Some(dir) = target.parent() {
+        fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    }
+    fs::write(target, jpeg).map_err(|e| e.to_string())
+}
+
+fn set_css_variable(window: &WebviewWindow, data_url: Option<&str>) {
+    let script = match data_url {
+        // Data URLs only hold base64 characters, so they can't break out of the quotes
+        Some(url) => format!(
+            r#"document.documentElement.style.setProperty("{}", 'url("{}")')"#,
+            CSS_VARIABLE, url
+        ),
+        None => format!(
+            r#"document.documentElement.style.removeProperty("{}")"#,
+            CSS_VARIABLE
+        ),
+    };
+    if let Err(e) = window.eval(&script) {
+        log::warn!("Failed to apply the background image: {}", e);
+    }
+}
@@ -0,0 +1,78 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::sync::{Mutex, OnceLock};
+use std::thread;
+use std::time::Duration;
+
+use tauri::{command, AppHandle, Emitter};
+
+use crate::backoff;
+
+/// Shortest interval the frontend may ask for, so an idle app doesn't hammer the server
+const MIN_INTERVAL_SECS: u64 = 15;
+
+/// Online status as last reported by the frontend
+static ONLINE: AtomicBool = AtomicBool::new(true);
+
+// Wakes the running timer early; dropping it stops the timer
+fn timer() -> &'static Mutex<Option<Sender<()>>> {
+    static TIMER: OnceLock<Mutex<Option<Sender<()>>>> = OnceLock::new();
+    TIMER.get_or_init(|| Mutex::new(None))
+}
+
+/// Emit `background-sync-tick` every `interval_secs` so the frontend can sync while idle.
+/// Replaces any running timer. While offline no ticks are emitted and the timer checks back
+/// following the shared backoff policy, never sooner than the interval; coming back online
+/// ticks right away.
+#[command]
+pub fn start_background_sync(app: AppHandle, interval_secs: u64) -> Result<(), String> {
+    if interval_secs < MIN_INTERVAL_SECS {
+        return Err(format!(
+            "The sync interval must be at least {} seconds",
+            MIN_INTERVAL_SECS
+        ));
+    }
+
+    let (wake_tx, wake_rx) = mpsc::channel();
+    // Dropping the previous sender stops the previous timer
+    *timer().lock().unwrap() = Some(wake_tx);
+
+    let interval = Duration::from_secs(interval_secs);
+    thread::spawn(move || {
+        let mut wait = interval;
+        let mut offline_attempts = 0;
+        loop {
+            match wake_rx.recv_timeout(wait) {
+                Ok(()) | Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => return,
+            }
+            if ONLINE.load(Ordering::SeqCst) {
+                let _ = app.emit("background-sync-tick", ());
+                wait = interval;
+                offline_attempts = 0;
+            } else {
+                let backoff = Duration::from_millis(backoff::delay_ms(offline_attempts));
+                wait = interval.max(backoff);
+                offline_attempts += 1;
+            }
+        }
+    });
+    Ok(())
+}
+
+/// Stop the background sync timer. Returns false if none was running.
+#[command]
+pub fn stop_background_sync() -> bool {
+    timer().lock().unwrap().take().is_some()
+}
+
+/// Report whether the app is online, e.g. from the browser's `online`/`offline` events
+#[command]
+pub fn set_online(online: bool) {
+    let was_online = ONLINE.swap(online, Ordering::SeqCst);
+    if online && !was_online {
+        if let Some(wake) = timer().lock().unwrap().as_ref() {
+            let _ = wake.send(());
+        }
+    }
+}
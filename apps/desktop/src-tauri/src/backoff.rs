@@ -0,0 +1,102 @@
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+
+use serde::Serialize;
+use tauri::{command, AppHandle, Emitter};
+
+/// Delay before the first retry
+const BASE_DELAY_MS: u64 = 1_000;
+
+/// Longest delay between retries, however many have failed
+const MAX_DELAY_MS: u64 = 60_000;
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct BackoffChanged {
+    attempt: u32,
+    delay_ms: u64,
+}
+
+/// Delay in milliseconds before retry number `attempt` (starting at 0), e.g. for websocket
+/// reconnects. Emits `backoff-changed` so every part of the UI shows the same countdown.
+#[command]
+pub fn next_backoff(app: AppHandle, attempt: u32) -> u64 {
+    let delay_ms = delay_ms(attempt);
+    let _ = app.emit("backoff-changed", BackoffChanged { attempt, delay_ms });
+    delay_ms
+}
+
+/// Report that the connection is back, emitting `backoff-changed` with no delay
+#[command]
+pub fn reset_backoff(app: AppHandle) {
+    let changed = BackoffChanged {
+        attempt: 0,
+        delay_ms: 0,
+    };
+    let _ = app.emit("backoff-changed", changed);
+}
+
+/// The shared retry policy: doubling from 1 second up to a minute, with the upper half
+/// of each delay randomized so clients that dropped together don't retry together
+pub fn delay_ms(attempt: u32) -> u64 {
+    let ceiling = BASE_DELAY_MS
+        .saturating_mul(1 << attempt.min(16))
+        .min(MAX_DELAY_MS);
+    let half = ceiling / 2;
+    half + jitter(half + 1)
+}
+
+// Uniform enough in 0..bound for spreading retries; not for anything secret
+fn jitter(bound: u64) -> u64 {
+    RandomState::new().build_hasher().finish() % bound
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Every delay for `attempt` lands in `low..=high`, over enough draws to hit both halves
+    fn assert_between(attempt: u32, low: u64, high: u64) {
+        for _ in 0..1_000 {
+            let delay = delay_ms(attempt);
+            assert!(
+                (low..=high).contains(&delay),
+                "attempt {} waited {} ms",
+                attempt,
+                delay
+            );
+        }
+    }
+
+    #[test]
+    fn first_delays_double() {
+        assert_between(0, 500, 1_000);
+        assert_between(1, 1_000, 2_000);
+        assert_between(2, 2_000, 4_000);
+        assert_between(3, 4_000, 8_000);
+        assert_between(5, 16_000, 32_000);
+    }
+
+    #[test]
+    fn delays_are_capped() {
+        for attempt in [6, 7, 16, 17, 100, u32::MAX] {
+            assert_between(attempt, 30_000, 60_000);
+        }
+    }
+
+    #[test]
+    fn jitter_stays_below_its_bound() {
+        for bound in [1, 2, 7, 501, 30_001] {
+            for _ in 0..1_000 {
+                assert!(jitter(bound) < bound);
+            }
+        }
+        assert_eq!(jitter(1), 0);
+    }
+
+    #[test]
+    fn delays_are_spread() {
+        let delays: std::collections::HashSet<u64> = (0..100).map(|_| delay_ms(4)).collect();
+        assert!(delays.len() > 50, "only {} distinct delays", delays.len());
+    }
+}
@@ -0,0 +1,111 @@
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use tauri::{command, AppHandle};
+
+use crate::settings;
+
+const UP_KEY: &str = "bandwidth.up_kbps";
+const DOWN_KEY: &str = "bandwidth.down_kbps";
+
+/// How much unused allowance can build up, so a transfer that starts after a quiet
+/// spell gets a short burst rather than a long one
+const BURST: Duration = Duration::from_secs(1);
+
+// Token bucket for one direction. Transfers take what they need even when that leaves
+// the bucket in debt, then wait for it to refill, so a chunk larger than the bucket
+// can't stall forever and small chunks only wait once the allowance is spent.
+struct Bucket {
+    /// Bytes per second, None when unlimited
+    rate: Option<f64>,
+    tokens: f64,
+    last: Instant,
+}
+
+impl Bucket {
+    fn new() -> Self {
+        Bucket {
+            rate: None,
+            tokens: 0.0,
+            last: Instant::now(),
+        }
+    }
+}
+
+fn uploads() -> &'static Mutex<Bucket> {
+    static UPLOADS: OnceLock<Mutex<Bucket>> = OnceLock::new();
+    UPLOADS.get_or_init(|| Mutex::new(Bucket::new()))
+}
+
+fn downloads() -> &'static Mutex<Bucket> {
+    static DOWNLOADS: OnceLock<Mutex<Bucket>> = OnceLock::new();
+    DOWNLOADS.get_or_init(|| Mutex::new(Bucket::new()))
+}
+
+/// Cap the bandwidth of Rust-side transfers (file and update downloads, tus and bug
+/// report uploads) in kilobits per second; None leaves a direction unlimited. Applies
+/// straight away, including to transfers already running, and is kept across launches.
+#[command]
+pub fn set_bandwidth_limit(
+    app: AppHandle,
+    up_kbps: Option<u32>,
+    down_kbps: Option<u32>,
+) -> Result<(), String> {
+    if up_kbps == Some(0) || down_kbps == Some(0) {
+        return Err("A bandwidth limit must be at least 1 kbps".to_string());
+    }
+    for (key, kbps) in [(UP_KEY, up_kbps), (DOWN_KEY, down_kbps)] {
+        match kbps {
+            Some(kbps) => settings::set(&app, key, kbps)?,
+            None => settings::delete(&app, key)?,
+        }
+    }
+    set_rate(uploads(), up_kbps);
+    set_rate(downloads(), down_kbps);
+    Ok(())
+}
+
+/// Apply the saved limits; call once at startup
+pub fn restore_bandwidth_limit(app: &AppHandle) {
+    set_rate(uploads(), settings::get(app, UP_KEY));
+    set_rate(downloads(), settings::get(app, DOWN_KEY));
+}
+
+/// Wait until `bytes` more may be uploaded
+pub async fn throttle_upload(bytes: usize) {
+    throttle(uploads(), bytes).await
+}
+
+/// Wait until `bytes` more may be downloaded; call after each chunk is received
+pub async fn throttle_download(bytes: usize) {
+    throttle(downloads(), bytes).await
+}
+
+fn set_rate(bucket: &Mutex<Bucket>, kbps: Option<u32>) {
+    let mut bucket = bucket.lock().unwrap();
+    bucket.rate = kbps.map(|kbps| kbps as f64 * 1000.0 / 8.0);
+    bucket.tokens = 0.0;
+    bucket.last = Instant::now();
+}
+
+async fn throttle(bucket: &Mutex<Bucket>, bytes: usize) {
+    // Work out the wait without holding the lock across it
+    let wait = {
+        let mut bucket = bucket.lock().unwrap();
+        let Some(rate) = bucket.rate else {
+            return;
+        };
+        let now = Instant::now();
+        let refill = now.duration_since(bucket.last).as_secs_f64() * rate;
+        bucket.tokens = (bucket.tokens + refill).min(rate * BURST.as_secs_f64());
+        bucket.last = now;
+        bucket.tokens -= bytes as f64;
+        match bucket.tokens < 0.0 {
+            true => Duration::from_secs_f64(-bucket.tokens / rate),
+            false => Duration::ZERO,
+        }
+    };
+    if !wait.is_zero() {
+        tokio::time::sleep(wait).await;
+    }
+}
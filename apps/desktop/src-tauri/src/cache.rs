@@ -0,0 +1,242 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use tauri::{command, AppHandle, Emitter};
+
+use crate::{data_dir, db, search, settings};
+
+const PURGE_AFTER_DAYS_KEY: &str = "cache.purge_after_days";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CacheCategory {
+    Images,
+    Attachments,
+    Webview,
+}
+
+const CATEGORIES: [CacheCategory; 3] = [
+    CacheCategory::Images,
+    CacheCategory::Attachments,
+    CacheCategory::Webview,
+];
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CacheSize {
+    total: u64,
+    by_category: BTreeMap<CacheCategory, u64>,
+}
+
+/// Disk space used by each cache category, in bytes
+#[command]
+pub async fn cache_size(app: AppHandle) -> Result<CacheSize, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let mut by_category = BTreeMap::new();
+        for category in CATEGORIES {
+            let size = category_dirs(&app, category)?
+                .iter()
+                .map(|dir| dir_size(dir))
+                .sum();
+            by_category.insert(category, size);
+        }
+        let total = by_category.values().sum();
+        Ok(CacheSize { total, by_category })
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// Delete everything in one cache category, then emit `cache-changed` with the category.
+/// Files the webview has open (WebView2 locks some of its cache) are skipped.
+#[command]
+pub async fn clear_cache(app: AppHandle, category: CacheCategory) -> Result<(), String> {
+    let dirs = category_dirs(&app, category)?;
+    tauri::async_runtime::spawn_blocking(move || {
+        for dir in dirs {
+            clear_dir(&dir);
+        }
+    })
+    .await
+    .map_err(|e| e.to_string())?;
+
+    let _ = app.emit("cache-changed", category);
+    Ok(())
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct CachePurged {
+    bytes_freed: u64,
+    messages: usize,
+}
+
+/// Delete cached images and attachments (thumbnails, waveforms and voice messages
+/// included) not modified for `days` days, and saved messages sent before then, along with
+/// their search index entries. Returns the bytes freed and emits `cache-purged`. Files in
+/// use are skipped, so this is safe to run at any time; the webview's own cache is left to
+/// the webview.
+#[command]
+pub async fn purge_cache_older_than(app: AppHandle, days: u32) -> Result<u64, String> {
+    if days == 0 {
+        return Err("days must be at least 1".to_string());
+    }
+    let cutoff = SystemTime::now() - Duration::from_secs(days as u64 * 24 * 60 * 60);
+    let dirs = [dir(&app, "images")?, dir(&app, "attachments")?];
+    tauri::async_runtime::spawn_blocking(move || {
+        let mut bytes_freed: u64 = dirs.iter().map(|dir| purge_dir(dir, cutoff)).sum();
+
+        let before = db::size(&app);
+        let cutoff_ms = cutoff
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since| since.as_millis() as i64);
+        let ids = db::purge_messages_before(cutoff_ms)?;
+        if !ids.is_empty() {
+            if let Err(e) = search::remove_messages(&app, &ids) {
+                log::warn!(
+                    "Failed to drop purged messages from the search index: {}",
+                    e
+                );
+            }
+        }
+        bytes_freed += before.saturating_sub(db::size(&app));
+
+        let purged = CachePurged {
+            bytes_freed,
+            messages: ids.len(),
+        };
+        let _ = app.emit("cache-purged", purged);
+        Ok(bytes_freed)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// Run `purge_cache_older_than` with this many days on every launch; None stops it
+#[command]
+pub fn set_cache_purge_on_startup(app: AppHandle, days: Option<u32>) -> Result<(), String> {
+    match days {
+        Some(0) => Err("days must be at least 1".to_string()),
+        Some(days) => settings::set(&app, PURGE_AFTER_DAYS_KEY, days),
+        None => settings::delete(&app, PURGE_AFTER_DAYS_KEY),
+    }
+}
+
+/// Purge old cached data in the background if `set_cache_purge_on_startup` asked for it
+pub fn purge_on_startup(app: &AppHandle) {
+    let Some(days) = settings::get::<u32>(app, PURGE_AFTER_DAYS_KEY) else {
+        return;
+    };
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        if let Err(e) = purge_cache_older_than(app, days).await {
+            log::warn!("Failed to purge old cached data: {}", e);
+        }
+    });
+}
+
+/// Folder for one of Hazel's own cache categories, e.g. for downloads to cache into
+pub fn dir(app: &AppHandle, name: &str) -> Result<PathBuf, String> {
+    Ok(data_dir::cache_dir(app)?.join(name))
+}
+
+fn category_dirs(app: &AppHandle, category: CacheCategory) -> Result<Vec<PathBuf>, String> {
+    match category {
+        CacheCategory::Images => Ok(vec![dir(app, "images")?]),
+        CacheCategory::Attachments => Ok(vec![dir(app, "attachments")?]),
+        CacheCategory::Webview => webview_cache_dirs(app),
+    }
+}
+
+// WebView2 keeps its cache in the default profile of the user data folder
+#[cfg(windows)]
+fn webview_cache_dirs(app: &AppHandle) -> Result<Vec<PathBuf>, String> {
+    let profile = data_dir::data_dir(app)?.join("EBWebView").join("Default");
+    Ok(["Cache", "Code Cache", "GPUCache"]
+        .iter()
+        .map(|name| profile.join(name))
+        .collect())
+}
+
+#[cfg(target_os = "macos")]
+fn webview_cache_dirs(app: &AppHandle) -> Result<Vec<PathBuf>, String> {
+    use tauri::Manager;
+
+    let caches = app.path().app_cache_dir().map_err(|e| e.to_string())?;
+    Ok(vec![caches.join("WebKit")])
+}
+
+// WebKitGTK only gets a data directory, so its cache lands in GLib's default
+// location, named after the executable
+#[cfg(not(any(windows, target_os = "macos")))]
+fn webview_cache_dirs(_app: &AppHandle) -> Result<Vec<PathBuf>, String> {
+    let exe = std::env::current_exe().map_err(|e| e.to_string())?;
+    let (Some(caches), Some(name)) = (dirs::cache_dir(), exe.file_name()) else {
+        return Ok(Vec::new());
+    };
+    let root = caches.join(name);
+    Ok(vec![root.join("WebKitCache"), root.join("CacheStorage")])
+}
+
+// Total size of the files under `path`, without following symlinks
+fn dir_size(path: &Path) -> u64 {
+    let Ok(entries) = fs::read_dir(path) else {
+        return 0;
+    };
+    entries
+        .flatten()
+        .map(|entry| match entry.metadata() {
+            Ok(meta) if meta.is_dir() => dir_size(&entry.path()),
+            Ok(meta) => meta.len(),
+            Err(_) => 0,
+        })
+        .sum()
+}
+
+// Returns the bytes freed. Folders left empty are removed, but not `path` itself.
+fn purge_dir(path: &Path, cutoff: SystemTime) -> u64 {
+    let Ok(entries) = fs::read_dir(path) else {
+        return 0;
+    };
+    let mut freed = 0;
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let Ok(meta) = entry.metadata() else {
+            continue;
+        };
+        if meta.is_dir() {
+            freed += purge_dir(&path, cutoff);
+            // Only succeeds once the folder is empty
+            let _ = fs::remove_dir(&path);
+            continue;
+        }
+        let old = meta.modified().is_ok_and(|modified| modified < cutoff);
+        if !old {
+            continue;
+        }
+        match fs::remove_file(&path) {
+            Ok(()) => freed += meta.len(),
+            Err(e) => log::debug!("Skipped {} while purging cache: {}", path.display(), e),
+        }
+    }
+    freed
+}
+
+fn clear_dir(path: &Path) {
+    let Ok(entries) = fs::read_dir(path) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let removed = match entry.file_type() {
+            Ok(kind) if kind.is_dir() => fs::remove_dir_all(&path),
+            _ => fs::remove_file(&path),
+        };
+        if let Err(e) = removed {
+            log::debug!("Skipped {} while clearing cache: {}", path.display(), e);
+        }
+    }
+}
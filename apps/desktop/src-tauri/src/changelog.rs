@@ -0,0 +1,139 @@
+use std::fs;
+
+use serde::{Deserialize, Serialize};
+use tauri::{command, AppHandle, Emitter};
+
+use crate::{cache, http, settings};
+
+const ENDPOINT_KEY: &str = "changelog.endpoint";
+const LAST_SEEN_VERSION_KEY: &str = "changelog.last_seen_version";
+
+/// Release notes come from GitHub's release API unless another endpoint is set.
+/// `{version}` is replaced with the version asked for.
+const DEFAULT_ENDPOINT: &str =
+    "https://api.github.com/repos/HazelChat/hazel/releases/tags/v{version}";
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Changelog {
+    version: String,
+    title: String,
+    /// Markdown
+    notes: String,
+    published_at: Option<String>,
+}
+
+// The parts of a GitHub release that make up a changelog; other endpoints answer the same way
+#[derive(Deserialize)]
+struct Release {
+    name: Option<String>,
+    body: Option<String>,
+    published_at: Option<String>,
+}
+
+/// Set where release notes are fetched from, with `{version}` standing for the version;
+/// None goes back to GitHub releases
+#[command]
+pub fn set_changelog_endpoint(app: AppHandle, url: Option<String>) -> Result<(), String> {
+    match url {
+        Some(url) => {
+            let parsed = reqwest::Url::parse(&url).map_err(|e| format!("Invalid URL: {}", e))?;
+            if parsed.scheme() != "https" {
+                return Err("Release notes can only be fetched over https".to_string());
+            }
+            settings::set(&app, ENDPOINT_KEY, url)
+        }
+        None => settings::delete(&app, ENDPOINT_KEY),
+    }
+}
+
+/// Release notes for a version. Notes don't change once published, so they're fetched
+/// once and served from the cache after that, including offline.
+#[command]
+pub async fn fetch_changelog(app: AppHandle, version: String) -> Result<Changelog, String> {
+    let version = version.trim_start_matches('v').to_string();
+    if version.is_empty()
+        || !version
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '+'))
+    {
+        return Err(format!("Invalid version \"{}\"", version));
+    }
+
+    let cached_path = cache::dir(&app, "changelog")?.join(format!("{}.json", version));
+    if let Some(cached) = fs::read(&cached_path)
+        .ok()
+        .and_then(|bytes| serde_json::from_slice::<Changelog>(&bytes).ok())
+    {
+        return Ok(cached);
+    }
+
+    let changelog = download(&app, &version).await?;
+    let saved = cached_path
+        .parent()
+        .map_or(Ok(()), fs::create_dir_all)
+        .and_then(|_| fs::write(&cached_path, serde_json::to_vec(&changelog).unwrap()));
+    if let Err(e) = saved {
+        log::warn!("Failed to cache the changelog for {}: {}", version, e);
+    }
+    Ok(changelog)
+}
+
+/// On the first launch after an update, fetch the new version's notes and emit
+/// `changelog-available` with them. If they can't be fetched, the next launch tries
+/// again. Fresh installs only record the version.
+pub fn check_for_new_version(app: &AppHandle) {
+    let current = app.package_info().version.to_string();
+    let last_seen = settings::get::<String>(app, LAST_SEEN_VERSION_KEY);
+    if last_seen.as_deref() == Some(current.as_str()) {
+        return;
+    }
+    if last_seen.is_none() {
+        save_last_seen(app, &current);
+        return;
+    }
+
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        match fetch_changelog(app.clone(), current.clone()).await {
+            Ok(changelog) => {
+                let _ = app.emit("changelog-available", changelog);
+                save_last_seen(&app, &current);
+            }
+            Err(e) => log::warn!("Failed to fetch the changelog: {}", e),
+        }
+    });
+}
+
+fn save_last_seen(app: &AppHandle, version: &str) {
+    if let Err(e) = settings::set(app, LAST_SEEN_VERSION_KEY, version) {
+        log::warn!("Failed to save the last seen version: {}", e);
+    }
+}
+
+async fn download(app: &AppHandle, version: &str) -> Result<Changelog, String> {
+    let endpoint = settings::get::<String>(app, ENDPOINT_KEY)
+        .unwrap_or_else(|| DEFAULT_ENDPOINT.to_string())
+        .replace("{version}", version);
+    let endpoint = http::check_url(app, &endpoint, true).await?;
+    let release: Release = http::client(app)?
+        .get(endpoint)
+        .header("Accept", "application/json")
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| format!("Failed to fetch the changelog: {}", e))?
+        .json()
+        .await
+        .map_err(|e| format!("Invalid changelog: {}", e))?;
+
+    Ok(Changelog {
+        version: version.to_string(),
+        title: release
+            .name
+            .filter(|name| !name.trim().is_empty())
+            .unwrap_or_else(|| format!("Hazel {}", version)),
+        notes: release.body.unwrap_or_default(),
+        published_at: release.published_at,
+    })
+}
@@ -0,0 +1,181 @@
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::sync::{Mutex, OnceLock};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use arboard::{Clipboard, Error};
+use serde::Serialize;
+use tauri::{command, AppHandle, Emitter};
+
+/// How often the clipboard is checked for changes. Linux has no change counter, so the
+/// contents themselves are compared there, which is dearer.
+#[cfg(any(target_os = "macos", windows))]
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+#[cfg(not(any(target_os = "macos", windows)))]
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How long the clipboard has to stay unchanged before a change is reported
+const DEBOUNCE: Duration = Duration::from_millis(500);
+
+// Dropping it stops the running watcher
+fn watcher() -> &'static Mutex<Option<Sender<()>>> {
+    static WATCHER: OnceLock<Mutex<Option<Sender<()>>>> = OnceLock::new();
+    WATCHER.get_or_init(|| Mutex::new(None))
+}
+
+#[derive(Clone, Copy, Serialize)]
+#[serde(rename_all = "camelCase")]
+enum ClipboardKind {
+    Text,
+    Image,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ClipboardChanged {
+    kind: ClipboardKind,
+}
+
+/// What's on the clipboard as PNG bytes, e.g. a screenshot to paste as an upload, or None
+/// when it holds no image. Images in any format the system offers come out as PNG.
+#[command]
+pub async fn read_clipboard_image() -> Result<Option<Vec<u8>>, String> {
+    tauri::async_runtime::spawn_blocking(|| {
+        let image = match Clipboard::new().and_then(|mut clipboard| clipboard.get_image()) {
+            Ok(image) => image,
+            Err(e) => return nothing_on_clipboard(e),
+        };
+        let (Ok(width), Ok(height)) = (u32::try_from(image.width), u32::try_from(image.height))
+        else {
+            return Ok(None);
+        };
+        if width == 0 || height == 0 {
+            return Ok(None);
+        }
+
+        // arboard hands images over as 8-bit RGBA
+        let mut png = Vec::new();
+        let mut encoder = png::Encoder::new(&mut png, width, height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder
+            .write_header()
+            .and_then(|mut writer| writer.write_image_data(&image.bytes))
+            .map_err(|e| e.to_string())?;
+        Ok(Some(png))
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// Text on the clipboard, or None when it holds none
+#[command]
+pub async fn read_clipboard_text() -> Result<Option<String>, String> {
+    tauri::async_runtime::spawn_blocking(|| {
+        match Clipboard::new().and_then(|mut clipboard| clipboard.get_text()) {
+            Ok(text) => Ok(Some(text)),
+            Err(e) => nothing_on_clipboard(e),
+        }
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+// An empty clipboard, or one holding something else, isn't an error
+fn nothing_on_clipboard<T>(error: Error) -> Result<Option<T>, String> {
+    match error {
+        Error::ContentNotAvailable | Error::ConversionFailure => Ok(None),
+        Error::ClipboardOccupied => {
+            Err("Another app is using the clipboard, try again".to_string())
+        }
+        e => Err(format!("Can't read the clipboard: {}", e)),
+    }
+}
+
+/// Emit `clipboard-changed` with the kind of content (text or image) whenever something
+/// new is copied, e.g. to offer pasting a link that was just copied. The contents aren't
+/// sent; the frontend reads them with `read_clipboard_text` or `read_clipboard_image` if
+/// it wants them. Changes in quick succession are reported once things settle, and other
+/// kinds of content aren't reported.
+///
+/// Off until this is called, since it watches everything the user copies, and off again
+/// at every launch.
+#[command]
+pub fn start_clipboard_watch(app: AppHandle) {
+    let (stop_tx, stop_rx) = mpsc::channel::<()>();
+    // Dropping the previous sender stops the previous watcher
+    *watcher().lock().unwrap() = Some(stop_tx);
+
+    thread::spawn(move || {
+        let Ok(mut clipboard) = Clipboard::new() else {
+            log::warn!("Can't watch the clipboard, it isn't available");
+            return;
+        };
+        let mut last = change_marker(&mut clipboard);
+        let mut changed_at = None;
+        loop {
+            match stop_rx.recv_timeout(POLL_INTERVAL) {
+                Err(RecvTimeoutError::Timeout) => {}
+                Ok(()) | Err(RecvTimeoutError::Disconnected) => return,
+            }
+            let marker = change_marker(&mut clipboard);
+            if marker != last {
+                last = marker;
+                changed_at = Some(Instant::now());
+                continue;
+            }
+            if changed_at.is_some_and(|at| at.elapsed() >= DEBOUNCE) {
+                changed_at = None;
+                if let Some(kind) = kind(&mut clipboard) {
+                    let _ = app.emit("clipboard-changed", ClipboardChanged { kind });
+                }
+            }
+        }
+    });
+}
+
+/// Stop the clipboard watcher. Returns false if none was running.
+#[command]
+pub fn stop_clipboard_watch() -> bool {
+    watcher().lock().unwrap().take().is_some()
+}
+
+fn kind(clipboard: &mut Clipboard) -> Option<ClipboardKind> {
+    if clipboard.get_text().is_ok() {
+        Some(ClipboardKind::Text)
+    } else if clipboard.get_image().is_ok() {
+        Some(ClipboardKind::Image)
+    } else {
+        None
+    }
+}
+
+// Anything that changes whenever something is copied
+#[cfg(target_os = "macos")]
+fn change_marker(_clipboard: &mut Clipboard) -> Option<u64> {
+    use objc2_app_kit::NSPasteboard;
+
+    Some(NSPasteboard::generalPasteboard().changeCount() as u64)
+}
+
+#[cfg(windows)]
+fn change_marker(_clipboard: &mut Clipboard) -> Option<u64> {
+    use windows_sys::Win32::System::DataExchange::GetClipboardSequenceNumber;
+
+    Some(unsafe { GetClipboardSequenceNumber() } as u64)
+}
+
+#[cfg(not(any(target_os = "macos", windows)))]
+fn change_marker(clipboard: &mut Clipboard) -> Option<u64> {
+    use std::hash::{DefaultHasher, Hasher};
+
+    let mut hasher = DefaultHasher::new();
+    if let Ok(text) = clipboard.get_text() {
+        hasher.write(text.as_bytes());
+    } else if let Ok(image) = clipboard.get_image() {
+        hasher.write(&image.bytes);
+    } else {
+        return None;
+    }
+    Some(hasher.finish())
+}
@@ -0,0 +1,317 @@
+use serde::Serialize;
+use tauri::{command, AppHandle, Emitter, Manager, WebviewWindow};
+
+use crate::{flags, settings};
+
+const CUSTOM_CSS_KEY: &str = "appearance.custom_css";
+
+/// Largest stylesheet accepted, in bytes
+const MAX_CSS_LEN: usize = 256 * 1024;
+
+/// Id of the `<style>` element holding the custom CSS
+const STYLE_ID: &str = "hazel-custom-css";
+
+/// Functions whose arguments can load a resource; `image-set(` covers `-webkit-image-set(`
+const FUNCTIONS: &[&str] = &["url(", "image-set(", "src("];
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct CustomCssApplied {
+    length: usize,
+    /// Remote imports and URLs stripped by the sanitizer
+    removed: usize,
+}
+
+/// Inject a user stylesheet into every window and keep it across reloads and launches.
+/// Emits `custom-css-applied` and returns it as stored.
+///
+/// Unless the `custom_css_remote` flag is on, `@import`s, `url()`s and `image-set()`s
+/// pointing at other hosts (which could track the user) and `javascript:` URLs are removed
+/// first, however they're escaped or split up with comments.
+#[command]
+pub fn apply_custom_css(app: AppHandle, css: String) -> Result<String, String> {
+    if css.len() > MAX_CSS_LEN {
+        return Err(format!(
+            "Custom CSS is {} KB; the limit is {} KB",
+            css.len() / 1024,
+            MAX_CSS_LEN / 1024
+        ));
+    }
+    let (css, removed) = if flags::enabled(&app, "custom_css_remote") {
+        (css, 0)
+    } else {
+        sanitize(&css)
+    };
+
+    settings::set(&app, CUSTOM_CSS_KEY, &css)?;
+    for window in app.webview_windows().values() {
+        inject(window, &css);
+    }
+    let applied = CustomCssApplied {
+        length: css.len(),
+        removed,
+    };
+    let _ = app.emit("custom-css-applied", applied);
+    Ok(css)
+}
+
+/// Remove the user stylesheet from every window; emits `custom-css-applied` with length 0
+#[command]
+pub fn clear_custom_css(app: AppHandle) -> Result<(), String> {
+    settings::delete(&app, CUSTOM_CSS_KEY)?;
+    for window in app.webview_windows().values() {
+        inject(window, "");
+    }
+    let cleared = CustomCssApplied {
+        length: 0,
+        removed: 0,
+    };
+    let _ = app.emit("custom-css-applied", cleared);
+    Ok(())
+}
+
+/// Re-apply the user stylesheet after the page reloads
+pub fn restore_custom_css(window: &WebviewWindow) {
+    if let Some(css) = settings::get::<String>(window.app_handle(), CUSTOM_CSS_KEY) {
+        inject(window, &css);
+    }
+}
+
+// Replaces the style element's contents, or removes it for empty CSS
+fn inject(window: &WebviewWindow, css: &str) {
+    // JSON-encode the CSS so it can't break out of the string literal
+    let Ok(css) = serde_json::to_string(css) else {
+        return;
+    };
+    let script = format!(
+        r#"(function (css) {{
+            let style = document.getElementById("{id}")
+            if (!css) return style?.remove()
+            if (!style) {{
+                style = document.createElement("style")
+                style.id = "{id}"
+                document.head.appendChild(style)
+            }}
+            style.textContent = css
+        }})({css})"#,
+        id = STYLE_ID,
+        css = css
+    );
+    if let Err(e) = window.eval(&script) {
+        log::warn!("Failed to apply custom CSS: {}", e);
+    }
+}
+
+// Drop remote `@import` rules and neutralize remote or script `url()`s, `image-set()`s
+// and `src()`s. Matching runs on the CSS as the browser reads it, so comments and escapes
+// can't hide a URL, but what's kept is copied as written. Returns the cleaned CSS and how
+// many constructs were removed.
+fn sanitize(css: &str) -> (String, usize) {
+    let (read, spans) = normalize(css);
+    let mut out = String::with_capacity(css.len());
+    let mut removed = 0;
+    let mut copied = 0;
+    let mut i = 0;
+    while i < read.len() {
+        let rest = &read.as_bytes()[i..];
+        // What to check, as a range of `read`, and the range of `css` to drop if it's remote
+        let (start, end, cut) = if rest.starts_with(b"@import") {
+            let end = read[i..].find(';').map_or(read.len(), |end| i + end + 1);
+            (i, end, spans[i].0..spans[end].0)
+        } else if let Some(function) = FUNCTIONS.iter().find(|f| rest.starts_with(f.as_bytes())) {
+            let start = i + function.len();
+            let end = closing_paren(&read, start);
+            (start, end, spans[start - 1].1..spans[end].0)
+        } else {
+            i += 1;
+            continue;
+        };
+        if is_remote(&read[start..end]) {
+            out.push_str(&css[copied..cut.start]);
+            copied = cut.end;
+            removed += 1;
+        }
+        i = end;
+    }
+    out.push_str(&css[copied..]);
+    (out, removed)
+}
+
+// `css` ASCII-lowercased, with comments dropped and escapes decoded, and for each of its
+// bytes the start and end in `css` of what it came from (plus an empty one at the end)
+fn normalize(css: &str) -> (String, Vec<(usize, usize)>) {
+    let mut read = String::with_capacity(css.len());
+    let mut spans = Vec::with_capacity(css.len() + 1);
+    let mut quote = None;
+    let mut chars = css.char_indices().peekable();
+    while let Some((at, c)) = chars.next() {
+        let c = match c {
+            '/' if quote.is_none() && css[at..].starts_with("/*") => {
+                let end = css[at + 2..]
+                    .find("*/")
+                    .map_or(css.len(), |end| at + 2 + end + 2);
+                while chars.next_if(|&(next, _)| next < end).is_some() {}
+                continue;
+            }
+            '\\' => match chars.peek().map(|&(_, next)| next) {
+                Some(next) if next.is_ascii_hexdigit() => {
+                    let mut value = 0;
+                    for _ in 0..6 {
+                        match chars.next_if(|(_, c)| c.is_ascii_hexdigit()) {
+                            Some((_, digit)) => value = value * 16 + digit.to_digit(16).unwrap(),
+                            None => break,
+                        }
+                    }
+                    chars.next_if(|(_, c)| c.is_ascii_whitespace());
+                    char::from_u32(value)
+                        .filter(|&c| c != '\0')
+                        .unwrap_or('\u{fffd}')
+                }
+                // Continues a string onto the next line
+                Some('\n') => {
+                    chars.next();
+                    continue;
+                }
+                // Escaped quotes don't open or close strings
+                Some(next) => {
+                    chars.next();
+                    next
+                }
+                None => c,
+            },
+            '"' | '\'' => {
+                match quote {
+                    None => quote = Some(c),
+                    Some(open) if open == c => quote = None,
+                    Some(_) => {}
+                }
+                c
+            }
+            '\n' => {
+                quote = None;
+                c
+            }
+            c => c,
+        };
+        let c = c.to_ascii_lowercase();
+        let end = chars.peek().map_or(css.len(), |&(next, _)| next);
+        spans.extend(std::iter::repeat((at, end)).take(c.len_utf8()));
+        read.push(c);
+    }
+    spans.push((css.len(), css.len()));
+    (read, spans)
+}
+
+// Where the parenthesis closing the one before `start` is, allowing for nested ones
+fn closing_paren(css: &str, start: usize) -> usize {
+    let mut depth = 0;
+    for (i, byte) in css.bytes().enumerate().skip(start) {
+        match byte {
+            b'(' => depth += 1,
+            b')' if depth == 0 => return i,
+            b')' => depth -= 1,
+            _ => {}
+        }
+    }
+    css.len()
+}
+
+// Whether any reference in an `@import` or a function's arguments, quoted or not, is remote
+fn is_remote(arguments: &str) -> bool {
+    arguments
+        .split(|c: char| c.is_whitespace() || matches!(c, '"' | '\'' | '(' | ')' | ','))
+        .any(|reference| {
+            ["http:", "https:", "//", "javascript:", "ftp:"]
+                .iter()
+                .any(|prefix| reference.starts_with(prefix))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn clean(css: &str) -> String {
+        sanitize(css).0
+    }
+
+    #[test]
+    fn local_css_is_kept() {
+        let css = ".sm\\:flex { background: url(data:image/png;base64,AAAA) }\n\
+                   .w-1\\/2 { background-image: image-set(\"a.png\" 1x, \"b.png\" 2x) }";
+        assert_eq!(sanitize(css), (css.to_string(), 0));
+    }
+
+    #[test]
+    fn remote_imports_and_urls_are_removed() {
+        assert_eq!(
+            clean("@import \"https://evil.example/a.css\"; a {}"),
+            " a {}"
+        );
+        assert_eq!(clean("@import url(//evil.example/a.css);"), "");
+        assert_eq!(
+            clean("a { background: url('https://evil.example/t.png') }"),
+            "a { background: url() }"
+        );
+        assert_eq!(clean("a { b: url(javascript:alert(1)) }"), "a { b: url() }");
+    }
+
+    #[test]
+    fn comments_dont_hide_urls() {
+        assert_eq!(clean("@im/**/port \"https://evil.example/a.css\";"), "");
+        assert_eq!(
+            clean("a { b: url(/* x */https://evil.example/t.png) }"),
+            "a { b: url() }"
+        );
+        assert_eq!(
+            clean("a { b: url(\"/*\" \"https://evil.example\") }"),
+            "a { b: url() }"
+        );
+        // A string can't open a comment that hides what follows it
+        assert_eq!(
+            clean("a { content: \"/*\" } b { c: url(https://evil.example) } d { content: \"*/\" }"),
+            "a { content: \"/*\" } b { c: url() } d { content: \"*/\" }"
+        );
+    }
+
+    #[test]
+    fn escapes_dont_hide_urls() {
+        assert_eq!(
+            clean("a { b: u\\72l(https://evil.example) }"),
+            "a { b: u\\72l() }"
+        );
+        assert_eq!(
+            clean("a { b: url(\"\\68ttps://evil.example\") }"),
+            "a { b: url() }"
+        );
+        assert_eq!(
+            clean("a { b: url(\"\\2f\\2f evil.example\") }"),
+            "a { b: url() }"
+        );
+        assert_eq!(clean("@\\69mport '//evil.example';"), "");
+        assert_eq!(
+            clean("a { b: URL(\\HTTPS://evil.example) }"),
+            "a { b: URL() }"
+        );
+    }
+
+    #[test]
+    fn image_sets_are_checked() {
+        assert_eq!(
+            clean("a { b: image-set(\"https://evil.example/1.png\" 1x, \"local.png\" 2x) }"),
+            "a { b: image-set() }"
+        );
+        assert_eq!(
+            clean("a { b: -webkit-image-set(url(//evil.example/1.png) 1x) }"),
+            "a { b: -webkit-image-set() }"
+        );
+        assert_eq!(
+            clean("a { b: src(\"https://evil.example/1.png\") }"),
+            "a { b: src() }"
+        );
+        assert_eq!(
+            sanitize("a { b: image-set(\"https://evil.example\" 1x) }").1,
+            1
+        );
+    }
+}
@@ -0,0 +1,352 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use tauri::{command, App, AppHandle, Context, Manager};
+
+use crate::{lifecycle, settings};
+
+const DATA_DIR_KEY: &str = "data_dir.path";
+const PENDING_DATA_DIR_KEY: &str = "data_dir.pending";
+
+/// Written into custom data directories so a later move can tell them from unrelated folders
+const MARKER_FILE: &str = ".hazel-data";
+
+/// Subdirectory of a custom data directory that replaces the app cache dir
+const CACHE_SUBDIR: &str = "cache";
+
+/// Entries of the default data directory that never move: the stores hold the data
+/// directory setting itself, and logs are opened before it's read
+const PINNED: &[&str] = &[settings::STORE_FILE, settings::FRONTEND_STORE_FILE, "logs"];
+
+// Custom data directory in effect for this run, and the outcome of a move done at startup
+struct Startup {
+    data_dir: Option<PathBuf>,
+    moved: Option<Result<(), String>>,
+}
+
+static STARTUP: OnceLock<Startup> = OnceLock::new();
+
+/// Move Hazel's data and cache to another folder, e.g. a larger volume. The move
+/// happens on the next launch, before the webview opens its files; emits `restart-required`.
+///
+/// The folder must be empty, missing, or a previous Hazel data folder. The settings
+/// stores and logs stay in the default location since they record where the data lives;
+/// no credentials reference data paths, so nothing in the keychain needs updating.
+///
+/// Webview storage moves with the data on Windows and Linux. On macOS WKWebView always
+/// keeps it in the system location, so only Hazel's own files move.
+#[command]
+pub fn set_data_dir(app: AppHandle, path: PathBuf) -> Result<(), String> {
+    if !path.is_absolute() {
+        return Err("The data directory must be an absolute path".to_string());
+    }
+    let current = data_dir(&app)?;
+    if path == current {
+        return settings::delete(&app, PENDING_DATA_DIR_KEY);
+    }
+    if path.starts_with(&current) || current.starts_with(&path) {
+        return Err(format!(
+            "{} overlaps the current data directory",
+            path.display()
+        ));
+    }
+
+    if !is_empty_dir(&path) && !path.join(MARKER_FILE).exists() {
+        return Err(format!(
+            "{} is not empty and isn't a Hazel data directory",
+            path.display()
+        ));
+    }
+    // Writing the marker doubles as the writability check
+    fs::create_dir_all(&path)
+        .and_then(|_| fs::write(path.join(MARKER_FILE), ""))
+        .map_err(|e| format!("{} is not writable: {}", path.display(), e))?;
+
+    settings::set(&app, PENDING_DATA_DIR_KEY, path)?;
+    lifecycle::notify_restart_required(&app, "data_dir");
+    Ok(())
+}
+
+/// Move data back to the default location on the next launch; emits `restart-required`
+#[command]
+pub fn reset_data_dir(app: AppHandle) -> Result<(), String> {
+    if settings::get::<PathBuf>(&app, DATA_DIR_KEY).is_none() {
+        return settings::delete(&app, PENDING_DATA_DIR_KEY);
+    }
+    let default = app.path().app_local_data_dir().map_err(|e| e.to_string())?;
+    settings::set(&app, PENDING_DATA_DIR_KEY, default)?;
+    lifecycle::notify_restart_required(&app, "data_dir");
+    Ok(())
+}
+
+/// Folder holding Hazel's data (and the webview's on Windows/Linux)
+pub fn data_dir(app: &AppHandle) -> Result<PathBuf, String> {
+    match settings::get(app, DATA_DIR_KEY) {
+        Some(path) => Ok(path),
+        None => app.path().app_local_data_dir().map_err(|e| e.to_string()),
+    }
+}
+
+/// Folder for Hazel's own caches, which moves along with a custom data directory
+pub fn cache_dir(app: &AppHandle) -> Result<PathBuf, String> {
+    match settings::get::<PathBuf>(app, DATA_DIR_KEY) {
+        Some(path) => Ok(path.join(CACHE_SUBDIR)),
+        None => app.path().app_cache_dir().map_err(|e| e.to_string()),
+    }
+}
+
+/// Run any pending move and point the webview at the custom data directory.
+/// Must run before the app is built.
+pub fn prepare(context: &mut Context) {
+    let identifier = context.config().identifier.clone();
+    let mut data_dir = settings::get_at_startup::<PathBuf>(&identifier, DATA_DIR_KEY);
+
+    let pending = settings::get_at_startup::<PathBuf>(&identifier, PENDING_DATA_DIR_KEY);
+    let moved = pending.map(|to| {
+        let default = default_layout(&identifier)
+            .ok_or_else(|| "Unknown default data directory".to_string())?;
+        let from = data_dir
+            .as_deref()
+            .map_or_else(|| default.clone(), custom_layout);
+        let to_default = to == default.0;
+        let to_layout = if to_default {
+            default
+        } else {
+            custom_layout(&to)
+        };
+        move_data(&from, &to_layout).map_err(|e| e.to_string())?;
+        data_dir = (!to_default).then_some(to);
+        Ok(())
+    });
+
+    // Config windows only take data directories relative to the default one,
+    // so windows using a custom one are created by hand in `setup`
+    #[cfg(any(windows, target_os = "linux"))]
+    if data_dir.is_some() {
+        for window in &mut context.config_mut().app.windows {
+            window.create = false;
+        }
+    }
+
+    let _ = STARTUP.set(Startup { data_dir, moved });
+}
+
+/// Create the windows deferred by `prepare` and record the outcome of a startup move
+pub fn setup(app: &App) -> tauri::Result<()> {
+    let Some(startup) = STARTUP.get() else {
+        return Ok(());
+    };
+
+    #[cfg(any(windows, target_os = "linux"))]
+    if let Some(data_dir) = &startup.data_dir {
+        for config in &app.config().app.windows {
+            tauri::WebviewWindowBuilder::from_config(app, config)?
+                .data_directory(data_dir.clone())
+                .build()?;
+        }
+    }
+
+    let app = app.handle();
+    match &startup.moved {
+        Some(Ok(_)) => {
+            let saved = match &startup.data_dir {
+                Some(data_dir) => settings::set(app, DATA_DIR_KEY, data_dir),
+                None => settings::delete(app, DATA_DIR_KEY),
+            };
+            if let Err(e) = saved.and_then(|_| settings::delete(app, PENDING_DATA_DIR_KEY)) {
+                log::warn!("Failed to save the data directory: {}", e);
+            }
+        }
+        Some(Err(e)) => {
+            log::warn!("Failed to move the data directory: {}", e);
+            let _ = settings::delete(app, PENDING_DATA_DIR_KEY);
+        }
+        None => {}
+    }
+    Ok(())
+}
+
+// Data and cache folders, as (data, cache)
+type Layout = (PathBuf, PathBuf);
+
+fn default_layout(identifier: &str) -> Option<Layout> {
+    let data = dirs::data_local_dir()?.join(identifier);
+    let cache = dirs::cache_dir()?.join(identifier);
+    Some((data, cache))
+}
+
+fn custom_layout(data: &Path) -> Layout {
+    (data.to_path_buf(), data.join(CACHE_SUBDIR))
+}
+
+// Everything moved so far, as (from, to), to move back if a later entry fails
+type Moved = Vec<(PathBuf, PathBuf)>;
+
+fn move_data(from: &Layout, to: &Layout) -> io::Result<()> {
+    let mut moved = Moved::new();
+    if let Err(e) = move_layout(from, to, &mut moved) {
+        // Leave the data all in one place, where the settings still point
+        for (source, target) in moved.iter().rev() {
+            if let Err(undo) = move_entry(target, source, &mut Moved::new()) {
+                return Err(io::Error::other(format!(
+                    "{}, and moving {} back failed: {}",
+                    e,
+                    target.display(),
+                    undo
+                )));
+            }
+        }
+        return Err(e);
+    }
+
+    // Leave the old custom folder behind only if something else was put in it
+    let (from_data, from_cache) = from;
+    if from_data.join(MARKER_FILE).exists() {
+        let _ = fs::remove_file(from_data.join(MARKER_FILE));
+        let _ = fs::remove_dir(from_cache);
+        let _ = fs::remove_dir(from_data);
+    }
+    Ok(())
+}
+
+fn move_layout(from: &Layout, to: &Layout, moved: &mut Moved) -> io::Result<()> {
+    let (from_data, from_cache) = from;
+    let (to_data, to_cache) = to;
+
+    if from_cache.exists() {
+        move_contents(from_cache, to_cache, &[], moved)?;
+    }
+    let mut skip: Vec<&Path> = PINNED.iter().map(Path::new).collect();
+    skip.push(Path::new(MARKER_FILE));
+    if let Ok(cache) = from_cache.strip_prefix(from_data) {
+        skip.push(cache);
+    }
+    if from_data.exists() {
+        move_contents(from_data, to_data, &skip, moved)?;
+    }
+    Ok(())
+}
+
+fn move_contents(from: &Path, to: &Path, skip: &[&Path], moved: &mut Moved) -> io::Result<()> {
+    fs::create_dir_all(to)?;
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        let name = entry.file_name();
+        if skip.iter().any(|skipped| skipped.as_os_str() == name) {
+            continue;
+        }
+        move_entry(&entry.path(), &to.join(name), moved)?;
+    }
+    Ok(())
+}
+
+// Rename when possible, falling back to copy-then-delete across volumes. An entry counts
+// as moved once it's all in `to`, even if deleting `from` then fails.
+fn move_entry(from: &Path, to: &Path, moved: &mut Moved) -> io::Result<()> {
+    if fs::rename(from, to).is_ok() {
+        moved.push((from.to_path_buf(), to.to_path_buf()));
+        return Ok(());
+    }
+    let existed = to.exists();
+    let copied = if from.is_dir() {
+        copy_dir(from, to)
+    } else {
+        fs::copy(from, to).map(|_| ())
+    };
+    if let Err(e) = copied {
+        // Don't leave half a copy behind
+        if !existed {
+            let _ = remove_path(to);
+        }
+        return Err(e);
+    }
+    moved.push((from.to_path_buf(), to.to_path_buf()));
+    remove_path(from)
+}
+
+fn remove_path(path: &Path) -> io::Result<()> {
+    if path.is_dir() {
+        fs::remove_dir_all(path)
+    } else {
+        fs::remove_file(path)
+    }
+}
+
+fn copy_dir(from: &Path, to: &Path) -> io::Result<()> {
+    fs::create_dir_all(to)?;
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        let target = to.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            copy_dir(&entry.path(), &target)?;
+        } else {
+            fs::copy(entry.path(), target)?;
+        }
+    }
+    Ok(())
+}
+
+fn is_empty_dir(path: &Path) -> bool {
+    match fs::read_dir(path) {
+        Ok(mut entries) => entries.next().is_none(),
+        Err(e) => e.kind() == io::ErrorKind::NotFound,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("hazel-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn moves_everything_but_pinned_entries() {
+        let root = temp_dir("data-move");
+        let (from, to) = (root.join("from"), root.join("to"));
+        fs::create_dir_all(from.join("db")).unwrap();
+        fs::write(from.join("db").join("hazel.db"), "rows").unwrap();
+        fs::write(from.join("a"), "a").unwrap();
+        fs::write(from.join(settings::STORE_FILE), "{}").unwrap();
+
+        move_data(&custom_layout(&from), &custom_layout(&to)).unwrap();
+        assert_eq!(
+            fs::read_to_string(to.join("db").join("hazel.db")).unwrap(),
+            "rows"
+        );
+        assert_eq!(fs::read_to_string(to.join("a")).unwrap(), "a");
+        assert!(!from.join("a").exists());
+        assert!(from.join(settings::STORE_FILE).exists());
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn failed_move_puts_everything_back() {
+        let root = temp_dir("data-rollback");
+        let (from, to) = (root.join("from"), root.join("to"));
+        fs::create_dir_all(&from).unwrap();
+        for name in ["a", "b", "c", "d"] {
+            fs::write(from.join(name), name).unwrap();
+        }
+        fs::create_dir_all(from.join("e")).unwrap();
+        fs::write(from.join("e").join("f"), "f").unwrap();
+        // A file can't replace a non-empty folder, so whichever order the entries
+        // come in, moving "c" fails
+        fs::create_dir_all(to.join("c").join("taken")).unwrap();
+
+        assert!(move_data(&custom_layout(&from), &custom_layout(&to)).is_err());
+        for name in ["a", "b", "c", "d"] {
+            assert_eq!(fs::read_to_string(from.join(name)).unwrap(), name);
+            assert!(name == "c" || !to.join(name).exists());
+        }
+        assert_eq!(fs::read_to_string(from.join("e").join("f")).unwrap(), "f");
+        assert!(!to.join("e").exists());
+        let _ = fs::remove_dir_all(&root);
+    }
+}
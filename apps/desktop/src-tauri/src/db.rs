@@ -0,0 +1,676 @@
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+
+use rusqlite::types::{Type, Value as SqlValue, ValueRef};
+use rusqlite::{params, Connection, OpenFlags, OptionalExtension, Row};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tauri::{command, AppHandle, Emitter};
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
+
+use crate::encryption::{self, Cipher};
+use crate::{data_dir, diagnostics};
+
+/// File in the data directory holding the offline message store
+const DB_FILE: &str = "messages.db";
+
+/// Most messages one page holds
+const MAX_PAGE_SIZE: usize = 200;
+
+/// `history-export-progress` is emitted every this many messages
+const EXPORT_PROGRESS_INTERVAL: u64 = 1000;
+
+/// Rows rewritten at a time when encryption is turned on or off
+const REENCRYPT_BATCH: i64 = 500;
+
+/// Schema changes in order; the database's `user_version` counts how many have run.
+/// Only ever append to this.
+const MIGRATIONS: &[&str] = &["
+    CREATE TABLE channels (
+        id TEXT PRIMARY KEY,
+        name TEXT NOT NULL,
+        data TEXT,
+        updated_at INTEGER NOT NULL
+    );
+    CREATE TABLE messages (
+        id TEXT PRIMARY KEY,
+        channel_id TEXT NOT NULL,
+        author_id TEXT NOT NULL,
+        content TEXT NOT NULL,
+        data TEXT,
+        created_at INTEGER NOT NULL,
+        edited_at INTEGER
+    );
+    CREATE INDEX messages_by_channel ON messages (channel_id, created_at, id);
+"];
+
+// Opened by `setup`; None if that failed
+static DB: OnceLock<Option<Mutex<Connection>>> = OnceLock::new();
+
+/// A channel as kept offline
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StoredChannel {
+    id: String,
+    name: String,
+    /// Anything else the frontend needs, kept as it was given
+    data: Option<Value>,
+    /// Milliseconds since the epoch
+    updated_at: i64,
+}
+
+/// A message as kept offline
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StoredMessage {
+    id: String,
+    channel_id: String,
+    author_id: String,
+    content: String,
+    /// Anything else the frontend needs (attachments, reactions...), kept as it was given
+    data: Option<Value>,
+    /// Milliseconds since the epoch
+    created_at: i64,
+    edited_at: Option<i64>,
+}
+
+#[derive(Clone, Copy, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ExportFormat {
+    Json,
+    Csv,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ExportProgress {
+    exported: u64,
+    total: u64,
+}
+
+/// Messages of a channel, newest first
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MessagePage {
+    messages: Vec<StoredMessage>,
+    /// Pass as `before` for the next (older) page; None when there are no more
+    next_cursor: Option<String>,
+}
+
+/// Save channels for offline use, replacing those already saved with the same id
+#[command]
+pub async fn upsert_channels(app: AppHandle, channels: Vec<StoredChannel>) -> Result<(), String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        // Locked first so the cipher can't change until these are saved
+        let mut db = connection()?;
+        let cipher = encryption::cipher(&app)?;
+        let cipher = cipher.as_deref();
+        let transaction = db.transaction().map_err(|e| e.to_string())?;
+        for channel in &channels {
+            transaction
+                .execute(
+                    "INSERT INTO channels (id, name, data, updated_at) VALUES (?1, ?2, ?3, ?4)
+                     ON CONFLICT (id) DO UPDATE SET
+                        name = excluded.name, data = excluded.data, updated_at = excluded.updated_at",
+                    params![
+                        channel.id,
+                        seal(cipher, &channel.name)?,
+                        seal_json(cipher, channel.data.as_ref())?,
+                        channel.updated_at
+                    ],
+                )
+                .map_err(|e| e.to_string())?;
+        }
+        transaction.commit().map_err(|e| e.to_string())
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// Channels saved for offline use, most recently updated first
+#[command]
+pub async fn list_stored_channels(app: AppHandle) -> Result<Vec<StoredChannel>, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let db = connection()?;
+        let cipher = encryption::cipher(&app)?;
+        let cipher = cipher.as_deref();
+        let mut statement = db
+            .prepare("SELECT id, name, data, updated_at FROM channels ORDER BY updated_at DESC")
+            .map_err(|e| e.to_string())?;
+        let channels = statement
+            .query_map([], |row| {
+                Ok((row.get::<_, String>(0)?, channel(row, cipher)))
+            })
+            .and_then(|rows| rows.collect::<Result<Vec<_>, _>>())
+            .map_err(|e| e.to_string())?;
+        Ok(channels
+            .into_iter()
+            .filter_map(|(id, channel)| readable("channel", &id, channel))
+            .collect())
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// Save messages for offline use, replacing those already saved with the same id (e.g.
+/// after an edit)
+#[command]
+pub async fn upsert_messages(app: AppHandle, messages: Vec<StoredMessage>) -> Result<(), String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        // Locked first so the cipher can't change until these are saved
+        let mut db = connection()?;
+        let cipher = encryption::cipher(&app)?;
+        let cipher = cipher.as_deref();
+        let transaction = db.transaction().map_err(|e| e.to_string())?;
+        for message in &messages {
+            transaction
+                .execute(
+                    "INSERT INTO messages
+                        (id, channel_id, author_id, content, data, created_at, edited_at)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+                     ON CONFLICT (id) DO UPDATE SET
+                        channel_id = excluded.channel_id, author_id = excluded.author_id,
+                        content = excluded.content, data = excluded.data,
+                        created_at = excluded.created_at, edited_at = excluded.edited_at",
+                    params![
+                        message.id,
+                        message.channel_id,
+                        message.author_id,
+                        seal(cipher, &message.content)?,
+                        seal_json(cipher, message.data.as_ref())?,
+                        message.created_at,
+                        message.edited_at
+                    ],
+                )
+                .map_err(|e| e.to_string())?;
+        }
+        transaction.commit().map_err(|e| e.to_string())
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// A page of a channel's saved messages, newest first. `before` is the `nextCursor` of
+/// the previous page; leave it out for the newest messages.
+#[command]
+pub async fn stored_messages(
+    app: AppHandle,
+    channel_id: String,
+    before: Option<String>,
+    limit: usize,
+) -> Result<MessagePage, String> {
+    let limit = limit.clamp(1, MAX_PAGE_SIZE);
+    tauri::async_runtime::spawn_blocking(move || {
+        let db = connection()?;
+        let cipher = encryption::cipher(&app)?;
+        // Paged by (created_at, id) so messages sent in the same millisecond aren't skipped
+        let cursor = match &before {
+            Some(id) => {
+                let cursor = db
+                    .query_row(
+                        "SELECT created_at, id FROM messages WHERE id = ?1 AND channel_id = ?2",
+                        params![id, channel_id],
+                        |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?)),
+                    )
+                    .optional()
+                    .map_err(|e| e.to_string())?;
+                Some(cursor.ok_or_else(|| format!("No saved message {}", id))?)
+            }
+            None => None,
+        };
+        let (created_at, id) = cursor.unwrap_or((i64::MAX, String::new()));
+
+        let mut statement = db
+            .prepare(
+                "SELECT id, channel_id, author_id, content, data, created_at, edited_at
+                 FROM messages
+                 WHERE channel_id = ?1 AND (created_at < ?2 OR (created_at = ?2 AND id < ?3))
+                 ORDER BY created_at DESC, id DESC
+                 LIMIT ?4",
+            )
+            .map_err(|e| e.to_string())?;
+        // One extra row tells whether there's another page
+        let mut rows = statement
+            .query_map(
+                params![channel_id, created_at, id, limit as i64 + 1],
+                |row| Ok((row.get::<_, String>(0)?, message(row, cipher.as_deref()))),
+            )
+            .and_then(|rows| rows.collect::<Result<Vec<_>, _>>())
+            .map_err(|e| e.to_string())?;
+        let more = rows.len() > limit;
+        rows.truncate(limit);
+        // From the rows rather than the messages, which can leave some out
+        let next_cursor = more
+            .then(|| rows.last().map(|(id, _)| id.clone()))
+            .flatten();
+        let messages = rows
+            .into_iter()
+            .filter_map(|(id, message)| readable("message", &id, message))
+            .collect();
+        Ok(MessagePage {
+            messages,
+            next_cursor,
+        })
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// Write the saved messages of one channel, or of every channel with None, to `path` as
+/// JSON or CSV, oldest first, and return how many there were. Messages are read and
+/// written one at a time, so histories of any size fit; `history-export-progress` is
+/// emitted as it goes. Credential-like fields in a message's extra data are redacted.
+#[command]
+pub async fn export_history(
+    app: AppHandle,
+    channel_id: Option<String>,
+    format: ExportFormat,
+    path: PathBuf,
+) -> Result<u64, String> {
+    // Read through a connection of its own so a long export doesn't hold up the store
+    let db_path = data_dir::data_dir(&app)?.join(DB_FILE);
+    tauri::async_runtime::spawn_blocking(move || {
+        let cipher = encryption::cipher(&app)?;
+        let db = Connection::open_with_flags(&db_path, OpenFlags::SQLITE_OPEN_READ_ONLY)
+            .map_err(|e| format!("Failed to open the message store: {}", e))?;
+        let mut partial = path.as_os_str().to_owned();
+        partial.push(".part");
+        let partial = PathBuf::from(partial);
+        let exported = write_history(
+            &app,
+            &db,
+            cipher.as_deref(),
+            channel_id.as_deref(),
+            format,
+            &partial,
+        )
+        .and_then(|exported| {
+            fs::rename(&partial, &path).map_err(|e| e.to_string())?;
+            Ok(exported)
+        });
+        if exported.is_err() {
+            let _ = fs::remove_file(&partial);
+        }
+        exported
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+fn write_history(
+    app: &AppHandle,
+    db: &Connection,
+    cipher: Option<&Cipher>,
+    channel_id: Option<&str>,
+    format: ExportFormat,
+    path: &Path,
+) -> Result<u64, String> {
+    let total: u64 = db
+        .query_row(
+            "SELECT COUNT(*) FROM messages WHERE ?1 IS NULL OR channel_id = ?1",
+            params![channel_id],
+            |row| row.get(0),
+        )
+        .map_err(|e| e.to_string())?;
+    let mut statement = db
+        .prepare(
+            "SELECT id, channel_id, author_id, content, data, created_at, edited_at
+             FROM messages
+             WHERE ?1 IS NULL OR channel_id = ?1
+             ORDER BY channel_id, created_at, id",
+        )
+        .map_err(|e| e.to_string())?;
+    let messages = statement
+        .query_map(params![channel_id], |row| {
+            Ok((row.get::<_, String>(0)?, message(row, cipher)))
+        })
+        .map_err(|e| e.to_string())?;
+
+    let file =
+        File::create(path).map_err(|e| format!("Failed to create {}: {}", path.display(), e))?;
+    let mut writer = HistoryWriter::new(BufWriter::new(file), format)
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    let mut exported = 0;
+    for row in messages {
+        let (id, message) = row.map_err(|e| e.to_string())?;
+        let Some(mut message) = readable("message", &id, message) else {
+            continue;
+        };
+        message.data = message.data.map(diagnostics::redact);
+        writer
+            .write(&message)
+            .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+        exported += 1;
+        if exported % EXPORT_PROGRESS_INTERVAL == 0 {
+            let _ = app.emit(
+                "history-export-progress",
+                ExportProgress { exported, total },
+            );
+        }
+    }
+    writer
+        .finish()
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    let _ = app.emit(
+        "history-export-progress",
+        ExportProgress {
+            exported,
+            total: total.max(exported),
+        },
+    );
+    Ok(exported)
+}
+
+/// Delete saved messages created before `cutoff` (milliseconds since the epoch) and
+/// return their ids, e.g. to drop them from the search index too
+pub fn purge_messages_before(cutoff: i64) -> Result<Vec<String>, String> {
+    let mut db = connection()?;
+    let transaction = db.transaction().map_err(|e| e.to_string())?;
+    let ids = transaction
+        .prepare("DELETE FROM messages WHERE created_at < ?1 RETURNING id")
+        .and_then(|mut statement| {
+            statement
+                .query_map(params![cutoff], |row| row.get(0))
+                .and_then(|rows| rows.collect::<Result<Vec<String>, _>>())
+        })
+        .map_err(|e| e.to_string())?;
+    transaction.commit().map_err(|e| e.to_string())?;
+    // Deleting only frees pages inside the file; giving the space back means rewriting it.
+    // An export reading at the same time makes this fail, which is fine.
+    if !ids.is_empty() {
+        if let Err(e) = db.execute_batch("VACUUM; PRAGMA wal_checkpoint(TRUNCATE);") {
+            log::info!("Didn't compact the message store: {}", e);
+        }
+    }
+    Ok(ids)
+}
+
+/// Rewrite the saved text of every channel and message from being sealed with `from` to
+/// being sealed with `to`, None meaning unencrypted. Text already in the target form is
+/// left alone, and text that can't be opened is logged and skipped.
+///
+/// It's one transaction, so a failure leaves the store as it was. `switch(true)` is called
+/// just before committing, with the store still locked, to put `to` in place for everything
+/// saved afterwards; `switch(false)` undoes that if the commit fails. The file is
+/// compacted afterwards so the old form doesn't linger in freed pages.
+pub fn reencrypt(
+    from: Option<&Cipher>,
+    to: Option<&Cipher>,
+    switch: impl Fn(bool) -> Result<(), String>,
+) -> Result<(), String> {
+    let mut db = connection()?;
+    let transaction = db.transaction().map_err(|e| e.to_string())?;
+    for (table, text) in [("channels", "name"), ("messages", "content")] {
+        let select = format!(
+            "SELECT rowid, {}, data FROM {} WHERE rowid > ?1 ORDER BY rowid LIMIT ?2",
+            text, table
+        );
+        let update = format!(
+            "UPDATE {} SET {} = ?2, data = ?3 WHERE rowid = ?1",
+            table, text
+        );
+        let mut after = 0;
+        loop {
+            let rows = transaction
+                .prepare_cached(&select)
+                .and_then(|mut statement| {
+                    statement
+                        .query_map(params![after, REENCRYPT_BATCH], |row| {
+                            Ok((row.get::<_, i64>(0)?, row.get(1)?, row.get(2)?))
+                        })
+                        .and_then(|rows| {
+                            rows.collect::<Result<Vec<(i64, SqlValue, SqlValue)>, _>>()
+                        })
+                })
+                .map_err(|e| e.to_string())?;
+            let Some(&(last, ..)) = rows.last() else {
+                break;
+            };
+            for (rowid, text, data) in rows {
+                let resealed = reseal(&text, from, to)
+                    .and_then(|new_text| Ok((new_text, reseal(&data, from, to)?)));
+                match resealed {
+                    Ok((None, None)) => {}
+                    Ok((new_text, new_data)) => {
+                        let text = new_text.unwrap_or(text);
+                        let data = new_data.unwrap_or(data);
+                        transaction
+                            .execute(&update, params![rowid, text, data])
+                            .map_err(|e| e.to_string())?;
+                    }
+                    Err(e) => {
+                        log::warn!("Skipped unreadable saved row {} in {}: {}", rowid, table, e)
+                    }
+                }
+            }
+            after = last;
+        }
+    }
+    switch(true)?;
+    if let Err(e) = transaction.commit() {
+        let _ = switch(false);
+        return Err(e.to_string());
+    }
+    if let Err(e) = db.execute_batch("VACUUM; PRAGMA wal_checkpoint(TRUNCATE);") {
+        log::warn!("Didn't compact the message store: {}", e);
+    }
+    Ok(())
+}
+
+/// Size of the message store's files on disk
+pub fn size(app: &AppHandle) -> u64 {
+    let Ok(dir) = data_dir::data_dir(app) else {
+        return 0;
+    };
+    // The write-ahead log holds changes not yet folded into the database file
+    ["", "-wal", "-shm"]
+        .iter()
+        .filter_map(|suffix| fs::metadata(dir.join(format!("{}{}", DB_FILE, suffix))).ok())
+        .map(|metadata| metadata.len())
+        .sum()
+}
+
+/// Open the message store in the data directory and bring its schema up to date. If that
+/// fails the app still starts, and the store's commands report the error.
+pub fn setup(app: &AppHandle) {
+    let opened = data_dir::data_dir(app).and_then(|dir| {
+        fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+        let mut db = Connection::open(dir.join(DB_FILE)).map_err(|e| e.to_string())?;
+        migrate(&mut db)?;
+        Ok(db)
+    });
+    let db = match opened {
+        Ok(db) => Some(Mutex::new(db)),
+        Err(e) => {
+            log::error!("Failed to open the message store: {}", e);
+            None
+        }
+    };
+    let _ = DB.set(db);
+}
+
+fn migrate(db: &mut Connection) -> Result<(), String> {
+    db.pragma_update(None, "journal_mode", "WAL")
+        .map_err(|e| e.to_string())?;
+    let version: usize = db
+        .pragma_query_value(None, "user_version", |row| row.get(0))
+        .map_err(|e| e.to_string())?;
+    for (index, migration) in MIGRATIONS.iter().enumerate().skip(version) {
+        let transaction = db.transaction().map_err(|e| e.to_string())?;
+        transaction
+            .execute_batch(migration)
+            .and_then(|_| transaction.pragma_update(None, "user_version", index + 1))
+            .and_then(|_| transaction.commit())
+            .map_err(|e| format!("Migration {} failed: {}", index + 1, e))?;
+    }
+    Ok(())
+}
+
+fn connection() -> Result<std::sync::MutexGuard<'static, Connection>, String> {
+    match DB.get() {
+        Some(Some(db)) => Ok(db.lock().unwrap()),
+        _ => Err("The message store couldn't be opened".to_string()),
+    }
+}
+
+// Streams messages out in either format
+enum HistoryWriter {
+    Csv(Box<csv::Writer<BufWriter<File>>>),
+    /// A JSON array, one message per line
+    Json {
+        writer: BufWriter<File>,
+        empty: bool,
+    },
+}
+
+impl HistoryWriter {
+    fn new(writer: BufWriter<File>, format: ExportFormat) -> Result<Self, String> {
+        match format {
+            ExportFormat::Csv => {
+                let mut csv = csv::Writer::from_writer(writer);
+                csv.write_record([
+                    "id",
+                    "channel_id",
+                    "author_id",
+                    "created_at",
+                    "edited_at",
+                    "content",
+                ])
+                .map_err(|e| e.to_string())?;
+                Ok(HistoryWriter::Csv(Box::new(csv)))
+            }
+            ExportFormat::Json => Ok(HistoryWriter::Json {
+                writer,
+                empty: true,
+            }),
+        }
+    }
+
+    fn write(&mut self, message: &StoredMessage) -> Result<(), String> {
+        match self {
+            HistoryWriter::Csv(csv) => csv
+                .write_record([
+                    message.id.as_str(),
+                    &message.channel_id,
+                    &message.author_id,
+                    &timestamp(message.created_at),
+                    &message.edited_at.map(timestamp).unwrap_or_default(),
+                    &message.content,
+                ])
+                .map_err(|e| e.to_string()),
+            HistoryWriter::Json { writer, empty } => {
+                let separator: &[u8] = if *empty { b"[\n" } else { b",\n" };
+                *empty = false;
+                writer.write_all(separator).map_err(|e| e.to_string())?;
+                serde_json::to_writer(writer, message).map_err(|e| e.to_string())
+            }
+        }
+    }
+
+    fn finish(self) -> Result<(), String> {
+        match self {
+            HistoryWriter::Csv(mut csv) => csv.flush().map_err(|e| e.to_string()),
+            HistoryWriter::Json { mut writer, empty } => {
+                let end: &[u8] = if empty { b"[]\n" } else { b"\n]\n" };
+                writer
+                    .write_all(end)
+                    .and_then(|_| writer.flush())
+                    .map_err(|e| e.to_string())
+            }
+        }
+    }
+}
+
+fn channel(row: &Row, cipher: Option<&Cipher>) -> rusqlite::Result<StoredChannel> {
+    Ok(StoredChannel {
+        id: row.get(0)?,
+        name: unseal(row, 1, cipher)?.unwrap_or_default(),
+        data: json(unseal(row, 2, cipher)?),
+        updated_at: row.get(3)?,
+    })
+}
+
+fn message(row: &Row, cipher: Option<&Cipher>) -> rusqlite::Result<StoredMessage> {
+    Ok(StoredMessage {
+        id: row.get(0)?,
+        channel_id: row.get(1)?,
+        author_id: row.get(2)?,
+        content: unseal(row, 3, cipher)?.unwrap_or_default(),
+        data: json(unseal(row, 4, cipher)?),
+        created_at: row.get(5)?,
+        edited_at: row.get(6)?,
+    })
+}
+
+// Kept as text without a cipher, sealed into a blob with one
+fn seal(cipher: Option<&Cipher>, text: &str) -> Result<SqlValue, String> {
+    match cipher {
+        Some(cipher) => cipher.seal(text.as_bytes()).map(SqlValue::Blob),
+        None => Ok(SqlValue::Text(text.to_string())),
+    }
+}
+
+fn seal_json(cipher: Option<&Cipher>, value: Option<&Value>) -> Result<SqlValue, String> {
+    match value {
+        Some(value) => seal(cipher, &value.to_string()),
+        None => Ok(SqlValue::Null),
+    }
+}
+
+// A column's value sealed with `to` instead of `from`; None when it's in that form already
+fn reseal(
+    value: &SqlValue,
+    from: Option<&Cipher>,
+    to: Option<&Cipher>,
+) -> Result<Option<SqlValue>, String> {
+    let text = match (value, to) {
+        (SqlValue::Blob(_), Some(_)) | (SqlValue::Text(_), None) | (SqlValue::Null, _) => {
+            return Ok(None)
+        }
+        (SqlValue::Text(text), Some(_)) => text.clone(),
+        (SqlValue::Blob(sealed), None) => {
+            let from = from.ok_or_else(|| "Encrypted, but no key was given".to_string())?;
+            String::from_utf8(from.open(sealed)?).map_err(|e| e.to_string())?
+        }
+        (value, _) => return Err(format!("Unexpected {:?}", value.data_type())),
+    };
+    seal(to, &text).map(Some)
+}
+
+// A row that can't be read, e.g. one sealed with another key, is logged and left out
+// rather than failing everything around it
+fn readable<T>(kind: &str, id: &str, row: rusqlite::Result<T>) -> Option<T> {
+    row.map_err(|e| log::warn!("Skipped unreadable saved {} {}: {}", kind, id, e))
+        .ok()
+}
+
+// Text columns hold text as is and blobs sealed text, so either reads no matter whether
+// encryption is on
+fn unseal(row: &Row, index: usize, cipher: Option<&Cipher>) -> rusqlite::Result<Option<String>> {
+    let ValueRef::Blob(sealed) = row.get_ref(index)? else {
+        return row.get(index);
+    };
+    let failed = |e: String| rusqlite::Error::FromSqlConversionFailure(index, Type::Blob, e.into());
+    let cipher =
+        cipher.ok_or_else(|| failed("This was encrypted, but encryption is off".to_string()))?;
+    let text = cipher.open(sealed).map_err(failed)?;
+    String::from_utf8(text)
+        .map(Some)
+        .map_err(|e| failed(e.to_string()))
+}
+
+// RFC 3339 in UTC, for spreadsheets; out of range timestamps are kept as milliseconds
+fn timestamp(millis: i64) -> String {
+    OffsetDateTime::from_unix_timestamp_nanos(millis as i128 * 1_000_000)
+        .ok()
+        .and_then(|time| time.format(&Rfc3339).ok())
+        .unwrap_or_else(|| millis.to_string())
+}
+
+// Stored JSON that no longer parses is dropped rather than failing the whole query
+fn json(text: Option<String>) -> Option<Value> {
+    text.and_then(|text| serde_json::from_str(&text).ok())
+}
@@ -0,0 +1,14 @@
+// Installed by devtools.rs. Ctrl/Cmd+Alt+Shift+I toggles devtools when the
+// devtools.enabled setting allows it; otherwise the command fails silently.
+(function () {
+	if (window.__hazelDevtoolsShortcut) return
+	window.__hazelDevtoolsShortcut = true
+
+	window.addEventListener("keydown", (event) => {
+		const modifier = navigator.platform.startsWith("Mac") ? event.metaKey : event.ctrlKey
+		if (modifier && event.altKey && event.shiftKey && event.code === "KeyI") {
+			event.preventDefault()
+			window.__TAURI__?.core.invoke("toggle_devtools").catch(() => {})
+		}
+	})
+})()
@@ -0,0 +1,72 @@
+use tauri::{command, AppHandle, Context, Manager, WebviewWindow};
+
+use crate::{kiosk, settings};
+
+const DEVTOOLS_ENABLED_KEY: &str = "devtools.enabled";
+
+/// Keyboard shortcut for `toggle_devtools`, kept out of the menus
+const SHORTCUT_SCRIPT: &str = include_str!("devtools.js");
+
+/// Allow or forbid devtools. Off by default in release builds. Turning them on takes
+/// effect from the next launch, as a webview's devtools are fixed when it's created.
+#[command]
+pub fn set_devtools_enabled(app: AppHandle, enabled: bool) -> Result<(), String> {
+    settings::set(&app, DEVTOOLS_ENABLED_KEY, enabled)?;
+    if !enabled {
+        for window in app.webview_windows().values() {
+            window.close_devtools();
+        }
+    }
+    Ok(())
+}
+
+/// Open devtools for the calling window. Fails unless enabled in settings, and in kiosk mode.
+#[command]
+pub fn open_devtools(window: WebviewWindow) -> Result<(), String> {
+    check_allowed(window.app_handle())?;
+    window.open_devtools();
+    Ok(())
+}
+
+#[command]
+pub fn close_devtools(window: WebviewWindow) {
+    window.close_devtools();
+}
+
+/// Open or close devtools for the calling window, for the Ctrl/Cmd+Alt+Shift+I shortcut
+#[command]
+pub fn toggle_devtools(window: WebviewWindow) -> Result<(), String> {
+    if window.is_devtools_open() {
+        window.close_devtools();
+        return Ok(());
+    }
+    open_devtools(window)
+}
+
+/// Create the config windows with devtools only if allowed, and never under `--kiosk`, so
+/// they don't offer Inspect Element from the context menu. Must run before the app is built.
+pub fn prepare(context: &mut Context) {
+    let identifier = &context.config().identifier;
+    let enabled = settings::get_at_startup(identifier, DEVTOOLS_ENABLED_KEY)
+        .unwrap_or(cfg!(debug_assertions))
+        && !kiosk::requested();
+    for window in &mut context.config_mut().app.windows {
+        window.devtools = Some(enabled);
+    }
+}
+
+/// Install the devtools shortcut in a freshly loaded page
+pub fn install_shortcut(window: &WebviewWindow) {
+    let _ = window.eval(SHORTCUT_SCRIPT);
+}
+
+fn check_allowed(app: &AppHandle) -> Result<(), String> {
+    let enabled = settings::get(app, DEVTOOLS_ENABLED_KEY).unwrap_or(cfg!(debug_assertions));
+    if !enabled {
+        return Err("Devtools are disabled; turn on devtools.enabled first".to_string());
+    }
+    if kiosk::enabled() {
+        return Err("Devtools are disabled in kiosk mode".to_string());
+    }
+    Ok(())
+}
@@ -0,0 +1,382 @@
+use std::fs;
+use std::ops::Range;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+use tauri::{command, AppHandle, Manager};
+
+use crate::{bandwidth, data_dir, emulation, flags, http, logging, settings, updates};
+
+/// Replaces values that must never leave the machine
+const REDACTED: &str = "[redacted]";
+
+/// Setting keys containing any of these hold credentials
+const SECRET_KEY_PARTS: &[&str] = &["token", "nonce", "secret", "password", "cookie", "auth"];
+
+const BUG_REPORT_ENDPOINT_KEY: &str = "diagnostics.bug_report_endpoint";
+
+/// Folder in the data directory for reports that couldn't be uploaded
+const BUG_REPORT_DIR: &str = "bug-reports";
+
+/// Log lines attached to a bug report
+const BUG_REPORT_LOG_LINES: usize = 500;
+
+/// Environment variables that configure a proxy for the updater's HTTP client
+const PROXY_VARS: &[&str] = &[
+    "HTTPS_PROXY",
+    "https_proxy",
+    "HTTP_PROXY",
+    "http_proxy",
+    "ALL_PROXY",
+    "all_proxy",
+    "NO_PROXY",
+    "no_proxy",
+];
+
+/// The configuration in effect, for pasting into support tickets: the native settings,
+/// data and log folders, log level, update endpoints, proxy and feature flags. Anything
+/// that looks like a credential is redacted, and the frontend's store (which holds session
+/// tokens) is left out entirely.
+#[command]
+pub fn dump_config(app: AppHandle) -> Value {
+    let path = |dir: Result<std::path::PathBuf, String>| match dir {
+        Ok(dir) => Value::String(dir.display().to_string()),
+        Err(_) => Value::Null,
+    };
+    let settings: Map<String, Value> = settings::entries(&app).into_iter().collect();
+    let update_endpoints = app
+        .config()
+        .plugins
+        .0
+        .get("updater")
+        .and_then(|updater| updater.get("endpoints"))
+        .cloned()
+        .unwrap_or(Value::Null);
+    let proxy: Map<String, Value> = PROXY_VARS
+        .iter()
+        .filter_map(|name| {
+            let value = std::env::var(name).ok()?;
+            Some((name.to_string(), Value::String(redact_url(&value))))
+        })
+        .collect();
+
+    json!({
+        "version": app.package_info().version.to_string(),
+        "os": std::env::consts::OS,
+        "arch": std::env::consts::ARCH,
+        "nativeArch": emulation::native_arch(),
+        "debugBuild": cfg!(debug_assertions),
+        "identifier": app.config().identifier,
+        "settings": redact(Value::Object(settings)),
+        "dataDir": path(data_dir::data_dir(&app)),
+        "cacheDir": path(data_dir::cache_dir(&app)),
+        "logDir": path(app.path().app_log_dir().map_err(|e| e.to_string())),
+        "updateEndpoints": update_endpoints,
+        "versionPin": updates::version_pin(),
+        "userAgent": http::user_agent(app.clone()),
+        "proxy": proxy,
+        "featureFlags": flags::feature_flags(app.clone()),
+        "logLevel": logging::log_level(),
+    })
+}
+
+/// Outcome of `submit_bug_report`: the server's ticket id, or where the report was saved
+/// when it couldn't be uploaded
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BugReport {
+    ticket_id: Option<String>,
+    saved_path: Option<PathBuf>,
+}
+
+/// Set where `submit_bug_report` uploads reports; None turns uploading off
+#[command]
+pub fn set_bug_report_endpoint(app: AppHandle, url: Option<String>) -> Result<(), String> {
+    match url {
+        Some(url) => {
+            let parsed = reqwest::Url::parse(&url).map_err(|e| format!("Invalid URL: {}", e))?;
+            if parsed.scheme() != "https" {
+                return Err("Bug reports can only be sent over https".to_string());
+            }
+            settings::set(&app, BUG_REPORT_ENDPOINT_KEY, url)
+        }
+        None => settings::delete(&app, BUG_REPORT_ENDPOINT_KEY),
+    }
+}
+
+/// Send a bug report with the user's description and `dump_config`, plus the last 500 log
+/// lines only when `include_logs` is set, which must come from the user ticking a box.
+/// Credentials are redacted and the home folder is replaced with `~` throughout.
+///
+/// The server answers with `{ "id": "..." }`. Without an endpoint, or when the upload
+/// fails, the report is saved to the data directory instead and its path returned.
+#[command]
+pub async fn submit_bug_report(
+    app: AppHandle,
+    description: String,
+    include_logs: bool,
+) -> Result<BugReport, String> {
+    let logs = include_logs.then(|| {
+        logging::recent_logs(BUG_REPORT_LOG_LINES)
+            .iter()
+            .map(|line| redact_text(line))
+            .collect::<Vec<_>>()
+    });
+    let report = json!({
+        "description": redact_text(&description),
+        "diagnostics": dump_config(app.clone()),
+        "logs": logs,
+    });
+    let report = anonymize(&report.to_string());
+
+    if let Some(endpoint) = settings::get::<String>(&app, BUG_REPORT_ENDPOINT_KEY) {
+        match upload_bug_report(&app, &endpoint, report.clone()).await {
+            Ok(ticket_id) => {
+                return Ok(BugReport {
+                    ticket_id: Some(ticket_id),
+                    saved_path: None,
+                })
+            }
+            Err(e) => log::warn!("Failed to upload bug report, saving it instead: {}", e),
+        }
+    }
+
+    let dir = data_dir::data_dir(&app)?.join(BUG_REPORT_DIR);
+    let millis = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis();
+    let path = dir.join(format!("bug-report-{}.json", millis));
+    fs::create_dir_all(&dir)
+        .and_then(|_| fs::write(&path, report))
+        .map_err(|e| e.to_string())?;
+    Ok(BugReport {
+        ticket_id: None,
+        saved_path: Some(path),
+    })
+}
+
+async fn upload_bug_report(
+    app: &AppHandle,
+    endpoint: &str,
+    report: String,
+) -> Result<String, String> {
+    // Without streaming bodies the whole report is paced before it's sent
+    let endpoint = http::check_url(app, endpoint, true).await?;
+    bandwidth::throttle_upload(report.len()).await;
+    let response = http::client(app)?
+        .post(endpoint)
+        .header("Content-Type", "application/json")
+        .body(report)
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| e.to_string())?;
+    let body: Value = response.json().await.map_err(|e| e.to_string())?;
+    match body.get("id") {
+        Some(Value::String(id)) => Ok(id.clone()),
+        Some(Value::Number(id)) => Ok(id.to_string()),
+        _ => Err("The response has no ticket id".to_string()),
+    }
+}
+
+// Home folder paths name the user account
+fn anonymize(text: &str) -> String {
+    let Some(home) = dirs::home_dir() else {
+        return text.to_string();
+    };
+    let home = home.display().to_string();
+    if home.len() < 2 {
+        return text.to_string();
+    }
+    // Paths are JSON-escaped in the report, so Windows backslashes appear doubled
+    text.replace(&home.replace('\\', "\\\\"), "~")
+}
+
+/// Replace the values of credential-like keys, at any depth
+pub fn redact(value: Value) -> Value {
+    match value {
+        Value::Object(map) => Value::Object(
+            map.into_iter()
+                .map(|(key, value)| {
+                    let lower = key.to_lowercase();
+                    let value = if SECRET_KEY_PARTS.iter().any(|part| lower.contains(part)) {
+                        Value::String(REDACTED.to_string())
+                    } else {
+                        redact(value)
+                    };
+                    (key, value)
+                })
+                .collect(),
+        ),
+        Value::Array(items) => Value::Array(items.into_iter().map(redact).collect()),
+        value => value,
+    }
+}
+
+/// Mask values that follow credential-like names in free text, e.g. `token=abc` or
+/// `"password": "hunter2"`, along with bearer tokens
+pub fn redact_text(text: &str) -> String {
+    // ASCII lowercasing keeps byte offsets lined up with `text`, and values only start
+    // and end next to ASCII delimiters, so every slice lands on a char boundary
+    let lower = text.to_ascii_lowercase();
+    let bytes = text.as_bytes();
+    let mut out = String::with_capacity(text.len());
+    let mut copied = 0;
+    let mut i = 0;
+    while i < bytes.len() {
+        let Some(value) = secret_value_at(&lower, i) else {
+            i += 1;
+            continue;
+        };
+        out.push_str(&text[copied..value.start]);
+        out.push_str(REDACTED);
+        copied = value.end;
+        i = value.end.max(i + 1);
+    }
+    out.push_str(&text[copied..]);
+    out
+}
+
+// Byte range of the value if a secret name or `bearer` starts at `start`
+fn secret_value_at(lower: &str, start: usize) -> Option<Range<usize>> {
+    let bytes = lower.as_bytes();
+    let is_word = |b: u8| b.is_ascii_alphanumeric() || b == b'_' || b == b'-';
+    let rest = &bytes[start..];
+    let mut i = start;
+    if rest.starts_with(b"bearer ") {
+        i += "bearer ".len();
+    } else {
+        SECRET_KEY_PARTS
+            .iter()
+            .find(|part| rest.starts_with(part.as_bytes()))?;
+        // Rest of the name, e.g. `tokens` or `token_id`
+        while i < bytes.len() && is_word(bytes[i]) {
+            i += 1;
+        }
+        while i < bytes.len() && matches!(bytes[i], b'"' | b'\'' | b' ') {
+            i += 1;
+        }
+        if i >= bytes.len() || !matches!(bytes[i], b'=' | b':') {
+            return None;
+        }
+        i += 1;
+        while i < bytes.len() && bytes[i] == b' ' {
+            i += 1;
+        }
+        // `Authorization: Bearer <token>` hides the token, not the scheme
+        for scheme in ["bearer ", "basic "] {
+            if bytes[i..].starts_with(scheme.as_bytes()) {
+                i += scheme.len();
+            }
+        }
+    }
+    let quote = bytes.get(i).copied().filter(|b| matches!(b, b'"' | b'\''));
+    if quote.is_some() {
+        i += 1;
+    }
+    let value_start = i;
+    while i < bytes.len() {
+        let b = bytes[i];
+        let ends = match quote {
+            Some(quote) => b == quote,
+            None => {
+                b.is_ascii_whitespace()
+                    || matches!(b, b'&' | b',' | b';' | b'}' | b')' | b'"' | b'\'')
+            }
+        };
+        if ends {
+            break;
+        }
+        i += 1;
+    }
+    (i > value_start).then_some(value_start..i)
+}
+
+// Proxy URLs can carry credentials as `user:password@`
+fn redact_url(url: &str) -> String {
+    let (scheme, rest) = match url.split_once("://") {
+        Some((scheme, rest)) => (Some(scheme), rest),
+        None => (None, url),
+    };
+    let Some((_, host)) = rest.split_once('@') else {
+        return url.to_string();
+    };
+    match scheme {
+        Some(scheme) => format!("{}://{}@{}", scheme, REDACTED, host),
+        None => format!("{}@{}", REDACTED, host),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn secrets_in_text_are_redacted() {
+        for (text, expected) in [
+            ("token=abc123", "token=[redacted]"),
+            ("url?token=abc&page=2", "url?token=[redacted]&page=2"),
+            (r#"password: "hunter2""#, r#"password: "[redacted]""#),
+            (
+                r#"{"token":"abc","id":1}"#,
+                r#"{"token":"[redacted]","id":1}"#,
+            ),
+            (
+                r#"{"access_token": "abc", "ok": true}"#,
+                r#"{"access_token": "[redacted]", "ok": true}"#,
+            ),
+            (
+                "Authorization: Bearer eyJ.abc.def",
+                "Authorization: Bearer [redacted]",
+            ),
+            (
+                "authorization: basic dXNlcjpwYXNz",
+                "authorization: basic [redacted]",
+            ),
+            (
+                "sent Bearer abc123 to the server",
+                "sent Bearer [redacted] to the server",
+            ),
+            ("TOKEN=ABC", "TOKEN=[redacted]"),
+            ("Password: Hunter2", "Password: [redacted]"),
+            (
+                "Set-Cookie: session=abc; Path=/",
+                "Set-Cookie: [redacted]; Path=/",
+            ),
+            ("done, token=abc", "done, token=[redacted]"),
+        ] {
+            assert_eq!(redact_text(text), expected, "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn secrets_next_to_multibyte_text() {
+        for (text, expected) in [
+            ("ключ token=секрет готово", "ключ token=[redacted] готово"),
+            ("token=ключ, дальше", "token=[redacted], дальше"),
+            (
+                "пароль password='日本語' 終わり",
+                "пароль password='[redacted]' 終わり",
+            ),
+        ] {
+            assert_eq!(redact_text(text), expected, "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn other_text_is_untouched() {
+        for text in [
+            "",
+            "no credentials here",
+            "tokens are counted per request",
+            "the secret is out: 42",
+            "retrying with token=",
+            "ключ: значение",
+        ] {
+            assert_eq!(redact_text(text), text);
+        }
+    }
+}
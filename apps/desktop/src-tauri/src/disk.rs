@@ -0,0 +1,90 @@
+use std::path::{Path, PathBuf};
+
+use tauri::command;
+
+/// Free bytes on the volume holding `for_path`, as available to this user. The path
+/// doesn't have to exist yet; its nearest existing parent decides the volume.
+#[command]
+pub fn available_disk_space(for_path: PathBuf) -> Result<u64, String> {
+    let existing = for_path
+        .ancestors()
+        .find(|path| path.exists())
+        .ok_or_else(|| format!("No such volume for {}", for_path.display()))?;
+    fs2::available_space(existing).map_err(|e| {
+        format!(
+            "Failed to read free space for {}: {}",
+            for_path.display(),
+            e
+        )
+    })
+}
+
+/// Fail with a message the user can act on when `needed` bytes won't fit at `path`. If
+/// free space can't be read the write is let through and fails on its own if it must.
+pub fn ensure_space(path: &Path, needed: u64) -> Result<(), String> {
+    let available = match available_disk_space(path.to_path_buf()) {
+        Ok(available) => available,
+        Err(e) => {
+            log::warn!("{}", e);
+            return Ok(());
+        }
+    };
+    if available < needed {
+        return Err(format!(
+            "Not enough disk space: {} is needed but only {} is free",
+            format_size(needed),
+            format_size(available)
+        ));
+    }
+    Ok(())
+}
+
+fn format_size(bytes: u64) -> String {
+    const UNITS: &[&str] = &["bytes", "KB", "MB", "GB", "TB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    match unit {
+        0 => format!("{} bytes", bytes),
+        _ => format!("{:.1} {}", size, UNITS[unit]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_tiny_write_fits() {
+        assert_eq!(ensure_space(&std::env::temp_dir(), 1), Ok(()));
+        assert_eq!(ensure_space(&std::env::temp_dir(), 0), Ok(()));
+    }
+
+    #[test]
+    fn a_huge_write_does_not_fit() {
+        let error = ensure_space(&std::env::temp_dir(), u64::MAX).unwrap_err();
+        assert!(
+            error.starts_with("Not enough disk space: 16777216.0 TB is needed"),
+            "{}",
+            error
+        );
+    }
+
+    #[test]
+    fn missing_paths_use_their_nearest_parent() {
+        let missing = std::env::temp_dir().join("hazel-disk-test").join("not-yet");
+        assert!(available_disk_space(missing.clone()).unwrap() > 0);
+        assert!(ensure_space(&missing, u64::MAX).is_err());
+    }
+
+    #[test]
+    fn sizes_are_readable() {
+        assert_eq!(format_size(0), "0 bytes");
+        assert_eq!(format_size(1023), "1023 bytes");
+        assert_eq!(format_size(1536), "1.5 KB");
+        assert_eq!(format_size(5 * 1024 * 1024 * 1024), "5.0 GB");
+    }
+}
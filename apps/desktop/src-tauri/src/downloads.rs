@@ -0,0 +1,475 @@
+use std::collections::{HashMap, VecDeque};
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tauri::{command, AppHandle, Emitter};
+
+use crate::{bandwidth, cache, disk, http};
+
+/// Most downloads a batch runs at once, however many are asked for
+const MAX_CONCURRENCY: usize = 8;
+
+// Cancel flags of the batches running now, by id
+fn batches() -> &'static Mutex<HashMap<String, Arc<AtomicBool>>> {
+    static BATCHES: OnceLock<Mutex<HashMap<String, Arc<AtomicBool>>>> = OnceLock::new();
+    BATCHES.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+#[derive(Clone, Copy, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum HashAlgorithm {
+    #[default]
+    Sha256,
+    Blake3,
+}
+
+/// Outcome of `download_file`
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DownloadedFile {
+    /// None when the file didn't match the expected hash and was deleted
+    path: Option<PathBuf>,
+    /// Lowercase hex
+    hash: String,
+    algorithm: HashAlgorithm,
+    /// None when no hash was expected
+    verified: Option<bool>,
+}
+
+/// One file of `download_batch`, with the same options as `download_file`
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BatchItem {
+    url: String,
+    expected_hash: Option<String>,
+    algorithm: Option<HashAlgorithm>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BatchItemResult {
+    url: String,
+    file: Option<DownloadedFile>,
+    /// Why the download failed, including hash mismatches
+    error: Option<String>,
+}
+
+/// Outcome of `download_batch`, with results in the order the items were given
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BatchSummary {
+    batch_id: String,
+    succeeded: usize,
+    failed: usize,
+    cancelled: bool,
+    results: Vec<BatchItemResult>,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct BatchProgress<'a> {
+    batch_id: &'a str,
+    finished: usize,
+    failed: usize,
+    total: usize,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct DownloadProgress<'a> {
+    url: &'a str,
+    downloaded: u64,
+    total: Option<u64>,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct DownloadChecked<'a> {
+    url: &'a str,
+    expected: &'a str,
+    actual: &'a str,
+}
+
+// Running hash of either kind
+enum Hasher {
+    Sha256(Sha256),
+    Blake3(Box<blake3::Hasher>),
+}
+
+impl Hasher {
+    fn new(algorithm: HashAlgorithm) -> Self {
+        match algorithm {
+            HashAlgorithm::Sha256 => Hasher::Sha256(Sha256::new()),
+            HashAlgorithm::Blake3 => Hasher::Blake3(Box::new(blake3::Hasher::new())),
+        }
+    }
+
+    fn update(&mut self, bytes: &[u8]) {
+        match self {
+            Hasher::Sha256(hasher) => hasher.update(bytes),
+            Hasher::Blake3(hasher) => {
+                hasher.update(bytes);
+            }
+        }
+    }
+
+    fn finish(self) -> String {
+        let digest: Vec<u8> = match self {
+            Hasher::Sha256(hasher) => hasher.finalize().to_vec(),
+            Hasher::Blake3(hasher) => hasher.finalize().as_bytes().to_vec(),
+        };
+        digest.iter().map(|byte| format!("{:02x}", byte)).collect()
+    }
+}
+
+/// Download a file into the attachments cache, emitting `download-progress`, and return
+/// where it was saved with its hash (SHA-256 unless another algorithm is asked for).
+///
+/// With `expected_hash`, the file is checked once complete: `download-verified` is
+/// emitted when it matches, and when it doesn't the file is deleted and
+/// `download-corrupt` emitted. The computed hash is returned either way.
+#[command]
+pub async fn download_file(
+    app: AppHandle,
+    url: String,
+    expected_hash: Option<String>,
+    algorithm: Option<HashAlgorithm>,
+) -> Result<DownloadedFile, String> {
+    fetch(&app, &url, expected_hash, algorithm, &|| false).await
+}
+
+/// Download several files, at most `concurrency` at a time (by default one per CPU, up
+/// to 8), e.g. for "download all" in a channel. Each file emits `download-progress` as
+/// with `download_file`, `download-batch-progress` follows every finished file, and the
+/// summary is emitted as `download-batch-finished` as well as returned.
+/// `cancel_download_batch` with the same id stops it; files in progress are deleted and
+/// the rest are skipped.
+#[command]
+pub async fn download_batch(
+    app: AppHandle,
+    batch_id: String,
+    items: Vec<BatchItem>,
+    concurrency: Option<usize>,
+) -> Result<BatchSummary, String> {
+    if concurrency == Some(0) {
+        return Err("concurrency must be at least 1".to_string());
+    }
+    let cancel = Arc::new(AtomicBool::new(false));
+    {
+        let mut batches = batches().lock().unwrap();
+        if batches.contains_key(&batch_id) {
+            return Err(format!("Batch {} is already running", batch_id));
+        }
+        batches.insert(batch_id.clone(), cancel.clone());
+    }
+
+    let total = items.len();
+    let workers = concurrency
+        .unwrap_or_else(|| std::thread::available_parallelism().map_or(4, |n| n.get()))
+        .min(MAX_CONCURRENCY)
+        .min(total.max(1));
+    let queue = Arc::new(Mutex::new(
+        items.into_iter().enumerate().collect::<VecDeque<_>>(),
+    ));
+    let results = Arc::new(Mutex::new(Vec::with_capacity(total)));
+
+    let handles: Vec<_> = (0..workers)
+        .map(|_| {
+            let app = app.clone();
+            let batch_id = batch_id.clone();
+            let queue = queue.clone();
+            let results = results.clone();
+            let cancel = cancel.clone();
+            tauri::async_runtime::spawn(async move {
+                loop {
+                    if cancel.load(Ordering::Relaxed) {
+                        break;
+                    }
+                    let Some((index, item)) = queue.lock().unwrap().pop_front() else {
+                        break;
+                    };
+                    let is_cancelled = || cancel.load(Ordering::Relaxed);
+                    let result = fetch(
+                        &app,
+                        &item.url,
+                        item.expected_hash,
+                        item.algorithm,
+                        &is_cancelled,
+                    )
+                    .await;
+                    let result = match result {
+                        Ok(file) if file.verified == Some(false) => BatchItemResult {
+                            url: item.url,
+                            file: Some(file),
+                            error: Some("The file doesn't match its expected hash".to_string()),
+                        },
+                        Ok(file) => BatchItemResult {
+                            url: item.url,
+                            file: Some(file),
+                            error: None,
+                        },
+                        Err(e) => BatchItemResult {
+                            url: item.url,
+                            file: None,
+                            error: Some(e),
+                        },
+                    };
+
+                    let mut results = results.lock().unwrap();
+                    results.push((index, result));
+                    let progress = BatchProgress {
+                        batch_id: &batch_id,
+                        finished: results.len(),
+                        failed: results.iter().filter(|(_, r)| r.error.is_some()).count(),
+                        total,
+                    };
+                    let _ = app.emit("download-batch-progress", progress);
+                }
+            })
+        })
+        .collect();
+    let mut joined = Ok(());
+    for handle in handles {
+        joined = joined.and(handle.await.map_err(|e| e.to_string()));
+    }
+    batches().lock().unwrap().remove(&batch_id);
+    joined?;
+
+    let mut results = std::mem::take(&mut *results.lock().unwrap());
+    results.sort_by_key(|(index, _)| *index);
+    let results: Vec<_> = results.into_iter().map(|(_, result)| result).collect();
+    let failed = results
+        .iter()
+        .filter(|result| result.error.is_some())
+        .count();
+    let summary = BatchSummary {
+        batch_id,
+        succeeded: results.len() - failed,
+        failed,
+        cancelled: cancel.load(Ordering::Relaxed),
+        results,
+    };
+    let _ = app.emit("download-batch-finished", &summary);
+    Ok(summary)
+}
+
+/// Stop a running `download_batch`. Returns false if no batch has that id.
+#[command]
+pub fn cancel_download_batch(batch_id: String) -> bool {
+    match batches().lock().unwrap().get(&batch_id) {
+        Some(cancel) => {
+            cancel.store(true, Ordering::Relaxed);
+            true
+        }
+        None => false,
+    }
+}
+
+// Stops early, deleting the partial file, once `cancelled` returns true
+async fn fetch(
+    app: &AppHandle,
+    url: &str,
+    expected_hash: Option<String>,
+    algorithm: Option<HashAlgorithm>,
+    cancelled: &(dyn Fn() -> bool + Sync),
+) -> Result<DownloadedFile, String> {
+    let parsed = http::check_url(app, url, false).await?;
+    let algorithm = algorithm.unwrap_or_default();
+    let expected_hash = expected_hash.map(|hash| hash.trim().to_lowercase());
+
+    let dir = cache::dir(app, "attachments")?.join("downloads");
+    let name = file_name(&parsed);
+    fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+
+    let mut response = http::client(app)?
+        .get(parsed)
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| format!("Failed to download {}: {}", url, e))?;
+    let total = response.content_length();
+    if let Some(total) = total {
+        disk::ensure_space(&dir, total)?;
+    }
+
+    let (path, mut file) = create_unique(&dir, &name)?;
+    let mut hasher = Hasher::new(algorithm);
+    let mut downloaded = 0;
+    loop {
+        if cancelled() {
+            drop(file);
+            let _ = fs::remove_file(&path);
+            return Err("cancelled".to_string());
+        }
+        let chunk = match response.chunk().await {
+            Ok(Some(chunk)) => chunk,
+            Ok(None) => break,
+            Err(e) => {
+                drop(file);
+                let _ = fs::remove_file(&path);
+                return Err(format!("Failed to download {}: {}", url, e));
+            }
+        };
+        if let Err(e) = file.write_all(&chunk) {
+            drop(file);
+            let _ = fs::remove_file(&path);
+            return Err(format!("Failed to save {}: {}", url, e));
+        }
+        hasher.update(&chunk);
+        downloaded += chunk.len() as u64;
+        let progress = DownloadProgress {
+            url,
+            downloaded,
+            total,
+        };
+        let _ = app.emit("download-progress", progress);
+        bandwidth::throttle_download(chunk.len()).await;
+    }
+    drop(file);
+
+    let hash = hasher.finish();
+    let verified = expected_hash.map(|expected| {
+        let checked = DownloadChecked {
+            url,
+            expected: &expected,
+            actual: &hash,
+        };
+        let matches = verify(&path, &expected, &hash);
+        if matches {
+            let _ = app.emit("download-verified", checked);
+        } else {
+            log::warn!("{} doesn't match its expected hash, deleted it", url);
+            let _ = app.emit("download-corrupt", checked);
+        }
+        matches
+    });
+    Ok(DownloadedFile {
+        path: (verified != Some(false)).then_some(path),
+        hash,
+        algorithm,
+        verified,
+    })
+}
+
+// Whether a downloaded file's hash is the expected one, deleting the file when it isn't
+fn verify(path: &Path, expected: &str, actual: &str) -> bool {
+    let matches = expected == actual;
+    if !matches {
+        let _ = fs::remove_file(path);
+    }
+    matches
+}
+
+// Create `name` in `dir`, or `name (1)`, `name (2)` and so on if it's taken, so files
+// with the same name don't overwrite each other
+fn create_unique(dir: &Path, name: &str) -> Result<(PathBuf, File), String> {
+    let (stem, extension) = match name.rfind('.') {
+        Some(dot) if dot > 0 => name.split_at(dot),
+        _ => (name, ""),
+    };
+    let mut candidate = name.to_string();
+    let mut copy = 1;
+    loop {
+        let path = dir.join(&candidate);
+        match File::create_new(&path) {
+            Ok(file) => return Ok((path, file)),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+                candidate = format!("{} ({}){}", stem, copy, extension);
+                copy += 1;
+            }
+            Err(e) => return Err(format!("Failed to create {}: {}", path.display(), e)),
+        }
+    }
+}
+
+// The URL's last path segment, made safe to use as a file name
+fn file_name(url: &reqwest::Url) -> String {
+    let name = url
+        .path_segments()
+        .and_then(|mut segments| segments.next_back())
+        .map(|name| urlencoding::decode(name).map_or(name.to_string(), |name| name.into_owned()))
+        .unwrap_or_default();
+    let name: String = name
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect();
+    let name = name.trim_matches(|c: char| c == '.' || c.is_whitespace());
+    match name.is_empty() {
+        true => "download".to_string(),
+        false => name.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ABC_SHA256: &str = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+    const ABC_BLAKE3: &str = "6437b3ac38465133ffb63b75273a8db548c558465d79db03fd359c6cd5bd9d85";
+
+    fn download(name: &str, contents: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("hazel-downloads-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(name);
+        fs::write(&path, contents).unwrap();
+        path
+    }
+
+    fn hash(algorithm: HashAlgorithm, contents: &[u8]) -> String {
+        let mut hasher = Hasher::new(algorithm);
+        // In pieces, as chunks arrive
+        for piece in contents.chunks(2) {
+            hasher.update(piece);
+        }
+        hasher.finish()
+    }
+
+    #[test]
+    fn hashes_are_lowercase_hex() {
+        assert_eq!(hash(HashAlgorithm::Sha256, b"abc"), ABC_SHA256);
+        assert_eq!(hash(HashAlgorithm::Blake3, b"abc"), ABC_BLAKE3);
+    }
+
+    #[test]
+    fn matching_file_is_kept() {
+        let path = download("match.txt", "abc");
+        assert!(verify(
+            &path,
+            ABC_SHA256,
+            &hash(HashAlgorithm::Sha256, b"abc")
+        ));
+        assert!(path.exists());
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn mismatched_file_is_deleted() {
+        let path = download("mismatch.txt", "abd");
+        assert!(!verify(
+            &path,
+            ABC_SHA256,
+            &hash(HashAlgorithm::Sha256, b"abd")
+        ));
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn taken_names_get_a_number() {
+        let dir = download("taken.tar.gz", "").parent().unwrap().to_path_buf();
+        fs::write(dir.join("taken.tar (1).gz"), "").unwrap();
+        let (path, _) = create_unique(&dir, "taken.tar.gz").unwrap();
+        assert_eq!(path, dir.join("taken.tar (2).gz"));
+        for name in ["taken.tar.gz", "taken.tar (1).gz", "taken.tar (2).gz"] {
+            fs::remove_file(dir.join(name)).unwrap();
+        }
+    }
+}
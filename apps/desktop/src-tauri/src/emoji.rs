@@ -0,0 +1,68 @@
+use tauri::{command, AppHandle};
+
+/// Open the OS emoji panel (Ctrl+Cmd+Space on macOS, Win+. on Windows) for the focused input.
+///
+/// The panel types the chosen emoji straight into the focused field, so the webview receives
+/// it as ordinary input and there's nothing for the native side to capture as `emoji-selected`.
+/// Emits `emoji-picker-unavailable` on platforms without a native panel so the frontend can
+/// open its own picker instead.
+#[command]
+pub fn open_emoji_picker(app: AppHandle) -> Result<(), String> {
+    show_native_picker(&app)
+}
+
+#[cfg(target_os = "macos")]
+fn show_native_picker(app: &AppHandle) -> Result<(), String> {
+    app.run_on_main_thread(|| {
+        let Some(mtm) = objc2::MainThreadMarker::new() else {
+            return;
+        };
+        objc2_app_kit::NSApplication::sharedApplication(mtm).orderFrontCharacterPalette(None);
+    })
+    .map_err(|e| e.to_string())
+}
+
+#[cfg(target_os = "windows")]
+fn show_native_picker(_app: &AppHandle) -> Result<(), String> {
+    use windows_sys::Win32::UI::Input::KeyboardAndMouse::{
+        SendInput, INPUT, INPUT_0, INPUT_KEYBOARD, KEYBDINPUT, KEYEVENTF_KEYUP, VIRTUAL_KEY,
+        VK_LWIN, VK_OEM_PERIOD,
+    };
+
+    let key = |vk: VIRTUAL_KEY, flags| INPUT {
+        r#type: INPUT_KEYBOARD,
+        Anonymous: INPUT_0 {
+            ki: KEYBDINPUT {
+                wVk: vk,
+                dwFlags: flags,
+                ..Default::default()
+            },
+        },
+    };
+    // Win+. opens the emoji panel for whichever window has keyboard focus
+    let inputs = [
+        key(VK_LWIN, 0),
+        key(VK_OEM_PERIOD, 0),
+        key(VK_OEM_PERIOD, KEYEVENTF_KEYUP),
+        key(VK_LWIN, KEYEVENTF_KEYUP),
+    ];
+    let sent = unsafe {
+        SendInput(
+            inputs.len() as u32,
+            inputs.as_ptr(),
+            std::mem::size_of::<INPUT>() as i32,
+        )
+    };
+    if sent as usize != inputs.len() {
+        return Err("Failed to open the emoji panel".into());
+    }
+    Ok(())
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+fn show_native_picker(app: &AppHandle) -> Result<(), String> {
+    use tauri::Emitter;
+
+    let _ = app.emit("emoji-picker-unavailable", ());
+    Ok(())
+}
@@ -0,0 +1,95 @@
+use std::sync::OnceLock;
+
+use serde::Serialize;
+use tauri::{command, Emitter, Manager, WebviewWindow};
+
+use crate::settings;
+
+const NOTIFIED_KEY: &str = "emulation.notified";
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct EmulationDetected {
+    /// What the app was built for
+    arch: &'static str,
+    /// What the machine is, i.e. which build to suggest
+    native_arch: &'static str,
+}
+
+/// Whether this build is running translated on a different CPU, e.g. the Intel build
+/// on Apple Silicon under Rosetta, or the x64 build on Windows on Arm. Always false on
+/// Linux.
+#[command]
+pub fn is_emulated() -> bool {
+    native_arch() != std::env::consts::ARCH
+}
+
+/// The machine's own CPU architecture, in Rust's naming (`aarch64`, `x86_64`, `x86`)
+#[command]
+pub fn native_arch() -> &'static str {
+    // Can't change while the app runs
+    static NATIVE: OnceLock<&'static str> = OnceLock::new();
+    NATIVE.get_or_init(detect_native_arch)
+}
+
+/// Emit `emulation-detected` to the main window the first time the app is found running
+/// under emulation, so it can suggest the native build. Later launches stay quiet.
+pub fn notify_if_emulated(window: &WebviewWindow) {
+    if window.label() != "main" || !is_emulated() {
+        return;
+    }
+    if settings::get::<bool>(window.app_handle(), NOTIFIED_KEY).unwrap_or(false) {
+        return;
+    }
+    let _ = window.emit(
+        "emulation-detected",
+        EmulationDetected {
+            arch: std::env::consts::ARCH,
+            native_arch: native_arch(),
+        },
+    );
+    if let Err(e) = settings::set(window.app_handle(), NOTIFIED_KEY, true) {
+        log::warn!("Failed to save that emulation was reported: {}", e);
+    }
+}
+
+// `sysctl.proc_translated` is 1 under Rosetta and missing on Intel Macs
+#[cfg(target_os = "macos")]
+fn detect_native_arch() -> &'static str {
+    let translated = std::process::Command::new("/usr/sbin/sysctl")
+        .args(["-n", "sysctl.proc_translated"])
+        .output()
+        .is_ok_and(|output| {
+            output.status.success() && String::from_utf8_lossy(&output.stdout).trim() == "1"
+        });
+    match translated {
+        true => "aarch64",
+        false => std::env::consts::ARCH,
+    }
+}
+
+#[cfg(windows)]
+fn detect_native_arch() -> &'static str {
+    use windows_sys::Win32::System::SystemInformation::{
+        IMAGE_FILE_MACHINE_AMD64, IMAGE_FILE_MACHINE_ARM64, IMAGE_FILE_MACHINE_I386,
+    };
+    use windows_sys::Win32::System::Threading::{GetCurrentProcess, IsWow64Process2};
+
+    let mut process = 0;
+    let mut native = 0;
+    // Reports the host's machine even for x64 code on Arm, which isn't WOW64
+    if unsafe { IsWow64Process2(GetCurrentProcess(), &mut process, &mut native) } == 0 {
+        return std::env::consts::ARCH;
+    }
+    match native {
+        IMAGE_FILE_MACHINE_AMD64 => "x86_64",
+        IMAGE_FILE_MACHINE_ARM64 => "aarch64",
+        IMAGE_FILE_MACHINE_I386 => "x86",
+        _ => std::env::consts::ARCH,
+    }
+}
+
+#[cfg(not(any(target_os = "macos", windows)))]
+fn detect_native_arch() -> &'static str {
+    std::env::consts::ARCH
+}
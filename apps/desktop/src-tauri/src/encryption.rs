@@ -0,0 +1,175 @@
+use std::sync::{Arc, Mutex, OnceLock};
+
+use base64::Engine;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use tauri::{command, AppHandle};
+
+use crate::{db, search, settings};
+
+const ENABLED_KEY: &str = "cache.encryption";
+
+/// Keychain account holding the key, under the app's identifier as the service
+const KEY_ACCOUNT: &str = "cache-encryption-key";
+
+/// First byte of everything sealed, so the format can change later
+const FORMAT_VERSION: u8 = 1;
+
+const NONCE_LEN: usize = 12;
+
+// Read from the keychain on first use while encryption is on
+fn loaded_cipher() -> &'static Mutex<Option<Arc<Cipher>>> {
+    static CIPHER: OnceLock<Mutex<Option<Arc<Cipher>>>> = OnceLock::new();
+    CIPHER.get_or_init(|| Mutex::new(None))
+}
+
+pub struct Cipher(ChaCha20Poly1305);
+
+impl Cipher {
+    pub fn seal(&self, plaintext: &[u8]) -> Result<Vec<u8>, String> {
+        let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = self
+            .0
+            .encrypt(&nonce, plaintext)
+            .map_err(|_| "Encryption failed".to_string())?;
+        let mut sealed = Vec::with_capacity(1 + NONCE_LEN + ciphertext.len());
+        sealed.push(FORMAT_VERSION);
+        sealed.extend_from_slice(&nonce);
+        sealed.extend_from_slice(&ciphertext);
+        Ok(sealed)
+    }
+
+    pub fn open(&self, sealed: &[u8]) -> Result<Vec<u8>, String> {
+        match sealed.split_first() {
+            Some((&FORMAT_VERSION, rest)) if rest.len() >= NONCE_LEN => {
+                let (nonce, ciphertext) = rest.split_at(NONCE_LEN);
+                self.0
+                    .decrypt(Nonce::from_slice(nonce), ciphertext)
+                    .map_err(|_| {
+                        "Encrypted data is corrupt or was sealed with another key".to_string()
+                    })
+            }
+            _ => Err("Unknown encrypted data format".to_string()),
+        }
+    }
+}
+
+/// Encrypt the offline message store (message text and extra data, channel names) with a
+/// key kept in the OS keychain, or decrypt it again. Existing messages are rewritten
+/// either way, which takes a while for large stores.
+///
+/// Ids, authors and timestamps stay readable so paging and purging still work. Each
+/// message costs an extra encryption on save and decryption on load, which is small next
+/// to the database itself, but the search index can't be encrypted: it's cleared when
+/// encryption is turned on and messages aren't indexed while it's on. Cached attachment
+/// files are opened by other apps and players by path, so they aren't encrypted.
+///
+/// While encryption is on, the store refuses to work if the key can't be read from the
+/// keychain rather than falling back to plaintext.
+#[command]
+pub async fn set_cache_encryption(app: AppHandle, enabled: bool) -> Result<(), String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        if enabled == is_enabled(&app) {
+            return Ok(());
+        }
+        let cipher = match enabled {
+            true => Arc::new(load_key(&app).or_else(|_| create_key(&app))?),
+            false => cipher(&app)?.ok_or_else(|| "Encryption isn't on".to_string())?,
+        };
+        // Switched while the store is locked, so nothing is saved with the old setting
+        // after its rows were rewritten
+        let switch = |apply: bool| use_cipher(&app, (apply == enabled).then(|| cipher.clone()));
+        if enabled {
+            db::reencrypt(None, Some(&cipher), switch)?;
+            if let Err(e) = search::clear(&app) {
+                log::warn!("Failed to clear the search index: {}", e);
+            }
+        } else {
+            db::reencrypt(Some(&cipher), None, switch)?;
+            if let Err(e) = forget_key(&app.config().identifier) {
+                log::warn!("Failed to delete the cache encryption key: {}", e);
+            }
+        }
+        Ok(())
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// Whether the offline message store is encrypted
+#[command]
+pub fn cache_encryption_enabled(app: AppHandle) -> bool {
+    is_enabled(&app)
+}
+
+pub fn is_enabled(app: &AppHandle) -> bool {
+    settings::get(app, ENABLED_KEY).unwrap_or(false)
+}
+
+/// The cipher to seal and open stored data with: None while encryption is off, and an
+/// error while it's on but the key can't be read, so nothing is ever stored unencrypted
+pub fn cipher(app: &AppHandle) -> Result<Option<Arc<Cipher>>, String> {
+    if !is_enabled(app) {
+        return Ok(None);
+    }
+    let mut loaded = loaded_cipher().lock().unwrap();
+    if let Some(cipher) = loaded.as_ref() {
+        return Ok(Some(cipher.clone()));
+    }
+    let cipher = Arc::new(
+        load_key(app)
+            .map_err(|e| format!("The cache is encrypted and its key can't be read: {}", e))?,
+    );
+    *loaded = Some(cipher.clone());
+    Ok(Some(cipher))
+}
+
+// Turn encryption on with `cipher`, or off with None
+fn use_cipher(app: &AppHandle, cipher: Option<Arc<Cipher>>) -> Result<(), String> {
+    match cipher {
+        Some(_) => settings::set(app, ENABLED_KEY, true)?,
+        None => settings::delete(app, ENABLED_KEY)?,
+    }
+    *loaded_cipher().lock().unwrap() = cipher;
+    Ok(())
+}
+
+/// Delete the key from the keychain, if there is one. Takes the app's identifier so it
+/// can run before the app is built.
+pub fn forget_key(identifier: &str) -> Result<(), String> {
+    match keychain_entry(identifier)?.delete_credential() {
+        Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+fn keychain_entry(identifier: &str) -> Result<keyring::Entry, String> {
+    keyring::Entry::new(identifier, KEY_ACCOUNT).map_err(|e| e.to_string())
+}
+
+fn load_key(app: &AppHandle) -> Result<Cipher, String> {
+    let encoded = keychain_entry(&app.config().identifier)?
+        .get_password()
+        .map_err(|e| e.to_string())?;
+    let key = base64::engine::general_purpose::STANDARD
+        .decode(encoded)
+        .ok()
+        .filter(|key| key.len() == 32)
+        .ok_or_else(|| "The key in the keychain is invalid".to_string())?;
+    Ok(Cipher(ChaCha20Poly1305::new(Key::from_slice(&key))))
+}
+
+// Read back before use, so a keychain that silently drops secrets is caught now rather
+// than after data was encrypted with a key that's gone
+fn create_key(app: &AppHandle) -> Result<Cipher, String> {
+    let key = ChaCha20Poly1305::generate_key(&mut OsRng);
+    let encoded = base64::engine::general_purpose::STANDARD.encode(key);
+    let entry = keychain_entry(&app.config().identifier)?;
+    entry
+        .set_password(&encoded)
+        .map_err(|e| format!("Failed to save the key to the keychain: {}", e))?;
+    match entry.get_password() {
+        Ok(saved) if saved == encoded => Ok(Cipher(ChaCha20Poly1305::new(&key))),
+        _ => Err("The keychain didn't keep the encryption key".to_string()),
+    }
+}
@@ -0,0 +1,135 @@
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+use tauri::{command, AppHandle, Manager};
+use tauri_plugin_opener::OpenerExt;
+
+use crate::{data_dir, kiosk};
+
+/// Largest preview `read_text_preview` returns, whatever is asked for
+const MAX_PREVIEW_BYTES: usize = 1024 * 1024;
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TextPreview {
+    content: String,
+    /// The file goes on past `content`
+    truncated: bool,
+    /// What the file was decoded from, e.g. `UTF-8`, `UTF-16LE` or `windows-1252`
+    encoding: &'static str,
+}
+
+/// Open a file or folder with its default application. Only paths in the downloads
+/// folder or Hazel's data/cache folders are accepted unless `allow_outside` is set
+/// outside kiosk mode.
+#[command]
+pub fn open_path(app: AppHandle, path: PathBuf, allow_outside: Option<bool>) -> Result<(), String> {
+    let path = checked_path(&app, &path, allow_outside.unwrap_or(false))?;
+    app.opener()
+        .open_path(path.to_string_lossy(), None::<&str>)
+        .map_err(|e| format!("Failed to open {}: {}", path.display(), e))
+}
+
+/// Show a file or folder selected in Finder/Explorer/the file manager, with the same
+/// restrictions as `open_path`
+#[command]
+pub fn reveal_in_file_manager(
+    app: AppHandle,
+    path: PathBuf,
+    allow_outside: Option<bool>,
+) -> Result<(), String> {
+    let path = checked_path(&app, &path, allow_outside.unwrap_or(false))?;
+    app.opener()
+        .reveal_item_in_dir(&path)
+        .map_err(|e| format!("Failed to reveal {}: {}", path.display(), e))
+}
+
+/// The start of a text file, up to `max_bytes` (at most 1 MB), converted to UTF-8 for a
+/// preview pane. The encoding comes from a byte order mark if there is one, and is
+/// guessed otherwise. Binary files are refused. Only paths `open_path` accepts without
+/// `allow_outside` can be read.
+#[command]
+pub async fn read_text_preview(
+    app: AppHandle,
+    path: PathBuf,
+    max_bytes: usize,
+) -> Result<TextPreview, String> {
+    if max_bytes == 0 {
+        return Err("max_bytes must be at least 1".to_string());
+    }
+    let path = checked_path(&app, &path, false)?;
+    let max_bytes = max_bytes.min(MAX_PREVIEW_BYTES);
+    tauri::async_runtime::spawn_blocking(move || {
+        let mut bytes = Vec::with_capacity(max_bytes + 1);
+        File::open(&path)
+            .and_then(|file| file.take(max_bytes as u64 + 1).read_to_end(&mut bytes))
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        let truncated = bytes.len() > max_bytes;
+        bytes.truncate(max_bytes);
+        text_preview(&bytes, truncated)
+            .ok_or_else(|| format!("{} is not a text file", path.display()))
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+fn text_preview(bytes: &[u8], truncated: bool) -> Option<TextPreview> {
+    let encoding = match encoding_rs::Encoding::for_bom(bytes) {
+        Some((encoding, _)) => encoding,
+        // Text other than UTF-16 never has NUL bytes
+        None if bytes.contains(&0) => return None,
+        None => {
+            let mut detector = chardetng::EncodingDetector::new();
+            detector.feed(bytes, !truncated);
+            detector.guess(None, true)
+        }
+    };
+    let (content, encoding, _) = encoding.decode(bytes);
+    let mut content = content.into_owned();
+    // Cutting the file short can split the last character
+    if truncated && content.ends_with('\u{FFFD}') {
+        content.pop();
+    }
+    let control = content
+        .chars()
+        .filter(|c| c.is_control() && !matches!(c, '\n' | '\r' | '\t' | '\u{c}'))
+        .count();
+    if control * 10 > content.chars().count() {
+        return None;
+    }
+    Some(TextPreview {
+        content,
+        truncated,
+        encoding: encoding.name(),
+    })
+}
+
+// Resolve `path` and make sure it exists and (unless allowed) sits in an expected folder
+fn checked_path(app: &AppHandle, path: &Path, allow_outside: bool) -> Result<PathBuf, String> {
+    let path = path
+        .canonicalize()
+        .map_err(|_| format!("{} doesn't exist", path.display()))?;
+    if allow_outside && !kiosk::enabled() {
+        return Ok(path);
+    }
+
+    let roots = [
+        app.path().download_dir().map_err(|e| e.to_string()),
+        data_dir::data_dir(app),
+        data_dir::cache_dir(app),
+    ];
+    let allowed = roots
+        .into_iter()
+        .flatten()
+        .filter_map(|root| root.canonicalize().ok())
+        .any(|root| path.starts_with(root));
+    if !allowed {
+        return Err(format!(
+            "{} is outside the downloads and app data folders",
+            path.display()
+        ));
+    }
+    Ok(path)
+}
@@ -0,0 +1,45 @@
+// Driven by find_in_page/stop_find in find.rs. Keeps the active match index on
+// window so repeated calls with the same query step through the matches.
+(function (query, forward, matchCase) {
+	const state = window.__hazelFind || (window.__hazelFind = { query: "", matchCase: false, active: 0 })
+	const emit = (matches, active) => window.__TAURI__?.event.emit("find-result", { matches, active })
+
+	const reset = () => {
+		state.query = query
+		state.matchCase = matchCase
+		state.active = 0
+		window.getSelection()?.removeAllRanges()
+	}
+
+	if (!query) {
+		reset()
+		emit(0, 0)
+		return
+	}
+
+	if (state.query !== query || state.matchCase !== matchCase) {
+		reset()
+	}
+
+	const text = document.body.innerText
+	const haystack = matchCase ? text : text.toLowerCase()
+	const needle = matchCase ? query : query.toLowerCase()
+	let matches = 0
+	for (let i = haystack.indexOf(needle); i !== -1; i = haystack.indexOf(needle, i + needle.length)) {
+		matches++
+	}
+
+	if (matches === 0) {
+		emit(0, 0)
+		return
+	}
+
+	// Selects and scrolls to the next match, wrapping around
+	window.find(query, matchCase, !forward, true)
+	if (forward) {
+		state.active = state.active >= matches ? 1 : state.active + 1
+	} else {
+		state.active = state.active <= 1 ? matches : state.active - 1
+	}
+	emit(matches, state.active)
+})
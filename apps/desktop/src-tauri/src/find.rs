@@ -0,0 +1,31 @@
+use tauri::{command, AppHandle};
+
+use crate::window::main_window;
+
+/// Find-in-page driver, evaluated in the main webview. It emits `find-result { matches, active }`.
+const FIND_SCRIPT: &str = include_str!("find.js");
+
+/// Select the next (or previous) match for `query` in the page. Repeated calls with the
+/// same query step through the matches; an empty query clears the selection.
+#[command]
+pub fn find_in_page(
+    app: AppHandle,
+    query: String,
+    forward: bool,
+    match_case: bool,
+) -> Result<(), String> {
+    run_find(&app, &query, forward, match_case)
+}
+
+/// Clear find highlights and reset the match counter
+#[command]
+pub fn stop_find(app: AppHandle) -> Result<(), String> {
+    run_find(&app, "", true, false)
+}
+
+fn run_find(app: &AppHandle, query: &str, forward: bool, match_case: bool) -> Result<(), String> {
+    // JSON-encode the query so it can't break out of the string literal
+    let query = serde_json::to_string(query).map_err(|e| e.to_string())?;
+    let script = format!("{}({}, {}, {})", FIND_SCRIPT, query, forward, match_case);
+    main_window(app)?.eval(&script).map_err(|e| e.to_string())
+}
@@ -0,0 +1,313 @@
+use std::collections::BTreeMap;
+use std::sync::OnceLock;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tauri::{command, AppHandle, Emitter};
+
+use crate::{http, settings};
+
+const FLAGS_KEY: &str = "feature_flags";
+const REMOTE_FLAGS_KEY: &str = "feature_flags.remote";
+
+/// How long a fetched flag document is used before it's fetched again
+const REMOTE_TTL: Duration = Duration::from_secs(60 * 60);
+
+/// Comma-separated `name=true|false` pairs that override stored flags, for QA
+const OVERRIDE_VAR: &str = "HAZEL_FEATURE_FLAGS";
+
+/// Native flags and their values when neither the store nor the environment sets them.
+/// Flags the frontend defines for itself default to off.
+pub const DEFAULTS: &[(&str, bool)] = &[
+    // Group and replace notifications through the OS notification center
+    ("native_notification_center", true),
+    // Collect notifications into a summary after the computer wakes
+    ("resume_batching", true),
+    // Let custom CSS load stylesheets and images from other hosts
+    ("custom_css_remote", false),
+];
+
+// Last flag document fetched from the server
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RemoteFlags {
+    url: String,
+    fetched_at: u64,
+    flags: BTreeMap<String, bool>,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct FlagChanged<'a> {
+    name: &'a str,
+    enabled: bool,
+}
+
+/// Whether a feature flag is on. `HAZEL_FEATURE_FLAGS` wins over the value set locally,
+/// then the server's value, then the default.
+#[command]
+pub fn get_flag(app: AppHandle, name: String) -> bool {
+    enabled(&app, &name)
+}
+
+/// Store a feature flag and emit `flag-changed` with its effective value,
+/// which stays the same while `HAZEL_FEATURE_FLAGS` overrides it
+#[command]
+pub fn set_flag(app: AppHandle, name: String, enabled: bool) -> Result<(), String> {
+    let mut stored = stored_flags(&app);
+    stored.insert(name.clone(), enabled);
+    settings::set(&app, FLAGS_KEY, stored)?;
+    emit_changed(&app, &name);
+    Ok(())
+}
+
+/// Drop the value `set_flag` stored, so the server's value or the default applies again,
+/// and emit `flag-changed` with the effective value
+#[command]
+pub fn clear_flag_override(app: AppHandle, name: String) -> Result<(), String> {
+    let mut stored = stored_flags(&app);
+    if stored.remove(&name).is_some() {
+        settings::set(&app, FLAGS_KEY, stored)?;
+    }
+    emit_changed(&app, &name);
+    Ok(())
+}
+
+/// Effective value of every known flag: the defaults plus any remote, stored or overridden ones
+#[command]
+pub fn feature_flags(app: AppHandle) -> BTreeMap<String, bool> {
+    let mut flags: BTreeMap<String, bool> = DEFAULTS
+        .iter()
+        .map(|(name, enabled)| (name.to_string(), *enabled))
+        .collect();
+    flags.extend(remote_flags(&app));
+    flags.extend(stored_flags(&app));
+    flags.extend(overrides().clone());
+    flags
+}
+
+/// Fetch the server's flag document, e.g. `{ "flags": { "new_sidebar": true } }`, and
+/// layer it under the local flags. The document is cached for an hour; when fetching fails,
+/// the cached copy is used if there is one. Emits `flags-updated` with every effective flag.
+#[command]
+pub async fn refresh_remote_flags(
+    app: AppHandle,
+    url: String,
+) -> Result<BTreeMap<String, bool>, String> {
+    let cached = settings::get::<RemoteFlags>(&app, REMOTE_FLAGS_KEY).filter(|c| c.url == url);
+    let fresh = cached
+        .as_ref()
+        .is_some_and(|c| now_ms().saturating_sub(c.fetched_at) < REMOTE_TTL.as_millis() as u64);
+    if !fresh {
+        match fetch_remote_flags(&app, &url).await {
+            Ok(flags) => {
+                let remote = RemoteFlags {
+                    url,
+                    fetched_at: now_ms(),
+                    flags,
+                };
+                settings::set(&app, REMOTE_FLAGS_KEY, remote)?;
+            }
+            Err(e) if cached.is_some() => {
+                log::warn!("Failed to fetch remote flags, using the cached copy: {}", e);
+            }
+            Err(e) => return Err(e),
+        }
+    }
+
+    let flags = feature_flags(app.clone());
+    let _ = app.emit("flags-updated", &flags);
+    Ok(flags)
+}
+
+/// Whether a feature flag is on, for gating native behavior
+pub fn enabled(app: &AppHandle, name: &str) -> bool {
+    resolve(name, overrides(), &stored_flags(app), &remote_flags(app))
+}
+
+fn resolve(
+    name: &str,
+    overrides: &BTreeMap<String, bool>,
+    stored: &BTreeMap<String, bool>,
+    remote: &BTreeMap<String, bool>,
+) -> bool {
+    overrides
+        .get(name)
+        .or_else(|| stored.get(name))
+        .or_else(|| remote.get(name))
+        .copied()
+        .or_else(|| {
+            DEFAULTS
+                .iter()
+                .find(|(flag, _)| *flag == name)
+                .map(|(_, enabled)| *enabled)
+        })
+        .unwrap_or(false)
+}
+
+fn emit_changed(app: &AppHandle, name: &str) {
+    let _ = app.emit(
+        "flag-changed",
+        FlagChanged {
+            name,
+            enabled: enabled(app, name),
+        },
+    );
+}
+
+fn stored_flags(app: &AppHandle) -> BTreeMap<String, bool> {
+    settings::get(app, FLAGS_KEY).unwrap_or_default()
+}
+
+fn remote_flags(app: &AppHandle) -> BTreeMap<String, bool> {
+    settings::get::<RemoteFlags>(app, REMOTE_FLAGS_KEY)
+        .map(|remote| remote.flags)
+        .unwrap_or_default()
+}
+
+async fn fetch_remote_flags(app: &AppHandle, url: &str) -> Result<BTreeMap<String, bool>, String> {
+    let url = http::check_url(app, url, true).await?;
+    let response = http::client(app)?
+        .get(url)
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| e.to_string())?;
+    let document: Value = response.json().await.map_err(|e| e.to_string())?;
+    parse_remote_flags(&document)
+}
+
+// Reject the whole document if any flag isn't a boolean, so a bad deploy can't flip flags
+fn parse_remote_flags(document: &Value) -> Result<BTreeMap<String, bool>, String> {
+    let Some(flags) = document.get("flags").and_then(Value::as_object) else {
+        return Err("The flag document must be an object with a \"flags\" object".to_string());
+    };
+    let mut parsed = BTreeMap::new();
+    let mut problems = Vec::new();
+    for (name, value) in flags {
+        match value.as_bool() {
+            Some(enabled) if !name.is_empty() => {
+                parsed.insert(name.clone(), enabled);
+            }
+            Some(_) => problems.push("a flag has an empty name".to_string()),
+            None => problems.push(format!("\"{}\" is not a boolean", name)),
+        }
+    }
+    if !problems.is_empty() {
+        return Err(format!("Invalid flag document: {}", problems.join("; ")));
+    }
+    Ok(parsed)
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64
+}
+
+// Read once, since the environment doesn't change while the app runs
+fn overrides() -> &'static BTreeMap<String, bool> {
+    static OVERRIDES: OnceLock<BTreeMap<String, bool>> = OnceLock::new();
+    OVERRIDES.get_or_init(|| match std::env::var(OVERRIDE_VAR) {
+        Ok(value) => parse_overrides(&value),
+        Err(_) => BTreeMap::new(),
+    })
+}
+
+// A bare name turns the flag on; unparseable values are skipped
+fn parse_overrides(value: &str) -> BTreeMap<String, bool> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|pair| !pair.is_empty())
+        .filter_map(|pair| {
+            let (name, enabled) = match pair.split_once('=') {
+                Some((name, value)) => (name.trim(), value.trim()),
+                None => (pair, "true"),
+            };
+            let enabled = match enabled.to_ascii_lowercase().as_str() {
+                "1" | "true" | "on" => true,
+                "0" | "false" | "off" => false,
+                _ => {
+                    log::warn!("Ignoring {} entry \"{}\"", OVERRIDE_VAR, pair);
+                    return None;
+                }
+            };
+            Some((name.to_string(), enabled))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn flags(pairs: &[(&str, bool)]) -> BTreeMap<String, bool> {
+        pairs
+            .iter()
+            .map(|(name, enabled)| (name.to_string(), *enabled))
+            .collect()
+    }
+
+    #[test]
+    fn local_beats_remote_beats_default() {
+        let none = BTreeMap::new();
+        // Defaults, and off for flags without one
+        assert!(resolve("resume_batching", &none, &none, &none));
+        assert!(!resolve("custom_css_remote", &none, &none, &none));
+        assert!(!resolve("new_sidebar", &none, &none, &none));
+
+        let remote = flags(&[("resume_batching", false), ("new_sidebar", true)]);
+        assert!(!resolve("resume_batching", &none, &none, &remote));
+        assert!(resolve("new_sidebar", &none, &none, &remote));
+
+        let stored = flags(&[("resume_batching", true), ("new_sidebar", false)]);
+        assert!(resolve("resume_batching", &none, &stored, &remote));
+        assert!(!resolve("new_sidebar", &none, &stored, &remote));
+
+        let overrides = flags(&[("new_sidebar", true)]);
+        assert!(resolve("new_sidebar", &overrides, &stored, &remote));
+    }
+
+    #[test]
+    fn clearing_a_local_flag_restores_remote() {
+        let none = BTreeMap::new();
+        let remote = flags(&[("new_sidebar", true)]);
+        let mut stored = flags(&[("new_sidebar", false)]);
+        assert!(!resolve("new_sidebar", &none, &stored, &remote));
+
+        stored.remove("new_sidebar");
+        assert!(resolve("new_sidebar", &none, &stored, &remote));
+        assert!(!resolve("new_sidebar", &none, &stored, &none));
+    }
+
+    #[test]
+    fn remote_documents() {
+        let document = json!({ "flags": { "new_sidebar": true, "resume_batching": false } });
+        assert_eq!(
+            parse_remote_flags(&document),
+            Ok(flags(&[("new_sidebar", true), ("resume_batching", false)]))
+        );
+        assert!(parse_remote_flags(&json!({ "flags": { "new_sidebar": "yes" } })).is_err());
+        assert!(parse_remote_flags(&json!({ "flags": { "": true } })).is_err());
+        assert!(parse_remote_flags(&json!({ "new_sidebar": true })).is_err());
+        assert!(parse_remote_flags(&json!([])).is_err());
+    }
+
+    #[test]
+    fn environment_overrides() {
+        assert_eq!(
+            parse_overrides("new_sidebar, resume_batching=off ,custom_css_remote=1,bad=maybe,"),
+            flags(&[
+                ("new_sidebar", true),
+                ("resume_batching", false),
+                ("custom_css_remote", true),
+            ])
+        );
+        assert!(parse_overrides("").is_empty());
+    }
+}
@@ -0,0 +1,194 @@
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::sync::{Mutex, OnceLock};
+use std::thread;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tauri::{command, AppHandle, Emitter};
+
+use crate::settings;
+use crate::status::{self, CustomStatus};
+
+const ENABLED_KEY: &str = "status.auto_from_focus";
+const APPLIED_KEY: &str = "status.auto_applied";
+
+/// How often the OS is asked whether Focus is on, since neither platform reports changes
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+const DND_EMOJI: &str = "⛔";
+const DND_TEXT: &str = "Do Not Disturb";
+
+// Dropping the sender stops the watcher
+fn watcher() -> &'static Mutex<Option<Sender<()>>> {
+    static WATCHER: OnceLock<Mutex<Option<Sender<()>>>> = OnceLock::new();
+    WATCHER.get_or_init(|| Mutex::new(None))
+}
+
+// Kept while the automatic status is set, so it can be undone after a restart too
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Applied {
+    /// What the status was before
+    previous: Option<CustomStatus>,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct AutoStatusChanged {
+    /// Whether the Do Not Disturb status is set
+    active: bool,
+    status: Option<CustomStatus>,
+}
+
+/// While macOS Focus or Windows Focus Assist (quiet time) is on, set a "Do Not Disturb"
+/// status, and put the previous one back once it's off, unless the status was changed by
+/// hand in between. Emits `auto-status-changed` each time. Kept across launches. macOS
+/// asks for permission to read the Focus state; where it can't be read this does nothing.
+#[command]
+pub fn set_auto_status_from_focus(app: AppHandle, enabled: bool) -> Result<(), String> {
+    if enabled {
+        settings::set(&app, ENABLED_KEY, true)?;
+        request_access();
+        start(&app);
+    } else {
+        settings::delete(&app, ENABLED_KEY)?;
+        watcher().lock().unwrap().take();
+        revert(&app)?;
+    }
+    Ok(())
+}
+
+/// Start watching Focus if that was on when the app last quit
+pub fn restore_auto_status(app: &AppHandle) {
+    if settings::get::<bool>(app, ENABLED_KEY).unwrap_or(false) {
+        start(app);
+    } else if let Err(e) = revert(app) {
+        log::warn!("Failed to restore the status from before Focus: {}", e);
+    }
+}
+
+fn start(app: &AppHandle) {
+    let (stop_tx, stop_rx) = mpsc::channel::<()>();
+    // Dropping the previous sender stops the previous watcher
+    *watcher().lock().unwrap() = Some(stop_tx);
+
+    let app = app.clone();
+    thread::spawn(move || loop {
+        // None when it can't be told, which changes nothing
+        let result = match focus_active() {
+            Some(true) => apply(&app),
+            Some(false) => revert(&app),
+            None => Ok(()),
+        };
+        if let Err(e) = result {
+            log::warn!("Failed to update the status for Focus: {}", e);
+        }
+        match stop_rx.recv_timeout(POLL_INTERVAL) {
+            Err(RecvTimeoutError::Timeout) => {}
+            Ok(()) | Err(RecvTimeoutError::Disconnected) => return,
+        }
+    });
+}
+
+fn apply(app: &AppHandle) -> Result<(), String> {
+    if settings::get::<Applied>(app, APPLIED_KEY).is_some() {
+        return Ok(());
+    }
+    let dnd = CustomStatus::new(DND_EMOJI, DND_TEXT);
+    let applied = Applied {
+        previous: status::custom_status(app.clone()),
+    };
+    settings::set(app, APPLIED_KEY, &applied)?;
+    status::replace(app, Some(dnd.clone()))?;
+    let changed = AutoStatusChanged {
+        active: true,
+        status: Some(dnd),
+    };
+    let _ = app.emit("auto-status-changed", changed);
+    Ok(())
+}
+
+fn revert(app: &AppHandle) -> Result<(), String> {
+    let Some(applied) = settings::get::<Applied>(app, APPLIED_KEY) else {
+        return Ok(());
+    };
+    // A status set by hand while Focus was on stays
+    if status::custom_status(app.clone()) == Some(CustomStatus::new(DND_EMOJI, DND_TEXT)) {
+        status::replace(app, applied.previous)?;
+    }
+    settings::delete(app, APPLIED_KEY)?;
+    let changed = AutoStatusChanged {
+        active: false,
+        status: status::custom_status(app.clone()),
+    };
+    let _ = app.emit("auto-status-changed", changed);
+    Ok(())
+}
+
+// INFocusStatusCenter only answers once the user allows it, and only for apps with the
+// Communication Notifications entitlement; otherwise the state is unknown
+#[cfg(target_os = "macos")]
+fn focus_active() -> Option<bool> {
+    use objc2::msg_send;
+    use objc2::runtime::AnyObject;
+
+    let center = focus_status_center()?;
+    unsafe {
+        let status: *mut AnyObject = msg_send![center, focusStatus];
+        let focused: *mut AnyObject = msg_send![status.as_ref()?, isFocused];
+        let focused: bool = msg_send![focused.as_ref()?, boolValue];
+        Some(focused)
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn request_access() {
+    use block2::RcBlock;
+    use objc2::msg_send;
+
+    let Some(center) = focus_status_center() else {
+        return;
+    };
+    let handler = RcBlock::new(|status: isize| {
+        log::info!("Focus status authorization: {}", status);
+    });
+    unsafe {
+        let _: () = msg_send![center, requestAuthorizationWithCompletionHandler: &*handler];
+    }
+}
+
+// Only there from macOS 12
+#[cfg(target_os = "macos")]
+fn focus_status_center() -> Option<&'static objc2::runtime::AnyObject> {
+    use objc2::msg_send;
+    use objc2::runtime::{AnyClass, AnyObject};
+
+    #[link(name = "Intents", kind = "framework")]
+    extern "C" {}
+
+    let class = AnyClass::get(c"INFocusStatusCenter")?;
+    unsafe {
+        let center: *mut AnyObject = msg_send![class, defaultCenter];
+        center.as_ref()
+    }
+}
+
+// Focus Assist shows up as quiet time
+#[cfg(target_os = "windows")]
+fn focus_active() -> Option<bool> {
+    use windows_sys::Win32::UI::Shell::{SHQueryUserNotificationState, QUNS_QUIET_TIME};
+
+    let mut state = 0;
+    if unsafe { SHQueryUserNotificationState(&mut state) } < 0 {
+        return None;
+    }
+    Some(state == QUNS_QUIET_TIME)
+}
+
+#[cfg(not(target_os = "macos"))]
+fn request_access() {}
+
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+fn focus_active() -> Option<bool> {
+    None
+}
@@ -0,0 +1,233 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use notify::event::{ModifyKind, RenameMode};
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use serde::Serialize;
+use tauri::{command, AppHandle, Emitter};
+
+use crate::settings;
+
+const WATCHED_FOLDERS_KEY: &str = "folders.watched";
+
+const MAX_WATCHED_FOLDERS: usize = 5;
+
+/// How long a new file must go without changes before it counts as fully written
+const QUIET_PERIOD: Duration = Duration::from_secs(2);
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Extensions browsers and editors use while a file is still being written
+const PARTIAL_EXTENSIONS: &[&str] = &["part", "partial", "crdownload", "download", "tmp"];
+
+struct FolderWatcher {
+    watcher: RecommendedWatcher,
+    /// Folders being watched now
+    folders: Vec<PathBuf>,
+    /// Folders to watch, as saved, including missing ones that couldn't be watched
+    saved: Vec<PathBuf>,
+}
+
+// New file waiting for writes to settle, with the size seen at the last check
+struct Pending {
+    changed: Instant,
+    size: Option<u64>,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct FolderFileAdded {
+    path: PathBuf,
+}
+
+// Started on the first watched folder
+fn folder_watcher() -> &'static Mutex<Option<FolderWatcher>> {
+    static WATCHER: OnceLock<Mutex<Option<FolderWatcher>>> = OnceLock::new();
+    WATCHER.get_or_init(|| Mutex::new(None))
+}
+
+fn pending_files() -> &'static Mutex<HashMap<PathBuf, Pending>> {
+    static PENDING: OnceLock<Mutex<HashMap<PathBuf, Pending>>> = OnceLock::new();
+    PENDING.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// Watch a folder (not its subfolders) and emit `folder-file-added` for each new file
+/// once it has finished being written. Watched folders are remembered across launches;
+/// at most 5 can be watched.
+#[command]
+pub fn watch_folder(app: AppHandle, path: PathBuf) -> Result<(), String> {
+    let path = path.canonicalize().map_err(|e| e.to_string())?;
+    if !path.is_dir() {
+        return Err(format!("{} is not a folder", path.display()));
+    }
+
+    let mut guard = folder_watcher().lock().unwrap();
+    let state = match guard.as_mut() {
+        Some(state) => state,
+        None => guard.insert(start(&app)?),
+    };
+    if state.folders.contains(&path) {
+        return Ok(());
+    }
+    let saved = state.saved.contains(&path);
+    if !saved && state.saved.len() >= MAX_WATCHED_FOLDERS {
+        return Err(format!(
+            "At most {} folders can be watched",
+            MAX_WATCHED_FOLDERS
+        ));
+    }
+
+    state
+        .watcher
+        .watch(&path, RecursiveMode::NonRecursive)
+        .map_err(|e| e.to_string())?;
+    state.folders.push(path.clone());
+    if saved {
+        return Ok(());
+    }
+    state.saved.push(path);
+    settings::set(&app, WATCHED_FOLDERS_KEY, &state.saved)
+}
+
+/// Stop watching a folder, including a remembered one that's missing right now. Returns
+/// false if it wasn't watched.
+#[command]
+pub fn unwatch_folder(app: AppHandle, path: PathBuf) -> Result<bool, String> {
+    // The folder may have been deleted since it was watched
+    let path = path.canonicalize().unwrap_or(path);
+
+    let mut guard = folder_watcher().lock().unwrap();
+    let Some(state) = guard.as_mut() else {
+        return Ok(false);
+    };
+    let Some(index) = state.saved.iter().position(|folder| *folder == path) else {
+        return Ok(false);
+    };
+
+    if let Some(active) = state.folders.iter().position(|folder| *folder == path) {
+        let _ = state.watcher.unwatch(&path);
+        state.folders.remove(active);
+    }
+    state.saved.remove(index);
+    settings::set(&app, WATCHED_FOLDERS_KEY, &state.saved)?;
+    Ok(true)
+}
+
+/// Resume watching the folders from the last session. Folders that are missing right now
+/// (e.g. on an unplugged drive) are skipped but stay remembered.
+pub fn restore_watched_folders(app: &AppHandle) {
+    let folders: Vec<PathBuf> = settings::get(app, WATCHED_FOLDERS_KEY).unwrap_or_default();
+    if folders.is_empty() {
+        return;
+    }
+
+    let mut guard = folder_watcher().lock().unwrap();
+    let state = match start(app) {
+        Ok(state) => guard.insert(state),
+        Err(e) => {
+            log::warn!("Failed to start the folder watcher: {}", e);
+            return;
+        }
+    };
+    for folder in folders.into_iter().take(MAX_WATCHED_FOLDERS) {
+        match state.watcher.watch(&folder, RecursiveMode::NonRecursive) {
+            Ok(()) => state.folders.push(folder.clone()),
+            Err(e) => log::warn!("Failed to watch {}: {}", folder.display(), e),
+        }
+        state.saved.push(folder);
+    }
+}
+
+fn start(app: &AppHandle) -> Result<FolderWatcher, String> {
+    let watcher = notify::recommended_watcher(|result: notify::Result<Event>| match result {
+        Ok(event) => on_event(event),
+        Err(e) => log::warn!("Folder watcher error: {}", e),
+    })
+    .map_err(|e| e.to_string())?;
+
+    let app = app.clone();
+    thread::spawn(move || loop {
+        thread::sleep(POLL_INTERVAL);
+        for path in settled_files() {
+            let _ = app.emit("folder-file-added", FolderFileAdded { path });
+        }
+    });
+
+    Ok(FolderWatcher {
+        watcher,
+        folders: Vec::new(),
+        saved: Vec::new(),
+    })
+}
+
+fn on_event(event: Event) {
+    // Renames count as new files so downloads that finish as `x.part` -> `x` are picked up
+    let added = matches!(
+        event.kind,
+        EventKind::Create(_)
+            | EventKind::Modify(ModifyKind::Name(
+                RenameMode::To | RenameMode::Both | RenameMode::Any
+            ))
+    );
+
+    let mut pending = pending_files().lock().unwrap();
+    for path in event.paths {
+        if added && !is_partial(&path) {
+            pending.insert(
+                path,
+                Pending {
+                    changed: Instant::now(),
+                    size: None,
+                },
+            );
+        } else if let Some(file) = pending.get_mut(&path) {
+            file.changed = Instant::now();
+        }
+    }
+}
+
+// Take the pending files that have gone quiet and kept the same size across two checks,
+// since some writers append without generating events
+fn settled_files() -> Vec<PathBuf> {
+    let mut settled = Vec::new();
+    pending_files().lock().unwrap().retain(|path, file| {
+        if file.changed.elapsed() < QUIET_PERIOD {
+            return true;
+        }
+        // Gone again (renamed away or deleted) or not a file
+        let Ok(meta) = fs::metadata(path) else {
+            return false;
+        };
+        if !meta.is_file() {
+            return false;
+        }
+        if file.size != Some(meta.len()) {
+            file.size = Some(meta.len());
+            file.changed = Instant::now();
+            return true;
+        }
+        settled.push(path.clone());
+        false
+    });
+    settled
+}
+
+// Hidden files, Office lock files and in-progress downloads
+fn is_partial(path: &Path) -> bool {
+    let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+        return true;
+    };
+    if name.starts_with('.') || name.starts_with("~$") {
+        return true;
+    }
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| {
+            PARTIAL_EXTENSIONS
+                .iter()
+                .any(|partial| ext.eq_ignore_ascii_case(partial))
+        })
+}
@@ -0,0 +1,45 @@
+use tauri::{command, AppHandle};
+
+use crate::{lifecycle, settings};
+
+const HARDWARE_ACCELERATION_KEY: &str = "gpu.hardware_acceleration";
+
+/// Turn GPU rendering in the webview on or off, e.g. to work around drivers that draw
+/// artifacts. Takes effect on the next launch; emits `restart-required`.
+///
+/// - **Windows:** launches WebView2 with `--disable-gpu`.
+/// - **Linux:** disables WebKitGTK's compositing mode and DMA-BUF renderer.
+/// - **macOS:** not supported; WKWebView has no switch for it, so the setting is ignored.
+#[command]
+pub fn set_hardware_acceleration(app: AppHandle, enabled: bool) -> Result<(), String> {
+    settings::set(&app, HARDWARE_ACCELERATION_KEY, enabled)?;
+    lifecycle::notify_restart_required(&app, "hardware_acceleration");
+    Ok(())
+}
+
+/// Set the environment the webview reads at creation. Must run before the app is built.
+pub fn apply_startup_flags(identifier: &str) {
+    let enabled = settings::get_at_startup(identifier, HARDWARE_ACCELERATION_KEY).unwrap_or(true);
+    if enabled {
+        return;
+    }
+
+    #[cfg(windows)]
+    append_env("WEBVIEW2_ADDITIONAL_BROWSER_ARGUMENTS", "--disable-gpu");
+
+    #[cfg(target_os = "linux")]
+    {
+        std::env::set_var("WEBKIT_DISABLE_COMPOSITING_MODE", "1");
+        std::env::set_var("WEBKIT_DISABLE_DMABUF_RENDERER", "1");
+    }
+}
+
+// Keep any arguments the user already passes to WebView2
+#[cfg(windows)]
+fn append_env(name: &str, arg: &str) {
+    let value = match std::env::var(name) {
+        Ok(existing) if !existing.is_empty() => format!("{} {}", existing, arg),
+        _ => arg.to_string(),
+    };
+    std::env::set_var(name, value);
+}
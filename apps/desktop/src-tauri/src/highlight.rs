@@ -0,0 +1,378 @@
+use std::sync::OnceLock;
+
+use syntect::html::{ClassStyle, ClassedHTMLGenerator};
+use syntect::parsing::SyntaxSet;
+use syntect::util::LinesWithEndings;
+use tauri::command;
+
+use Signal::{Contains, Line, Starts};
+
+/// Longer code is escaped without highlighting, which would take too long
+const MAX_CODE_LEN: usize = 100 * 1024;
+
+/// Prefix of the scope classes, so they can't clash with the app's own
+const CLASS_PREFIX: &str = "hl-";
+
+/// Snippets shorter than this, ignoring whitespace, are too short to tell apart
+const MIN_DETECT_LEN: usize = 20;
+
+/// Lines of a snippet looked at to guess its language
+const DETECT_LINES: usize = 500;
+
+/// Score the best guess needs before it's believed
+const MIN_SCORE: u32 = 6;
+
+/// Something in a line that hints at a language, matched against the lowercased line with
+/// leading whitespace removed, and how strongly
+enum Signal {
+    Starts(&'static str, u32),
+    Contains(&'static str, u32),
+    Line(&'static str, u32),
+}
+
+/// Languages `detect_language` can tell apart, by the id `highlight_code` takes
+const LANGUAGES: &[(&str, &[Signal])] = &[
+    (
+        "rust",
+        &[
+            Starts("fn ", 3),
+            Starts("pub fn ", 5),
+            Starts("pub struct ", 5),
+            Starts("impl ", 3),
+            Starts("use std::", 6),
+            Starts("#[derive(", 6),
+            Starts("let mut ", 4),
+            Contains("println!(", 5),
+            Contains(".unwrap()", 4),
+            Contains("&mut ", 3),
+            Contains("&str", 3),
+            Contains("vec<", 2),
+            Contains("option<", 2),
+            Contains("::new(", 1),
+        ],
+    ),
+    (
+        "python",
+        &[
+            Starts("def ", 3),
+            Starts("elif ", 5),
+            Starts("from ", 1),
+            Contains(" import ", 1),
+            Contains("self.", 2),
+            Contains("__init__", 5),
+            Contains("__name__", 5),
+            Contains("print(", 1),
+            Contains("lambda ", 2),
+            Contains(" is not ", 3),
+            Contains("\"\"\"", 2),
+        ],
+    ),
+    (
+        "javascript",
+        &[
+            Starts("function ", 3),
+            Starts("const ", 2),
+            Starts("let ", 1),
+            Starts("export default ", 4),
+            Contains(" from '", 2),
+            Contains(" from \"", 2),
+            Contains("=> ", 2),
+            Contains("===", 4),
+            Contains("!==", 4),
+            Contains("console.log(", 5),
+            Contains("document.", 3),
+            Contains("require(", 3),
+            Contains("undefined", 3),
+        ],
+    ),
+    (
+        "go",
+        &[
+            Starts("package ", 4),
+            Starts("func ", 4),
+            Starts("import (", 5),
+            Contains(":= ", 3),
+            Contains("fmt.", 4),
+            Contains("err != nil", 6),
+        ],
+    ),
+    (
+        "java",
+        &[
+            Starts("public class ", 5),
+            Starts("import java.", 6),
+            Starts("@override", 4),
+            Starts("private ", 1),
+            Contains("public static void main", 6),
+            Contains("system.out.print", 6),
+            Contains("string[] ", 3),
+        ],
+    ),
+    (
+        "c",
+        &[
+            Starts("#include <", 4),
+            Starts("int main(", 4),
+            Contains("printf(", 3),
+            Contains("malloc(", 4),
+            Contains("sizeof(", 2),
+            Contains("null", 1),
+        ],
+    ),
+    (
+        "cpp",
+        &[
+            Starts("#include <", 2),
+            Starts("#include <iostream>", 5),
+            Starts("using namespace ", 5),
+            Starts("template <", 4),
+            Starts("template<", 4),
+            Contains("std::", 4),
+            Contains("cout <<", 5),
+            Contains("nullptr", 4),
+        ],
+    ),
+    (
+        "cs",
+        &[
+            Starts("using system", 6),
+            Starts("namespace ", 2),
+            Contains("console.writeline", 6),
+            Contains("async task", 5),
+            Contains("{ get; set; }", 6),
+        ],
+    ),
+    (
+        "ruby",
+        &[
+            Starts("def ", 2),
+            Starts("require '", 4),
+            Starts("puts ", 4),
+            Starts("elsif ", 5),
+            Starts("module ", 2),
+            Line("end", 2),
+            Contains(" do |", 5),
+            Contains("attr_accessor", 6),
+        ],
+    ),
+    (
+        "php",
+        &[
+            Starts("<?php", 10),
+            Contains("$this->", 6),
+            Starts("echo ", 1),
+            Starts("namespace ", 1),
+        ],
+    ),
+    (
+        "bash",
+        &[
+            Starts("echo ", 2),
+            Starts("export ", 1),
+            Starts("sudo ", 5),
+            Starts("cd ", 3),
+            Starts("if [ ", 5),
+            Starts("if [[ ", 5),
+            Starts("apt ", 3),
+            Starts("brew ", 3),
+            Starts("npm ", 3),
+            Starts("cargo ", 3),
+            Starts("git ", 3),
+            Line("fi", 4),
+            Line("done", 3),
+            Contains("$(", 2),
+            Contains("| grep ", 4),
+        ],
+    ),
+    (
+        "sql",
+        &[
+            Starts("select ", 4),
+            Starts("insert into ", 6),
+            Starts("create table ", 6),
+            Starts("update ", 2),
+            Starts("delete from ", 6),
+            Contains(" from ", 1),
+            Contains(" where ", 3),
+            Contains(" join ", 3),
+            Contains("group by ", 4),
+            Contains("order by ", 4),
+        ],
+    ),
+    (
+        "css",
+        &[
+            Starts("@media ", 5),
+            Starts("@import ", 3),
+            Contains("px;", 4),
+            Contains("color:", 3),
+            Contains("margin:", 3),
+            Contains("padding:", 3),
+            Contains("display:", 3),
+            Contains("font-", 2),
+        ],
+    ),
+    (
+        "lua",
+        &[
+            Starts("local ", 4),
+            Starts("function ", 1),
+            Contains(" then", 2),
+            Contains(" ~= ", 5),
+            Line("end", 1),
+        ],
+    ),
+    (
+        "haskell",
+        &[
+            Starts("import qualified ", 6),
+            Starts("module ", 1),
+            Contains(" :: ", 4),
+            Contains(" <- ", 1),
+            Contains(" where", 1),
+        ],
+    ),
+    (
+        "diff",
+        &[
+            Starts("diff --git ", 8),
+            Starts("@@ ", 5),
+            Starts("+++ ", 4),
+            Starts("--- ", 2),
+        ],
+    ),
+];
+
+// Loading the grammars takes a while, so it's done once, on first use
+fn syntaxes() -> &'static SyntaxSet {
+    static SYNTAXES: OnceLock<SyntaxSet> = OnceLock::new();
+    SYNTAXES.get_or_init(SyntaxSet::load_defaults_newlines)
+}
+
+/// Highlight a code block as HTML for a `<pre>`: spans with a class per TextMate scope,
+/// prefixed `hl-` (e.g. `hl-string hl-quoted`), for the frontend's stylesheet to colour.
+/// `lang` is a language name or file extension; when it's empty the language is guessed
+/// as `detect_language` does, then from the first line (`<?xml` and the like). Unknown
+/// languages, and code over 100 KB, come back escaped but not highlighted.
+#[command]
+pub async fn highlight_code(code: String, lang: String) -> Result<String, String> {
+    tauri::async_runtime::spawn_blocking(move || highlight(&code, lang.trim()))
+        .await
+        .map_err(|e| e.to_string())
+}
+
+fn highlight(code: &str, lang: &str) -> String {
+    if code.len() > MAX_CODE_LEN {
+        return escape(code);
+    }
+    let syntaxes = syntaxes();
+    let syntax = match lang.is_empty() {
+        true => detect(code)
+            .and_then(|lang| syntaxes.find_syntax_by_token(lang))
+            .or_else(|| {
+                let first_line = code.lines().next()?;
+                syntaxes.find_syntax_by_first_line(first_line)
+            }),
+        false => syntaxes.find_syntax_by_token(lang),
+    };
+    let Some(syntax) = syntax else {
+        return escape(code);
+    };
+    let style = ClassStyle::SpacedPrefixed {
+        prefix: CLASS_PREFIX,
+    };
+    let mut generator = ClassedHTMLGenerator::new_with_class_style(syntax, syntaxes, style);
+    for line in LinesWithEndings::from(code) {
+        if let Err(e) = generator.parse_html_for_line_which_includes_newline(line) {
+            log::warn!("Failed to highlight {}: {}", syntax.name, e);
+            return escape(code);
+        }
+    }
+    generator.finalize()
+}
+
+/// Guess the language of a code snippet without a fence language, returning an id
+/// `highlight_code` takes (e.g. `rust`, `python`, `bash`). Goes by shebangs, then
+/// whether it parses as JSON or looks like markup, then keywords and idioms typical of
+/// each language. None when the snippet is too short or the guess isn't clear.
+#[command]
+pub fn detect_language(code: String) -> Option<String> {
+    detect(&code).map(String::from)
+}
+
+fn detect(code: &str) -> Option<&'static str> {
+    let code = code.trim();
+    if code.chars().filter(|c| !c.is_whitespace()).count() < MIN_DETECT_LEN {
+        return None;
+    }
+    let first_line = code.lines().next()?;
+    if let Some(interpreter) = first_line.strip_prefix("#!") {
+        return interpreter_language(interpreter);
+    }
+    if code.starts_with(['{', '[']) && serde_json::from_str::<serde_json::Value>(code).is_ok() {
+        return Some("json");
+    }
+    if code.starts_with('<') && code.ends_with('>') {
+        let lower = first_line.to_lowercase();
+        if lower.starts_with("<?xml") {
+            return Some("xml");
+        }
+        if !lower.starts_with("<?php") {
+            return Some("html");
+        }
+    }
+
+    let mut scores = vec![0; LANGUAGES.len()];
+    for line in code.lines().take(DETECT_LINES) {
+        let line = line.trim().to_lowercase();
+        for ((_, signals), score) in LANGUAGES.iter().zip(scores.iter_mut()) {
+            for signal in signals.iter() {
+                *score += match *signal {
+                    Starts(prefix, weight) if line.starts_with(prefix) => weight,
+                    Contains(part, weight) if line.contains(part) => weight,
+                    Line(whole, weight) if line == whole => weight,
+                    _ => 0,
+                };
+            }
+        }
+    }
+    let mut ranked: Vec<_> = LANGUAGES.iter().map(|(id, _)| *id).zip(scores).collect();
+    ranked.sort_by_key(|(_, score)| std::cmp::Reverse(*score));
+    let ((best, best_score), runner_up) = (ranked[0], ranked[1].1);
+    // A clear lead over the next guess, so e.g. C and C++ aren't picked at random
+    (best_score >= MIN_SCORE && best_score * 2 >= runner_up * 3).then_some(best)
+}
+
+// The language a shebang's interpreter runs, e.g. `/usr/bin/env python3`
+fn interpreter_language(interpreter: &str) -> Option<&'static str> {
+    let program = interpreter
+        .split_whitespace()
+        .find(|part| !part.ends_with("/env") && !part.starts_with('-'))?;
+    let name = program.rsplit('/').next()?;
+    let name = name.trim_end_matches(|c: char| c.is_ascii_digit() || c == '.');
+    match name {
+        "sh" | "bash" | "zsh" | "dash" | "ksh" => Some("bash"),
+        "python" => Some("python"),
+        "node" | "deno" | "bun" => Some("javascript"),
+        "ruby" => Some("ruby"),
+        "perl" => Some("perl"),
+        "php" => Some("php"),
+        "lua" => Some("lua"),
+        _ => None,
+    }
+}
+
+fn escape(code: &str) -> String {
+    let mut escaped = String::with_capacity(code.len());
+    for c in code.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
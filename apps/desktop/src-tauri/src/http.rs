@@ -0,0 +1,414 @@
+use std::net::{IpAddr, SocketAddr, ToSocketAddrs};
+use std::sync::Arc;
+use std::time::Duration;
+
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use reqwest::redirect::Policy;
+use reqwest::Url;
+use tauri::http::header::{HeaderMap, HeaderValue, USER_AGENT};
+use tauri::plugin::TauriPlugin;
+use tauri::{command, AppHandle, Runtime};
+
+use crate::{lifecycle, settings, updates};
+
+const USER_AGENT_KEY: &str = "http.user_agent";
+const ALLOWED_PRIVATE_HOSTS_KEY: &str = "http.allowed_private_hosts";
+
+/// How long Rust-side requests may take before they're abandoned
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Most redirects a request follows
+const MAX_REDIRECTS: usize = 10;
+
+/// Proxies from these variables are allowed even on the local network
+const PROXY_VARIABLES: [&str; 6] = [
+    "HTTPS_PROXY",
+    "https_proxy",
+    "HTTP_PROXY",
+    "http_proxy",
+    "ALL_PROXY",
+    "all_proxy",
+];
+
+/// Replace the user-agent sent by Rust-side HTTP requests (update checks, remote flags),
+/// e.g. for self-hosted servers that route by client. None restores `Hazel/<version> (<os>)`.
+/// The updater picks it up on the next launch; emits `restart-required`.
+#[command]
+pub fn set_user_agent(app: AppHandle, user_agent: Option<String>) -> Result<(), String> {
+    match user_agent {
+        Some(user_agent) => {
+            if user_agent.trim().is_empty() {
+                return Err("The user-agent can't be empty".to_string());
+            }
+            HeaderValue::from_str(&user_agent)
+                .map_err(|_| format!("\"{}\" is not a valid header value", user_agent))?;
+            settings::set(&app, USER_AGENT_KEY, user_agent)?;
+        }
+        None => settings::delete(&app, USER_AGENT_KEY)?,
+    }
+    lifecycle::notify_restart_required(&app, "user_agent");
+    Ok(())
+}
+
+/// The user-agent Rust-side HTTP requests send. The updater keeps the one it started
+/// with until the next launch.
+#[command]
+pub fn user_agent(app: AppHandle) -> String {
+    settings::get(&app, USER_AGENT_KEY).unwrap_or_else(default_user_agent)
+}
+
+/// Let Rust-side requests reach these hosts (names or addresses) even though they're on
+/// this machine or the local network, e.g. for an on-premises server. Replaces the list
+/// set before; an empty list allows none.
+#[command]
+pub fn set_allowed_private_hosts(app: AppHandle, hosts: Vec<String>) -> Result<(), String> {
+    let mut allowed = Vec::new();
+    for host in hosts {
+        let host = host.trim().trim_matches(['[', ']']).to_ascii_lowercase();
+        if host.is_empty() {
+            continue;
+        }
+        if host.parse::<IpAddr>().is_err() && host.contains([':', '/', '@', ' ']) {
+            return Err(format!("\"{}\" is not a host name or address", host));
+        }
+        allowed.push(host);
+    }
+    if allowed.is_empty() {
+        settings::delete(&app, ALLOWED_PRIVATE_HOSTS_KEY)
+    } else {
+        settings::set(&app, ALLOWED_PRIVATE_HOSTS_KEY, allowed)
+    }
+}
+
+/// Hosts on the local network Rust-side requests may reach
+#[command]
+pub fn allowed_private_hosts(app: AppHandle) -> Vec<String> {
+    settings::get(&app, ALLOWED_PRIVATE_HOSTS_KEY).unwrap_or_default()
+}
+
+/// HTTP client for Rust-side requests. Proxies come from the usual environment variables.
+/// It won't connect to this machine or the local network, whatever a host name resolves
+/// to or a redirect points at, unless the host is allowed.
+pub fn client(app: &AppHandle) -> Result<reqwest::Client, String> {
+    client_builder(app).build().map_err(|e| e.to_string())
+}
+
+/// Builder for a client set up like `client`, for requests that need more
+pub fn client_builder(app: &AppHandle) -> reqwest::ClientBuilder {
+    let guard = Guard::new(app);
+    let redirect_guard = guard.clone();
+    reqwest::Client::builder()
+        .user_agent(user_agent(app.clone()))
+        .timeout(REQUEST_TIMEOUT)
+        .dns_resolver(Arc::new(guard))
+        .redirect(Policy::custom(move |attempt| {
+            if attempt.previous().len() >= MAX_REDIRECTS {
+                return attempt.error("too many redirects");
+            }
+            match redirect_guard.check_literal(attempt.url(), false) {
+                Ok(()) => attempt.follow(),
+                Err(e) => attempt.error(e),
+            }
+        }))
+}
+
+/// Check a URL before fetching it: it must be http or https (only https with
+/// `https_only`), and unless its host is allowed, it can't be on this machine or the
+/// local network. Host names are looked up for a clear error up front; the client looks
+/// them up again when connecting, so a name that changes in between is still caught.
+pub async fn check_url(app: &AppHandle, url: &str, https_only: bool) -> Result<Url, String> {
+    let url = Url::parse(url.trim()).map_err(|e| format!("Invalid URL: {}", e))?;
+    let guard = Guard::new(app);
+    guard.check_literal(&url, https_only)?;
+    if let Some(domain) = url.domain() {
+        let addrs = lookup(domain.to_string()).await?;
+        guard.check_resolved(domain, &addrs)?;
+    }
+    Ok(url)
+}
+
+/// Updater plugin that sends the user-agent with update checks and downloads, and holds
+/// updates at the version pin. Must be built before the app, so it reads the settings
+/// straight from disk.
+pub fn updater_plugin<R: Runtime>(
+    identifier: &str,
+) -> TauriPlugin<R, tauri_plugin_updater::Config> {
+    let user_agent =
+        settings::get_at_startup(identifier, USER_AGENT_KEY).unwrap_or_else(default_user_agent);
+    let mut headers = HeaderMap::new();
+    // The store file can be edited by hand, so this may still be invalid
+    if let Ok(value) = HeaderValue::from_str(&user_agent) {
+        headers.insert(USER_AGENT, value);
+    }
+    updates::restore_version_pin(identifier);
+    tauri_plugin_updater::Builder::new()
+        .headers(headers)
+        .default_version_comparator(updates::allows_update)
+        .build()
+}
+
+fn default_user_agent() -> String {
+    let os = match std::env::consts::OS {
+        "macos" => "macOS",
+        "windows" => "Windows",
+        "linux" => "Linux",
+        other => other,
+    };
+    format!("Hazel/{} ({})", env!("CARGO_PKG_VERSION"), os)
+}
+
+/// Whether an address is on the internet rather than this machine or a local network
+pub fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [first, second, ..] = ip.octets();
+            !(ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_multicast()
+                || first == 0
+                // Reserved, broadcast included
+                || first >= 240
+                // Carrier-grade NAT
+                || (first == 100 && (second & 0xc0) == 64)
+                // Benchmarking
+                || (first == 198 && (second & 0xfe) == 18))
+        }
+        IpAddr::V6(ip) => {
+            let segments = ip.segments();
+            let first = segments[0];
+            !(ip.is_loopback()
+                || ip.is_unspecified()
+                || ip.is_multicast()
+                // Unique local and link-local
+                || (first & 0xfe00) == 0xfc00
+                || (first & 0xffc0) == 0xfe80
+                // NAT64, which translates to any IPv4 address
+                || segments[..6] == [0x64, 0xff9b, 0, 0, 0, 0]
+                // IPv4-compatible (the deprecated ::a.b.c.d)
+                || segments[..6] == [0; 6]
+                || ip.to_ipv4_mapped().is_some_and(|ip| !is_public(IpAddr::V4(ip))))
+        }
+    }
+}
+
+// Where Rust-side requests may connect; built with each client, so changes to the allowed
+// hosts apply from the next request
+#[derive(Clone)]
+struct Guard {
+    allowed: Arc<Vec<String>>,
+}
+
+impl Guard {
+    fn new(app: &AppHandle) -> Guard {
+        let mut allowed = allowed_private_hosts(app.clone());
+        allowed.extend(PROXY_VARIABLES.iter().filter_map(|variable| {
+            let proxy = std::env::var(variable).ok()?;
+            let proxy = if proxy.contains("://") {
+                proxy
+            } else {
+                format!("http://{}", proxy)
+            };
+            let url = Url::parse(&proxy).ok()?;
+            Some(
+                url.host_str()?
+                    .trim_matches(['[', ']'])
+                    .to_ascii_lowercase(),
+            )
+        }));
+        Guard {
+            allowed: Arc::new(allowed),
+        }
+    }
+
+    fn allows(&self, host: &str) -> bool {
+        let host = host.trim_matches(['[', ']']).trim_end_matches('.');
+        self.allowed
+            .iter()
+            .any(|allowed| allowed.eq_ignore_ascii_case(host))
+    }
+
+    // What can be told without a lookup: the scheme, and hosts that are addresses or
+    // localhost (which is never looked up)
+    fn check_literal(&self, url: &Url, https_only: bool) -> Result<(), String> {
+        match url.scheme() {
+            "https" => {}
+            "http" if !https_only => {}
+            scheme if https_only => return Err(format!("Only https is allowed, not {}", scheme)),
+            scheme => return Err(format!("Only http and https are allowed, not {}", scheme)),
+        }
+        let host = url.host_str().ok_or("The URL has no host")?;
+        if self.allows(host) {
+            return Ok(());
+        }
+        let local = match host.trim_matches(['[', ']']).parse::<IpAddr>() {
+            Ok(ip) => !is_public(ip),
+            Err(_) => {
+                let host = host.trim_end_matches('.').to_ascii_lowercase();
+                host == "localhost" || host.ends_with(".localhost")
+            }
+        };
+        if local {
+            return Err(format!("{} is a local address", host));
+        }
+        Ok(())
+    }
+
+    fn check_resolved(&self, host: &str, addrs: &[SocketAddr]) -> Result<(), String> {
+        if self.allows(host) {
+            return Ok(());
+        }
+        if addrs.iter().any(|addr| !is_public(addr.ip())) {
+            return Err(format!("{} points at a local address", host));
+        }
+        Ok(())
+    }
+}
+
+// Every name the client connects to is looked up here, so nothing reaches a local
+// address by way of DNS
+impl Resolve for Guard {
+    fn resolve(&self, name: Name) -> Resolving {
+        let guard = self.clone();
+        Box::pin(async move {
+            let host = name.as_str().to_string();
+            let addrs = lookup(host.clone()).await?;
+            guard.check_resolved(&host, &addrs)?;
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+async fn lookup(host: String) -> Result<Vec<SocketAddr>, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let addrs: Vec<SocketAddr> = (host.as_str(), 0)
+            .to_socket_addrs()
+            .map_err(|e| format!("Failed to look up {}: {}", host, e))?
+            .collect();
+        if addrs.is_empty() {
+            return Err(format!("{} has no addresses", host));
+        }
+        Ok(addrs)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+
+    #[test]
+    fn public_addresses() {
+        let cases = [
+            // Private
+            ("10.1.2.3", false),
+            ("172.16.0.1", false),
+            ("192.168.1.1", false),
+            ("100.64.0.1", false),
+            ("fc00::1", false),
+            ("fd12:3456::1", false),
+            // Loopback and unspecified
+            ("127.0.0.1", false),
+            ("127.255.255.254", false),
+            ("0.0.0.0", false),
+            ("0.1.2.3", false),
+            ("::1", false),
+            ("::", false),
+            // Link-local
+            ("169.254.169.254", false),
+            ("fe80::1", false),
+            // Multicast, reserved and benchmarking
+            ("224.0.0.1", false),
+            ("239.255.255.250", false),
+            ("240.0.0.1", false),
+            ("255.255.255.255", false),
+            ("198.18.0.1", false),
+            ("198.19.255.255", false),
+            ("ff02::1", false),
+            // IPv4 inside IPv6
+            ("::ffff:127.0.0.1", false),
+            ("::ffff:10.0.0.1", false),
+            ("::127.0.0.1", false),
+            ("::8.8.8.8", false),
+            ("64:ff9b::7f00:1", false),
+            ("64:ff9b::808:808", false),
+            // Public
+            ("8.8.8.8", true),
+            ("1.1.1.1", true),
+            ("100.128.0.1", true),
+            ("198.17.255.255", true),
+            ("198.20.0.1", true),
+            ("223.255.255.255", true),
+            ("::ffff:8.8.8.8", true),
+            ("2606:4700:4700::1111", true),
+            ("2001:4860:4860::8888", true),
+        ];
+        for (ip, public) in cases {
+            assert_eq!(is_public(ip.parse().unwrap()), public, "{}", ip);
+        }
+    }
+
+    fn guard(allowed: &[&str]) -> Guard {
+        Guard {
+            allowed: Arc::new(allowed.iter().map(|host| host.to_string()).collect()),
+        }
+    }
+
+    #[test]
+    fn literal_hosts() {
+        let guard = guard(&["192.168.1.10", "intranet.example"]);
+        let check =
+            |url: &str, https_only| guard.check_literal(&Url::parse(url).unwrap(), https_only);
+
+        assert!(check("https://example.com/", false).is_ok());
+        assert!(check("http://example.com/", false).is_ok());
+        assert!(check("http://example.com/", true).is_err());
+        assert!(check("ftp://example.com/", false).is_err());
+        assert!(check("http://localhost:8080/", false).is_err());
+        assert!(check("http://api.localhost./", false).is_err());
+        assert!(check("http://127.0.0.1/", false).is_err());
+        assert!(check("http://[::1]/", false).is_err());
+        assert!(check("http://[::ffff:7f00:1]/", false).is_err());
+        assert!(check("http://169.254.169.254/latest/", false).is_err());
+        assert!(check("http://192.168.1.10/", false).is_ok());
+        assert!(check("http://192.168.1.11/", false).is_err());
+    }
+
+    #[test]
+    fn resolved_hosts() {
+        let guard = guard(&["intranet.example"]);
+        let addrs = |ips: &[&str]| -> Vec<SocketAddr> {
+            ips.iter()
+                .map(|ip| SocketAddr::new(ip.parse().unwrap(), 0))
+                .collect()
+        };
+
+        assert!(guard
+            .check_resolved("example.com", &addrs(&["93.184.215.14"]))
+            .is_ok());
+        assert!(guard
+            .check_resolved("example.com", &addrs(&["93.184.215.14", "10.0.0.1"]))
+            .is_err());
+        assert!(guard
+            .check_resolved("rebind.example", &addrs(&["127.0.0.1"]))
+            .is_err());
+        assert!(guard
+            .check_resolved("intranet.example", &addrs(&["10.0.0.1"]))
+            .is_ok());
+        assert!(guard
+            .check_resolved("INTRANET.example.", &addrs(&["10.0.0.1"]))
+            .is_ok());
+    }
+
+    #[test]
+    fn resolver_refuses_local_names() {
+        let guard = guard(&[]);
+        let resolved =
+            tauri::async_runtime::block_on(guard.resolve(Name::from_str("localhost").unwrap()));
+        assert!(resolved.is_err());
+    }
+}
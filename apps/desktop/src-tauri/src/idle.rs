@@ -0,0 +1,61 @@
+use std::time::Duration;
+
+/// How long since the user last used the keyboard, mouse or trackpad anywhere on the
+/// system, or None where the system doesn't say
+#[cfg(windows)]
+pub fn idle_time() -> Option<Duration> {
+    use windows_sys::Win32::System::SystemInformation::GetTickCount;
+    use windows_sys::Win32::UI::Input::KeyboardAndMouse::{GetLastInputInfo, LASTINPUTINFO};
+
+    let mut info = LASTINPUTINFO {
+        cbSize: std::mem::size_of::<LASTINPUTINFO>() as u32,
+        dwTime: 0,
+    };
+    if unsafe { GetLastInputInfo(&mut info) } == 0 {
+        return None;
+    }
+    // Both are in milliseconds since boot and wrap after 49 days
+    let now = unsafe { GetTickCount() };
+    Some(Duration::from_millis(now.wrapping_sub(info.dwTime) as u64))
+}
+
+#[cfg(target_os = "macos")]
+pub fn idle_time() -> Option<Duration> {
+    use objc2_core_graphics::{CGEventSource, CGEventSourceStateID, CGEventType};
+
+    // kCGAnyInputEventType
+    let any_input = CGEventType(u32::MAX);
+    let seconds = CGEventSource::seconds_since_last_event_type(
+        CGEventSourceStateID::CombinedSessionState,
+        any_input,
+    );
+    (seconds.is_finite() && seconds >= 0.0).then(|| Duration::from_secs_f64(seconds))
+}
+
+// Only GNOME tells; other desktops have no common interface for it
+#[cfg(target_os = "linux")]
+pub fn idle_time() -> Option<Duration> {
+    use std::sync::OnceLock;
+    use zbus::blocking::Connection;
+
+    static SESSION: OnceLock<Option<Connection>> = OnceLock::new();
+    let session = SESSION
+        .get_or_init(|| Connection::session().ok())
+        .as_ref()?;
+    let reply = session
+        .call_method(
+            Some("org.gnome.Mutter.IdleMonitor"),
+            "/org/gnome/Mutter/IdleMonitor/Core",
+            Some("org.gnome.Mutter.IdleMonitor"),
+            "GetIdletime",
+            &(),
+        )
+        .ok()?;
+    let millis: u64 = reply.body().deserialize().ok()?;
+    Some(Duration::from_millis(millis))
+}
+
+#[cfg(not(any(windows, target_os = "macos", target_os = "linux")))]
+pub fn idle_time() -> Option<Duration> {
+    None
+}
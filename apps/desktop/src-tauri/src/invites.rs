@@ -0,0 +1,112 @@
+use std::collections::HashSet;
+use std::fs;
+use std::path::PathBuf;
+
+use serde::Serialize;
+use tauri::command;
+
+/// Most rows a single invite file may contain
+const MAX_ROWS: usize = 1000;
+
+/// Roles an invite can grant; owners can't be invited
+const ROLES: &[&str] = &["member", "admin"];
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InviteRow {
+    /// 1-based line in the file, for pointing the admin at bad rows
+    line: u64,
+    email: String,
+    name: Option<String>,
+    role: String,
+    /// Why the row can't be sent; valid rows have none
+    error: Option<String>,
+}
+
+/// Read a CSV of `email,name,role` rows for bulk invites. A header row is optional and
+/// may order the columns freely; name and role may be blank (role defaults to member).
+///
+/// Invalid rows are returned with an `error` rather than failing the whole file.
+/// Fails only if the file can't be read or parsed, or has more than 1000 rows.
+#[command]
+pub fn parse_invite_csv(path: PathBuf) -> Result<Vec<InviteRow>, String> {
+    let contents = fs::read_to_string(&path).map_err(|e| e.to_string())?;
+    // Spreadsheet exports often start with a BOM, and csv miscounts lines on CRLF
+    let contents = contents.strip_prefix('\u{feff}').unwrap_or(&contents);
+    let contents = contents.replace("\r\n", "\n");
+
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .trim(csv::Trim::All)
+        .from_reader(contents.as_bytes());
+    let mut records = reader.records().peekable();
+
+    // Columns by position unless the first row names them
+    let mut columns = [Some(0), Some(1), Some(2)];
+    if let Some(Ok(first)) = records.peek() {
+        let names: Vec<String> = first.iter().map(str::to_lowercase).collect();
+        if names.iter().any(|name| name == "email") {
+            columns =
+                ["email", "name", "role"].map(|column| names.iter().position(|n| n == column));
+            records.next();
+        }
+    }
+
+    let mut rows = Vec::new();
+    let mut seen = HashSet::new();
+    for record in records {
+        let record = record.map_err(|e| e.to_string())?;
+        let field = |column: Option<usize>| {
+            column
+                .and_then(|i| record.get(i))
+                .filter(|value| !value.is_empty())
+                .map(str::to_string)
+        };
+        let email = field(columns[0]).unwrap_or_default();
+        let name = field(columns[1]);
+        let role = field(columns[2])
+            .map(|role| role.to_lowercase())
+            .unwrap_or_else(|| ROLES[0].to_string());
+
+        // Skip lines that are blank apart from separators
+        if record.iter().all(str::is_empty) {
+            continue;
+        }
+        if rows.len() == MAX_ROWS {
+            return Err(format!("Invite files are limited to {} rows", MAX_ROWS));
+        }
+
+        let error = if !is_valid_email(&email) {
+            Some(format!("\"{}\" is not a valid email address", email))
+        } else if !ROLES.contains(&role.as_str()) {
+            Some(format!("Role must be one of {}", ROLES.join(", ")))
+        } else if !seen.insert(email.to_lowercase()) {
+            Some("Duplicate email".to_string())
+        } else {
+            None
+        };
+        let line = record.position().map_or(0, |position| position.line());
+        rows.push(InviteRow {
+            line,
+            email,
+            name,
+            role,
+            error,
+        });
+    }
+    Ok(rows)
+}
+
+// Deliberately loose: the server does the real check, this only catches obvious typos
+fn is_valid_email(email: &str) -> bool {
+    let Some((local, domain)) = email.split_once('@') else {
+        return false;
+    };
+    !local.is_empty()
+        && !domain.contains('@')
+        && domain.contains('.')
+        && !domain.starts_with('.')
+        && !domain.ends_with('.')
+        && !email.chars().any(char::is_whitespace)
+}
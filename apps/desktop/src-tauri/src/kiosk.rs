@@ -0,0 +1,198 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+#[cfg(desktop)]
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+
+use tauri::plugin::TauriPlugin;
+use tauri::{command, AppHandle, Emitter, Manager, Url, Wry};
+
+#[cfg(desktop)]
+use crate::menu;
+use crate::passcode;
+
+const KIOSK_FLAG: &str = "--kiosk";
+
+/// Menu items taken out of the menu bar while in kiosk mode
+#[cfg(desktop)]
+const RESTRICTED_ITEMS: &[&str] = &["settings", "check_updates", "copy_diagnostics", "quit"];
+
+/// Whether kiosk mode is on. Never persisted: a restart without `--kiosk` leaves it.
+static KIOSK: AtomicBool = AtomicBool::new(false);
+
+/// How often open devtools are looked for while in kiosk mode
+const DEVTOOLS_POLL: Duration = Duration::from_millis(250);
+
+// What kiosk mode took out of the menu bar, to put back when it's turned off
+#[cfg(desktop)]
+fn removed_items() -> &'static Mutex<Option<menu::Removed>> {
+    static REMOVED: OnceLock<Mutex<Option<menu::Removed>>> = OnceLock::new();
+    REMOVED.get_or_init(|| Mutex::new(None))
+}
+
+/// Turn kiosk mode on or off for shared terminals, emitting `kiosk-changed`. While on:
+///
+/// - Devtools are kept closed, including ones opened with Inspect Element.
+/// - Webviews can't navigate away from the app's own pages, or the dev server's when
+///   running under `tauri dev`; `open_path` and `reveal_in_file_manager` only accept the
+///   downloads and app data folders.
+/// - Settings, Check for Updates, Copy Diagnostics and Quit are hidden from the menu bar,
+///   along with the Help menu they leave empty.
+/// - Quitting from the menu, the tray or `quit_app` emits `kiosk-quit-requested` instead,
+///   so the frontend can ask for a passcode before turning kiosk mode off and quitting.
+/// - The passcode can't be changed or cleared.
+///
+/// Turning it on needs a passcode to be set, and turning it off takes that `passcode`,
+/// which counts towards the wait like `verify_passcode`. Turning it off lifts every
+/// restriction. Launching with `--kiosk` turns it on at startup.
+#[command]
+pub async fn set_kiosk(
+    app: AppHandle,
+    enabled: bool,
+    passcode: Option<String>,
+) -> Result<(), String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        if enabled && !passcode::is_set(&app) {
+            return Err("Set a passcode before turning on kiosk mode".to_string());
+        }
+        if !enabled && self::enabled() {
+            let passcode = passcode.ok_or_else(|| "Enter the passcode".to_string())?;
+            if !passcode::check(&app, &passcode)? {
+                return Err("Wrong passcode".to_string());
+            }
+        }
+        apply(&app, enabled)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+pub fn enabled() -> bool {
+    KIOSK.load(Ordering::SeqCst)
+}
+
+/// Whether the app was launched with `--kiosk`, for choices made before it's built
+pub fn requested() -> bool {
+    std::env::args().skip(1).any(|arg| arg == KIOSK_FLAG)
+}
+
+/// Apply `--kiosk` from the command line. Runs after the menu is built.
+pub fn setup(app: &AppHandle) {
+    if !requested() {
+        return;
+    }
+    // Kiosk mode still comes on, since a shared terminal shouldn't start unrestricted,
+    // but without a passcode only stopping the process leaves it
+    if !passcode::is_set(app) {
+        log::warn!("Entering kiosk mode without a passcode set");
+    }
+    if let Err(e) = apply(app, true) {
+        log::warn!("Failed to enter kiosk mode: {}", e);
+    }
+}
+
+fn apply(app: &AppHandle, enabled: bool) -> Result<(), String> {
+    let was_enabled = KIOSK.swap(enabled, Ordering::SeqCst);
+    if enabled && !was_enabled {
+        keep_devtools_closed(app.clone());
+    }
+
+    #[cfg(desktop)]
+    {
+        let mut removed = removed_items().lock().unwrap();
+        if enabled && removed.is_none() {
+            let items = menu::remove_items(app, RESTRICTED_ITEMS).map_err(|e| e.to_string())?;
+            *removed = Some(items);
+        } else if let Some(items) = removed.take().filter(|_| !enabled) {
+            menu::restore_items(app, items).map_err(|e| e.to_string())?;
+        }
+    }
+
+    let _ = app.emit("kiosk-changed", enabled);
+    Ok(())
+}
+
+// Windows built with devtools keep Inspect Element in their context menu, so close
+// whatever opens until kiosk mode is turned off
+fn keep_devtools_closed(app: AppHandle) {
+    std::thread::spawn(move || {
+        while enabled() {
+            for window in app.webview_windows().values() {
+                if window.is_devtools_open() {
+                    window.close_devtools();
+                }
+            }
+            std::thread::sleep(DEVTOOLS_POLL);
+        }
+    });
+}
+
+/// Emit `kiosk-quit-requested` in place of quitting. Returns false outside kiosk mode.
+pub fn intercept_quit(app: &AppHandle) -> bool {
+    if !enabled() {
+        return false;
+    }
+    let _ = app.emit("kiosk-quit-requested", ());
+    true
+}
+
+/// Plugin that keeps webviews on the app's own pages while in kiosk mode
+pub fn plugin() -> TauriPlugin<Wry> {
+    tauri::plugin::Builder::new("kiosk")
+        .on_navigation(|webview, url| {
+            let config = webview.app_handle().config();
+            let dev_url = config.build.dev_url.as_ref().filter(|_| tauri::is_dev());
+            !enabled() || is_app_url(url, dev_url)
+        })
+        .build()
+}
+
+// The app's own protocols (tauri://localhost, or http://tauri.localhost on Windows), the
+// dev server's origin when there is one, and URLs that don't leave the page
+fn is_app_url(url: &Url, dev_url: Option<&Url>) -> bool {
+    match url.scheme() {
+        "tauri" | "asset" | "ipc" => url.host_str() == Some("localhost"),
+        "data" | "blob" | "about" => true,
+        "http" | "https" => {
+            matches!(
+                url.host_str(),
+                Some("tauri.localhost" | "asset.localhost" | "ipc.localhost")
+            ) || dev_url.is_some_and(|dev_url| dev_url.origin() == url.origin())
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn url(url: &str) -> Url {
+        Url::parse(url).unwrap()
+    }
+
+    #[test]
+    fn app_pages_are_allowed() {
+        assert!(is_app_url(&url("tauri://localhost/channels"), None));
+        assert!(is_app_url(&url("http://tauri.localhost/channels"), None));
+        assert!(is_app_url(&url("https://tauri.localhost/"), None));
+        assert!(is_app_url(&url("about:blank"), None));
+
+        let dev_url = url("http://localhost:3000");
+        assert!(is_app_url(
+            &url("http://localhost:3000/channels"),
+            Some(&dev_url)
+        ));
+    }
+
+    #[test]
+    fn other_pages_are_refused() {
+        let dev_url = url("http://localhost:3000");
+        assert!(!is_app_url(&url("http://localhost:3000/"), None));
+        assert!(!is_app_url(&url("http://localhost:8080/"), Some(&dev_url)));
+        assert!(!is_app_url(&url("http://127.0.0.1:3000/"), Some(&dev_url)));
+        assert!(!is_app_url(&url("https://localhost:3000/"), Some(&dev_url)));
+        assert!(!is_app_url(&url("https://example.com/"), Some(&dev_url)));
+        assert!(!is_app_url(&url("tauri://example.com/"), None));
+        assert!(!is_app_url(&url("file:///etc/passwd"), None));
+    }
+}
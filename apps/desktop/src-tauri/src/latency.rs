@@ -0,0 +1,142 @@
+use std::net::{SocketAddr, TcpStream, ToSocketAddrs};
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+use tauri::{command, AppHandle};
+
+use crate::http;
+
+/// How long one sample may take before it counts as failed
+const SAMPLE_TIMEOUT: Duration = Duration::from_secs(5);
+
+const MAX_SAMPLES: u32 = 20;
+
+/// Round-trip times in milliseconds. `dns_ms` and `connect_ms` are averages of separate
+/// lookups and TCP connects; the other figures time requests over a kept-alive connection,
+/// so they're close to the server's response time.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Latency {
+    samples: u32,
+    failed: u32,
+    min_ms: f64,
+    avg_ms: f64,
+    max_ms: f64,
+    /// Mean difference between consecutive samples
+    jitter_ms: f64,
+    dns_ms: Option<f64>,
+    connect_ms: Option<f64>,
+}
+
+/// Time `samples` lightweight HEAD requests to `url` (at most 20), e.g. for a connection
+/// status readout. Fails only if every sample fails.
+#[command]
+pub async fn measure_latency(app: AppHandle, url: String, samples: u32) -> Result<Latency, String> {
+    let parsed = http::check_url(&app, &url, false).await?;
+    // IPv6 hosts come bracketed, which the resolver doesn't accept
+    let host = parsed.host_str().ok_or("The URL has no host")?;
+    let host = host
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .to_string();
+    let port = parsed
+        .port_or_known_default()
+        .ok_or("The URL has no port")?;
+    let samples = samples.clamp(1, MAX_SAMPLES);
+    let allow_local = http::allowed_private_hosts(app.clone())
+        .iter()
+        .any(|allowed| allowed.eq_ignore_ascii_case(&host));
+
+    let (dns_ms, connect_ms) = tauri::async_runtime::spawn_blocking(move || {
+        time_dns_and_connect(&host, port, samples, allow_local)
+    })
+    .await
+    .map_err(|e| e.to_string())?;
+
+    let client = http::client(&app)?;
+    // Opens the connection (and TLS session) the timed requests reuse
+    let _ = client
+        .head(parsed.clone())
+        .timeout(SAMPLE_TIMEOUT)
+        .send()
+        .await;
+
+    let mut times = Vec::new();
+    for _ in 0..samples {
+        let started = Instant::now();
+        let sent = client
+            .head(parsed.clone())
+            .timeout(SAMPLE_TIMEOUT)
+            .send()
+            .await;
+        if sent.is_ok() {
+            times.push(millis(started.elapsed()));
+        }
+    }
+    if times.is_empty() {
+        return Err(format!(
+            "No response from {} within {:?}",
+            url, SAMPLE_TIMEOUT
+        ));
+    }
+
+    let jitter_ms = match times.len() {
+        1 => 0.0,
+        n => times.windows(2).map(|w| (w[1] - w[0]).abs()).sum::<f64>() / (n - 1) as f64,
+    };
+    Ok(Latency {
+        samples,
+        failed: samples - times.len() as u32,
+        min_ms: times.iter().copied().fold(f64::INFINITY, f64::min),
+        avg_ms: times.iter().sum::<f64>() / times.len() as f64,
+        max_ms: times.iter().copied().fold(0.0, f64::max),
+        jitter_ms,
+        dns_ms,
+        connect_ms,
+    })
+}
+
+// Average lookup and TCP connect times, None where every attempt failed. Lookups may be
+// answered from the OS resolver cache after the first one, like real requests. Unless
+// `allow_local`, only public addresses are connected to, as with `http::client`, since
+// the name may have been changed to point elsewhere after `check_url`.
+fn time_dns_and_connect(
+    host: &str,
+    port: u16,
+    samples: u32,
+    allow_local: bool,
+) -> (Option<f64>, Option<f64>) {
+    let mut dns = Vec::new();
+    let mut connect = Vec::new();
+    for _ in 0..samples {
+        let started = Instant::now();
+        let Some(addrs) = resolve(host, port) else {
+            continue;
+        };
+        dns.push(millis(started.elapsed()));
+        let Some(addr) = addrs
+            .into_iter()
+            .find(|addr| allow_local || http::is_public(addr.ip()))
+        else {
+            continue;
+        };
+
+        let started = Instant::now();
+        if TcpStream::connect_timeout(&addr, SAMPLE_TIMEOUT).is_ok() {
+            connect.push(millis(started.elapsed()));
+        }
+    }
+    (average(&dns), average(&connect))
+}
+
+fn resolve(host: &str, port: u16) -> Option<Vec<SocketAddr>> {
+    Some((host, port).to_socket_addrs().ok()?.collect())
+}
+
+fn average(times: &[f64]) -> Option<f64> {
+    (!times.is_empty()).then(|| times.iter().sum::<f64>() / times.len() as f64)
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
@@ -0,0 +1,73 @@
+use std::sync::{Mutex, OnceLock};
+
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, WebviewWindow};
+
+const WORKSPACE_FLAG: &str = "--workspace";
+
+/// Longest workspace id accepted from the command line
+const MAX_WORKSPACE_ID_LEN: usize = 64;
+
+// Workspace the app was last launched into, kept for reloads of the main window
+fn workspace() -> &'static Mutex<Option<String>> {
+    static WORKSPACE: OnceLock<Mutex<Option<String>>> = OnceLock::new();
+    WORKSPACE.get_or_init(|| Mutex::new(None))
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct LaunchWorkspace<'a> {
+    id: &'a str,
+}
+
+/// Remember `--workspace <id>` from this process's arguments. The main window is told
+/// about it once its page loads.
+pub fn setup() {
+    if let Some(id) = workspace_arg(std::env::args().skip(1)) {
+        *workspace().lock().unwrap() = Some(id);
+    }
+}
+
+/// Switch to the workspace a second launch asked for, e.g. from a kiosk shortcut
+pub fn handle_second_instance(app: &AppHandle, argv: Vec<String>) {
+    let Some(id) = workspace_arg(argv.into_iter().skip(1)) else {
+        return;
+    };
+    let _ = app.emit("launch-workspace", LaunchWorkspace { id: &id });
+    *workspace().lock().unwrap() = Some(id);
+}
+
+/// Emit `launch-workspace` to the main window after it loads, if a workspace was requested
+pub fn restore_workspace(window: &WebviewWindow) {
+    if window.label() != "main" {
+        return;
+    }
+    if let Some(id) = workspace().lock().unwrap().as_deref() {
+        let _ = window.emit("launch-workspace", LaunchWorkspace { id });
+    }
+}
+
+// Accepts `--workspace <id>` and `--workspace=<id>`; every other argument is ignored
+fn workspace_arg(args: impl IntoIterator<Item = String>) -> Option<String> {
+    let mut args = args.into_iter();
+    let mut id = None;
+    while let Some(arg) = args.next() {
+        if arg == WORKSPACE_FLAG {
+            id = args.next();
+        } else if let Some(value) = arg.strip_prefix("--workspace=") {
+            id = Some(value.to_string());
+        }
+    }
+
+    let id = id?;
+    let valid = !id.is_empty()
+        && id.len() <= MAX_WORKSPACE_ID_LEN
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if !valid {
+        log::warn!("Ignoring invalid workspace id \"{}\"", id);
+        return None;
+    }
+    Some(id)
+}
@@ -1,12 +1,82 @@
 use std::collections::HashMap;
 use std::sync::{Mutex, OnceLock};
 use std::thread;
-#[cfg(desktop)]
-use tauri::menu::{Menu, MenuItem, PredefinedMenuItem, Submenu};
-use tauri::{command, AppHandle, Emitter, Manager};
+use tauri::webview::PageLoadEvent;
+use tauri::{command, AppHandle, Emitter, Manager, RunEvent, WindowEvent};
 use tauri_plugin_decorum::WebviewWindowExt;
 use tiny_http::{Header, Method, Response, Server};
 
+mod accent;
+mod accessibility;
+mod archive;
+mod asset_protocol;
+mod audio;
+mod audio_devices;
+mod automation;
+mod background;
+mod background_sync;
+mod backoff;
+mod bandwidth;
+mod cache;
+mod changelog;
+mod clipboard;
+mod custom_css;
+mod data_dir;
+mod db;
+mod devtools;
+mod diagnostics;
+mod disk;
+mod downloads;
+mod emoji;
+mod emulation;
+mod encryption;
+mod files;
+mod find;
+mod flags;
+mod focus;
+mod folder_watch;
+mod gpu;
+mod highlight;
+mod http;
+mod idle;
+mod invites;
+mod kiosk;
+mod latency;
+mod launch;
+mod lifecycle;
+mod link_preview;
+mod locale;
+mod lock;
+mod logging;
+mod markdown;
+mod media_keys;
+#[cfg(desktop)]
+mod menu;
+mod message_text;
+mod notification_center;
+mod notifications;
+mod passcode;
+mod power;
+mod qr;
+mod quiet_hours;
+mod recording;
+mod redirects;
+mod search;
+mod settings;
+#[cfg(desktop)]
+mod shortcuts;
+mod status;
+mod timezone;
+mod transcode;
+#[cfg(desktop)]
+mod tray;
+mod updates;
+mod uploads;
+mod waveform;
+mod webhooks;
+mod window;
+mod wipe;
+
 // Port range for OAuth callback server (dynamic)
 const OAUTH_PORT_MIN: u16 = 17900;
 const OAUTH_PORT_MAX: u16 = 17999;
@@ -170,30 +240,283 @@ fn start_oauth_server(app: AppHandle) -> Result<(u16, String), String> {
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
-    let builder = tauri::Builder::default()
+    let mut context = tauri::generate_context!();
+    wipe::prepare(&context);
+    gpu::apply_startup_flags(&context.config().identifier);
+    #[cfg(desktop)]
+    let updater = http::updater_plugin(&context.config().identifier);
+    devtools::prepare(&mut context);
+    data_dir::prepare(&mut context);
+
+    let builder = tauri::Builder::default();
+
+    // Must be registered first so a second launch is handed off before anything else starts
+    #[cfg(desktop)]
+    let builder = builder.plugin(tauri_plugin_single_instance::init(|app, argv, _cwd| {
+        if let Some(window) = app.get_webview_window("main") {
+            let _ = window::show_and_focus(&window);
+        }
+        launch::handle_second_instance(app, argv);
+    }));
+
+    let builder = builder
         .plugin(tauri_plugin_window_state::Builder::new().build())
         .plugin(tauri_plugin_deep_link::init())
         .plugin(tauri_plugin_opener::init())
         .plugin(tauri_plugin_notification::init())
         .plugin(tauri_plugin_store::Builder::new().build())
-        .invoke_handler(tauri::generate_handler![start_oauth_server]);
+        .plugin(kiosk::plugin())
+        .register_asynchronous_uri_scheme_protocol(
+            asset_protocol::SCHEME,
+            |ctx, request, responder| {
+                // File reads stay off the webview's thread
+                let app = ctx.app_handle().clone();
+                thread::spawn(move || responder.respond(asset_protocol::handle(&app, &request)));
+            },
+        )
+        .on_window_event(|window, event| {
+            if let WindowEvent::Focused(true) = event {
+                // OS settings are usually changed in another app, so coming back to Hazel
+                // is when to look for changes
+                locale::check_for_change(window.app_handle());
+                accessibility::check_for_change(window.app_handle());
+                accent::check_for_change(window.app_handle());
+                timezone::check_for_change(window.app_handle());
+                #[cfg(desktop)]
+                tray::stop_blink(window.app_handle());
+            }
+        })
+        .on_page_load(|webview, payload| {
+            if payload.event() != PageLoadEvent::Finished {
+                return;
+            }
+            if let Some(window) = webview.app_handle().get_webview_window(webview.label()) {
+                window::restore_titlebar_color(&window);
+                accessibility::restore_high_contrast(&window);
+                custom_css::restore_custom_css(&window);
+                background::restore_background_image(&window);
+                launch::restore_workspace(&window);
+                emulation::notify_if_emulated(&window);
+                devtools::install_shortcut(&window);
+            }
+        })
+        .invoke_handler(tauri::generate_handler![
+            start_oauth_server,
+            accent::system_accent_color,
+            accessibility::accessibility_prefs,
+            accessibility::set_high_contrast,
+            archive::zip_paths,
+            archive::cancel_zip,
+            archive::extract_archive,
+            audio_devices::list_audio_devices,
+            audio_devices::set_audio_output,
+            automation::set_automation_api,
+            automation::automation_api_port,
+            automation::get_local_api_token,
+            automation::rotate_local_api_token,
+            background::set_background_image,
+            background::clear_background_image,
+            background_sync::start_background_sync,
+            background_sync::stop_background_sync,
+            background_sync::set_online,
+            backoff::next_backoff,
+            backoff::reset_backoff,
+            bandwidth::set_bandwidth_limit,
+            cache::cache_size,
+            cache::clear_cache,
+            cache::purge_cache_older_than,
+            cache::set_cache_purge_on_startup,
+            changelog::set_changelog_endpoint,
+            changelog::fetch_changelog,
+            clipboard::read_clipboard_image,
+            clipboard::read_clipboard_text,
+            clipboard::start_clipboard_watch,
+            clipboard::stop_clipboard_watch,
+            custom_css::apply_custom_css,
+            custom_css::clear_custom_css,
+            data_dir::set_data_dir,
+            data_dir::reset_data_dir,
+            db::upsert_channels,
+            db::list_stored_channels,
+            db::upsert_messages,
+            db::stored_messages,
+            db::export_history,
+            encryption::set_cache_encryption,
+            encryption::cache_encryption_enabled,
+            devtools::set_devtools_enabled,
+            devtools::open_devtools,
+            devtools::close_devtools,
+            devtools::toggle_devtools,
+            diagnostics::dump_config,
+            diagnostics::set_bug_report_endpoint,
+            diagnostics::submit_bug_report,
+            disk::available_disk_space,
+            downloads::download_file,
+            downloads::download_batch,
+            downloads::cancel_download_batch,
+            emoji::open_emoji_picker,
+            emulation::is_emulated,
+            emulation::native_arch,
+            files::open_path,
+            files::reveal_in_file_manager,
+            files::read_text_preview,
+            find::find_in_page,
+            find::stop_find,
+            flags::get_flag,
+            flags::set_flag,
+            flags::clear_flag_override,
+            flags::feature_flags,
+            flags::refresh_remote_flags,
+            focus::set_auto_status_from_focus,
+            folder_watch::watch_folder,
+            folder_watch::unwatch_folder,
+            gpu::set_hardware_acceleration,
+            highlight::highlight_code,
+            highlight::detect_language,
+            http::set_user_agent,
+            http::user_agent,
+            http::set_allowed_private_hosts,
+            http::allowed_private_hosts,
+            invites::parse_invite_csv,
+            kiosk::set_kiosk,
+            latency::measure_latency,
+            lifecycle::restart_app,
+            lifecycle::quit_app,
+            lifecycle::veto_quit,
+            link_preview::fetch_link_preview,
+            locale::system_locale,
+            lock::lock_app,
+            lock::unlock_app,
+            lock::is_app_locked,
+            lock::unlock_methods,
+            lock::set_auto_lock,
+            lock::set_lock_on_sleep,
+            logging::set_log_level,
+            logging::recent_logs,
+            markdown::render_markdown,
+            markdown::plaintext_preview,
+            media_keys::set_media_session,
+            media_keys::set_now_playing,
+            media_keys::clear_now_playing,
+            message_text::process_message_text,
+            notifications::notify,
+            notifications::notify_summary,
+            notifications::clear_notifications,
+            notifications::clear_all_notifications,
+            notifications::set_notification_sound,
+            notifications::play_sound,
+            notifications::set_presenting,
+            notifications::snooze_notifications,
+            notifications::snooze_status,
+            passcode::set_passcode,
+            passcode::verify_passcode,
+            passcode::clear_passcode,
+            qr::make_qr,
+            quiet_hours::set_quiet_hours,
+            quiet_hours::clear_quiet_hours,
+            quiet_hours::quiet_hours,
+            recording::start_recording,
+            recording::stop_recording,
+            recording::cancel_recording,
+            recording::start_mic_test,
+            recording::stop_mic_test,
+            redirects::resolve_redirects,
+            search::index_messages,
+            search::delete_indexed_messages,
+            search::search_messages,
+            search::compact_search_index,
+            search::clear_search_index,
+            search::search_index_stats,
+            #[cfg(desktop)]
+            shortcuts::register_shortcut,
+            #[cfg(desktop)]
+            shortcuts::unregister_shortcut,
+            #[cfg(desktop)]
+            shortcuts::export_shortcuts,
+            #[cfg(desktop)]
+            shortcuts::import_shortcuts,
+            #[cfg(desktop)]
+            shortcuts::default_shortcuts,
+            #[cfg(desktop)]
+            shortcuts::reset_shortcuts,
+            status::set_status,
+            status::clear_status,
+            status::custom_status,
+            timezone::system_timezone,
+            transcode::transcode_audio,
+            #[cfg(desktop)]
+            tray::set_tray_status,
+            #[cfg(desktop)]
+            tray::update_unread,
+            #[cfg(desktop)]
+            tray::set_dnd,
+            #[cfg(desktop)]
+            tray::set_recent_channels,
+            updates::set_version_pin,
+            #[cfg(desktop)]
+            updates::check_for_update,
+            #[cfg(desktop)]
+            updates::download_update,
+            #[cfg(desktop)]
+            updates::pause_update_download,
+            #[cfg(desktop)]
+            updates::resume_update_download,
+            #[cfg(desktop)]
+            updates::cancel_update_download,
+            #[cfg(desktop)]
+            updates::install_downloaded_update,
+            updates::verify_update_signature,
+            uploads::start_upload,
+            uploads::resume_upload,
+            uploads::pending_uploads,
+            uploads::cancel_upload,
+            waveform::waveform_peaks,
+            webhooks::register_webhook,
+            webhooks::webhook_events,
+            webhooks::list_webhooks,
+            webhooks::delete_webhook,
+            window::set_size_constraints,
+            window::set_decorations,
+            window::snapshot_window_state,
+            window::restore_window_state,
+            window::focus_composer,
+            window::set_titlebar_color,
+            #[cfg(desktop)]
+            window::set_focus_composer_shortcut,
+            wipe::wipe_local_data
+        ]);
 
     #[cfg(desktop)]
     let builder = builder
         .plugin(tauri_plugin_autostart::Builder::new().build())
-        .plugin(tauri_plugin_updater::Builder::new().build())
+        .plugin(updater)
         .plugin(tauri_plugin_process::init())
         .plugin(tauri_plugin_decorum::init());
 
     builder
         .setup(|app| {
-            if cfg!(debug_assertions) {
-                app.handle().plugin(
-                    tauri_plugin_log::Builder::default()
-                        .level(log::LevelFilter::Info)
-                        .build(),
-                )?;
-            }
+            logging::setup(app)?;
+            wipe::setup();
+            launch::setup();
+            data_dir::setup(app)?;
+            lock::setup(app.handle());
+            db::setup(app.handle());
+            cache::purge_on_startup(app.handle());
+            window::restore_size_constraints(app.handle());
+            notifications::restore_snooze(app.handle());
+            timezone::watch_timezone(app.handle());
+            accessibility::watch_prefs(app.handle());
+            quiet_hours::restore_quiet_hours(app.handle());
+            status::restore_status(app.handle());
+            focus::restore_auto_status(app.handle());
+            bandwidth::restore_bandwidth_limit(app.handle());
+            folder_watch::restore_watched_folders(app.handle());
+            notifications::watch_presenting(app.handle());
+            power::watch_resume(app.handle());
+            power::watch_suspend(app.handle());
+            audio_devices::watch_devices(app.handle());
+            webhooks::restore_webhooks(app.handle());
+            automation::restore_automation_api(app.handle());
 
             // Configure custom titlebar with decorum
             #[cfg(desktop)]
@@ -203,126 +526,32 @@ pub fn run() {
 
                 // macOS: Position traffic lights
                 #[cfg(target_os = "macos")]
-                main_window.set_traffic_lights_inset(16.0, 20.0).unwrap();
+                {
+                    let (x, y) = window::TRAFFIC_LIGHTS_INSET;
+                    main_window.set_traffic_lights_inset(x, y).unwrap();
+                }
+
+                window::restore_decorations(app.handle());
             }
 
             // Create native menu
             #[cfg(desktop)]
-            {
-                let settings =
-                    MenuItem::with_id(app, "settings", "Settings...", true, Some("CmdOrCtrl+,"))?;
-                let check_updates = MenuItem::with_id(
-                    app,
-                    "check_updates",
-                    "Check for Updates...",
-                    true,
-                    None::<&str>,
-                )?;
-
-                let app_submenu = Submenu::with_items(
-                    app,
-                    "Hazel",
-                    true,
-                    &[
-                        &settings,
-                        &check_updates,
-                        #[cfg(target_os = "macos")]
-                        &PredefinedMenuItem::separator(app)?,
-                        #[cfg(target_os = "macos")]
-                        &PredefinedMenuItem::hide(app, None::<&str>)?,
-                        &PredefinedMenuItem::separator(app)?,
-                        &PredefinedMenuItem::quit(app, Some("Quit Hazel"))?,
-                    ],
-                )?;
-
-                let new_channel = MenuItem::with_id(
-                    app,
-                    "new_channel",
-                    "New Channel...",
-                    true,
-                    Some("CmdOrCtrl+Alt+N"),
-                )?;
-                let invite = MenuItem::with_id(
-                    app,
-                    "invite",
-                    "Invite People...",
-                    true,
-                    Some("CmdOrCtrl+Alt+I"),
-                )?;
-
-                let file_submenu = Submenu::with_items(
-                    app,
-                    "File",
-                    true,
-                    &[&new_channel, &PredefinedMenuItem::separator(app)?, &invite],
-                )?;
+            menu::setup(app)?;
 
-                #[cfg(target_os = "macos")]
-                let edit_submenu = Submenu::with_items(
-                    app,
-                    "Edit",
-                    true,
-                    &[
-                        &PredefinedMenuItem::undo(app, None::<&str>)?,
-                        &PredefinedMenuItem::redo(app, None::<&str>)?,
-                        &PredefinedMenuItem::separator(app)?,
-                        &PredefinedMenuItem::cut(app, None::<&str>)?,
-                        &PredefinedMenuItem::copy(app, None::<&str>)?,
-                        &PredefinedMenuItem::paste(app, None::<&str>)?,
-                        &PredefinedMenuItem::separator(app)?,
-                        &PredefinedMenuItem::select_all(app, None::<&str>)?,
-                    ],
-                )?;
-                #[cfg(not(target_os = "macos"))]
-                let edit_submenu = Submenu::with_items(
-                    app,
-                    "Edit",
-                    true,
-                    &[
-                        &PredefinedMenuItem::cut(app, None::<&str>)?,
-                        &PredefinedMenuItem::copy(app, None::<&str>)?,
-                        &PredefinedMenuItem::paste(app, None::<&str>)?,
-                        &PredefinedMenuItem::separator(app)?,
-                        &PredefinedMenuItem::select_all(app, None::<&str>)?,
-                    ],
-                )?;
+            #[cfg(desktop)]
+            tray::setup(app)?;
 
-                #[cfg(target_os = "macos")]
-                let window_submenu = Submenu::with_items(
-                    app,
-                    "Window",
-                    true,
-                    &[&PredefinedMenuItem::minimize(app, None::<&str>)?],
-                )?;
-
-                #[cfg(target_os = "macos")]
-                let menu =
-                    Menu::with_items(app, &[&app_submenu, &file_submenu, &edit_submenu, &window_submenu])?;
-                #[cfg(not(target_os = "macos"))]
-                let menu = Menu::with_items(app, &[&app_submenu, &file_submenu, &edit_submenu])?;
-                app.set_menu(menu)?;
-
-                // Handle menu events
-                let app_handle = app.handle().clone();
-                app.on_menu_event(move |_app, event| match event.id().as_ref() {
-                    "settings" => {
-                        let _ = app_handle.emit("menu-open-settings", ());
-                    }
-                    "check_updates" => {
-                        let _ = app_handle.emit("menu-check-updates", ());
-                    }
-                    "new_channel" => {
-                        let _ = app_handle.emit("menu-new-channel", ());
-                    }
-                    "invite" => {
-                        let _ = app_handle.emit("menu-invite", ());
-                    }
-                    _ => {}
-                });
-            }
+            kiosk::setup(app.handle());
+            changelog::check_for_new_version(app.handle());
 
             Ok(())
         })
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(context)
+        .expect("error while building tauri application")
+        .run(|app, event| {
+            #[cfg(desktop)]
+            if let RunEvent::Exit = event {
+                tray::stop_blink(app);
+            }
+        });
 }
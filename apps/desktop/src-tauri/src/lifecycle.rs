@@ -0,0 +1,94 @@
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::sync::{Mutex, OnceLock};
+use std::thread;
+use std::time::Duration;
+
+use tauri::{command, AppHandle, Emitter};
+
+use crate::{kiosk, settings};
+
+/// How long the frontend gets to save unsaved state after `app-restarting`
+const RESTART_GRACE: Duration = Duration::from_millis(500);
+
+/// How long the frontend has to veto a non-forced quit before it goes ahead
+const QUIT_VETO_TIMEOUT: Duration = Duration::from_secs(2);
+
+// Veto channel for the quit request currently waiting on the frontend, if any
+fn pending_quit() -> &'static Mutex<Option<Sender<()>>> {
+    static PENDING: OnceLock<Mutex<Option<Sender<()>>>> = OnceLock::new();
+    PENDING.get_or_init(|| Mutex::new(None))
+}
+
+/// Relaunch the app, e.g. after changing a setting that only applies at startup.
+/// Emits `app-restarting` first, then flushes the stores and restarts through the
+/// normal exit path so plugins (single-instance, window-state) release their
+/// resources before the new process starts.
+///
+/// The process plugin's `restart` makes the same `request_restart` call but skips the
+/// event and the flush, and the plugin exposes nothing to Rust to build on, hence this.
+#[command]
+pub fn restart_app(app: AppHandle) {
+    let _ = app.emit("app-restarting", ());
+
+    // Off the main thread so the frontend's IPC calls can still be served during the grace period
+    thread::spawn(move || {
+        thread::sleep(RESTART_GRACE);
+        settings::flush(&app);
+        app.request_restart();
+    });
+}
+
+/// Quit the app. Unless forced, emits `quit-requested` and gives the frontend
+/// a short window to call `veto_quit` (e.g. while an upload is in progress).
+#[command]
+pub fn quit_app(app: AppHandle, force: bool) {
+    quit(&app, force);
+}
+
+/// Cancel the pending non-forced quit. Returns false if there was nothing to cancel.
+#[command]
+pub fn veto_quit() -> bool {
+    let Some(veto) = pending_quit().lock().unwrap().take() else {
+        return false;
+    };
+    veto.send(()).is_ok()
+}
+
+/// Emit `restart-required` naming the setting that only applies after `restart_app`
+pub fn notify_restart_required(app: &AppHandle, setting: &str) {
+    let _ = app.emit("restart-required", setting);
+}
+
+pub fn quit(app: &AppHandle, force: bool) {
+    if kiosk::intercept_quit(app) {
+        return;
+    }
+    if force {
+        settings::flush(app);
+        app.exit(0);
+        return;
+    }
+
+    let (veto_tx, veto_rx) = mpsc::channel();
+    {
+        let mut pending = pending_quit().lock().unwrap();
+        // A request is already waiting on the frontend; let it run its course
+        if pending.is_some() {
+            return;
+        }
+        *pending = Some(veto_tx);
+    }
+    let _ = app.emit("quit-requested", ());
+
+    let app = app.clone();
+    thread::spawn(move || match veto_rx.recv_timeout(QUIT_VETO_TIMEOUT) {
+        Ok(()) | Err(RecvTimeoutError::Disconnected) => {
+            let _ = app.emit("quit-cancelled", ());
+        }
+        Err(RecvTimeoutError::Timeout) => {
+            pending_quit().lock().unwrap().take();
+            settings::flush(&app);
+            app.exit(0);
+        }
+    });
+}
@@ -0,0 +1,321 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use reqwest::header::{ACCEPT, CONTENT_TYPE};
+use reqwest::Url;
+use serde::Serialize;
+use tauri::{command, AppHandle};
+
+use crate::http;
+
+/// How long fetching a page for its preview may take
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Most of a page that's read; the tags previews come from are in its head
+const MAX_HTML_SIZE: usize = 1024 * 1024;
+
+const MAX_TITLE_CHARS: usize = 300;
+const MAX_DESCRIPTION_CHARS: usize = 1000;
+const MAX_SITE_CHARS: usize = 100;
+
+/// Whatever a page says about itself; fields it doesn't give are None
+#[derive(Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LinkPreview {
+    title: Option<String>,
+    description: Option<String>,
+    /// Absolute http(s) URL
+    image: Option<String>,
+    site: Option<String>,
+}
+
+/// Fetch a page and read its preview from OpenGraph and Twitter card tags, falling back to
+/// its title and description. Fetched here rather than in the webview so any site works,
+/// without CORS or mixed-content trouble. Only the first megabyte is read and it gives up
+/// after 10 seconds. Links to images preview as the image. Addresses on this machine or the
+/// local network aren't fetched.
+#[command]
+pub async fn fetch_link_preview(app: AppHandle, url: String) -> Result<LinkPreview, String> {
+    let url = http::check_url(&app, &url, false).await?;
+
+    let mut response = http::client(&app)?
+        .get(url)
+        .header(ACCEPT, "text/html,application/xhtml+xml;q=0.9,*/*;q=0.1")
+        .timeout(TIMEOUT)
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| format!("Failed to fetch the page: {}", e))?;
+    let url = response.url().clone();
+
+    let content_type = response
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    if content_type.starts_with("image/") {
+        return Ok(LinkPreview {
+            image: Some(url.to_string()),
+            site: site_of(&url),
+            ..Default::default()
+        });
+    }
+    if !content_type.is_empty() && !content_type.contains("html") {
+        return Ok(LinkPreview {
+            site: site_of(&url),
+            ..Default::default()
+        });
+    }
+
+    let mut body = Vec::new();
+    while body.len() < MAX_HTML_SIZE {
+        match response.chunk().await {
+            Ok(Some(chunk)) => body.extend_from_slice(&chunk),
+            Ok(None) => break,
+            Err(e) => return Err(format!("Failed to fetch the page: {}", e)),
+        }
+    }
+    body.truncate(MAX_HTML_SIZE);
+
+    // Pages not in UTF-8 say so in the header; a byte order mark wins over that
+    let encoding = content_type
+        .split(';')
+        .filter_map(|param| param.trim().strip_prefix("charset="))
+        .find_map(|label| encoding_rs::Encoding::for_label(label.trim_matches('"').as_bytes()))
+        .unwrap_or(encoding_rs::UTF_8);
+    let (html, _, _) = encoding.decode(&body);
+    Ok(preview(&html, &url))
+}
+
+fn preview(html: &str, url: &Url) -> LinkPreview {
+    let head = Head::parse(html);
+    let first = |keys: &[&str]| keys.iter().find_map(|key| head.meta.get(*key));
+
+    let title = first(&["og:title", "twitter:title"])
+        .or(head.title.as_ref())
+        .and_then(|text| clean(text, MAX_TITLE_CHARS));
+    let description = first(&["og:description", "twitter:description", "description"])
+        .and_then(|text| clean(text, MAX_DESCRIPTION_CHARS));
+    let image = first(&[
+        "og:image:secure_url",
+        "og:image",
+        "og:image:url",
+        "twitter:image",
+        "twitter:image:src",
+    ])
+    .and_then(|src| url.join(decode_entities(src).trim()).ok())
+    .filter(|image| matches!(image.scheme(), "http" | "https"))
+    .map(String::from);
+    let site = first(&["og:site_name", "application-name"])
+        .and_then(|text| clean(text, MAX_SITE_CHARS))
+        .or_else(|| site_of(url));
+
+    LinkPreview {
+        title,
+        description,
+        image,
+        site,
+    }
+}
+
+fn site_of(url: &Url) -> Option<String> {
+    url.host_str()
+        .map(|host| host.strip_prefix("www.").unwrap_or(host).to_string())
+}
+
+// The parts of a page's head a preview is made of
+#[derive(Default)]
+struct Head {
+    title: Option<String>,
+    /// Content of meta tags by their lowercased property or name; the first of each wins
+    meta: HashMap<String, String>,
+}
+
+impl Head {
+    // Not a full HTML parser, but tags in scripts, styles and comments don't confuse it
+    fn parse(html: &str) -> Head {
+        // ASCII lowercasing keeps byte offsets the same
+        let lower = html.to_ascii_lowercase();
+        let mut head = Head::default();
+        let mut pos = 0;
+        while let Some(start) = lower[pos..].find('<').map(|offset| pos + offset) {
+            let rest = &lower[start..];
+            let skip_to = |end: &str| {
+                lower[start..]
+                    .find(end)
+                    .map_or(lower.len(), |offset| start + offset + end.len())
+            };
+            if rest.starts_with("<!--") {
+                pos = skip_to("-->");
+            } else if tag_is(rest, "script") {
+                pos = skip_to("</script");
+            } else if tag_is(rest, "style") {
+                pos = skip_to("</style");
+            } else if tag_is(rest, "/head") || tag_is(rest, "body") {
+                break;
+            } else if tag_is(rest, "title") {
+                let Some(open_end) = rest.find('>') else {
+                    break;
+                };
+                let text_start = start + open_end + 1;
+                let text_end = lower[text_start..]
+                    .find("</title")
+                    .map_or(lower.len(), |offset| text_start + offset);
+                if head.title.is_none() {
+                    head.title = Some(html[text_start..text_end].to_string());
+                }
+                pos = text_end;
+            } else if tag_is(rest, "meta") {
+                let (attributes, end) = attributes(html, start + "<meta".len());
+                let key = attributes
+                    .get("property")
+                    .or_else(|| attributes.get("name"))
+                    .map(|key| key.to_ascii_lowercase());
+                if let (Some(key), Some(content)) = (key, attributes.get("content")) {
+                    head.meta.entry(key).or_insert_with(|| content.clone());
+                }
+                pos = end;
+            } else {
+                pos = start + 1;
+            }
+        }
+        head
+    }
+}
+
+// Whether `rest` opens (or closes, for names starting with /) the tag `name`
+fn tag_is(rest: &str, name: &str) -> bool {
+    rest[1..].starts_with(name)
+        && rest[1 + name.len()..]
+            .bytes()
+            .next()
+            .map_or(true, |c| c.is_ascii_whitespace() || c == b'>' || c == b'/')
+}
+
+// Attributes of the tag whose name ends at `pos` (lowercased names, raw values), and
+// where the tag ends
+fn attributes(html: &str, mut pos: usize) -> (HashMap<String, String>, usize) {
+    let bytes = html.as_bytes();
+    let mut attributes = HashMap::new();
+    loop {
+        while pos < bytes.len() && (bytes[pos].is_ascii_whitespace() || bytes[pos] == b'/') {
+            pos += 1;
+        }
+        if pos >= bytes.len() || bytes[pos] == b'>' {
+            return (attributes, (pos + 1).min(bytes.len()));
+        }
+        let name_start = pos;
+        while pos < bytes.len()
+            && !matches!(bytes[pos], b'=' | b'>' | b'/')
+            && !bytes[pos].is_ascii_whitespace()
+        {
+            pos += 1;
+        }
+        let name = html[name_start..pos].to_ascii_lowercase();
+        while pos < bytes.len() && bytes[pos].is_ascii_whitespace() {
+            pos += 1;
+        }
+        if pos >= bytes.len() || bytes[pos] != b'=' {
+            attributes.entry(name).or_default();
+            continue;
+        }
+        pos += 1;
+        while pos < bytes.len() && bytes[pos].is_ascii_whitespace() {
+            pos += 1;
+        }
+        let value = match bytes.get(pos) {
+            Some(&quote @ (b'"' | b'\'')) => {
+                let value_start = pos + 1;
+                let value_end = html[value_start..]
+                    .find(quote as char)
+                    .map_or(bytes.len(), |offset| value_start + offset);
+                pos = (value_end + 1).min(bytes.len());
+                &html[value_start..value_end]
+            }
+            _ => {
+                let value_start = pos;
+                while pos < bytes.len() && bytes[pos] != b'>' && !bytes[pos].is_ascii_whitespace() {
+                    pos += 1;
+                }
+                &html[value_start..pos]
+            }
+        };
+        attributes.entry(name).or_insert_with(|| value.to_string());
+    }
+}
+
+// Entities decoded, markup and control characters dropped, whitespace collapsed and the
+// length capped; None if nothing's left
+fn clean(text: &str, max_chars: usize) -> Option<String> {
+    let decoded = decode_entities(text);
+    let mut cleaned = String::new();
+    let mut in_tag = false;
+    for c in decoded.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' if in_tag => in_tag = false,
+            _ if in_tag => {}
+            c if c.is_whitespace() => {
+                if !cleaned.is_empty() && !cleaned.ends_with(' ') {
+                    cleaned.push(' ');
+                }
+            }
+            c if c.is_control() => {}
+            c => cleaned.push(c),
+        }
+    }
+    let cleaned = cleaned.trim_end();
+    if cleaned.is_empty() {
+        return None;
+    }
+    let mut chars = cleaned.chars();
+    let capped: String = chars.by_ref().take(max_chars).collect();
+    Some(if chars.next().is_some() {
+        format!("{}…", capped.trim_end())
+    } else {
+        capped
+    })
+}
+
+// The named entities that show up in titles, plus numeric ones
+fn decode_entities(text: &str) -> String {
+    let mut decoded = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(amp) = rest.find('&') {
+        decoded.push_str(&rest[..amp]);
+        rest = &rest[amp..];
+        let entity = rest[1..]
+            .find(';')
+            .filter(|&end| end <= 10)
+            .map(|end| &rest[1..1 + end]);
+        let character = entity.and_then(|entity| match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            "nbsp" => Some(' '),
+            _ => {
+                let number = entity.strip_prefix('#')?;
+                let code = match number.strip_prefix(['x', 'X']) {
+                    Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+                    None => number.parse().ok()?,
+                };
+                char::from_u32(code)
+            }
+        });
+        match (entity, character) {
+            (Some(entity), Some(character)) => {
+                decoded.push(character);
+                rest = &rest[entity.len() + 2..];
+            }
+            _ => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    decoded.push_str(rest);
+    decoded
+}
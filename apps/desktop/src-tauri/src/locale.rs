@@ -0,0 +1,118 @@
+use std::sync::{Mutex, OnceLock};
+
+use serde::Serialize;
+use tauri::{command, AppHandle, Emitter};
+
+const DEFAULT_LOCALE: &str = "en-US";
+
+/// Regions where the 12-hour clock is the norm, used when the OS doesn't expose the preference
+const TWELVE_HOUR_REGIONS: &[&str] = &[
+    "AU", "BD", "CA", "CO", "EG", "IN", "JO", "KR", "MY", "NZ", "PH", "PK", "SA", "SV", "US",
+];
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SystemLocale {
+    /// BCP-47 tag, e.g. "en-US"
+    pub locale: String,
+    /// Region subtag, e.g. "US", if the locale has one
+    pub region: Option<String>,
+    pub uses_24_hour_clock: bool,
+}
+
+// Last locale reported to the frontend, for change detection
+fn last_locale() -> &'static Mutex<Option<SystemLocale>> {
+    static LAST: OnceLock<Mutex<Option<SystemLocale>>> = OnceLock::new();
+    LAST.get_or_init(|| Mutex::new(None))
+}
+
+/// The OS locale, region and clock preference. Falls back to "en-US" if undetectable.
+#[command]
+pub fn system_locale() -> SystemLocale {
+    let locale = detect();
+    *last_locale().lock().unwrap() = Some(locale.clone());
+    locale
+}
+
+/// Emit `locale-changed` if the OS locale changed since it was last read.
+/// Called when the window regains focus.
+pub fn check_for_change(app: &AppHandle) {
+    let current = detect();
+    let mut last = last_locale().lock().unwrap();
+    if last.as_ref().is_some_and(|last| *last != current) {
+        let _ = app.emit("locale-changed", &current);
+    }
+    *last = Some(current);
+}
+
+fn detect() -> SystemLocale {
+    let locale = sys_locale::get_locale()
+        .and_then(|raw| normalize(&raw))
+        .unwrap_or_else(|| DEFAULT_LOCALE.to_string());
+    let region = region_of(&locale);
+    let uses_24_hour_clock = platform_uses_24_hour_clock().unwrap_or_else(|| {
+        !region
+            .as_deref()
+            .is_some_and(|r| TWELVE_HOUR_REGIONS.contains(&r))
+    });
+    SystemLocale {
+        locale,
+        region,
+        uses_24_hour_clock,
+    }
+}
+
+/// Turn POSIX-style values like "en_US.UTF-8@euro" into BCP-47
+fn normalize(raw: &str) -> Option<String> {
+    let tag = raw.split(['.', '@']).next()?.replace('_', "-");
+    if tag.is_empty() || tag == "C" || tag == "POSIX" {
+        return None;
+    }
+    Some(tag)
+}
+
+/// The region subtag: two letters or three digits after the language (and optional script)
+fn region_of(locale: &str) -> Option<String> {
+    locale.split('-').skip(1).find_map(|subtag| {
+        let is_region = (subtag.len() == 2 && subtag.chars().all(|c| c.is_ascii_alphabetic()))
+            || (subtag.len() == 3 && subtag.chars().all(|c| c.is_ascii_digit()));
+        is_region.then(|| subtag.to_ascii_uppercase())
+    })
+}
+
+#[cfg(target_os = "macos")]
+fn platform_uses_24_hour_clock() -> Option<bool> {
+    use objc2_foundation::{ns_string, NSDateFormatter, NSLocale};
+
+    // "j" expands to the locale's preferred hour format, which includes "a" for 12-hour clocks
+    let locale = NSLocale::autoupdatingCurrentLocale();
+    let format =
+        NSDateFormatter::dateFormatFromTemplate_options_locale(ns_string!("j"), 0, Some(&locale))?;
+    Some(!format.to_string().contains('a'))
+}
+
+#[cfg(target_os = "windows")]
+fn platform_uses_24_hour_clock() -> Option<bool> {
+    use windows_sys::Win32::Globalization::{GetLocaleInfoEx, LOCALE_STIMEFORMAT};
+
+    let mut buf = [0u16; 80];
+    // A null locale name means the user's default locale
+    let len = unsafe {
+        GetLocaleInfoEx(
+            std::ptr::null(),
+            LOCALE_STIMEFORMAT,
+            buf.as_mut_ptr(),
+            buf.len() as i32,
+        )
+    };
+    if len <= 0 {
+        return None;
+    }
+    let format = String::from_utf16_lossy(&buf[..len as usize - 1]);
+    Some(format.contains('H'))
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+fn platform_uses_24_hour_clock() -> Option<bool> {
+    None
+}
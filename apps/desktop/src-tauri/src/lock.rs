@@ -0,0 +1,243 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::Duration;
+
+use serde::Serialize;
+use tauri::{command, AppHandle, Emitter};
+
+use crate::{idle, passcode, settings};
+
+const LOCKED_KEY: &str = "lock.locked";
+const AUTO_LOCK_KEY: &str = "lock.auto_lock_minutes";
+const ON_SLEEP_KEY: &str = "lock.on_sleep";
+
+/// How often idle time is checked for auto-lock
+const POLL_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Shown by the system's authentication prompt
+#[cfg(any(target_os = "macos", windows))]
+const UNLOCK_REASON: &str = "unlock Hazel";
+
+// Mirrors LOCKED_KEY, which keeps the app locked across restarts
+static LOCKED: AtomicBool = AtomicBool::new(false);
+
+/// Ways the app can be unlocked here, for the lock screen to offer
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UnlockMethods {
+    /// Touch ID or the account password on macOS, Windows Hello on Windows
+    system: bool,
+    passcode: bool,
+}
+
+/// Lock the app: emits `locked` so the frontend covers its content until `unlock_app`
+/// succeeds. The lock holds across restarts. Fails if there'd be no way to unlock, i.e.
+/// the system has no authentication Hazel can use and no passcode is set.
+#[command]
+pub async fn lock_app(app: AppHandle) -> Result<(), String> {
+    let methods = tauri::async_runtime::spawn_blocking({
+        let app = app.clone();
+        move || unlock_methods_for(&app)
+    })
+    .await
+    .map_err(|e| e.to_string())?;
+    if !methods.system && !methods.passcode {
+        return Err("Set a passcode first, since this system can't unlock Hazel".to_string());
+    }
+    lock(&app)
+}
+
+/// Unlock the app with the system's authentication (Touch ID or the account password on
+/// macOS, Windows Hello on Windows), or with the passcode if one is given. Emits
+/// `unlocked`. Wrong passcodes count towards the passcode's lockout.
+#[command]
+pub async fn unlock_app(app: AppHandle, passcode: Option<String>) -> Result<(), String> {
+    if !is_app_locked() {
+        return Ok(());
+    }
+    let verified = tauri::async_runtime::spawn_blocking({
+        let app = app.clone();
+        move || match passcode {
+            Some(code) => passcode::check(&app, &code),
+            None => system_auth(&app),
+        }
+    })
+    .await
+    .map_err(|e| e.to_string())??;
+    if !verified {
+        return Err("Authentication failed".to_string());
+    }
+    LOCKED.store(false, Ordering::SeqCst);
+    settings::delete(&app, LOCKED_KEY)?;
+    let _ = app.emit("unlocked", ());
+    Ok(())
+}
+
+#[command]
+pub fn is_app_locked() -> bool {
+    LOCKED.load(Ordering::SeqCst)
+}
+
+#[command]
+pub async fn unlock_methods(app: AppHandle) -> Result<UnlockMethods, String> {
+    tauri::async_runtime::spawn_blocking(move || unlock_methods_for(&app))
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Lock the app after `minutes` without keyboard or mouse input anywhere on the system,
+/// or never with None. Needs the system to report idle time, which on Linux only GNOME
+/// does.
+#[command]
+pub fn set_auto_lock(app: AppHandle, minutes: Option<u32>) -> Result<(), String> {
+    match minutes {
+        Some(0) => Err("The timeout must be at least a minute".to_string()),
+        Some(minutes) => settings::set(&app, AUTO_LOCK_KEY, minutes),
+        None => settings::delete(&app, AUTO_LOCK_KEY),
+    }
+}
+
+/// Lock the app whenever the computer goes to sleep or the screensaver starts, in addition
+/// to any auto-lock timeout
+#[command]
+pub fn set_lock_on_sleep(app: AppHandle, enabled: bool) -> Result<(), String> {
+    settings::set(&app, ON_SLEEP_KEY, enabled)
+}
+
+/// Restore the lock from the last run and start watching for idleness to auto-lock
+pub fn setup(app: &AppHandle) {
+    if settings::get(app, LOCKED_KEY).unwrap_or(false) {
+        LOCKED.store(true, Ordering::SeqCst);
+    }
+    let app = app.clone();
+    thread::spawn(move || loop {
+        thread::sleep(POLL_INTERVAL);
+        let Some(minutes) = settings::get::<u32>(&app, AUTO_LOCK_KEY) else {
+            continue;
+        };
+        if is_app_locked() || idle::idle_time().unwrap_or_default().as_secs() < minutes as u64 * 60
+        {
+            continue;
+        }
+        if !can_unlock(&app) {
+            continue;
+        }
+        if let Err(e) = lock(&app) {
+            log::warn!("Failed to auto-lock: {}", e);
+        }
+    });
+}
+
+/// Lock as the system goes to sleep or its screensaver starts, if `lock_on_sleep` is set.
+/// This runs before the system suspends, so waking it again quickly finds the app locked.
+pub fn lock_for_suspend(app: &AppHandle) {
+    if !settings::get(app, ON_SLEEP_KEY).unwrap_or(false) || !can_unlock(app) {
+        return;
+    }
+    if let Err(e) = lock(app) {
+        log::warn!("Failed to lock for sleep: {}", e);
+    }
+}
+
+// Emitted first so the content is covered even if saving the lock fails
+fn lock(app: &AppHandle) -> Result<(), String> {
+    if LOCKED.swap(true, Ordering::SeqCst) {
+        return Ok(());
+    }
+    let _ = app.emit("locked", ());
+    settings::set(app, LOCKED_KEY, true)
+}
+
+// Never lock with no way back in
+fn can_unlock(app: &AppHandle) -> bool {
+    passcode::is_set(app) || system_auth_available()
+}
+
+fn unlock_methods_for(app: &AppHandle) -> UnlockMethods {
+    UnlockMethods {
+        system: system_auth_available(),
+        passcode: passcode::is_set(app),
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn system_auth_available() -> bool {
+    use objc2_local_authentication::{LAContext, LAPolicy};
+
+    let context = unsafe { LAContext::new() };
+    unsafe { context.canEvaluatePolicy_error(LAPolicy::DeviceOwnerAuthentication) }.is_ok()
+}
+
+// Touch ID where there is one, with the account password as the system's own fallback
+#[cfg(target_os = "macos")]
+fn system_auth(_app: &AppHandle) -> Result<bool, String> {
+    use std::sync::mpsc;
+
+    use block2::RcBlock;
+    use objc2::runtime::Bool;
+    use objc2_foundation::{NSError, NSString};
+    use objc2_local_authentication::{LAContext, LAPolicy};
+
+    let context = unsafe { LAContext::new() };
+    let (tx, rx) = mpsc::channel();
+    let reply = RcBlock::new(move |success: Bool, _error: *mut NSError| {
+        let _ = tx.send(success.as_bool());
+    });
+    unsafe {
+        context.evaluatePolicy_localizedReason_reply(
+            LAPolicy::DeviceOwnerAuthentication,
+            &NSString::from_str(UNLOCK_REASON),
+            &reply,
+        )
+    };
+    Ok(rx.recv().unwrap_or(false))
+}
+
+#[cfg(windows)]
+fn system_auth_available() -> bool {
+    use windows::Security::Credentials::UI::{
+        UserConsentVerifier, UserConsentVerifierAvailability,
+    };
+
+    UserConsentVerifier::CheckAvailabilityAsync()
+        .and_then(|availability| availability.get())
+        .is_ok_and(|availability| availability == UserConsentVerifierAvailability::Available)
+}
+
+// Windows Hello, with the prompt over the main window so it isn't hidden behind it
+#[cfg(windows)]
+fn system_auth(app: &AppHandle) -> Result<bool, String> {
+    use tauri::Manager;
+    use windows::core::{factory, HSTRING};
+    use windows::Security::Credentials::UI::{UserConsentVerificationResult, UserConsentVerifier};
+    use windows::Win32::Foundation::HWND;
+    use windows::Win32::System::WinRT::IUserConsentVerifierInterop;
+    use windows_future::IAsyncOperation;
+
+    let window = app
+        .get_webview_window("main")
+        .ok_or_else(|| "The main window is gone".to_string())?;
+    let hwnd = HWND(window.hwnd().map_err(|e| e.to_string())?.0);
+    let interop =
+        factory::<UserConsentVerifier, IUserConsentVerifierInterop>().map_err(|e| e.to_string())?;
+    let result = unsafe {
+        interop.RequestVerificationForWindowAsync::<IAsyncOperation<UserConsentVerificationResult>>(
+            hwnd,
+            &HSTRING::from(UNLOCK_REASON),
+        )
+    }
+    .and_then(|operation| operation.get())
+    .map_err(|e| e.to_string())?;
+    Ok(result == UserConsentVerificationResult::Verified)
+}
+
+// Linux has no system prompt to unlock an app with, so only the passcode works there
+#[cfg(not(any(target_os = "macos", windows)))]
+fn system_auth_available() -> bool {
+    false
+}
+
+#[cfg(not(any(target_os = "macos", windows)))]
+fn system_auth(_app: &AppHandle) -> Result<bool, String> {
+    Err("Enter your passcode to unlock".to_string())
+}
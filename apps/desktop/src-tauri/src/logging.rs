@@ -0,0 +1,145 @@
+use std::cell::Cell;
+use std::collections::VecDeque;
+use std::sync::{Mutex, OnceLock};
+
+use log::LevelFilter;
+use serde::{Deserialize, Serialize};
+use tauri::{command, App, AppHandle, Emitter};
+use tauri_plugin_log::{fern, Target, TargetKind};
+use time::macros::format_description;
+
+use crate::{diagnostics, settings};
+
+const LOG_LEVEL_KEY: &str = "logging.level";
+
+/// Lines kept for `recent_logs`
+const BUFFER_LINES: usize = 1000;
+
+/// Longer lines are cut, so the buffer stays under about 2 MB
+const MAX_LINE_LEN: usize = 2000;
+
+// Most recent log lines, oldest first
+fn buffer() -> &'static Mutex<VecDeque<String>> {
+    static BUFFER: OnceLock<Mutex<VecDeque<String>>> = OnceLock::new();
+    BUFFER.get_or_init(|| Mutex::new(VecDeque::with_capacity(BUFFER_LINES)))
+}
+
+// Handle for emitting `log-appended`, set once the logger is running
+static APP: OnceLock<AppHandle> = OnceLock::new();
+
+thread_local! {
+    // Set while emitting `log-appended`, so whatever the emit logs isn't emitted again
+    static EMITTING: Cell<bool> = const { Cell::new(false) };
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogLevel {
+    Off,
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl From<LogLevel> for LevelFilter {
+    fn from(level: LogLevel) -> Self {
+        match level {
+            LogLevel::Off => LevelFilter::Off,
+            LogLevel::Error => LevelFilter::Error,
+            LogLevel::Warn => LevelFilter::Warn,
+            LogLevel::Info => LevelFilter::Info,
+            LogLevel::Debug => LevelFilter::Debug,
+            LogLevel::Trace => LevelFilter::Trace,
+        }
+    }
+}
+
+/// Change how much is logged, effective immediately and remembered across launches.
+/// Credentials are masked in every log line, so `trace` is safe to share.
+#[command]
+pub fn set_log_level(app: AppHandle, level: LogLevel) -> Result<(), String> {
+    settings::set(&app, LOG_LEVEL_KEY, level)?;
+    log::set_max_level(level.into());
+    Ok(())
+}
+
+/// The last `count` log lines (up to 1000), oldest first, with credentials masked.
+/// New lines are emitted as `log-appended` for live tailing.
+#[command]
+pub fn recent_logs(count: usize) -> Vec<String> {
+    let buffer = buffer().lock().unwrap();
+    let skip = buffer.len().saturating_sub(count);
+    buffer.iter().skip(skip).cloned().collect()
+}
+
+/// The current log level, e.g. "info"
+pub fn log_level() -> String {
+    log::max_level().to_string().to_lowercase()
+}
+
+/// Register the logger. It accepts everything and `log::set_max_level` does the filtering,
+/// so the level can change without rebuilding it. Nothing is logged in release builds
+/// unless a level has been set.
+pub fn setup(app: &App) -> tauri::Result<()> {
+    let format = format_description!("[[[year]-[month]-[day]][[[hour]:[minute]:[second]]");
+    app.handle().plugin(
+        tauri_plugin_log::Builder::default()
+            .level(LevelFilter::Trace)
+            .format(move |out, message, record| {
+                let now = tauri_plugin_log::TimezoneStrategy::UseUtc.get_now();
+                out.finish(format_args!(
+                    "{}[{}][{}] {}",
+                    now.format(&format).unwrap_or_default(),
+                    record.target(),
+                    record.level(),
+                    diagnostics::redact_text(&message.to_string())
+                ))
+            })
+            .target(Target::new(TargetKind::Dispatch(
+                fern::Dispatch::new().chain(fern::Output::call(append)),
+            )))
+            .build(),
+    )?;
+    let _ = APP.set(app.handle().clone());
+
+    let default = if cfg!(debug_assertions) {
+        LogLevel::Info
+    } else {
+        LogLevel::Off
+    };
+    let level = settings::get(app.handle(), LOG_LEVEL_KEY).unwrap_or(default);
+    log::set_max_level(level.into());
+    Ok(())
+}
+
+// Records reach targets already formatted and redacted
+fn append(record: &log::Record) {
+    let mut line = record.args().to_string();
+    if line.len() > MAX_LINE_LEN {
+        let mut end = MAX_LINE_LEN;
+        while !line.is_char_boundary(end) {
+            end -= 1;
+        }
+        line.truncate(end);
+        line.push('…');
+    }
+
+    {
+        let mut buffer = buffer().lock().unwrap();
+        if buffer.len() == BUFFER_LINES {
+            buffer.pop_front();
+        }
+        buffer.push_back(line.clone());
+    }
+
+    let Some(app) = APP.get() else {
+        return;
+    };
+    if EMITTING.with(|emitting| emitting.replace(true)) {
+        return;
+    }
+    let _ = app.emit("log-appended", line);
+    EMITTING.with(|emitting| emitting.set(false));
+}
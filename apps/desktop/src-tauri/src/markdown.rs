@@ -0,0 +1,280 @@
+use std::sync::OnceLock;
+
+use ammonia::{Builder, UrlRelative};
+use pulldown_cmark::{html, Event, Options, Parser, TagEnd};
+use tauri::command;
+
+// Allows what Markdown produces, minus anything that runs script or takes input
+fn sanitizer() -> &'static Builder<'static> {
+    static SANITIZER: OnceLock<Builder<'static>> = OnceLock::new();
+    SANITIZER.get_or_init(|| {
+        let mut builder = Builder::default();
+        builder
+            // Task list checkboxes, which can't be anything else
+            .add_tags(["input"])
+            .add_tag_attributes("input", ["checked"])
+            .set_tag_attribute_value("input", "type", "checkbox")
+            .set_tag_attribute_value("input", "disabled", "")
+            // Code fence languages, for highlighting
+            .add_tag_attributes("code", ["class"])
+            .attribute_filter(|element, attribute, value| match (element, attribute) {
+                ("code", "class") => {
+                    let language = value.strip_prefix("language-")?;
+                    let valid = language
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '+' | '#'));
+                    valid.then(|| value.into())
+                }
+                _ => Some(value.into()),
+            })
+            // Relative links would point into the app
+            .url_relative(UrlRelative::Deny);
+        builder
+    })
+}
+
+/// Render Markdown to HTML that's safe to insert as is: CommonMark with tables, task lists
+/// and strikethrough, then sanitized so that scripts, event handlers, styles, forms,
+/// frames and `javascript:` links are gone, including from raw HTML in the source.
+/// Links get `rel="noopener noreferrer"`.
+#[command]
+pub fn render_markdown(md: String) -> String {
+    let mut rendered = String::with_capacity(md.len() * 3 / 2);
+    html::push_html(&mut rendered, Parser::new_ext(&md, options()));
+    sanitizer().clean(&rendered).to_string()
+}
+
+/// Markdown as one line of plain text for previews, e.g. in notifications: formatting and
+/// HTML tags are dropped, links keep their text but not their URL, and whitespace is
+/// collapsed. Longer than `max_len` characters, it's cut at a word boundary and ends
+/// with an ellipsis, which counts towards `max_len`.
+#[command]
+pub fn plaintext_preview(md: String, max_len: usize) -> String {
+    plaintext(&md, max_len)
+}
+
+/// `plaintext_preview` for the native side
+pub fn plaintext(md: &str, max_len: usize) -> String {
+    let mut text = String::with_capacity(md.len());
+    for event in Parser::new_ext(md, options()) {
+        match event {
+            Event::Text(part) | Event::Code(part) => text.push_str(&part),
+            Event::SoftBreak | Event::HardBreak | Event::Rule => text.push(' '),
+            // Blocks run together otherwise, e.g. a heading and the paragraph after it
+            Event::End(
+                TagEnd::Paragraph
+                | TagEnd::Heading(_)
+                | TagEnd::BlockQuote(_)
+                | TagEnd::CodeBlock
+                | TagEnd::Item
+                | TagEnd::TableCell,
+            ) => text.push(' '),
+            _ => {}
+        }
+    }
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    truncate(text, max_len)
+}
+
+// Cuts at the last space that leaves at least half the text, or mid-word without one
+fn truncate(text: String, max_len: usize) -> String {
+    if text.chars().count() <= max_len {
+        return text;
+    }
+    if max_len == 0 {
+        return String::new();
+    }
+    let end = text
+        .char_indices()
+        .nth(max_len - 1)
+        .map_or(text.len(), |(i, _)| i);
+    let kept = &text[..end];
+    let kept = match kept.rfind(' ') {
+        _ if text[end..].starts_with(' ') => kept,
+        Some(space) if kept[..space].chars().count() >= max_len / 2 => &kept[..space],
+        _ => kept,
+    };
+    let kept = kept.trim_end_matches(|c: char| c.is_whitespace() || ",;:-".contains(c));
+    format!("{}…", kept)
+}
+
+fn options() -> Options {
+    Options::ENABLE_TABLES | Options::ENABLE_TASKLISTS | Options::ENABLE_STRIKETHROUGH
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(md: &str) -> String {
+        render_markdown(md.to_string())
+    }
+
+    // Rendered output without any of `needles`, case-insensitively. Text that only looks
+    // like a URL is harmless, so link checks look for the attribute it would be in.
+    fn assert_free_of(md: &str, needles: &[&str]) {
+        let rendered = render(md).to_ascii_lowercase();
+        for needle in needles {
+            assert!(
+                !rendered.contains(needle),
+                "{:?} rendered {:?}",
+                md,
+                rendered
+            );
+        }
+    }
+
+    #[test]
+    fn markdown_renders() {
+        assert_eq!(
+            render("**bold** and `code`"),
+            "<p><strong>bold</strong> and <code>code</code></p>\n"
+        );
+        assert_eq!(
+            render("[site](https://example.com)"),
+            "<p><a href=\"https://example.com\" rel=\"noopener noreferrer\">site</a></p>\n"
+        );
+        assert!(render("```rust\nfn main() {}\n```").contains("<code class=\"language-rust\">"));
+        // Attribute order isn't fixed
+        let task = render("- [x] done");
+        for attribute in [
+            "<input ",
+            "checked=\"\"",
+            "type=\"checkbox\"",
+            "disabled=\"\"",
+        ] {
+            assert!(task.contains(attribute), "{}", task);
+        }
+    }
+
+    #[test]
+    fn scripts_are_removed() {
+        assert_free_of("<script>alert(1)</script>", &["<script", "alert"]);
+        assert_free_of(
+            "hi <script src=\"https://evil.example/x.js\"></script>",
+            &["<script"],
+        );
+        assert_free_of("<SCRIPT>alert(1)</SCRIPT>", &["<script"]);
+        assert_free_of("<svg><script>alert(1)</script></svg>", &["<script", "<svg"]);
+    }
+
+    #[test]
+    fn javascript_links_are_removed() {
+        assert_free_of("[click](javascript:alert(1))", &["href"]);
+        assert_free_of("[click](JaVaScRiPt:alert(1))", &["href"]);
+        assert_free_of("[click](<java\tscript:alert(1)>)", &["href"]);
+        assert_free_of("[click](<javascript:alert(1)>)", &["href"]);
+        assert_free_of("<a href=\"javascript:alert(1)\">click</a>", &["href"]);
+        assert_free_of("<a href=\"&#106;avascript:alert(1)\">click</a>", &["href"]);
+        assert_free_of("<a href=\" javascript:alert(1)\">click</a>", &["href"]);
+        assert_free_of("<javascript:alert(1)>", &["href"]);
+    }
+
+    #[test]
+    fn event_handlers_are_removed() {
+        assert_free_of(
+            "<img src=\"x\" onerror=\"alert(1)\">",
+            &["onerror", "alert"],
+        );
+        assert_free_of(
+            "<a href=\"https://example.com\" onclick=\"alert(1)\">x</a>",
+            &["onclick"],
+        );
+        assert_free_of("<p onmouseover=alert(1)>hover</p>", &["onmouseover"]);
+        assert_free_of("<details open ontoggle=alert(1)>", &["ontoggle"]);
+    }
+
+    #[test]
+    fn raw_html_blocks_are_sanitized() {
+        let md = [
+            "<div>",
+            "<iframe src=\"https://evil.example\"></iframe>",
+            "<form action=\"https://evil.example\"><input name=\"password\"></form>",
+            "<style>body { display: none }</style>",
+            "</div>",
+        ]
+        .join("\n");
+        assert_free_of(
+            &md,
+            &[
+                "<iframe",
+                "<form",
+                "<style",
+                "display: none",
+                "name=\"password\"",
+            ],
+        );
+        assert_free_of(
+            "<object data=\"x.swf\"></object><embed src=\"x.swf\">",
+            &["<object", "<embed"],
+        );
+        assert_free_of("<p style=\"position: fixed\">x</p>", &["style="]);
+        assert_free_of(
+            "<meta http-equiv=\"refresh\" content=\"0; url=https://evil.example\">",
+            &["<meta"],
+        );
+    }
+
+    #[test]
+    fn data_urls_are_removed() {
+        assert_free_of(
+            "[x](data:text/html;base64,PHNjcmlwdD5hbGVydCgxKTwvc2NyaXB0Pg==)",
+            &["href"],
+        );
+        assert_free_of("![x](data:image/svg+xml;base64,PHN2Zz48L3N2Zz4=)", &["src"]);
+        assert_free_of(
+            "![x](<data:image/svg+xml,<svg onload=alert(1)>>)",
+            &["src", "onload"],
+        );
+        assert_free_of(
+            "<a href=\"data:text/html,<script>alert(1)</script>\">x</a>",
+            &["href", "<script"],
+        );
+        assert_free_of("<img src=\"data:image/png;base64,iVBORw0KGgo=\">", &["src"]);
+    }
+
+    #[test]
+    fn relative_links_are_removed() {
+        assert_free_of("[settings](/settings)", &["href"]);
+        assert_free_of("[up](../index.html)", &["href"]);
+    }
+
+    #[test]
+    fn previews_cut_on_characters_not_bytes() {
+        assert_eq!(plaintext("héllo wörld ünïcödé text", 10), "héllo…");
+        assert_eq!(plaintext("日本語のテキストです", 5), "日本語の…");
+        assert_eq!(plaintext("emoji 👍 here and more", 8), "emoji 👍…");
+        assert_eq!(plaintext("👍🏽👍🏽👍🏽", 3), "👍🏽…");
+        assert_eq!(plaintext("日本語", 3), "日本語");
+        assert_eq!(plaintext("one two three four five", 12), "one two…");
+        assert_eq!(plaintext("abc", 0), "");
+        assert_eq!(plaintext("", 10), "");
+    }
+
+    #[test]
+    fn previews_keep_link_text() {
+        assert_eq!(
+            plaintext("See [the docs](https://example.com/docs) now", 100),
+            "See the docs now"
+        );
+        assert_eq!(
+            plaintext("<https://example.com>", 100),
+            "https://example.com"
+        );
+        assert_eq!(plaintext("![alt text](x.png)", 100), "alt text");
+        assert_eq!(plaintext("<b>bold</b> text", 100), "bold text");
+    }
+
+    #[test]
+    fn previews_flatten_code() {
+        let md = "Intro\n\n```rust\nfn main() {\n    println!(\"hi\");\n}\n```\n\nAfter";
+        assert_eq!(
+            plaintext(md, 100),
+            "Intro fn main() { println!(\"hi\"); } After"
+        );
+        assert_eq!(
+            plaintext("# Title\nBody `inline code` end", 100),
+            "Title Body inline code end"
+        );
+    }
+}
@@ -0,0 +1,713 @@
+use std::sync::{Mutex, OnceLock};
+
+use serde::{Deserialize, Serialize};
+use tauri::{command, AppHandle, Emitter};
+
+/// Whether the app's media is playing, as shown by the system's media controls
+#[derive(Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Playback {
+    Playing,
+    Paused,
+}
+
+#[derive(Clone, Copy, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum MediaAction {
+    Play,
+    Pause,
+    /// Play/pause keys that don't say which; Windows always does
+    #[cfg_attr(windows, allow(dead_code))]
+    Toggle,
+    Next,
+    Previous,
+    Stop,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct MediaKey {
+    action: MediaAction,
+}
+
+// What `set_now_playing` shows in the system's media controls
+#[derive(Clone)]
+struct NowPlaying {
+    title: String,
+    artist: Option<String>,
+    /// Seconds
+    duration: f64,
+    /// Seconds into the media
+    position: f64,
+}
+
+// The media session while there's media, with what it shows
+struct Active {
+    session: platform::Session,
+    playback: Playback,
+    now_playing: Option<NowPlaying>,
+}
+
+// Registration with the system's media controls while there's media; ending it
+// gives the keys back
+fn active() -> &'static Mutex<Option<Active>> {
+    static ACTIVE: OnceLock<Mutex<Option<Active>>> = OnceLock::new();
+    ACTIVE.get_or_init(|| Mutex::new(None))
+}
+
+/// Take the media keys while a voice message or call is playing or paused, emitting
+/// `media-key { action }` with `play`, `pause`, `toggle`, `next`, `previous` or `stop`
+/// when one is pressed. Call it again as playback changes, and with None once there's no
+/// media, which gives the keys back to other apps. Uses the system media session (Now
+/// Playing on macOS, the media overlay on Windows, MPRIS on Linux); does nothing where
+/// there's none.
+#[command]
+pub fn set_media_session(app: AppHandle, playback: Option<Playback>) -> Result<(), String> {
+    match playback {
+        Some(playback) => update(&app, |active| active.playback = playback),
+        None => {
+            end();
+            Ok(())
+        }
+    }
+}
+
+/// Show a voice message in the system's media controls (Now Playing on macOS, the media
+/// overlay on Windows, MPRIS on Linux), taking the media keys as `set_media_session`
+/// does. `duration` and `position` are in seconds; call it again as playback proceeds,
+/// and with `set_media_session` when it's paused or resumed. Does nothing where there are
+/// no media controls.
+#[command]
+pub fn set_now_playing(
+    app: AppHandle,
+    title: String,
+    artist: Option<String>,
+    duration: f64,
+    position: f64,
+) -> Result<(), String> {
+    if !duration.is_finite() || duration < 0.0 {
+        return Err(format!("Invalid duration {}", duration));
+    }
+    if !position.is_finite() {
+        return Err(format!("Invalid position {}", position));
+    }
+    let now_playing = NowPlaying {
+        title,
+        artist: artist.filter(|artist| !artist.trim().is_empty()),
+        duration,
+        position: position.clamp(0.0, duration),
+    };
+    update(&app, |active| active.now_playing = Some(now_playing))
+}
+
+/// Take the voice message out of the system's media controls and give the media keys back
+#[command]
+pub fn clear_now_playing() {
+    end();
+}
+
+// Starts the session as playing if there isn't one
+fn update(app: &AppHandle, change: impl FnOnce(&mut Active)) -> Result<(), String> {
+    let mut active = active().lock().unwrap();
+    if active.is_none() {
+        let Some(session) = platform::Session::start(app)? else {
+            return Ok(());
+        };
+        *active = Some(Active {
+            session,
+            playback: Playback::Playing,
+            now_playing: None,
+        });
+    }
+    let active = active.as_mut().unwrap();
+    change(active);
+    active
+        .session
+        .update(active.playback, active.now_playing.as_ref())
+}
+
+fn end() {
+    if let Some(active) = active().lock().unwrap().take() {
+        active.session.end();
+    }
+}
+
+fn emit(app: &AppHandle, action: MediaAction) {
+    let _ = app.emit("media-key", MediaKey { action });
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use block2::RcBlock;
+    use objc2::msg_send;
+    use objc2::rc::Retained;
+    use objc2::runtime::{AnyClass, AnyObject};
+    use objc2_foundation::{NSDictionary, NSNumber, NSString};
+    use tauri::AppHandle;
+
+    use super::{emit, MediaAction, NowPlaying, Playback};
+
+    #[link(name = "MediaPlayer", kind = "framework")]
+    extern "C" {
+        static MPMediaItemPropertyTitle: &'static NSString;
+        static MPMediaItemPropertyArtist: &'static NSString;
+        static MPMediaItemPropertyPlaybackDuration: &'static NSString;
+        static MPNowPlayingInfoPropertyElapsedPlaybackTime: &'static NSString;
+        static MPNowPlayingInfoPropertyPlaybackRate: &'static NSString;
+    }
+
+    const ACTIONS: [MediaAction; 6] = [
+        MediaAction::Play,
+        MediaAction::Pause,
+        MediaAction::Toggle,
+        MediaAction::Next,
+        MediaAction::Previous,
+        MediaAction::Stop,
+    ];
+
+    /// MPRemoteCommandHandlerStatusSuccess
+    const HANDLED: isize = 0;
+
+    /// MPNowPlayingPlaybackState values
+    const PLAYING: usize = 1;
+    const PAUSED: usize = 2;
+    const STOPPED: usize = 3;
+
+    pub struct Session {
+        /// Each command with the target its handler was added as
+        targets: Vec<(Retained<AnyObject>, Retained<AnyObject>)>,
+    }
+
+    // MediaPlayer's command center and its targets may be used from any thread
+    unsafe impl Send for Session {}
+
+    impl Session {
+        pub fn start(app: &AppHandle) -> Result<Option<Session>, String> {
+            // Only there from macOS 10.12.2
+            let (Some(commands), Some(_)) = (
+                AnyClass::get(c"MPRemoteCommandCenter"),
+                AnyClass::get(c"MPNowPlayingInfoCenter"),
+            ) else {
+                return Ok(None);
+            };
+            let center: Retained<AnyObject> = unsafe { msg_send![commands, sharedCommandCenter] };
+            let mut targets = Vec::new();
+            for action in ACTIONS {
+                let Some(command) = command(&center, action) else {
+                    continue;
+                };
+                let app = app.clone();
+                let handler = RcBlock::new(move |_event: *mut AnyObject| -> isize {
+                    emit(&app, action);
+                    HANDLED
+                });
+                let target: Retained<AnyObject> =
+                    unsafe { msg_send![&*command, addTargetWithHandler: &*handler] };
+                let _: () = unsafe { msg_send![&*command, setEnabled: true] };
+                targets.push((command, target));
+            }
+            Ok(Some(Session { targets }))
+        }
+
+        pub fn update(
+            &self,
+            playback: Playback,
+            now_playing: Option<&NowPlaying>,
+        ) -> Result<(), String> {
+            set_state(match playback {
+                Playback::Playing => PLAYING,
+                Playback::Paused => PAUSED,
+            });
+            set_info(now_playing.map(|now_playing| info(playback, now_playing)));
+            Ok(())
+        }
+
+        pub fn end(self) {
+            for (command, target) in self.targets {
+                let _: () = unsafe { msg_send![&*command, removeTarget: &*target] };
+                let _: () = unsafe { msg_send![&*command, setEnabled: false] };
+            }
+            set_info(None);
+            set_state(STOPPED);
+        }
+    }
+
+    // The MPRemoteCommand for an action
+    fn command(center: &AnyObject, action: MediaAction) -> Option<Retained<AnyObject>> {
+        unsafe {
+            match action {
+                MediaAction::Play => msg_send![center, playCommand],
+                MediaAction::Pause => msg_send![center, pauseCommand],
+                MediaAction::Toggle => msg_send![center, togglePlayPauseCommand],
+                MediaAction::Next => msg_send![center, nextTrackCommand],
+                MediaAction::Previous => msg_send![center, previousTrackCommand],
+                MediaAction::Stop => msg_send![center, stopCommand],
+            }
+        }
+    }
+
+    // Tells macOS which app the keys are for
+    fn set_state(state: usize) {
+        let Some(class) = AnyClass::get(c"MPNowPlayingInfoCenter") else {
+            return;
+        };
+        unsafe {
+            let center: Retained<AnyObject> = msg_send![class, defaultCenter];
+            let _: () = msg_send![&*center, setPlaybackState: state];
+        }
+    }
+
+    // Now Playing moves the position on by itself at the playback rate
+    fn info(
+        playback: Playback,
+        now_playing: &NowPlaying,
+    ) -> Retained<NSDictionary<NSString, AnyObject>> {
+        let title = NSString::from_str(&now_playing.title);
+        let artist = now_playing.artist.as_deref().map(NSString::from_str);
+        let duration = NSNumber::new_f64(now_playing.duration);
+        let position = NSNumber::new_f64(now_playing.position);
+        let rate = NSNumber::new_f64(match playback {
+            Playback::Playing => 1.0,
+            Playback::Paused => 0.0,
+        });
+        let mut keys = unsafe {
+            vec![
+                MPMediaItemPropertyTitle,
+                MPMediaItemPropertyPlaybackDuration,
+                MPNowPlayingInfoPropertyElapsedPlaybackTime,
+                MPNowPlayingInfoPropertyPlaybackRate,
+            ]
+        };
+        let mut values: Vec<&AnyObject> = vec![&title, &duration, &position, &rate];
+        if let Some(artist) = &artist {
+            keys.push(unsafe { MPMediaItemPropertyArtist });
+            values.push(artist);
+        }
+        NSDictionary::from_slices(&keys, &values)
+    }
+
+    fn set_info(info: Option<Retained<NSDictionary<NSString, AnyObject>>>) {
+        let Some(class) = AnyClass::get(c"MPNowPlayingInfoCenter") else {
+            return;
+        };
+        unsafe {
+            let center: Retained<AnyObject> = msg_send![class, defaultCenter];
+            let _: () = msg_send![&*center, setNowPlayingInfo: info.as_deref()];
+        }
+    }
+}
+
+#[cfg(windows)]
+mod platform {
+    use tauri::{AppHandle, Manager};
+    use windows::core::{factory, Ref, HSTRING};
+    use windows::Foundation::{TimeSpan, TypedEventHandler};
+    use windows::Media::{
+        MediaPlaybackStatus, MediaPlaybackType, SystemMediaTransportControls,
+        SystemMediaTransportControlsButton, SystemMediaTransportControlsButtonPressedEventArgs,
+        SystemMediaTransportControlsTimelineProperties,
+    };
+    use windows::Win32::Foundation::HWND;
+    use windows::Win32::System::WinRT::ISystemMediaTransportControlsInterop;
+
+    use super::{emit, MediaAction, NowPlaying, Playback};
+
+    pub struct Session {
+        controls: SystemMediaTransportControls,
+        /// Registration of the button handler
+        token: i64,
+    }
+
+    impl Session {
+        // The controls belong to the main window, which is how Windows knows whose they are
+        pub fn start(app: &AppHandle) -> Result<Option<Session>, String> {
+            let window = app
+                .get_webview_window("main")
+                .ok_or_else(|| "The main window is gone".to_string())?;
+            let hwnd = HWND(window.hwnd().map_err(|e| e.to_string())?.0);
+            let interop =
+                factory::<SystemMediaTransportControls, ISystemMediaTransportControlsInterop>()
+                    .map_err(|e| e.to_string())?;
+            let controls: SystemMediaTransportControls =
+                unsafe { interop.GetForWindow(hwnd) }.map_err(|e| e.to_string())?;
+
+            let app = app.clone();
+            let handler = TypedEventHandler::new(
+                move |_: Ref<SystemMediaTransportControls>,
+                      args: Ref<SystemMediaTransportControlsButtonPressedEventArgs>| {
+                    let action = match args.ok()?.Button()? {
+                        SystemMediaTransportControlsButton::Play => MediaAction::Play,
+                        SystemMediaTransportControlsButton::Pause => MediaAction::Pause,
+                        SystemMediaTransportControlsButton::Next => MediaAction::Next,
+                        SystemMediaTransportControlsButton::Previous => MediaAction::Previous,
+                        SystemMediaTransportControlsButton::Stop => MediaAction::Stop,
+                        _ => return Ok(()),
+                    };
+                    emit(&app, action);
+                    Ok(())
+                },
+            );
+            let token = controls
+                .ButtonPressed(&handler)
+                .and_then(|token| {
+                    controls.SetIsPlayEnabled(true)?;
+                    controls.SetIsPauseEnabled(true)?;
+                    controls.SetIsNextEnabled(true)?;
+                    controls.SetIsPreviousEnabled(true)?;
+                    controls.SetIsStopEnabled(true)?;
+                    controls.SetIsEnabled(true)?;
+                    Ok(token)
+                })
+                .map_err(|e| e.to_string())?;
+            Ok(Some(Session { controls, token }))
+        }
+
+        pub fn update(
+            &self,
+            playback: Playback,
+            now_playing: Option<&NowPlaying>,
+        ) -> Result<(), String> {
+            let status = match playback {
+                Playback::Playing => MediaPlaybackStatus::Playing,
+                Playback::Paused => MediaPlaybackStatus::Paused,
+            };
+            self.controls
+                .SetPlaybackStatus(status)
+                .and_then(|_| match now_playing {
+                    Some(now_playing) => self.show(now_playing),
+                    None => self.controls.DisplayUpdater()?.ClearAll(),
+                })
+                .map_err(|e| e.to_string())
+        }
+
+        fn show(&self, now_playing: &NowPlaying) -> windows::core::Result<()> {
+            let display = self.controls.DisplayUpdater()?;
+            display.SetType(MediaPlaybackType::Music)?;
+            let music = display.MusicProperties()?;
+            music.SetTitle(&HSTRING::from(&now_playing.title))?;
+            music.SetArtist(&HSTRING::from(now_playing.artist.as_deref().unwrap_or("")))?;
+            display.Update()?;
+
+            let timeline = SystemMediaTransportControlsTimelineProperties::new()?;
+            let duration = time_span(now_playing.duration);
+            timeline.SetStartTime(time_span(0.0))?;
+            timeline.SetMinSeekTime(time_span(0.0))?;
+            timeline.SetEndTime(duration)?;
+            timeline.SetMaxSeekTime(duration)?;
+            timeline.SetPosition(time_span(now_playing.position))?;
+            self.controls.UpdateTimelineProperties(&timeline)
+        }
+
+        pub fn end(self) {
+            let _ = self.controls.RemoveButtonPressed(self.token);
+            let _ = self
+                .controls
+                .DisplayUpdater()
+                .and_then(|display| display.ClearAll());
+            let _ = self.controls.SetPlaybackStatus(MediaPlaybackStatus::Closed);
+            let _ = self.controls.SetIsEnabled(false);
+        }
+    }
+
+    // TimeSpans count 100 ns ticks
+    fn time_span(seconds: f64) -> TimeSpan {
+        TimeSpan {
+            Duration: (seconds * 10_000_000.0) as i64,
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod platform {
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+
+    use tauri::{AppHandle, Manager};
+    use zbus::blocking::connection::Builder;
+    use zbus::blocking::Connection;
+    use zbus::interface;
+    use zbus::zvariant::{ObjectPath, OwnedValue, Value};
+
+    use super::{emit, MediaAction, NowPlaying, Playback};
+
+    const BUS_NAME: &str = "org.mpris.MediaPlayer2.hazel";
+    const OBJECT_PATH: &str = "/org/mpris/MediaPlayer2";
+
+    /// MPRIS wants an id for what's playing; there's only ever the one
+    const TRACK_ID: &str = "/org/mpris/MediaPlayer2/hazel/voice_message";
+
+    /// What the player shows, which the bus reads through its properties
+    struct Shown {
+        playback: Playback,
+        now_playing: Option<NowPlaying>,
+    }
+
+    /// The MPRIS player Hazel shows as; desktops send media keys to the player that's
+    /// playing
+    pub struct Session {
+        /// Dropping it releases the bus name, which removes the player
+        connection: Connection,
+        shown: Arc<Mutex<Shown>>,
+    }
+
+    impl Session {
+        // Without a session bus there are no media keys to take
+        pub fn start(app: &AppHandle) -> Result<Option<Session>, String> {
+            let shared = Arc::new(Mutex::new(Shown {
+                playback: Playback::Playing,
+                now_playing: None,
+            }));
+            let connection = Builder::session()
+                .and_then(|builder| builder.name(BUS_NAME))
+                .and_then(|builder| builder.serve_at(OBJECT_PATH, Root { app: app.clone() }))
+                .and_then(|builder| {
+                    let player = Player {
+                        app: app.clone(),
+                        shown: shared.clone(),
+                    };
+                    builder.serve_at(OBJECT_PATH, player)
+                })
+                .and_then(|builder| builder.build());
+            match connection {
+                Ok(connection) => Ok(Some(Session {
+                    connection,
+                    shown: shared,
+                })),
+                Err(e) => {
+                    log::info!("Media keys aren't available: {}", e);
+                    Ok(None)
+                }
+            }
+        }
+
+        pub fn update(
+            &self,
+            playback: Playback,
+            now_playing: Option<&NowPlaying>,
+        ) -> Result<(), String> {
+            *self.shown.lock().unwrap() = Shown {
+                playback,
+                now_playing: now_playing.cloned(),
+            };
+            let interface = self
+                .connection
+                .object_server()
+                .interface::<_, Player>(OBJECT_PATH)
+                .map_err(|e| e.to_string())?;
+            let (emitter, player) = (interface.signal_emitter(), interface.get());
+            let changed = zbus::block_on(async {
+                player.playback_status_changed(emitter).await?;
+                player.metadata_changed(emitter).await
+            });
+            changed.map_err(|e| e.to_string())
+        }
+
+        pub fn end(self) {}
+    }
+
+    struct Root {
+        app: AppHandle,
+    }
+
+    #[interface(name = "org.mpris.MediaPlayer2")]
+    impl Root {
+        fn raise(&self) {
+            if let Some(window) = self.app.get_webview_window("main") {
+                let _ = window.unminimize();
+                let _ = window.show();
+                let _ = window.set_focus();
+            }
+        }
+
+        fn quit(&self) {}
+
+        #[zbus(property)]
+        fn can_quit(&self) -> bool {
+            false
+        }
+
+        #[zbus(property)]
+        fn can_raise(&self) -> bool {
+            true
+        }
+
+        #[zbus(property)]
+        fn has_track_list(&self) -> bool {
+            false
+        }
+
+        #[zbus(property)]
+        fn identity(&self) -> &str {
+            "Hazel"
+        }
+
+        #[zbus(property)]
+        fn supported_uri_schemes(&self) -> Vec<String> {
+            Vec::new()
+        }
+
+        #[zbus(property)]
+        fn supported_mime_types(&self) -> Vec<String> {
+            Vec::new()
+        }
+    }
+
+    struct Player {
+        app: AppHandle,
+        shown: Arc<Mutex<Shown>>,
+    }
+
+    #[interface(name = "org.mpris.MediaPlayer2.Player")]
+    impl Player {
+        fn play(&self) {
+            emit(&self.app, MediaAction::Play);
+        }
+
+        fn pause(&self) {
+            emit(&self.app, MediaAction::Pause);
+        }
+
+        fn play_pause(&self) {
+            emit(&self.app, MediaAction::Toggle);
+        }
+
+        fn next(&self) {
+            emit(&self.app, MediaAction::Next);
+        }
+
+        fn previous(&self) {
+            emit(&self.app, MediaAction::Previous);
+        }
+
+        fn stop(&self) {
+            emit(&self.app, MediaAction::Stop);
+        }
+
+        // Seeking isn't offered, so these are never sent
+        fn seek(&self, _offset: i64) {}
+
+        fn set_position(&self, _track_id: zbus::zvariant::ObjectPath<'_>, _position: i64) {}
+
+        fn open_uri(&self, _uri: &str) {}
+
+        #[zbus(property)]
+        fn playback_status(&self) -> &str {
+            match self.shown.lock().unwrap().playback {
+                Playback::Playing => "Playing",
+                Playback::Paused => "Paused",
+            }
+        }
+
+        #[zbus(property)]
+        fn rate(&self) -> f64 {
+            1.0
+        }
+
+        #[zbus(property)]
+        fn metadata(&self) -> HashMap<String, OwnedValue> {
+            let shown = self.shown.lock().unwrap();
+            let Some(now_playing) = &shown.now_playing else {
+                return HashMap::new();
+            };
+            let mut values = vec![
+                (
+                    "mpris:trackid",
+                    Value::from(ObjectPath::from_static_str_unchecked(TRACK_ID)),
+                ),
+                ("mpris:length", Value::from(micros(now_playing.duration))),
+                ("xesam:title", Value::from(now_playing.title.as_str())),
+            ];
+            if let Some(artist) = &now_playing.artist {
+                values.push(("xesam:artist", Value::from(vec![artist.as_str()])));
+            }
+            values
+                .into_iter()
+                .filter_map(|(key, value)| Some((key.to_string(), value.try_to_owned().ok()?)))
+                .collect()
+        }
+
+        #[zbus(property)]
+        fn volume(&self) -> f64 {
+            1.0
+        }
+
+        // Players move it on by themselves while playing
+        #[zbus(property)]
+        fn position(&self) -> i64 {
+            let shown = self.shown.lock().unwrap();
+            shown
+                .now_playing
+                .as_ref()
+                .map_or(0, |now_playing| micros(now_playing.position))
+        }
+
+        #[zbus(property)]
+        fn minimum_rate(&self) -> f64 {
+            1.0
+        }
+
+        #[zbus(property)]
+        fn maximum_rate(&self) -> f64 {
+            1.0
+        }
+
+        #[zbus(property)]
+        fn can_go_next(&self) -> bool {
+            true
+        }
+
+        #[zbus(property)]
+        fn can_go_previous(&self) -> bool {
+            true
+        }
+
+        #[zbus(property)]
+        fn can_play(&self) -> bool {
+            true
+        }
+
+        #[zbus(property)]
+        fn can_pause(&self) -> bool {
+            true
+        }
+
+        #[zbus(property)]
+        fn can_seek(&self) -> bool {
+            false
+        }
+
+        #[zbus(property)]
+        fn can_control(&self) -> bool {
+            true
+        }
+    }
+
+    fn micros(seconds: f64) -> i64 {
+        (seconds * 1_000_000.0) as i64
+    }
+}
+
+#[cfg(not(any(target_os = "macos", windows, target_os = "linux")))]
+mod platform {
+    use tauri::AppHandle;
+
+    use super::{NowPlaying, Playback};
+
+    pub struct Session;
+
+    impl Session {
+        pub fn start(_app: &AppHandle) -> Result<Option<Session>, String> {
+            Ok(None)
+        }
+
+        pub fn update(
+            &self,
+            _playback: Playback,
+            _now_playing: Option<&NowPlaying>,
+        ) -> Result<(), String> {
+            Ok(())
+        }
+
+        pub fn end(self) {}
+    }
+}
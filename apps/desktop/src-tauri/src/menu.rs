@@ -0,0 +1,357 @@
+use tauri::menu::{CheckMenuItem, Menu, MenuItem, MenuItemKind, PredefinedMenuItem, Submenu};
+use tauri::{App, AppHandle, Emitter, Wry};
+
+use crate::{accessibility, diagnostics, emoji, lifecycle, shortcuts, window};
+
+/// Build the native menu bar and route its events to the frontend
+pub fn setup(app: &App) -> tauri::Result<()> {
+    let settings = MenuItem::with_id(
+        app,
+        "settings",
+        "Settings...",
+        true,
+        shortcuts::default_accelerator("settings"),
+    )?;
+    let check_updates = MenuItem::with_id(
+        app,
+        "check_updates",
+        "Check for Updates...",
+        true,
+        None::<&str>,
+    )?;
+
+    // Custom rather than predefined so the menu always takes the forced quit path
+    let quit = MenuItem::with_id(
+        app,
+        "quit",
+        "Quit Hazel",
+        true,
+        shortcuts::default_accelerator("quit"),
+    )?;
+
+    let app_submenu = Submenu::with_items(
+        app,
+        "Hazel",
+        true,
+        &[
+            &settings,
+            &check_updates,
+            #[cfg(target_os = "macos")]
+            &PredefinedMenuItem::separator(app)?,
+            #[cfg(target_os = "macos")]
+            &PredefinedMenuItem::hide(app, None::<&str>)?,
+            &PredefinedMenuItem::separator(app)?,
+            &quit,
+        ],
+    )?;
+
+    let new_channel = MenuItem::with_id(
+        app,
+        "new_channel",
+        "New Channel...",
+        true,
+        shortcuts::default_accelerator("new_channel"),
+    )?;
+    let invite = MenuItem::with_id(
+        app,
+        "invite",
+        "Invite People...",
+        true,
+        shortcuts::default_accelerator("invite"),
+    )?;
+
+    let file_submenu = Submenu::with_items(
+        app,
+        "File",
+        true,
+        &[&new_channel, &PredefinedMenuItem::separator(app)?, &invite],
+    )?;
+
+    // macOS adds its own "Emoji & Symbols" item to the Edit menu
+    #[cfg(not(target_os = "macos"))]
+    let emoji_picker =
+        MenuItem::with_id(app, "emoji_picker", "Emoji & Symbols", true, None::<&str>)?;
+    let find = MenuItem::with_id(
+        app,
+        "find",
+        "Find...",
+        true,
+        shortcuts::default_accelerator("find"),
+    )?;
+    let focus_composer = MenuItem::with_id(
+        app,
+        "focus_composer",
+        "Focus Message Input",
+        true,
+        Some(window::focus_composer_accelerator(app.handle())),
+    )?;
+
+    #[cfg(target_os = "macos")]
+    let edit_submenu = Submenu::with_items(
+        app,
+        "Edit",
+        true,
+        &[
+            &PredefinedMenuItem::undo(app, None::<&str>)?,
+            &PredefinedMenuItem::redo(app, None::<&str>)?,
+            &PredefinedMenuItem::separator(app)?,
+            &PredefinedMenuItem::cut(app, None::<&str>)?,
+            &PredefinedMenuItem::copy(app, None::<&str>)?,
+            &PredefinedMenuItem::paste(app, None::<&str>)?,
+            &PredefinedMenuItem::separator(app)?,
+            &PredefinedMenuItem::select_all(app, None::<&str>)?,
+            &PredefinedMenuItem::separator(app)?,
+            &find,
+            &focus_composer,
+        ],
+    )?;
+    #[cfg(not(target_os = "macos"))]
+    let edit_submenu = Submenu::with_items(
+        app,
+        "Edit",
+        true,
+        &[
+            &PredefinedMenuItem::cut(app, None::<&str>)?,
+            &PredefinedMenuItem::copy(app, None::<&str>)?,
+            &PredefinedMenuItem::paste(app, None::<&str>)?,
+            &PredefinedMenuItem::separator(app)?,
+            &PredefinedMenuItem::select_all(app, None::<&str>)?,
+            &emoji_picker,
+            &PredefinedMenuItem::separator(app)?,
+            &find,
+            &focus_composer,
+        ],
+    )?;
+
+    let high_contrast = CheckMenuItem::with_id(
+        app,
+        "high_contrast",
+        "High Contrast",
+        true,
+        accessibility::high_contrast_enabled(app.handle()),
+        None::<&str>,
+    )?;
+    let view_submenu = Submenu::with_items(
+        app,
+        "View",
+        true,
+        &[
+            &high_contrast,
+            &PredefinedMenuItem::separator(app)?,
+            &shortcuts::submenu(app)?,
+        ],
+    )?;
+
+    let copy_diagnostics = MenuItem::with_id(
+        app,
+        "copy_diagnostics",
+        "Copy Diagnostics",
+        true,
+        None::<&str>,
+    )?;
+    let help_submenu = Submenu::with_items(app, "Help", true, &[&copy_diagnostics])?;
+
+    #[cfg(target_os = "macos")]
+    let window_submenu = Submenu::with_items(
+        app,
+        "Window",
+        true,
+        &[&PredefinedMenuItem::minimize(app, None::<&str>)?],
+    )?;
+
+    #[cfg(target_os = "macos")]
+    let menu = Menu::with_items(
+        app,
+        &[
+            &app_submenu,
+            &file_submenu,
+            &edit_submenu,
+            &view_submenu,
+            &window_submenu,
+            &help_submenu,
+        ],
+    )?;
+    #[cfg(not(target_os = "macos"))]
+    let menu = Menu::with_items(
+        app,
+        &[
+            &app_submenu,
+            &file_submenu,
+            &edit_submenu,
+            &view_submenu,
+            &help_submenu,
+        ],
+    )?;
+    app.set_menu(menu)?;
+
+    // Handle menu events
+    let app_handle = app.handle().clone();
+    app.on_menu_event(move |_app, event| match event.id().as_ref() {
+        "settings" => {
+            let _ = app_handle.emit("menu-open-settings", ());
+        }
+        "check_updates" => {
+            let _ = app_handle.emit("menu-check-updates", ());
+        }
+        "new_channel" => {
+            let _ = app_handle.emit("menu-new-channel", ());
+        }
+        "invite" => {
+            let _ = app_handle.emit("menu-invite", ());
+        }
+        "emoji_picker" => {
+            let _ = emoji::open_emoji_picker(app_handle.clone());
+        }
+        "find" => {
+            let _ = app_handle.emit("menu-find", ());
+        }
+        "focus_composer" => {
+            let _ = window::focus_composer(app_handle.clone());
+        }
+        "high_contrast" => {
+            let enabled = !accessibility::high_contrast_enabled(&app_handle);
+            let _ = accessibility::set_high_contrast(app_handle.clone(), Some(enabled));
+        }
+        // The frontend puts the config on the clipboard
+        "copy_diagnostics" => {
+            let config = diagnostics::dump_config(app_handle.clone());
+            let _ = app_handle.emit("menu-copy-diagnostics", config);
+        }
+        "quit" => lifecycle::quit(&app_handle, true),
+        id => {
+            if let Some(action_id) = id.strip_prefix(shortcuts::ITEM_PREFIX) {
+                shortcuts::trigger(&app_handle, action_id);
+            }
+        }
+    });
+
+    Ok(())
+}
+
+/// Items taken out of the menu bar by `remove_items`, in the order they were taken,
+/// each with the submenu it was in (none for the menu bar itself) and its position there
+pub struct Removed(Vec<(Option<Submenu<Wry>>, usize, MenuItemKind<Wry>)>);
+
+/// Take the items with these ids out of the menu bar's submenus, along with the separators
+/// and submenus that would be left with nothing to separate or show
+pub fn remove_items(app: &AppHandle, ids: &[&str]) -> tauri::Result<Removed> {
+    let mut removed = Vec::new();
+    let Some(menu) = app.menu() else {
+        return Ok(Removed(removed));
+    };
+    for (position, item) in menu.items()?.into_iter().enumerate().rev() {
+        let MenuItemKind::Submenu(submenu) = item else {
+            continue;
+        };
+        let items = submenu.items()?;
+        let kinds: Vec<_> = items
+            .iter()
+            .map(|item| (ids.contains(&item.id().as_ref()), is_separator(item)))
+            .collect();
+        let dropped = dropped_positions(&kinds);
+        if dropped.is_empty() {
+            continue;
+        }
+        // From the end, so the positions still to go stay where they were
+        for &dropped in dropped.iter().rev() {
+            submenu.remove(&items[dropped])?;
+            removed.push((Some(submenu.clone()), dropped, items[dropped].clone()));
+        }
+        if dropped.len() == items.len() {
+            menu.remove(&submenu)?;
+            removed.push((None, position, MenuItemKind::Submenu(submenu)));
+        }
+    }
+    Ok(Removed(removed))
+}
+
+/// Put items taken out by `remove_items` back where they were
+pub fn restore_items(app: &AppHandle, removed: Removed) -> tauri::Result<()> {
+    let Some(menu) = app.menu() else {
+        return Ok(());
+    };
+    for (submenu, position, item) in removed.0.into_iter().rev() {
+        match submenu {
+            Some(submenu) => submenu.insert(&item, position)?,
+            None => menu.insert(&item, position)?,
+        }
+    }
+    Ok(())
+}
+
+fn is_separator(item: &MenuItemKind<Wry>) -> bool {
+    // Separators are the only predefined items without text
+    item.as_predefined_menuitem()
+        .is_some_and(|item| item.text().is_ok_and(|text| text.is_empty()))
+}
+
+// Positions of the items to drop, given whether each is to be removed and whether it's a
+// separator, plus the separators that would be left first, last or next to another
+fn dropped_positions(items: &[(bool, bool)]) -> Vec<usize> {
+    if !items.iter().any(|&(remove, _)| remove) {
+        return Vec::new();
+    }
+    let mut dropped = Vec::new();
+    let mut kept_separator = None;
+    let mut kept_any = false;
+    for (position, &(remove, separator)) in items.iter().enumerate() {
+        if remove {
+            dropped.push(position);
+        } else if separator {
+            // Each separator waits for an item after it before it's kept
+            if !kept_any {
+                dropped.push(position);
+            } else if let Some(previous) = kept_separator.replace(position) {
+                dropped.push(previous);
+            }
+        } else {
+            kept_separator = None;
+            kept_any = true;
+        }
+    }
+    dropped.extend(kept_separator);
+    dropped.sort_unstable();
+    dropped
+}
+
+/// Find a menu item by id, looking one level into the menu bar's submenus
+pub fn find_item(app: &AppHandle, id: &str) -> Option<MenuItemKind<Wry>> {
+    let items = app.menu()?.items().ok()?;
+    items.into_iter().find_map(|item| match item {
+        MenuItemKind::Submenu(submenu) => submenu.get(id),
+        item if item.id() == id => Some(item),
+        _ => None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ITEM: (bool, bool) = (false, false);
+    const GONE: (bool, bool) = (true, false);
+    const SEPARATOR: (bool, bool) = (false, true);
+
+    #[test]
+    fn removed_items_take_their_separators() {
+        assert_eq!(dropped_positions(&[ITEM, GONE, SEPARATOR, ITEM]), [1]);
+        assert_eq!(
+            dropped_positions(&[GONE, GONE, SEPARATOR, GONE]),
+            [0, 1, 2, 3]
+        );
+        assert_eq!(dropped_positions(&[ITEM, SEPARATOR, GONE]), [1, 2]);
+        assert_eq!(dropped_positions(&[GONE, SEPARATOR, ITEM]), [0, 1]);
+        assert_eq!(
+            dropped_positions(&[ITEM, SEPARATOR, GONE, SEPARATOR, ITEM]),
+            [1, 2]
+        );
+        assert_eq!(dropped_positions(&[GONE]), [0]);
+    }
+
+    #[test]
+    fn menus_without_removed_items_are_left_alone() {
+        assert!(dropped_positions(&[ITEM, SEPARATOR, ITEM]).is_empty());
+        assert!(dropped_positions(&[SEPARATOR, ITEM, SEPARATOR]).is_empty());
+        assert!(dropped_positions(&[]).is_empty());
+    }
+}
@@ -0,0 +1,377 @@
+use std::collections::HashSet;
+
+use serde::Serialize;
+use tauri::command;
+
+/// Longest username a mention can have
+const MAX_USERNAME_LEN: usize = 64;
+
+/// Longest emoji shortcode looked up, colons included
+const MAX_SHORTCODE_LEN: usize = 64;
+
+/// Characters dropped from the end of a URL, since they're usually the sentence's
+const TRAILING_PUNCTUATION: &str = ".,;:!?'\"*";
+
+/// A piece of a message, in order; joined back together they make up the whole text
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum Token {
+    Text {
+        text: String,
+    },
+    /// `text` as written, e.g. `www.example.com`, and `href` the URL to open, normalized
+    Url {
+        text: String,
+        href: String,
+    },
+    /// `username` is without the `@`
+    Mention {
+        text: String,
+        username: String,
+    },
+    /// `shortcode` is without the colons
+    Emoji {
+        shortcode: String,
+        emoji: String,
+    },
+    /// The inside of a code span, or of a fenced block with its language taken off
+    Code {
+        text: String,
+        language: Option<String>,
+        block: bool,
+    },
+}
+
+/// Split a message into text, URLs, `@mentions`, `:shortcode:` emoji and code for the
+/// frontend to render. Nothing inside a code span or block is linked, and where matches
+/// would overlap the one starting first wins, e.g. a URL containing `@` or `:`. Only
+/// http(s) and `www.` URLs are linked, without trailing punctuation or unbalanced
+/// closing brackets; `@` after a letter (as in email addresses) isn't a mention, and
+/// unknown shortcodes stay text.
+#[command]
+pub fn process_message_text(text: String) -> Vec<Token> {
+    tokenize(&text)
+}
+
+/// `process_message_text` for the native side
+pub fn tokenize(text: &str) -> Vec<Token> {
+    let bytes = text.as_bytes();
+    let mut tokens = Vec::new();
+    // Every match starts with an ASCII byte, so `plain` and `i` are always char boundaries
+    let mut plain = 0;
+    let mut i = 0;
+    // Lengths of backtick runs with no closing run left in the text
+    let mut unclosed = HashSet::new();
+    while i < bytes.len() {
+        let found = match bytes[i] {
+            b'`' => {
+                let run = bytes[i..].iter().take_while(|&&b| b == b'`').count();
+                match code(text, i, run, &mut unclosed) {
+                    Some(found) => Some(found),
+                    None => {
+                        // The whole run is text, not just its first backtick
+                        i += run;
+                        continue;
+                    }
+                }
+            }
+            b'h' | b'H' | b'w' | b'W' => url(text, i),
+            b'@' => mention(text, i),
+            b':' => emoji(text, i),
+            _ => None,
+        };
+        match found {
+            Some((end, token)) => {
+                if plain < i {
+                    tokens.push(Token::Text {
+                        text: text[plain..i].to_string(),
+                    });
+                }
+                tokens.push(token);
+                i = end;
+                plain = end;
+            }
+            None => i += 1,
+        }
+    }
+    if plain < text.len() {
+        tokens.push(Token::Text {
+            text: text[plain..].to_string(),
+        });
+    }
+    tokens
+}
+
+// A code span opened by `run` backticks at `start`, closed by the next run of the same length
+fn code(
+    text: &str,
+    start: usize,
+    run: usize,
+    unclosed: &mut HashSet<usize>,
+) -> Option<(usize, Token)> {
+    if unclosed.contains(&run) {
+        return None;
+    }
+    let bytes = text.as_bytes();
+    let open_end = start + run;
+    let mut j = open_end;
+    let close = loop {
+        let Some(offset) = bytes[j..].iter().position(|&b| b == b'`') else {
+            unclosed.insert(run);
+            return None;
+        };
+        let close = j + offset;
+        let close_run = bytes[close..].iter().take_while(|&&b| b == b'`').count();
+        if close_run == run {
+            break close;
+        }
+        j = close + close_run;
+    };
+    let inner = &text[open_end..close];
+    let end = close + run;
+
+    // Fences put the language on the opening line
+    if run >= 3 {
+        if let Some((first_line, rest)) = inner.split_once('\n') {
+            let language = first_line.trim();
+            let token = Token::Code {
+                text: rest.strip_suffix('\n').unwrap_or(rest).to_string(),
+                language: (!language.is_empty()).then(|| language.to_string()),
+                block: true,
+            };
+            return Some((end, token));
+        }
+    }
+    // As in Markdown, one space either side lets a span start or end with a backtick
+    let inner = match inner.strip_prefix(' ').and_then(|s| s.strip_suffix(' ')) {
+        Some(stripped) if !inner.trim().is_empty() => stripped,
+        _ => inner,
+    };
+    let token = Token::Code {
+        text: inner.to_string(),
+        language: None,
+        block: false,
+    };
+    Some((end, token))
+}
+
+fn url(text: &str, start: usize) -> Option<(usize, Token)> {
+    if previous_char(text, start).is_some_and(|c| c.is_alphanumeric() || "/.@_-".contains(c)) {
+        return None;
+    }
+    let rest = &text[start..];
+    let starts_with = |prefix: &str| {
+        rest.get(..prefix.len())
+            .is_some_and(|start| start.eq_ignore_ascii_case(prefix))
+    };
+    let bare = starts_with("www.");
+    if !bare && !starts_with("https://") && !starts_with("http://") {
+        return None;
+    }
+    let length = rest
+        .find(|c: char| c.is_whitespace() || matches!(c, '<' | '>' | '`'))
+        .unwrap_or(rest.len());
+    let mut candidate = &rest[..length];
+    while let Some(last) = candidate.chars().next_back() {
+        let unbalanced =
+            |open: char| candidate.matches(open).count() < candidate.matches(last).count();
+        let trim = match last {
+            ')' => unbalanced('('),
+            ']' => unbalanced('['),
+            '}' => unbalanced('{'),
+            c => TRAILING_PUNCTUATION.contains(c),
+        };
+        if !trim {
+            break;
+        }
+        candidate = &candidate[..candidate.len() - last.len_utf8()];
+    }
+
+    let href = match bare {
+        true => format!("https://{}", candidate),
+        false => candidate.to_string(),
+    };
+    let parsed = reqwest::Url::parse(&href).ok()?;
+    let host = parsed.host_str()?;
+    // `www.` alone, or `www.example`, is more likely a typo than a link
+    if bare {
+        let domain = host.get(4..).unwrap_or_default();
+        if !domain.contains('.') || domain.ends_with('.') {
+            return None;
+        }
+    }
+    let token = Token::Url {
+        text: candidate.to_string(),
+        href: parsed.to_string(),
+    };
+    Some((start + candidate.len(), token))
+}
+
+fn mention(text: &str, start: usize) -> Option<(usize, Token)> {
+    if previous_char(text, start).is_some_and(|c| c.is_alphanumeric() || "_.-+@`".contains(c)) {
+        return None;
+    }
+    let rest = &text[start + 1..];
+    let length = rest
+        .find(|c: char| !(c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '-')))
+        .unwrap_or(rest.len());
+    let username = rest[..length].trim_end_matches(['.', '-']);
+    if username.is_empty() || username.len() > MAX_USERNAME_LEN {
+        return None;
+    }
+    let end = start + 1 + username.len();
+    let token = Token::Mention {
+        text: text[start..end].to_string(),
+        username: username.to_string(),
+    };
+    Some((end, token))
+}
+
+fn emoji(text: &str, start: usize) -> Option<(usize, Token)> {
+    let rest = &text[start + 1..];
+    let length = rest
+        .bytes()
+        .take(MAX_SHORTCODE_LEN)
+        .position(|b| b == b':')?;
+    let shortcode = &rest[..length];
+    let valid = shortcode
+        .bytes()
+        .all(|b| b.is_ascii_alphanumeric() || matches!(b, b'_' | b'+' | b'-'));
+    if shortcode.is_empty() || !valid {
+        return None;
+    }
+    let emoji = emojis::get_by_shortcode(shortcode)?;
+    let token = Token::Emoji {
+        shortcode: shortcode.to_string(),
+        emoji: emoji.as_str().to_string(),
+    };
+    Some((start + length + 2, token))
+}
+
+fn previous_char(text: &str, index: usize) -> Option<char> {
+    text[..index].chars().next_back()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(text: &str) -> Token {
+        Token::Text {
+            text: text.to_string(),
+        }
+    }
+
+    fn url(text: &str, href: &str) -> Token {
+        Token::Url {
+            text: text.to_string(),
+            href: href.to_string(),
+        }
+    }
+
+    fn mention(username: &str) -> Token {
+        Token::Mention {
+            text: format!("@{}", username),
+            username: username.to_string(),
+        }
+    }
+
+    fn code(text: &str, block: bool) -> Token {
+        Token::Code {
+            text: text.to_string(),
+            language: None,
+            block,
+        }
+    }
+
+    #[test]
+    fn empty_input() {
+        assert_eq!(tokenize(""), []);
+        assert_eq!(tokenize("   "), [text("   ")]);
+    }
+
+    #[test]
+    fn mentions_next_to_punctuation() {
+        assert_eq!(
+            tokenize("hi @alice, how are you?"),
+            [text("hi "), mention("alice"), text(", how are you?")]
+        );
+        assert_eq!(tokenize("(@bob)"), [text("("), mention("bob"), text(")")]);
+        assert_eq!(tokenize("@carol."), [mention("carol"), text(".")]);
+        assert_eq!(tokenize("@dave's"), [mention("dave"), text("'s")]);
+        assert_eq!(
+            tokenize("email me@example.com"),
+            [text("email me@example.com")]
+        );
+    }
+
+    #[test]
+    fn urls_without_trailing_punctuation() {
+        assert_eq!(
+            tokenize("see https://example.com."),
+            [
+                text("see "),
+                url("https://example.com", "https://example.com/"),
+                text(".")
+            ]
+        );
+        assert_eq!(
+            tokenize("https://example.com/a?b=1!"),
+            [
+                url("https://example.com/a?b=1", "https://example.com/a?b=1"),
+                text("!")
+            ]
+        );
+        assert_eq!(
+            tokenize("\"https://example.com\""),
+            [
+                text("\""),
+                url("https://example.com", "https://example.com/"),
+                text("\"")
+            ]
+        );
+        assert_eq!(
+            tokenize("www.example.com/path,"),
+            [
+                url("www.example.com/path", "https://www.example.com/path"),
+                text(",")
+            ]
+        );
+    }
+
+    #[test]
+    fn urls_in_parentheses() {
+        assert_eq!(
+            tokenize("(see https://example.com)"),
+            [
+                text("(see "),
+                url("https://example.com", "https://example.com/"),
+                text(")")
+            ]
+        );
+        let wiki = "https://en.wikipedia.org/wiki/Rust_(programming_language)";
+        assert_eq!(
+            tokenize(&format!("({})", wiki)),
+            [text("("), url(wiki, wiki), text(")")]
+        );
+    }
+
+    #[test]
+    fn nothing_inside_code_is_matched() {
+        assert_eq!(
+            tokenize("`:smile: @x https://e.com`"),
+            [code(":smile: @x https://e.com", false)]
+        );
+        assert_eq!(tokenize("```\n:smile:\n```"), [code(":smile:", true)]);
+        assert_eq!(
+            tokenize(":smile: ok"),
+            [
+                Token::Emoji {
+                    shortcode: "smile".to_string(),
+                    emoji: "😄".to_string(),
+                },
+                text(" ok")
+            ]
+        );
+    }
+}
@@ -0,0 +1,412 @@
+use tauri::AppHandle;
+
+/// A notification to group under `thread_id` (e.g. a channel) and to replace any
+/// earlier one with the same `tag`
+pub struct Notification<'a> {
+    pub title: &'a str,
+    pub body: &'a str,
+    pub thread_id: Option<&'a str>,
+    pub tag: Option<&'a str>,
+    /// Play the platform's standard sound
+    pub sound: bool,
+    /// Event to emit when the user clicks the notification
+    pub clicked_event: Option<&'static str>,
+}
+
+/// Show a grouped notification directly through the OS, since the notification plugin
+/// drops grouping and ids on desktop. Returns false when the platform can do neither grouping
+/// nor replacement, so the caller can show a plain notification instead.
+///
+/// - **macOS:** `thread_id` is the thread identifier and `tag` the request identifier.
+///   Only works in the bundled app; dev builds return false.
+/// - **Windows:** `thread_id` and `tag` are the toast group and tag, at most 64 characters each.
+/// - **Linux:** `tag` replaces the earlier notification through its server id. The
+///   freedesktop spec has no grouping, so `thread_id` is only kept for `clear`.
+pub fn show(app: &AppHandle, notification: &Notification) -> Result<bool, String> {
+    platform::show(app, notification)
+}
+
+/// Remove delivered notifications shown with `thread_id`, or all of Hazel's when None.
+///
+/// - **macOS:** only notifications shown through `show`, and only in the bundled app.
+/// - **Windows:** every toast from the app, including ones shown by the notification plugin.
+/// - **Linux:** only the most recent notifications shown through `show` are tracked.
+pub fn clear(app: &AppHandle, thread_id: Option<&str>) -> Result<(), String> {
+    platform::clear(app, thread_id)
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use std::collections::HashMap;
+    use std::ptr::NonNull;
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::{Mutex, Once, OnceLock};
+
+    use block2::{DynBlock, RcBlock};
+    use objc2::rc::Retained;
+    use objc2::runtime::{Bool, NSObject, NSObjectProtocol, ProtocolObject};
+    use objc2::{define_class, msg_send, AllocAnyThread};
+    use objc2_foundation::{NSArray, NSBundle, NSError, NSString};
+    use objc2_user_notifications::{
+        UNAuthorizationOptions, UNMutableNotificationContent, UNNotification,
+        UNNotificationPresentationOptions, UNNotificationRequest, UNNotificationResponse,
+        UNNotificationSound, UNUserNotificationCenter, UNUserNotificationCenterDelegate,
+    };
+    use tauri::{AppHandle, Emitter};
+
+    use super::Notification;
+
+    // Handle for the delegate to emit click events through
+    static APP: OnceLock<AppHandle> = OnceLock::new();
+
+    // Events to emit when the notification with a request identifier is clicked
+    fn click_events() -> &'static Mutex<HashMap<String, &'static str>> {
+        static EVENTS: OnceLock<Mutex<HashMap<String, &'static str>>> = OnceLock::new();
+        EVENTS.get_or_init(|| Mutex::new(HashMap::new()))
+    }
+
+    define_class!(
+        #[unsafe(super(NSObject))]
+        #[name = "HazelNotificationDelegate"]
+        struct Delegate;
+
+        unsafe impl NSObjectProtocol for Delegate {}
+
+        unsafe impl UNUserNotificationCenterDelegate for Delegate {
+            // Without this the center hides notifications while Hazel is frontmost
+            #[unsafe(method(userNotificationCenter:willPresentNotification:withCompletionHandler:))]
+            fn will_present(
+                &self,
+                _center: &UNUserNotificationCenter,
+                _notification: &UNNotification,
+                completion: &DynBlock<dyn Fn(UNNotificationPresentationOptions)>,
+            ) {
+                completion.call((UNNotificationPresentationOptions::Banner
+                    | UNNotificationPresentationOptions::List
+                    | UNNotificationPresentationOptions::Sound,));
+            }
+
+            #[unsafe(method(userNotificationCenter:didReceiveNotificationResponse:withCompletionHandler:))]
+            fn did_receive(
+                &self,
+                _center: &UNUserNotificationCenter,
+                response: &UNNotificationResponse,
+                completion: &DynBlock<dyn Fn()>,
+            ) {
+                let identifier = response.notification().request().identifier().to_string();
+                let event = click_events().lock().unwrap().remove(&identifier);
+                if let (Some(event), Some(app)) = (event, APP.get()) {
+                    let _ = app.emit(event, ());
+                }
+                completion.call(());
+            }
+        }
+    );
+
+    // Request identifiers for untagged notifications, which never replace each other
+    static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+    // UNUserNotificationCenter throws when the process isn't a bundled app
+    fn center() -> Option<objc2::rc::Retained<UNUserNotificationCenter>> {
+        NSBundle::mainBundle().bundleIdentifier()?;
+        Some(UNUserNotificationCenter::currentNotificationCenter())
+    }
+
+    pub fn show(app: &AppHandle, notification: &Notification) -> Result<bool, String> {
+        let Some(center) = center() else {
+            return Ok(false);
+        };
+
+        static SETUP: Once = Once::new();
+        SETUP.call_once(|| {
+            let _ = APP.set(app.clone());
+            // The center only keeps a weak reference, so the delegate lives for the whole run
+            let delegate: Retained<Delegate> = unsafe { msg_send![Delegate::alloc(), init] };
+            center.setDelegate(Some(ProtocolObject::from_ref(&*delegate)));
+            std::mem::forget(delegate);
+
+            let done = RcBlock::new(|_granted: Bool, _error: *mut NSError| {});
+            center.requestAuthorizationWithOptions_completionHandler(
+                UNAuthorizationOptions::Alert | UNAuthorizationOptions::Sound,
+                &done,
+            );
+        });
+
+        let content = UNMutableNotificationContent::new();
+        content.setTitle(&NSString::from_str(notification.title));
+        content.setBody(&NSString::from_str(notification.body));
+        if let Some(thread_id) = notification.thread_id {
+            content.setThreadIdentifier(&NSString::from_str(thread_id));
+        }
+        if notification.sound {
+            content.setSound(Some(&UNNotificationSound::defaultSound()));
+        }
+
+        let identifier = match notification.tag {
+            Some(tag) => format!("tag:{}", tag),
+            None => format!("hazel:{}", NEXT_ID.fetch_add(1, Ordering::Relaxed)),
+        };
+        if let Some(event) = notification.clicked_event {
+            click_events()
+                .lock()
+                .unwrap()
+                .insert(identifier.clone(), event);
+        }
+        let request = UNNotificationRequest::requestWithIdentifier_content_trigger(
+            &NSString::from_str(&identifier),
+            &content,
+            None,
+        );
+        center.addNotificationRequest_withCompletionHandler(&request, None);
+        Ok(true)
+    }
+
+    pub fn clear(_app: &AppHandle, thread_id: Option<&str>) -> Result<(), String> {
+        let Some(center) = center() else {
+            return Ok(());
+        };
+        let Some(thread_id) = thread_id else {
+            center.removeAllDeliveredNotifications();
+            return Ok(());
+        };
+
+        let thread_id = thread_id.to_string();
+        let remover = center.clone();
+        let handler = RcBlock::new(move |delivered: NonNull<NSArray<UNNotification>>| {
+            // SAFETY: the center passes a valid array for the duration of the callback
+            let delivered = unsafe { delivered.as_ref() };
+            let identifiers: Vec<_> = delivered
+                .iter()
+                .map(|notification| notification.request())
+                .filter(|request| request.content().threadIdentifier().to_string() == thread_id)
+                .map(|request| request.identifier())
+                .collect();
+            remover.removeDeliveredNotificationsWithIdentifiers(&NSArray::from_retained_slice(
+                &identifiers,
+            ));
+        });
+        center.getDeliveredNotificationsWithCompletionHandler(&handler);
+        Ok(())
+    }
+}
+
+#[cfg(target_os = "windows")]
+mod platform {
+    use std::path::MAIN_SEPARATOR as SEP;
+
+    use tauri::{AppHandle, Emitter};
+    use windows::core::{IInspectable, HSTRING};
+    use windows::Data::Xml::Dom::XmlDocument;
+    use windows::Foundation::TypedEventHandler;
+    use windows::UI::Notifications::{ToastNotification, ToastNotificationManager};
+
+    use super::Notification;
+
+    /// Toasts need a registered app id, which only the installed app has
+    const POWERSHELL_APP_ID: &str =
+        r"{1AC14E77-02E7-4E5D-B744-2EB1AE5198B7}\WindowsPowerShell\v1.0\powershell.exe";
+
+    pub fn show(app: &AppHandle, notification: &Notification) -> Result<bool, String> {
+        let audio = if notification.sound {
+            r#"<audio src="ms-winsoundevent:Notification.Default"/>"#
+        } else {
+            r#"<audio silent="true"/>"#
+        };
+        let xml = format!(
+            r#"<toast><visual><binding template="ToastGeneric"><text>{}</text><text>{}</text></binding></visual>{}</toast>"#,
+            escape(notification.title),
+            escape(notification.body),
+            audio
+        );
+
+        let show = || -> windows::core::Result<()> {
+            let document = XmlDocument::new()?;
+            document.LoadXml(&HSTRING::from(xml))?;
+            let toast = ToastNotification::CreateToastNotification(&document)?;
+            if let Some(tag) = notification.tag {
+                toast.SetTag(&HSTRING::from(tag))?;
+            }
+            if let Some(thread_id) = notification.thread_id {
+                toast.SetGroup(&HSTRING::from(thread_id))?;
+            }
+            if let Some(event) = notification.clicked_event {
+                let app = app.clone();
+                toast.Activated(&TypedEventHandler::<ToastNotification, IInspectable>::new(
+                    move |_, _| {
+                        let _ = app.emit(event, ());
+                        Ok(())
+                    },
+                ))?;
+            }
+            ToastNotificationManager::CreateToastNotifierWithId(&HSTRING::from(app_id(app)))?
+                .Show(&toast)
+        };
+        show().map_err(|e| e.to_string())?;
+        Ok(true)
+    }
+
+    pub fn clear(app: &AppHandle, thread_id: Option<&str>) -> Result<(), String> {
+        let app_id = HSTRING::from(app_id(app));
+        let history = ToastNotificationManager::History().map_err(|e| e.to_string())?;
+        match thread_id {
+            Some(thread_id) => history.RemoveGroupWithId(&HSTRING::from(thread_id), &app_id),
+            None => history.ClearWithId(&app_id),
+        }
+        .map_err(|e| e.to_string())
+    }
+
+    // Same rule as the notification plugin: dev builds borrow PowerShell's id
+    fn app_id(app: &AppHandle) -> String {
+        let dev_build = tauri::utils::platform::current_exe()
+            .ok()
+            .and_then(|exe| exe.parent().map(|dir| dir.display().to_string()))
+            .is_some_and(|dir| {
+                dir.ends_with(&format!("{SEP}target{SEP}debug"))
+                    || dir.ends_with(&format!("{SEP}target{SEP}release"))
+            });
+        if dev_build {
+            POWERSHELL_APP_ID.to_string()
+        } else {
+            app.config().identifier.clone()
+        }
+    }
+
+    fn escape(text: &str) -> String {
+        text.replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;")
+            .replace('"', "&quot;")
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod platform {
+    use std::sync::{Mutex, OnceLock};
+    use std::thread;
+
+    use tauri::{AppHandle, Emitter};
+
+    use super::Notification;
+    use crate::notifications::PLATFORM_SOUND;
+
+    /// Oldest tracked notifications are forgotten past this many
+    const MAX_TRACKED: usize = 50;
+
+    // By server id, since waiting for a click takes the handle
+    struct Delivered {
+        thread_id: Option<String>,
+        tag: Option<String>,
+        id: u32,
+    }
+
+    // Notifications shown through here, so tagged ones can be replaced and any cleared
+    fn delivered() -> &'static Mutex<Vec<Delivered>> {
+        static DELIVERED: OnceLock<Mutex<Vec<Delivered>>> = OnceLock::new();
+        DELIVERED.get_or_init(|| Mutex::new(Vec::new()))
+    }
+
+    pub fn show(app: &AppHandle, notification: &Notification) -> Result<bool, String> {
+        let mut native = notify_rust::Notification::new();
+        native
+            .summary(notification.title)
+            .body(notification.body)
+            .auto_icon();
+        if notification.sound {
+            native.sound_name(PLATFORM_SOUND);
+        }
+        // "default" is the action servers invoke when the notification itself is clicked
+        if notification.clicked_event.is_some() {
+            native.action("default", "Open");
+        }
+        let thread_id = notification.thread_id.map(str::to_string);
+        let tag = notification.tag.map(str::to_string);
+        let clicked_event = notification.clicked_event;
+        let app = app.clone();
+
+        // The D-Bus round trip blocks, so keep it off the main thread
+        thread::spawn(move || {
+            let mut delivered = delivered().lock().unwrap();
+            if let Some(index) = delivered
+                .iter()
+                .position(|shown| tag.is_some() && shown.tag == tag)
+            {
+                native.id(delivered.remove(index).id);
+            }
+            let handle = match native.show() {
+                Ok(handle) => handle,
+                Err(e) => {
+                    log::warn!("Failed to show notification: {}", e);
+                    return;
+                }
+            };
+
+            if delivered.len() == MAX_TRACKED {
+                delivered.remove(0);
+            }
+            delivered.push(Delivered {
+                thread_id,
+                tag,
+                id: handle.id(),
+            });
+            drop(delivered);
+
+            if let Some(event) = clicked_event {
+                handle.wait_for_action(|action| {
+                    if action == "default" {
+                        let _ = app.emit(event, ());
+                    }
+                });
+            }
+        });
+        Ok(true)
+    }
+
+    pub fn clear(_app: &AppHandle, thread_id: Option<&str>) -> Result<(), String> {
+        let thread_id = thread_id.map(str::to_string);
+        thread::spawn(move || {
+            let mut delivered = delivered().lock().unwrap();
+            let (cleared, kept) = delivered
+                .drain(..)
+                .partition(|shown| thread_id.is_none() || shown.thread_id == thread_id);
+            *delivered = kept;
+            drop(delivered);
+            if cleared.is_empty() {
+                return;
+            }
+            let ids = cleared.iter().map(|shown| shown.id).collect::<Vec<_>>();
+            if let Err(e) = close(&ids) {
+                log::warn!("Failed to clear notifications: {}", e);
+            }
+        });
+        Ok(())
+    }
+
+    fn close(ids: &[u32]) -> zbus::Result<()> {
+        let session = zbus::blocking::Connection::session()?;
+        for &id in ids {
+            session.call_method(
+                Some("org.freedesktop.Notifications"),
+                "/org/freedesktop/Notifications",
+                Some("org.freedesktop.Notifications"),
+                "CloseNotification",
+                &(id,),
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "windows", target_os = "linux")))]
+mod platform {
+    use tauri::AppHandle;
+
+    use super::Notification;
+
+    pub fn show(_app: &AppHandle, _notification: &Notification) -> Result<bool, String> {
+        Ok(false)
+    }
+
+    pub fn clear(_app: &AppHandle, _thread_id: Option<&str>) -> Result<(), String> {
+        Ok(())
+    }
+}
@@ -0,0 +1,456 @@
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use tauri::{command, AppHandle, Emitter};
+use tauri_plugin_notification::NotificationExt;
+
+use crate::notification_center::{self, Notification};
+use crate::{audio, flags, markdown, quiet_hours, settings};
+
+const SNOOZE_UNTIL_KEY: &str = "notifications.snooze_until";
+const SOUND_KEY: &str = "notifications.sound";
+
+/// Characters of a message shown in a notification
+const BODY_PREVIEW_LEN: usize = 240;
+
+/// Name of the platform's standard notification sound
+#[cfg(target_os = "macos")]
+pub(crate) const PLATFORM_SOUND: &str = "Ping";
+#[cfg(target_os = "windows")]
+pub(crate) const PLATFORM_SOUND: &str = "Default";
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+pub(crate) const PLATFORM_SOUND: &str = "message-new-instant";
+
+/// File behind `PLATFORM_SOUND`, for previewing it without a notification
+#[cfg(target_os = "macos")]
+const PLATFORM_SOUND_FILE: &str = "/System/Library/Sounds/Ping.aiff";
+#[cfg(target_os = "windows")]
+const PLATFORM_SOUND_FILE: &str = r"C:\Windows\Media\Windows Notify System Generic.wav";
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+const PLATFORM_SOUND_FILE: &str = "/usr/share/sounds/freedesktop/stereo/message-new-instant.oga";
+
+// End of the current snooze as unix millis, if one is active
+fn snooze_until() -> &'static Mutex<Option<u64>> {
+    static UNTIL: OnceLock<Mutex<Option<u64>>> = OnceLock::new();
+    UNTIL.get_or_init(|| Mutex::new(None))
+}
+
+/// Presentation state reported by the OS, where it can be detected
+static PRESENTING_DETECTED: AtomicBool = AtomicBool::new(false);
+
+// Manual presenting override; None follows detection
+fn presenting_override() -> &'static Mutex<Option<bool>> {
+    static OVERRIDE: OnceLock<Mutex<Option<bool>>> = OnceLock::new();
+    OVERRIDE.get_or_init(|| Mutex::new(None))
+}
+
+/// How often the OS presentation state is polled
+#[cfg(target_os = "windows")]
+const PRESENTING_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// How long notifications are collected before being posted as one summary
+const BATCH_WINDOW: Duration = Duration::from_secs(10);
+
+/// Tag shared by summaries so each replaces the last
+const SUMMARY_TAG: &str = "summary";
+
+// Notification held back in a batch
+struct Batched {
+    title: String,
+    body: String,
+    thread_id: Option<String>,
+    tag: Option<String>,
+}
+
+#[derive(Default)]
+struct Batch {
+    count: u32,
+    latest: Option<Batched>,
+}
+
+// Notifications collected while a batch window is open
+fn current_batch() -> &'static Mutex<Option<Batch>> {
+    static BATCH: OnceLock<Mutex<Option<Batch>>> = OnceLock::new();
+    BATCH.get_or_init(|| Mutex::new(None))
+}
+
+// Bumped whenever the snooze changes so stale expiry timers know to do nothing
+static SNOOZE_GENERATION: AtomicU64 = AtomicU64::new(0);
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64
+}
+
+/// Sound played with notifications, e.g. `{ "kind": "custom", "path": "/path/to/tone.wav" }`
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", content = "path", rename_all = "lowercase")]
+pub enum NotificationSound {
+    /// The system notification sound
+    #[default]
+    Default,
+    None,
+    /// An audio file, played by the app since the OS can't play arbitrary files with a notification
+    Custom(PathBuf),
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SnoozeStatus {
+    pub active: bool,
+    /// End of the snooze as unix millis
+    pub until: Option<u64>,
+    pub remaining_secs: u64,
+}
+
+/// Show a native notification. Returns false without showing anything while notifications are suppressed.
+/// `body` is Markdown and shown as plain text, cut to 240 characters.
+///
+/// Notifications with the same `thread_id` (e.g. a channel id) are grouped in the notification
+/// center, and one with the same `tag` as an earlier notification replaces it in place.
+/// Both are ignored where the platform can't do it; see `notification_center::show`.
+///
+/// With `batch` set, or during the batch window that opens on `system-resume`, the notification
+/// is held back for 10 seconds and posted together with any others as one summary.
+#[command]
+pub fn notify(
+    app: AppHandle,
+    title: String,
+    body: String,
+    thread_id: Option<String>,
+    tag: Option<String>,
+    batch: Option<bool>,
+) -> Result<bool, String> {
+    if is_suppressed() {
+        return Ok(false);
+    }
+
+    let notification = Batched {
+        title,
+        body: markdown::plaintext(&body, BODY_PREVIEW_LEN),
+        thread_id,
+        tag,
+    };
+    let Some(notification) = add_to_batch(&app, notification, batch.unwrap_or(false)) else {
+        return Ok(true);
+    };
+    deliver(
+        &app,
+        &notification.title,
+        &notification.body,
+        notification.thread_id.as_deref(),
+        notification.tag.as_deref(),
+        None,
+    )?;
+    Ok(true)
+}
+
+/// Show one "N new messages" notification in place of many, e.g. for messages missed while
+/// the computer was asleep. Replaces the previous summary; clicking it emits `summary-opened`.
+/// Returns false without showing anything while notifications are suppressed.
+#[command]
+pub fn notify_summary(app: AppHandle, count: u32, preview: Option<String>) -> Result<bool, String> {
+    if count == 0 || is_suppressed() {
+        return Ok(false);
+    }
+    show_summary(&app, count, preview.as_deref())?;
+    Ok(true)
+}
+
+/// Collect notifications into one summary for the next 10 seconds, e.g. after resume.
+/// Does nothing with the `resume_batching` flag off.
+pub fn start_batch(app: &AppHandle) {
+    if !flags::enabled(app, "resume_batching") {
+        return;
+    }
+    let mut batch = current_batch().lock().unwrap();
+    if batch.is_none() {
+        open_batch(app, &mut batch);
+    }
+}
+
+// Add to the open batch, opening one if asked. Hands the notification back if there's
+// no batch to hold it.
+fn add_to_batch(app: &AppHandle, notification: Batched, open: bool) -> Option<Batched> {
+    let mut batch = current_batch().lock().unwrap();
+    if batch.is_none() {
+        if !open {
+            return Some(notification);
+        }
+        open_batch(app, &mut batch);
+    }
+    if let Some(batch) = batch.as_mut() {
+        batch.count += 1;
+        batch.latest = Some(notification);
+    }
+    None
+}
+
+fn open_batch(app: &AppHandle, batch: &mut Option<Batch>) {
+    *batch = Some(Batch::default());
+    let app = app.clone();
+    thread::spawn(move || {
+        thread::sleep(BATCH_WINDOW);
+        flush_batch(&app);
+    });
+}
+
+// A lone batched notification is shown as it was; more become a summary
+fn flush_batch(app: &AppHandle) {
+    let Some(batch) = current_batch().lock().unwrap().take() else {
+        return;
+    };
+    let Some(latest) = batch.latest else {
+        return;
+    };
+    if is_suppressed() {
+        return;
+    }
+
+    let result = if batch.count == 1 {
+        deliver(
+            app,
+            &latest.title,
+            &latest.body,
+            latest.thread_id.as_deref(),
+            latest.tag.as_deref(),
+            None,
+        )
+    } else {
+        let preview = format!("{}: {}", latest.title, latest.body);
+        show_summary(app, batch.count, Some(&preview))
+    };
+    if let Err(e) = result {
+        log::warn!("Failed to show batched notifications: {}", e);
+    }
+}
+
+fn show_summary(app: &AppHandle, count: u32, preview: Option<&str>) -> Result<(), String> {
+    let title = match count {
+        1 => "1 new message".to_string(),
+        count => format!("{} new messages", count),
+    };
+    deliver(
+        app,
+        &title,
+        preview.unwrap_or_default(),
+        None,
+        Some(SUMMARY_TAG),
+        Some("summary-opened"),
+    )
+}
+
+// Show a notification with the user's sound, natively when it needs grouping or clicks
+fn deliver(
+    app: &AppHandle,
+    title: &str,
+    body: &str,
+    thread_id: Option<&str>,
+    tag: Option<&str>,
+    clicked_event: Option<&'static str>,
+) -> Result<(), String> {
+    let sound = notification_sound(app);
+    let native = (thread_id.is_some() || tag.is_some() || clicked_event.is_some())
+        && flags::enabled(app, "native_notification_center")
+        && notification_center::show(
+            app,
+            &Notification {
+                title,
+                body,
+                thread_id,
+                tag,
+                sound: sound == NotificationSound::Default,
+                clicked_event,
+            },
+        )?;
+    if !native {
+        let mut builder = app.notification().builder().title(title).body(body);
+        if sound == NotificationSound::Default {
+            builder = builder.sound(PLATFORM_SOUND);
+        }
+        builder.show().map_err(|e| e.to_string())?;
+    }
+
+    if let NotificationSound::Custom(path) = sound {
+        if let Err(e) = audio::play_file(&path) {
+            log::warn!("Failed to play notification sound: {}", e);
+        }
+    }
+    Ok(())
+}
+
+/// Remove a channel's delivered notifications (those shown with it as `thread_id`) from the
+/// notification center, e.g. once the channel has been read. Does nothing where the platform
+/// doesn't allow it.
+#[command]
+pub fn clear_notifications(app: AppHandle, channel_id: String) -> Result<(), String> {
+    notification_center::clear(&app, Some(&channel_id))
+}
+
+/// Remove all of Hazel's delivered notifications from the notification center, where the
+/// platform allows it
+#[command]
+pub fn clear_all_notifications(app: AppHandle) -> Result<(), String> {
+    notification_center::clear(&app, None)
+}
+
+/// Choose the sound played with notifications. Custom files must exist and decode as audio.
+#[command]
+pub fn set_notification_sound(app: AppHandle, choice: NotificationSound) -> Result<(), String> {
+    if let NotificationSound::Custom(path) = &choice {
+        audio::validate_sound_file(path)?;
+    }
+    settings::set(&app, SOUND_KEY, choice)
+}
+
+/// Play a notification sound once without showing a notification, e.g. to audition it in
+/// settings. Replaces any preview that's still playing; `none` just stops it.
+#[command]
+pub fn play_sound(choice: NotificationSound) -> Result<(), String> {
+    match choice {
+        NotificationSound::Default => audio::play_file(Path::new(PLATFORM_SOUND_FILE)),
+        NotificationSound::None => {
+            audio::stop();
+            Ok(())
+        }
+        NotificationSound::Custom(path) => {
+            audio::validate_sound_file(&path)?;
+            audio::play_file(&path)
+        }
+    }
+}
+
+pub fn notification_sound(app: &AppHandle) -> NotificationSound {
+    settings::get(app, SOUND_KEY).unwrap_or_default()
+}
+
+/// Suppress notifications for the given number of minutes, emitting `snooze-started` now and
+/// `snooze-ended` when it runs out. Pass 0 to end the current snooze early.
+#[command]
+pub fn snooze_notifications(app: AppHandle, minutes: u32) -> Result<SnoozeStatus, String> {
+    if minutes == 0 {
+        end_snooze(&app);
+        return Ok(snooze_status());
+    }
+
+    let until = now_ms() + u64::from(minutes) * 60_000;
+    settings::set(&app, SNOOZE_UNTIL_KEY, until)?;
+    start_snooze(&app, until);
+    Ok(snooze_status())
+}
+
+#[command]
+pub fn snooze_status() -> SnoozeStatus {
+    let now = now_ms();
+    let until = snooze_until().lock().unwrap().filter(|&until| until > now);
+    SnoozeStatus {
+        active: until.is_some(),
+        until,
+        remaining_secs: until.map_or(0, |until| until.saturating_sub(now) / 1000),
+    }
+}
+
+/// Suppress notifications while presenting. `true`/`false` override detection; null goes back
+/// to following the OS. Emits `presenting-changed` when the effective state flips and returns it.
+///
+/// Detection only exists on Windows (presentation mode and full-screen apps, via
+/// `SHQueryUserNotificationState`); elsewhere this override is the only source.
+#[command]
+pub fn set_presenting(app: AppHandle, presenting: Option<bool>) -> bool {
+    let before = is_presenting();
+    *presenting_override().lock().unwrap() = presenting;
+    emit_presenting_change(&app, before);
+    is_presenting()
+}
+
+pub fn is_presenting() -> bool {
+    presenting_override()
+        .lock()
+        .unwrap()
+        .unwrap_or_else(|| PRESENTING_DETECTED.load(Ordering::SeqCst))
+}
+
+fn emit_presenting_change(app: &AppHandle, before: bool) {
+    let after = is_presenting();
+    if after != before {
+        let _ = app.emit("presenting-changed", after);
+    }
+}
+
+/// Poll the OS presentation state in the background, where the platform exposes it
+#[cfg(target_os = "windows")]
+pub fn watch_presenting(app: &AppHandle) {
+    use windows_sys::Win32::UI::Shell::{
+        SHQueryUserNotificationState, QUNS_BUSY, QUNS_PRESENTATION_MODE,
+        QUNS_RUNNING_D3D_FULL_SCREEN,
+    };
+
+    let app = app.clone();
+    thread::spawn(move || loop {
+        let mut state = 0;
+        let detected = unsafe { SHQueryUserNotificationState(&mut state) } >= 0
+            && matches!(
+                state,
+                QUNS_BUSY | QUNS_RUNNING_D3D_FULL_SCREEN | QUNS_PRESENTATION_MODE
+            );
+        let before = is_presenting();
+        PRESENTING_DETECTED.store(detected, Ordering::SeqCst);
+        emit_presenting_change(&app, before);
+        thread::sleep(PRESENTING_POLL_INTERVAL);
+    });
+}
+
+#[cfg(not(target_os = "windows"))]
+pub fn watch_presenting(_app: &AppHandle) {}
+
+/// Re-arm a snooze that was still running when the app last quit
+pub fn restore_snooze(app: &AppHandle) {
+    match settings::get::<u64>(app, SNOOZE_UNTIL_KEY) {
+        Some(until) if until > now_ms() => start_snooze(app, until),
+        Some(_) => {
+            let _ = settings::delete(app, SNOOZE_UNTIL_KEY);
+        }
+        None => {}
+    }
+}
+
+// Snoozed, presenting or in quiet hours
+fn is_suppressed() -> bool {
+    is_snoozed() || is_presenting() || quiet_hours::is_active()
+}
+
+fn is_snoozed() -> bool {
+    snooze_until()
+        .lock()
+        .unwrap()
+        .is_some_and(|until| until > now_ms())
+}
+
+fn start_snooze(app: &AppHandle, until: u64) {
+    *snooze_until().lock().unwrap() = Some(until);
+    let generation = SNOOZE_GENERATION.fetch_add(1, Ordering::SeqCst) + 1;
+    let _ = app.emit("snooze-started", until);
+
+    let app = app.clone();
+    thread::spawn(move || {
+        thread::sleep(Duration::from_millis(until.saturating_sub(now_ms())));
+        if SNOOZE_GENERATION.load(Ordering::SeqCst) == generation {
+            end_snooze(&app);
+        }
+    });
+}
+
+fn end_snooze(app: &AppHandle) {
+    SNOOZE_GENERATION.fetch_add(1, Ordering::SeqCst);
+    let was_active = snooze_until().lock().unwrap().take().is_some();
+    let _ = settings::delete(app, SNOOZE_UNTIL_KEY);
+    if was_active {
+        let _ = app.emit("snooze-ended", ());
+    }
+}
@@ -0,0 +1,253 @@
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+use chacha20poly1305::aead::OsRng;
+use serde::{Deserialize, Serialize};
+use tauri::{command, AppHandle};
+
+use crate::{kiosk, lock, settings};
+
+/// The passcode's Argon2id hash as a PHC string, which carries its salt and parameters
+const HASH_KEY: &str = "lock.passcode";
+const FAILURES_KEY: &str = "lock.failures";
+
+const MIN_LEN: usize = 4;
+
+/// Wrong passcodes in a row allowed before each further try has to wait
+const FREE_ATTEMPTS: u32 = 5;
+
+/// Wrong passcodes in a row after which every try waits `LOCKOUT`
+const MAX_ATTEMPTS: u32 = 10;
+
+const LOCKOUT: Duration = Duration::from_secs(15 * 60);
+
+// Held for a whole check, so that tries made at once can't all get in before the wait
+fn checking() -> &'static Mutex<()> {
+    static CHECKING: OnceLock<Mutex<()>> = OnceLock::new();
+    CHECKING.get_or_init(|| Mutex::new(()))
+}
+
+// Wrong passcodes in a row, kept across restarts so restarting doesn't reset the wait
+#[derive(Default, Serialize, Deserialize)]
+struct Failures {
+    count: u32,
+    /// Milliseconds since the epoch before which no passcode is checked
+    retry_at: u64,
+}
+
+/// Set the passcode that unlocks the app where the system's authentication isn't
+/// available. Only a salted Argon2id hash of it is kept. Changing it takes the `current`
+/// one, which counts towards the wait like `verify_passcode`. Can't be changed while locked
+/// or in kiosk mode.
+#[command]
+pub async fn set_passcode(
+    app: AppHandle,
+    code: String,
+    current: Option<String>,
+) -> Result<(), String> {
+    if lock::is_app_locked() {
+        return Err("Unlock Hazel first".to_string());
+    }
+    if kiosk::enabled() {
+        return Err("Leave kiosk mode first".to_string());
+    }
+    if code.chars().count() < MIN_LEN {
+        return Err(format!(
+            "The passcode must be at least {} characters",
+            MIN_LEN
+        ));
+    }
+    tauri::async_runtime::spawn_blocking(move || {
+        if is_set(&app) {
+            let current = current.ok_or_else(|| "Enter the current passcode".to_string())?;
+            if !check(&app, &current)? {
+                return Err("Wrong passcode".to_string());
+            }
+        }
+        settings::set(&app, HASH_KEY, hash(&code)?)?;
+        settings::delete(&app, FAILURES_KEY)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// Whether `code` is the passcode. After 5 wrong ones in a row each try has to wait,
+/// doubling from a second, and from 10 on every try waits 15 minutes. The count is kept
+/// across restarts and reset by the right passcode.
+#[command]
+pub async fn verify_passcode(app: AppHandle, code: String) -> Result<bool, String> {
+    tauri::async_runtime::spawn_blocking(move || check(&app, &code))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+/// Remove the passcode, which `current` has to match. Can't be done while locked or in
+/// kiosk mode.
+#[command]
+pub async fn clear_passcode(app: AppHandle, current: String) -> Result<(), String> {
+    if lock::is_app_locked() {
+        return Err("Unlock Hazel first".to_string());
+    }
+    if kiosk::enabled() {
+        return Err("Leave kiosk mode first".to_string());
+    }
+    tauri::async_runtime::spawn_blocking(move || {
+        if !is_set(&app) {
+            return Ok(());
+        }
+        if !check(&app, &current)? {
+            return Err("Wrong passcode".to_string());
+        }
+        settings::delete(&app, HASH_KEY)?;
+        settings::delete(&app, FAILURES_KEY)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+pub fn is_set(app: &AppHandle) -> bool {
+    settings::get::<String>(app, HASH_KEY).is_some()
+}
+
+/// Check a passcode, counting a wrong one towards the wait before the next try
+pub fn check(app: &AppHandle, code: &str) -> Result<bool, String> {
+    let _checking = checking().lock().unwrap();
+    let stored =
+        settings::get::<String>(app, HASH_KEY).ok_or_else(|| "No passcode is set".to_string())?;
+    let mut failures = settings::get::<Failures>(app, FAILURES_KEY).unwrap_or_default();
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64;
+    let matches = attempt(&mut failures, code, &stored, now)?;
+    if matches {
+        settings::delete(app, FAILURES_KEY)?;
+    } else {
+        settings::set(app, FAILURES_KEY, failures)?;
+    }
+    Ok(matches)
+}
+
+// One try at `now`, counting it in `failures`, which `check` loads and saves around it
+fn attempt(failures: &mut Failures, code: &str, stored: &str, now: u64) -> Result<bool, String> {
+    if failures.retry_at > now {
+        return Err(format!(
+            "Too many wrong passcodes, try again in {} seconds",
+            (failures.retry_at - now).div_ceil(1000)
+        ));
+    }
+    let matches = matches(code, stored)?;
+    if matches {
+        *failures = Failures::default();
+    } else {
+        failures.count = failures.count.saturating_add(1);
+        failures.retry_at = now + retry_delay(failures.count).as_millis() as u64;
+    }
+    Ok(matches)
+}
+
+fn hash(code: &str) -> Result<String, String> {
+    let salt = SaltString::generate(&mut OsRng);
+    Argon2::default()
+        .hash_password(code.as_bytes(), &salt)
+        .map(|hash| hash.to_string())
+        .map_err(|e| e.to_string())
+}
+
+// The comparison inside is constant-time
+fn matches(code: &str, stored: &str) -> Result<bool, String> {
+    let stored =
+        PasswordHash::new(stored).map_err(|_| "The saved passcode is invalid".to_string())?;
+    Ok(Argon2::default()
+        .verify_password(code.as_bytes(), &stored)
+        .is_ok())
+}
+
+// Wait after `failures` wrong passcodes in a row
+fn retry_delay(failures: u32) -> Duration {
+    if failures < FREE_ATTEMPTS {
+        Duration::ZERO
+    } else if failures < MAX_ATTEMPTS {
+        Duration::from_secs(1 << (failures - FREE_ATTEMPTS))
+    } else {
+        LOCKOUT
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hash_round_trip() {
+        let hash = hash("2468").unwrap();
+        assert!(hash.starts_with("$argon2id$"));
+        assert!(matches("2468", &hash).unwrap());
+    }
+
+    #[test]
+    fn wrong_code_is_rejected() {
+        let hash = hash("2468").unwrap();
+        assert!(!matches("1357", &hash).unwrap());
+        assert!(!matches("", &hash).unwrap());
+        assert!(matches("2468", "not a hash").is_err());
+    }
+
+    #[test]
+    fn same_code_hashes_differently() {
+        assert_ne!(hash("2468").unwrap(), hash("2468").unwrap());
+    }
+
+    #[test]
+    fn failures_count_up_until_the_right_code() {
+        let stored = hash("2468").unwrap();
+        let mut failures = Failures::default();
+        let mut now = 1_000_000;
+        for count in 1..=FREE_ATTEMPTS {
+            assert!(!attempt(&mut failures, "1357", &stored, now).unwrap());
+            assert_eq!(failures.count, count);
+        }
+        assert_eq!(failures.retry_at, now + 1000);
+
+        // Too soon: refused without being checked or counted, even when right
+        assert!(attempt(&mut failures, "2468", &stored, now + 999).is_err());
+        assert_eq!(failures.count, FREE_ATTEMPTS);
+
+        now += 1000;
+        assert!(!attempt(&mut failures, "1357", &stored, now).unwrap());
+        assert_eq!(failures.count, FREE_ATTEMPTS + 1);
+        assert_eq!(failures.retry_at, now + 2000);
+
+        now += 2000;
+        assert!(attempt(&mut failures, "2468", &stored, now).unwrap());
+        assert_eq!((failures.count, failures.retry_at), (0, 0));
+    }
+
+    #[test]
+    fn failures_survive_being_saved() {
+        let stored = hash("2468").unwrap();
+        let mut failures = Failures::default();
+        assert!(!attempt(&mut failures, "1357", &stored, 0).unwrap());
+        // As `check` saves and loads it between calls
+        let saved = serde_json::to_value(&failures).unwrap();
+        let mut failures: Failures = serde_json::from_value(saved).unwrap();
+        assert_eq!(failures.count, 1);
+        assert!(!attempt(&mut failures, "1357", &stored, 0).unwrap());
+        assert_eq!(failures.count, 2);
+    }
+
+    #[test]
+    fn backoff_schedule() {
+        for failures in 0..FREE_ATTEMPTS {
+            assert_eq!(retry_delay(failures), Duration::ZERO);
+        }
+        assert_eq!(retry_delay(5), Duration::from_secs(1));
+        assert_eq!(retry_delay(6), Duration::from_secs(2));
+        assert_eq!(retry_delay(9), Duration::from_secs(16));
+        assert_eq!(retry_delay(10), LOCKOUT);
+        assert_eq!(retry_delay(11), LOCKOUT);
+        assert_eq!(retry_delay(u32::MAX), LOCKOUT);
+    }
+}
@@ -0,0 +1,278 @@
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use serde::Serialize;
+use tauri::{AppHandle, Emitter};
+
+use crate::{lock, notifications};
+
+/// How often the resume detector wakes up
+const TICK: Duration = Duration::from_secs(5);
+
+/// Wall-clock time beyond a tick that means the system was asleep rather than busy
+const SLEEP_THRESHOLD: Duration = Duration::from_secs(30);
+
+/// How often Windows is asked whether its screensaver is running
+#[cfg(windows)]
+const SCREENSAVER_POLL: Duration = Duration::from_secs(2);
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SystemResume {
+    slept_secs: u64,
+}
+
+#[derive(Clone, Copy, Serialize)]
+#[serde(rename_all = "camelCase")]
+enum SuspendReason {
+    Sleep,
+    Screensaver,
+    /// The session was locked
+    #[cfg(target_os = "macos")]
+    ScreenLocked,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SystemSuspend {
+    reason: SuspendReason,
+}
+
+/// Emit `system-resume` when the computer wakes from sleep, and batch the notifications
+/// that follow so missed messages arrive as one summary.
+///
+/// Sleep shows up as a jump in wall-clock time between ticks, since threads don't run while
+/// the system is suspended. This works the same on every platform without power event hooks.
+pub fn watch_resume(app: &AppHandle) {
+    let app = app.clone();
+    thread::spawn(move || {
+        let mut last = SystemTime::now();
+        loop {
+            thread::sleep(TICK);
+            let now = SystemTime::now();
+            let elapsed = now.duration_since(last).unwrap_or_default();
+            last = now;
+
+            if elapsed > TICK + SLEEP_THRESHOLD {
+                // In case the system slept without saying so first
+                lock::lock_for_suspend(&app);
+                notifications::start_batch(&app);
+                let slept_secs = (elapsed - TICK).as_secs();
+                let _ = app.emit("system-resume", SystemResume { slept_secs });
+            }
+        }
+    });
+}
+
+/// Emit `system-suspend` as the computer goes to sleep or its screensaver starts (and on
+/// macOS when the screen locks), locking the app first if it's set to lock on sleep.
+/// Windows and Linux (through logind) wait for this before suspending. The screensaver is
+/// reported on Linux desktops implementing the ScreenSaver D-Bus interface.
+pub fn watch_suspend(app: &AppHandle) {
+    watch_native(app);
+}
+
+fn suspending(app: &AppHandle, reason: SuspendReason) {
+    lock::lock_for_suspend(app);
+    let _ = app.emit("system-suspend", SystemSuspend { reason });
+}
+
+#[cfg(target_os = "macos")]
+fn watch_native(app: &AppHandle) {
+    use std::ptr::NonNull;
+
+    use block2::RcBlock;
+    use objc2_app_kit::{
+        NSWorkspace, NSWorkspaceScreensDidSleepNotification, NSWorkspaceWillSleepNotification,
+    };
+    use objc2_foundation::{
+        NSDistributedNotificationCenter, NSNotification, NSNotificationCenter, NSString,
+    };
+
+    let observe = |center: &NSNotificationCenter, name: &NSString, reason: SuspendReason| {
+        let app = app.clone();
+        let block = RcBlock::new(move |_: NonNull<NSNotification>| suspending(&app, reason));
+        let observer = unsafe {
+            center.addObserverForName_object_queue_usingBlock(Some(name), None, None, &block)
+        };
+        // Observers stay registered for the life of the app
+        std::mem::forget(observer);
+    };
+
+    let workspace = NSWorkspace::sharedWorkspace().notificationCenter();
+    observe(
+        &workspace,
+        unsafe { NSWorkspaceWillSleepNotification },
+        SuspendReason::Sleep,
+    );
+    // The display going to sleep is as good as the screensaver starting
+    observe(
+        &workspace,
+        unsafe { NSWorkspaceScreensDidSleepNotification },
+        SuspendReason::Screensaver,
+    );
+    let distributed = NSDistributedNotificationCenter::defaultCenter();
+    observe(
+        &distributed,
+        &NSString::from_str("com.apple.screensaver.didstart"),
+        SuspendReason::Screensaver,
+    );
+    observe(
+        &distributed,
+        &NSString::from_str("com.apple.screenIsLocked"),
+        SuspendReason::ScreenLocked,
+    );
+}
+
+#[cfg(windows)]
+fn watch_native(app: &AppHandle) {
+    use std::ffi::c_void;
+
+    use windows_sys::Win32::System::Power::{
+        PowerRegisterSuspendResumeNotification, DEVICE_NOTIFY_SUBSCRIBE_PARAMETERS,
+    };
+    use windows_sys::Win32::UI::WindowsAndMessaging::{
+        SystemParametersInfoW, DEVICE_NOTIFY_CALLBACK, PBT_APMSUSPEND, SPI_GETSCREENSAVERRUNNING,
+    };
+
+    // Windows holds the suspend until this returns
+    unsafe extern "system" fn on_power(
+        context: *const c_void,
+        kind: u32,
+        _setting: *const c_void,
+    ) -> u32 {
+        if kind == PBT_APMSUSPEND {
+            let app = unsafe { &*(context as *const AppHandle) };
+            suspending(app, SuspendReason::Sleep);
+        }
+        0
+    }
+
+    // Both have to outlive the registration, which lasts as long as the app
+    let context = Box::into_raw(Box::new(app.clone()));
+    let parameters = Box::leak(Box::new(DEVICE_NOTIFY_SUBSCRIBE_PARAMETERS {
+        Callback: Some(on_power),
+        Context: context.cast(),
+    }));
+    let mut registration = std::ptr::null_mut();
+    let status = unsafe {
+        PowerRegisterSuspendResumeNotification(
+            DEVICE_NOTIFY_CALLBACK,
+            (parameters as *mut DEVICE_NOTIFY_SUBSCRIBE_PARAMETERS).cast(),
+            &mut registration,
+        )
+    };
+    if status != 0 {
+        log::warn!("Failed to watch for sleep: error {}", status);
+    }
+
+    // The screensaver only notifies windows of its own, so it's polled
+    let app = app.clone();
+    thread::spawn(move || {
+        let mut was_running = false;
+        loop {
+            thread::sleep(SCREENSAVER_POLL);
+            let mut running = 0;
+            unsafe {
+                SystemParametersInfoW(
+                    SPI_GETSCREENSAVERRUNNING,
+                    0,
+                    (&mut running as *mut i32).cast(),
+                    0,
+                )
+            };
+            if running != 0 && !was_running {
+                suspending(&app, SuspendReason::Screensaver);
+            }
+            was_running = running != 0;
+        }
+    });
+}
+
+#[cfg(target_os = "linux")]
+fn watch_native(app: &AppHandle) {
+    let sleep_app = app.clone();
+    thread::spawn(move || {
+        if let Err(e) = watch_logind(&sleep_app) {
+            log::info!("Not watching for sleep: {}", e);
+        }
+    });
+    let app = app.clone();
+    thread::spawn(move || {
+        if let Err(e) = watch_screensaver(&app) {
+            log::info!("Not watching for the screensaver: {}", e);
+        }
+    });
+}
+
+// logind only waits for apps holding a delay lock, so one is held until the app has locked
+#[cfg(target_os = "linux")]
+fn watch_logind(app: &AppHandle) -> zbus::Result<()> {
+    use zbus::blocking::{Connection, MessageIterator};
+    use zbus::message::Type;
+    use zbus::zvariant::OwnedFd;
+    use zbus::MatchRule;
+
+    let system = Connection::system()?;
+    let inhibit = || -> zbus::Result<OwnedFd> {
+        system
+            .call_method(
+                Some("org.freedesktop.login1"),
+                "/org/freedesktop/login1",
+                Some("org.freedesktop.login1.Manager"),
+                "Inhibit",
+                &("sleep", "Hazel", "Locking Hazel", "delay"),
+            )?
+            .body()
+            .deserialize()
+    };
+    let rule = MatchRule::builder()
+        .msg_type(Type::Signal)
+        .sender("org.freedesktop.login1")?
+        .interface("org.freedesktop.login1.Manager")?
+        .member("PrepareForSleep")?
+        .build();
+    let signals = MessageIterator::for_match_rule(rule, &system, None)?;
+
+    let mut inhibitor = inhibit().ok();
+    for message in signals {
+        let going_to_sleep: bool = message?.body().deserialize().unwrap_or(false);
+        if going_to_sleep {
+            suspending(app, SuspendReason::Sleep);
+            // Closing it lets the system go ahead
+            inhibitor = None;
+        } else if inhibitor.is_none() {
+            inhibitor = inhibit().ok();
+        }
+    }
+    drop(inhibitor);
+    Ok(())
+}
+
+// GNOME, KDE and others send ActiveChanged under their own ScreenSaver interface names
+#[cfg(target_os = "linux")]
+fn watch_screensaver(app: &AppHandle) -> zbus::Result<()> {
+    use zbus::blocking::{Connection, MessageIterator};
+    use zbus::message::Type;
+    use zbus::MatchRule;
+
+    let session = Connection::session()?;
+    let rule = MatchRule::builder()
+        .msg_type(Type::Signal)
+        .member("ActiveChanged")?
+        .build();
+    for message in MessageIterator::for_match_rule(rule, &session, None)? {
+        let message = message?;
+        let is_screensaver = message
+            .header()
+            .interface()
+            .is_some_and(|interface| interface.as_str().ends_with(".ScreenSaver"));
+        if is_screensaver && message.body().deserialize::<bool>().unwrap_or(false) {
+            suspending(app, SuspendReason::Screensaver);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(any(target_os = "macos", windows, target_os = "linux")))]
+fn watch_native(_app: &AppHandle) {}
@@ -0,0 +1,83 @@
+use qrcode::types::QrError;
+use qrcode::{Color, EcLevel, QrCode};
+use serde::Deserialize;
+use tauri::command;
+
+const DEFAULT_SIZE: u32 = 256;
+const MAX_SIZE: u32 = 2048;
+
+/// Blank border around the code, in modules, as required by the QR spec
+const QUIET_ZONE: u32 = 4;
+
+/// How much of the code can be damaged or covered and still scan
+#[derive(Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ErrorCorrection {
+    Low,
+    #[default]
+    Medium,
+    Quartile,
+    High,
+}
+
+impl From<ErrorCorrection> for EcLevel {
+    fn from(level: ErrorCorrection) -> Self {
+        match level {
+            ErrorCorrection::Low => EcLevel::L,
+            ErrorCorrection::Medium => EcLevel::M,
+            ErrorCorrection::Quartile => EcLevel::Q,
+            ErrorCorrection::High => EcLevel::H,
+        }
+    }
+}
+
+/// Encode `data` as a PNG QR code, e.g. for the mobile device-link modal.
+/// `size` is the target width in pixels (default 256, up to 2048); the image is rounded
+/// down to a whole number of pixels per module, but never below one.
+#[command]
+pub fn make_qr(
+    data: String,
+    size: Option<u32>,
+    error_correction: Option<ErrorCorrection>,
+) -> Result<Vec<u8>, String> {
+    let level = error_correction.unwrap_or_default();
+    let code =
+        QrCode::with_error_correction_level(data.as_bytes(), level.into()).map_err(
+            |e| match e {
+                QrError::DataTooLong => {
+                    "Too much data for a QR code at this error correction level".to_string()
+                }
+                e => e.to_string(),
+            },
+        )?;
+
+    let modules = code.width() as u32 + QUIET_ZONE * 2;
+    let size = size.unwrap_or(DEFAULT_SIZE).min(MAX_SIZE);
+    let scale = (size / modules).max(1);
+    let width = modules * scale;
+
+    // 8-bit grayscale, white background
+    let mut pixels = vec![255u8; (width * width) as usize];
+    let colors = code.to_colors();
+    for (i, color) in colors.iter().enumerate() {
+        if *color != Color::Dark {
+            continue;
+        }
+        let x = (i as u32 % code.width() as u32 + QUIET_ZONE) * scale;
+        let y = (i as u32 / code.width() as u32 + QUIET_ZONE) * scale;
+        for row in y..y + scale {
+            let start = (row * width + x) as usize;
+            pixels[start..start + scale as usize].fill(0);
+        }
+    }
+
+    let mut png = Vec::new();
+    let mut encoder = png::Encoder::new(&mut png, width, width);
+    encoder.set_color(png::ColorType::Grayscale);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder
+        .write_header()
+        .and_then(|mut writer| writer.write_image_data(&pixels))
+        .map_err(|e| e.to_string())?;
+    Ok(png)
+}
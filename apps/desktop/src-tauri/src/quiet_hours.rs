@@ -0,0 +1,170 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::sync::{Mutex, OnceLock};
+use std::thread;
+use std::time::Duration;
+
+use chrono::{DateTime, Datelike, Local, NaiveTime, Timelike};
+use serde::{Deserialize, Serialize};
+use tauri::{command, AppHandle, Emitter};
+
+use crate::{settings, timezone};
+
+const QUIET_HOURS_KEY: &str = "notifications.quiet_hours";
+
+/// Whether notifications are being held back by the schedule right now
+static ACTIVE: AtomicBool = AtomicBool::new(false);
+
+// Dropping the sender stops the timer
+fn timer() -> &'static Mutex<Option<Sender<()>>> {
+    static TIMER: OnceLock<Mutex<Option<Sender<()>>>> = OnceLock::new();
+    TIMER.get_or_init(|| Mutex::new(None))
+}
+
+/// A daily window, in local time, when notifications are muted
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QuietHours {
+    /// `HH:MM`, 24-hour
+    start: String,
+    /// `HH:MM`, 24-hour; earlier than `start` for windows that run past midnight
+    end: String,
+    /// Days the window starts on, 0 for Sunday to 6 for Saturday
+    days: Vec<u8>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QuietHoursStatus {
+    schedule: Option<QuietHours>,
+    active: bool,
+}
+
+/// Mute notifications from `start` to `end` (`HH:MM`, local time) on each of `days`
+/// (0 for Sunday to 6 for Saturday), emitting `quiet-hours-started` and
+/// `quiet-hours-ended` as the window opens and closes. A window that ends earlier than it
+/// starts runs past midnight, into the next day. It follows the OS time zone as it is, so
+/// daylight saving and time zone changes take effect within a minute. Replaces the previous
+/// schedule; kept across launches.
+#[command]
+pub fn set_quiet_hours(
+    app: AppHandle,
+    start: String,
+    end: String,
+    days: Vec<u8>,
+) -> Result<QuietHoursStatus, String> {
+    let (start_time, end_time) = (parse_time(&start)?, parse_time(&end)?);
+    if start_time == end_time {
+        return Err("Quiet hours must end at a different time than they start".to_string());
+    }
+    let mut days = days;
+    days.sort_unstable();
+    days.dedup();
+    if days.is_empty() {
+        return Err("Quiet hours need at least one day".to_string());
+    }
+    if let Some(day) = days.iter().find(|&&day| day > 6) {
+        return Err(format!(
+            "{} is not a day; use 0 for Sunday to 6 for Saturday",
+            day
+        ));
+    }
+
+    let schedule = QuietHours { start, end, days };
+    settings::set(&app, QUIET_HOURS_KEY, &schedule)?;
+    start_timer(&app, schedule);
+    Ok(quiet_hours(app))
+}
+
+/// Turn quiet hours off, ending them now if they're on
+#[command]
+pub fn clear_quiet_hours(app: AppHandle) -> Result<(), String> {
+    timer().lock().unwrap().take();
+    settings::delete(&app, QUIET_HOURS_KEY)?;
+    set_active(&app, false);
+    Ok(())
+}
+
+#[command]
+pub fn quiet_hours(app: AppHandle) -> QuietHoursStatus {
+    QuietHoursStatus {
+        schedule: settings::get(&app, QUIET_HOURS_KEY),
+        active: is_active(),
+    }
+}
+
+/// Whether notifications should be held back for quiet hours
+pub fn is_active() -> bool {
+    ACTIVE.load(Ordering::SeqCst)
+}
+
+/// Start following the schedule set before the app last quit
+pub fn restore_quiet_hours(app: &AppHandle) {
+    if let Some(schedule) = settings::get::<QuietHours>(app, QUIET_HOURS_KEY) {
+        start_timer(app, schedule);
+    }
+}
+
+fn start_timer(app: &AppHandle, schedule: QuietHours) {
+    let (stop_tx, stop_rx) = mpsc::channel::<()>();
+    // Dropping the previous sender stops the previous timer
+    *timer().lock().unwrap() = Some(stop_tx);
+
+    set_active(app, within(&schedule, timezone::now()));
+    let app = app.clone();
+    thread::spawn(move || loop {
+        // Windows start and end on the minute, so checking just after each one is enough,
+        // and picks up clock changes within a minute
+        let now = timezone::now();
+        let into_minute =
+            now.second() as u64 * 1000 + (now.nanosecond() / 1_000_000).min(999) as u64;
+        match stop_rx.recv_timeout(Duration::from_millis(60_050 - into_minute)) {
+            Err(RecvTimeoutError::Timeout) => {}
+            Ok(()) | Err(RecvTimeoutError::Disconnected) => return,
+        }
+        set_active(&app, within(&schedule, timezone::now()));
+    });
+}
+
+fn set_active(app: &AppHandle, active: bool) {
+    if ACTIVE.swap(active, Ordering::SeqCst) != active {
+        let event = if active {
+            "quiet-hours-started"
+        } else {
+            "quiet-hours-ended"
+        };
+        let _ = app.emit(event, ());
+    }
+}
+
+// Whether `now` is inside a window that opened today or yesterday. Windows are turned
+// into instants, so an hour that repeats when clocks go back doesn't mute twice, and one
+// that starts in an hour that's skipped starts when the clocks skip to
+fn within(schedule: &QuietHours, now: DateTime<Local>) -> bool {
+    let (Ok(start), Ok(end)) = (parse_time(&schedule.start), parse_time(&schedule.end)) else {
+        return false;
+    };
+    let today = now.date_naive();
+    [today.pred_opt(), Some(today)]
+        .into_iter()
+        .flatten()
+        .filter(|date| {
+            let day = date.weekday().num_days_from_sunday() as u8;
+            schedule.days.contains(&day)
+        })
+        .any(|date| {
+            let end_date = if start < end {
+                Some(date)
+            } else {
+                date.succ_opt()
+            };
+            end_date.is_some_and(|end_date| {
+                timezone::at(date, start) <= now && now < timezone::at(end_date, end)
+            })
+        })
+}
+
+fn parse_time(time: &str) -> Result<NaiveTime, String> {
+    NaiveTime::parse_from_str(time.trim(), "%H:%M")
+        .map_err(|_| format!("\"{}\" is not a time like 22:30", time))
+}
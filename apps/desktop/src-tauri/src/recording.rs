@@ -0,0 +1,419 @@
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use cpal::{Device, FromSample, SampleFormat, SizedSample, Stream, StreamConfig};
+use hound::{WavSpec, WavWriter};
+use serde::Serialize;
+use tauri::{command, AppHandle, Emitter};
+
+use crate::cache;
+
+/// Recordings stop by themselves after this long
+const MAX_DURATION: Duration = Duration::from_secs(10 * 60);
+
+/// Mic tests close the microphone by themselves after this long
+const MIC_TEST_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Level events are emitted this many times a second
+const LEVELS_PER_SECOND: u32 = 20;
+
+enum Control {
+    Stop,
+    Cancel,
+}
+
+struct Recording {
+    /// Lets a recording that failed to start tell whether it's still the current one
+    id: u64,
+    control: Sender<Control>,
+    done: Receiver<Result<PathBuf, String>>,
+}
+
+// The recording in progress. cpal streams aren't Send, so each recording runs on
+// its own thread and is steered through `control`.
+fn recording() -> &'static Mutex<Option<Recording>> {
+    static RECORDING: OnceLock<Mutex<Option<Recording>>> = OnceLock::new();
+    RECORDING.get_or_init(|| Mutex::new(None))
+}
+
+struct MicTest {
+    /// Lets a test that timed out tell whether it's still the current one
+    id: u64,
+    /// Dropping this ends the test
+    _stop: Sender<()>,
+}
+
+fn mic_test() -> &'static Mutex<Option<MicTest>> {
+    static MIC_TEST: OnceLock<Mutex<Option<MicTest>>> = OnceLock::new();
+    MIC_TEST.get_or_init(|| Mutex::new(None))
+}
+
+static NEXT_RECORDING: AtomicU64 = AtomicU64::new(0);
+static NEXT_MIC_TEST: AtomicU64 = AtomicU64::new(0);
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Level {
+    /// From 0 to 1
+    level: f32,
+}
+
+#[derive(Clone, Copy)]
+enum Meter {
+    /// `recording-level` with the peak amplitude, which suits a level bar
+    Peak,
+    /// `mic-level` with the RMS amplitude, which tracks perceived loudness
+    Rms,
+}
+
+// Mono WAV output plus the state behind the level meter, fed from the audio callback
+struct Sink {
+    app: AppHandle,
+    writer: Option<WavWriter<BufWriter<File>>>,
+    error: Option<String>,
+    meter: Meter,
+    peak: u16,
+    sum_of_squares: f64,
+    samples: u32,
+    samples_per_level: u32,
+}
+
+impl Sink {
+    fn push(&mut self, sample: i16) {
+        if let Some(writer) = &mut self.writer {
+            if let Err(e) = writer.write_sample(sample) {
+                self.error = Some(e.to_string());
+                self.writer = None;
+            }
+        }
+
+        self.peak = self.peak.max(sample.unsigned_abs());
+        self.sum_of_squares += (sample as f64).powi(2);
+        self.samples += 1;
+        if self.samples >= self.samples_per_level {
+            let (event, level) = match self.meter {
+                Meter::Peak => ("recording-level", self.peak as f64),
+                Meter::Rms => (
+                    "mic-level",
+                    (self.sum_of_squares / self.samples as f64).sqrt(),
+                ),
+            };
+            let level = (level / i16::MAX as f64).min(1.0) as f32;
+            let _ = self.app.emit(event, Level { level });
+            self.peak = 0;
+            self.sum_of_squares = 0.0;
+            self.samples = 0;
+        }
+    }
+}
+
+/// Start recording a voice message from the default microphone, emitting
+/// `recording-level` for a live meter. Recording stops by itself after 10 minutes,
+/// emitting `recording-limit-reached`; `stop_recording` then still returns the file.
+/// The first recording asks for microphone access on macOS, and fails if it's denied.
+#[command]
+pub async fn start_recording(app: AppHandle) -> Result<(), String> {
+    let (id, started_rx) = {
+        let mut current = recording().lock().unwrap();
+        if current.is_some() {
+            return Err("Already recording".to_string());
+        }
+
+        let dir = cache::dir(&app, "attachments")?.join("voice");
+        fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+        let millis = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_millis())
+            .unwrap_or_default();
+        let path = dir.join(format!("recording-{}.wav", millis));
+
+        let id = NEXT_RECORDING.fetch_add(1, Ordering::Relaxed);
+        let (control_tx, control_rx) = mpsc::channel();
+        let (started_tx, started_rx) = mpsc::channel();
+        let (done_tx, done_rx) = mpsc::channel();
+        thread::spawn(move || {
+            let (stream, sink) = match open_stream(&app, Some(&path), Meter::Peak) {
+                Ok(opened) => {
+                    let _ = started_tx.send(Ok(()));
+                    opened
+                }
+                Err(e) => {
+                    let _ = fs::remove_file(&path);
+                    let _ = started_tx.send(Err(e));
+                    return;
+                }
+            };
+
+            let keep = match control_rx.recv_timeout(MAX_DURATION) {
+                Ok(Control::Stop) => true,
+                Err(RecvTimeoutError::Timeout) => {
+                    let _ = app.emit("recording-limit-reached", ());
+                    true
+                }
+                Ok(Control::Cancel) | Err(RecvTimeoutError::Disconnected) => false,
+            };
+            drop(stream);
+
+            let mut sink = sink.lock().unwrap();
+            let finished = match (sink.writer.take(), sink.error.take()) {
+                (_, Some(e)) => Err(e),
+                (Some(writer), None) => writer.finalize().map_err(|e| e.to_string()),
+                (None, None) => Err("Recording was lost".to_string()),
+            };
+            let result = match finished {
+                Ok(()) if keep => Ok(path),
+                Ok(()) => {
+                    let _ = fs::remove_file(&path);
+                    Err("Recording was cancelled".to_string())
+                }
+                Err(e) => {
+                    let _ = fs::remove_file(&path);
+                    Err(format!("Failed to save the recording: {}", e))
+                }
+            };
+            let _ = done_tx.send(result);
+        });
+
+        // Taken before the microphone opens, which can wait on the access prompt, so a second
+        // start is refused meanwhile and a stop is picked up once it's open
+        *current = Some(Recording {
+            id,
+            control: control_tx,
+            done: done_rx,
+        });
+        (id, started_rx)
+    };
+
+    let started = wait_until_started(started_rx, "The recording thread stopped").await;
+    if started.is_err() {
+        let mut current = recording().lock().unwrap();
+        if current.as_ref().is_some_and(|recording| recording.id == id) {
+            *current = None;
+        }
+    }
+    started
+}
+
+/// Open the default microphone and emit `mic-level` about 20 times a second, without
+/// recording anything. The test ends by itself after a minute, emitting
+/// `mic-test-stopped`. Like recording, this needs microphone access.
+#[command]
+pub async fn start_mic_test(app: AppHandle) -> Result<(), String> {
+    let (id, started_rx) = {
+        let mut current = mic_test().lock().unwrap();
+        if current.is_some() {
+            return Err("The mic test is already running".to_string());
+        }
+
+        let id = NEXT_MIC_TEST.fetch_add(1, Ordering::Relaxed);
+        let (stop_tx, stop_rx) = mpsc::channel::<()>();
+        let (started_tx, started_rx) = mpsc::channel();
+        thread::spawn(move || {
+            let stream = match open_stream(&app, None, Meter::Rms) {
+                Ok((stream, _)) => {
+                    let _ = started_tx.send(Ok(()));
+                    stream
+                }
+                Err(e) => {
+                    let _ = started_tx.send(Err(e));
+                    return;
+                }
+            };
+            if let Err(RecvTimeoutError::Timeout) = stop_rx.recv_timeout(MIC_TEST_TIMEOUT) {
+                let mut current = mic_test().lock().unwrap();
+                if current.as_ref().is_some_and(|test| test.id == id) {
+                    *current = None;
+                }
+            }
+            drop(stream);
+            let _ = app.emit("mic-test-stopped", ());
+        });
+
+        *current = Some(MicTest { id, _stop: stop_tx });
+        (id, started_rx)
+    };
+
+    let started = wait_until_started(started_rx, "The mic test thread stopped").await;
+    if started.is_err() {
+        let mut current = mic_test().lock().unwrap();
+        if current.as_ref().is_some_and(|test| test.id == id) {
+            *current = None;
+        }
+    }
+    started
+}
+
+// Off the main thread, since opening the microphone can wait on the access prompt
+async fn wait_until_started(
+    started: Receiver<Result<(), String>>,
+    stopped: &'static str,
+) -> Result<(), String> {
+    tauri::async_runtime::spawn_blocking(move || started.recv().map_err(|_| stopped.to_string())?)
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+/// End the mic test. Returns false if none was running.
+#[command]
+pub fn stop_mic_test() -> bool {
+    mic_test().lock().unwrap().take().is_some()
+}
+
+/// Stop recording and return the WAV file, e.g. to pass to `transcode_audio`
+#[command]
+pub async fn stop_recording() -> Result<PathBuf, String> {
+    finish(Control::Stop).await
+}
+
+/// Stop recording and delete what was recorded
+#[command]
+pub async fn cancel_recording() -> Result<(), String> {
+    match finish(Control::Cancel).await {
+        // Cancelling after the time limit stopped the recording still drops the file
+        Ok(path) => fs::remove_file(path).map_err(|e| e.to_string()),
+        Err(_) => Ok(()),
+    }
+}
+
+async fn finish(control: Control) -> Result<PathBuf, String> {
+    let Some(recording) = recording().lock().unwrap().take() else {
+        return Err("Not recording".to_string());
+    };
+    // Fails if the time limit already stopped it, which leaves the result waiting
+    let _ = recording.control.send(control);
+    tauri::async_runtime::spawn_blocking(move || {
+        recording
+            .done
+            .recv()
+            .map_err(|_| "The recording thread stopped".to_string())?
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+// Start capturing from the default microphone, writing to `path` if given
+fn open_stream(
+    app: &AppHandle,
+    path: Option<&Path>,
+    meter: Meter,
+) -> Result<(Stream, Arc<Mutex<Sink>>), String> {
+    check_access()?;
+    let device = cpal::default_host()
+        .default_input_device()
+        .ok_or_else(|| "No microphone found".to_string())?;
+    let supported = device
+        .default_input_config()
+        .map_err(|e| format!("Can't use the microphone: {}", e))?;
+    let format = supported.sample_format();
+    let config: StreamConfig = supported.into();
+
+    let spec = WavSpec {
+        channels: 1,
+        sample_rate: config.sample_rate.0,
+        bits_per_sample: 16,
+        sample_format: hound::SampleFormat::Int,
+    };
+    let writer = match path {
+        Some(path) => Some(WavWriter::create(path, spec).map_err(|e| e.to_string())?),
+        None => None,
+    };
+    let sink = Arc::new(Mutex::new(Sink {
+        app: app.clone(),
+        writer,
+        error: None,
+        meter,
+        peak: 0,
+        sum_of_squares: 0.0,
+        samples: 0,
+        samples_per_level: (config.sample_rate.0 / LEVELS_PER_SECOND).max(1),
+    }));
+
+    let stream = match format {
+        SampleFormat::F32 => build_stream::<f32>(&device, &config, sink.clone()),
+        SampleFormat::I16 => build_stream::<i16>(&device, &config, sink.clone()),
+        SampleFormat::U16 => build_stream::<u16>(&device, &config, sink.clone()),
+        SampleFormat::I32 => build_stream::<i32>(&device, &config, sink.clone()),
+        format => return Err(format!("Unsupported microphone format {}", format)),
+    }?;
+    stream
+        .play()
+        .map_err(|e| format!("Can't use the microphone: {}", e))?;
+    Ok((stream, sink))
+}
+
+// Mix each frame down to mono and hand it to the sink
+fn build_stream<T>(
+    device: &Device,
+    config: &StreamConfig,
+    sink: Arc<Mutex<Sink>>,
+) -> Result<Stream, String>
+where
+    T: SizedSample,
+    i16: FromSample<T>,
+{
+    let channels = config.channels.max(1) as usize;
+    device
+        .build_input_stream(
+            config,
+            move |data: &[T], _| {
+                let mut sink = sink.lock().unwrap();
+                for frame in data.chunks(channels) {
+                    let sum: i32 = frame
+                        .iter()
+                        .map(|&sample| sample.to_sample::<i16>() as i32)
+                        .sum();
+                    sink.push((sum / frame.len() as i32) as i16);
+                }
+            },
+            |e| log::warn!("Microphone stream error: {}", e),
+            None,
+        )
+        .map_err(|e| format!("Can't use the microphone: {}", e))
+}
+
+// Ask for microphone access the first time; until it's granted macOS only hands out silence
+#[cfg(target_os = "macos")]
+fn check_access() -> Result<(), String> {
+    use block2::RcBlock;
+    use objc2::runtime::Bool;
+    use objc2_av_foundation::{AVAuthorizationStatus, AVCaptureDevice, AVMediaTypeAudio};
+
+    let Some(audio) = (unsafe { AVMediaTypeAudio }) else {
+        return Ok(());
+    };
+    let granted = match unsafe { AVCaptureDevice::authorizationStatusForMediaType(audio) } {
+        AVAuthorizationStatus::Authorized => true,
+        AVAuthorizationStatus::NotDetermined => {
+            let (tx, rx) = mpsc::channel();
+            let handler = RcBlock::new(move |granted: Bool| {
+                let _ = tx.send(granted.as_bool());
+            });
+            unsafe {
+                AVCaptureDevice::requestAccessForMediaType_completionHandler(audio, &handler)
+            };
+            rx.recv().unwrap_or(false)
+        }
+        _ => false,
+    };
+    if !granted {
+        return Err(
+            "Microphone access is denied. Allow Hazel under System Settings > \
+            Privacy & Security > Microphone."
+                .to_string(),
+        );
+    }
+    Ok(())
+}
+
+// Windows reports blocked access when the stream is opened, and Linux has no such setting
+#[cfg(not(target_os = "macos"))]
+fn check_access() -> Result<(), String> {
+    Ok(())
+}
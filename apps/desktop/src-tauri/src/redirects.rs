@@ -0,0 +1,88 @@
+use std::time::Duration;
+
+use reqwest::header::LOCATION;
+use reqwest::redirect::Policy;
+use serde::Serialize;
+use tauri::{command, AppHandle};
+
+use crate::http;
+
+/// Most redirects that are followed, whatever's asked for
+const MAX_HOPS: u32 = 20;
+
+/// How long each redirect may take to answer
+const HOP_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Hop {
+    url: String,
+    /// The status it answered with, a redirect for every hop but the last
+    status: u16,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Redirects {
+    /// Where the link leads, or the last URL reached if not all redirects were followed
+    final_url: String,
+    /// Every URL visited, starting with the link itself
+    chain: Vec<Hop>,
+    /// Whether more redirects were left when `max_hops` ran out
+    truncated: bool,
+}
+
+/// Follow a link's redirects, at most `max_hops` of them (capped at 20), to show where a
+/// short link leads before it's opened. Only status codes and `Location` headers are
+/// looked at, so nothing the pages contain is run. Fails if a hop points at this machine
+/// or the local network, or takes more than 10 seconds.
+#[command]
+pub async fn resolve_redirects(
+    app: AppHandle,
+    url: String,
+    max_hops: u32,
+) -> Result<Redirects, String> {
+    let client = http::client_builder(&app)
+        .redirect(Policy::none())
+        .timeout(HOP_TIMEOUT)
+        .build()
+        .map_err(|e| e.to_string())?;
+    let mut url = http::check_url(&app, &url, false).await?;
+    let max_hops = max_hops.min(MAX_HOPS) as usize;
+    let mut chain = Vec::new();
+    let truncated = loop {
+        // The body's never read
+        let response = client
+            .get(url.clone())
+            .send()
+            .await
+            .map_err(|e| format!("Failed to reach {}: {}", url, e))?;
+        let status = response.status();
+        chain.push(Hop {
+            url: url.to_string(),
+            status: status.as_u16(),
+        });
+
+        let Some(location) = response
+            .headers()
+            .get(LOCATION)
+            .and_then(|value| value.to_str().ok())
+            .filter(|_| status.is_redirection())
+        else {
+            break false;
+        };
+        if chain.len() > max_hops {
+            break true;
+        }
+        let next = url
+            .join(location)
+            .map_err(|e| format!("{} redirects to an invalid URL: {}", url, e))?;
+        url = http::check_url(&app, next.as_str(), false).await?;
+    };
+
+    Ok(Redirects {
+        final_url: url.to_string(),
+        chain,
+        truncated,
+    })
+}
@@ -0,0 +1,348 @@
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+
+use serde::{Deserialize, Serialize};
+use tantivy::collector::TopDocs;
+use tantivy::directory::MmapDirectory;
+use tantivy::query::QueryParser;
+use tantivy::schema::{Field, Schema, Value, STORED, STRING, TEXT};
+use tantivy::snippet::SnippetGenerator;
+use tantivy::{doc, Index, IndexReader, IndexWriter, ReloadPolicy, TantivyDocument, Term};
+use tauri::{command, AppHandle, Emitter};
+
+use crate::{data_dir, encryption};
+
+/// Folder in the data directory holding the index
+const INDEX_DIR: &str = "search";
+
+/// Memory the writer buffers documents in before flushing them to disk
+const WRITER_MEMORY: usize = 15_000_000;
+
+/// Most hits one search returns
+const MAX_LIMIT: usize = 200;
+
+/// Longest snippet of a message shown with a hit, in characters
+const SNIPPET_CHARS: usize = 160;
+
+// Opened on first use, once the data directory is known
+fn index() -> &'static Mutex<Option<Arc<MessageIndex>>> {
+    static INDEX: OnceLock<Mutex<Option<Arc<MessageIndex>>>> = OnceLock::new();
+    INDEX.get_or_init(|| Mutex::new(None))
+}
+
+struct MessageIndex {
+    dir: PathBuf,
+    index: Index,
+    reader: IndexReader,
+    writer: Mutex<IndexWriter>,
+    id: Field,
+    channel_id: Field,
+    text: Field,
+    timestamp: Field,
+}
+
+/// A message to add to the search index
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IndexedMessage {
+    id: String,
+    channel_id: String,
+    text: String,
+    /// Milliseconds since the epoch
+    timestamp: i64,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchHit {
+    id: String,
+    channel_id: String,
+    timestamp: i64,
+    score: f32,
+    /// The part of the message that best matches
+    snippet: String,
+    /// Matched words in `snippet`, as UTF-16 offsets for slicing JavaScript strings
+    highlights: Vec<Range<usize>>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchIndexStats {
+    documents: u64,
+    segments: usize,
+    /// On disk
+    size_bytes: u64,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SearchIndexProgress {
+    /// `compact` or `clear`
+    operation: &'static str,
+    /// `merging`, `clearing`, `cleaningUp` or `finished`
+    stage: &'static str,
+}
+
+/// Add messages to the offline search index, replacing any already indexed with the same
+/// id, so edited messages can be indexed again. The index lives in the data directory.
+/// Its text can't be encrypted, so nothing is indexed while the cache is.
+#[command]
+pub async fn index_messages(app: AppHandle, batch: Vec<IndexedMessage>) -> Result<(), String> {
+    if encryption::is_enabled(&app) {
+        return Err("Messages aren't indexed while the cache is encrypted".to_string());
+    }
+    let index = open(&app)?;
+    tauri::async_runtime::spawn_blocking(move || {
+        let mut writer = index.writer.lock().unwrap();
+        for message in batch {
+            writer.delete_term(Term::from_field_text(index.id, &message.id));
+            writer
+                .add_document(doc!(
+                    index.id => message.id,
+                    index.channel_id => message.channel_id,
+                    index.text => message.text,
+                    index.timestamp => message.timestamp,
+                ))
+                .map_err(|e| e.to_string())?;
+        }
+        index.commit(&mut writer)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// Remove messages from the offline search index, e.g. once they're deleted
+#[command]
+pub async fn delete_indexed_messages(app: AppHandle, ids: Vec<String>) -> Result<(), String> {
+    tauri::async_runtime::spawn_blocking(move || remove_messages(&app, &ids))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+/// Remove messages from the search index; blocks, so run it off the main thread
+pub fn remove_messages(app: &AppHandle, ids: &[String]) -> Result<(), String> {
+    let index = open(app)?;
+    let mut writer = index.writer.lock().unwrap();
+    for id in ids {
+        writer.delete_term(Term::from_field_text(index.id, id));
+    }
+    index.commit(&mut writer)
+}
+
+/// Search indexed messages, best matches first, with a highlighted snippet of each. The
+/// query takes words, `"phrases"` and `-excluded` words; anything it can't make sense of
+/// is searched for as plain words.
+#[command]
+pub async fn search_messages(
+    app: AppHandle,
+    query: String,
+    limit: usize,
+) -> Result<Vec<SearchHit>, String> {
+    let index = open(&app)?;
+    let limit = limit.clamp(1, MAX_LIMIT);
+    tauri::async_runtime::spawn_blocking(move || {
+        let searcher = index.reader.searcher();
+        let parser = QueryParser::for_index(&index.index, vec![index.text]);
+        let (query, _) = parser.parse_query_lenient(&query);
+        let top = searcher
+            .search(&query, &TopDocs::with_limit(limit))
+            .map_err(|e| e.to_string())?;
+        let mut snippets =
+            SnippetGenerator::create(&searcher, &*query, index.text).map_err(|e| e.to_string())?;
+        snippets.set_max_num_chars(SNIPPET_CHARS);
+
+        top.into_iter()
+            .map(|(score, address)| {
+                let doc: TantivyDocument = searcher.doc(address).map_err(|e| e.to_string())?;
+                let text = |field| {
+                    doc.get_first(field)
+                        .and_then(|value| value.as_str())
+                        .unwrap_or_default()
+                        .to_string()
+                };
+                let snippet = snippets.snippet_from_doc(&doc);
+                let fragment = snippet.fragment();
+                Ok(SearchHit {
+                    id: text(index.id),
+                    channel_id: text(index.channel_id),
+                    timestamp: doc
+                        .get_first(index.timestamp)
+                        .and_then(|value| value.as_i64())
+                        .unwrap_or_default(),
+                    score,
+                    snippet: fragment.to_string(),
+                    highlights: snippet
+                        .highlighted()
+                        .iter()
+                        .map(|range| {
+                            utf16_offset(fragment, range.start)..utf16_offset(fragment, range.end)
+                        })
+                        .collect(),
+                })
+            })
+            .collect()
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// Merge the search index's segments into one and drop deleted messages for good, which
+/// keeps searches fast and the index small. Emits `search-index-progress` at each stage.
+#[command]
+pub async fn compact_search_index(app: AppHandle) -> Result<SearchIndexStats, String> {
+    let index = open(&app)?;
+    tauri::async_runtime::spawn_blocking(move || {
+        let progress = |stage| {
+            let operation = "compact";
+            let _ = app.emit(
+                "search-index-progress",
+                SearchIndexProgress { operation, stage },
+            );
+        };
+        let mut writer = index.writer.lock().unwrap();
+        let segments = index
+            .index
+            .searchable_segment_ids()
+            .map_err(|e| e.to_string())?;
+        progress("merging");
+        if segments.len() > 1 {
+            writer.merge(&segments).wait().map_err(|e| e.to_string())?;
+        }
+        progress("cleaningUp");
+        index.clean_up(&writer)?;
+        progress("finished");
+        index.stats()
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// Remove every message from the search index, e.g. on sign-out or to rebuild it.
+/// Emits `search-index-progress` at each stage.
+#[command]
+pub async fn clear_search_index(app: AppHandle) -> Result<(), String> {
+    let index = open(&app)?;
+    tauri::async_runtime::spawn_blocking(move || {
+        let progress = |stage| {
+            let operation = "clear";
+            let _ = app.emit(
+                "search-index-progress",
+                SearchIndexProgress { operation, stage },
+            );
+        };
+        progress("clearing");
+        index.clear()?;
+        progress("finished");
+        Ok(())
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// Remove every message from the search index, from outside the search commands
+pub fn clear(app: &AppHandle) -> Result<(), String> {
+    open(app)?.clear()
+}
+
+/// How many messages the search index holds, in how many segments, and its size on disk
+#[command]
+pub async fn search_index_stats(app: AppHandle) -> Result<SearchIndexStats, String> {
+    let index = open(&app)?;
+    tauri::async_runtime::spawn_blocking(move || index.stats())
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+impl MessageIndex {
+    fn clear(&self) -> Result<(), String> {
+        let mut writer = self.writer.lock().unwrap();
+        writer.delete_all_documents().map_err(|e| e.to_string())?;
+        self.commit(&mut writer)?;
+        self.clean_up(&writer)
+    }
+
+    // Deletes the files of segments that were merged away or emptied
+    fn clean_up(&self, writer: &IndexWriter) -> Result<(), String> {
+        writer
+            .garbage_collect_files()
+            .wait()
+            .map_err(|e| e.to_string())?;
+        self.reader.reload().map_err(|e| e.to_string())
+    }
+
+    fn stats(&self) -> Result<SearchIndexStats, String> {
+        let segments = self
+            .index
+            .searchable_segment_ids()
+            .map_err(|e| e.to_string())?;
+        let size_bytes = std::fs::read_dir(&self.dir)
+            .map_err(|e| e.to_string())?
+            .flatten()
+            .filter_map(|entry| entry.metadata().ok())
+            .filter(|metadata| metadata.is_file())
+            .map(|metadata| metadata.len())
+            .sum();
+        Ok(SearchIndexStats {
+            documents: self.reader.searcher().num_docs(),
+            segments: segments.len(),
+            size_bytes,
+        })
+    }
+
+    // Makes the changes visible to searches straight away
+    fn commit(&self, writer: &mut IndexWriter) -> Result<(), String> {
+        writer.commit().map_err(|e| e.to_string())?;
+        self.reader.reload().map_err(|e| e.to_string())
+    }
+}
+
+fn open(app: &AppHandle) -> Result<Arc<MessageIndex>, String> {
+    let mut index = index().lock().unwrap();
+    if let Some(index) = index.as_ref() {
+        return Ok(index.clone());
+    }
+    let dir = data_dir::data_dir(app)?.join(INDEX_DIR);
+    let opened = Arc::new(open_at(&dir).or_else(|e| {
+        // The index only mirrors messages the frontend has, so it can be rebuilt
+        log::warn!("Recreating the search index: {}", e);
+        let _ = std::fs::remove_dir_all(&dir);
+        open_at(&dir)
+    })?);
+    *index = Some(opened.clone());
+    Ok(opened)
+}
+
+fn open_at(dir: &Path) -> Result<MessageIndex, String> {
+    let mut schema = Schema::builder();
+    let id = schema.add_text_field("id", STRING | STORED);
+    let channel_id = schema.add_text_field("channel_id", STRING | STORED);
+    let text = schema.add_text_field("text", TEXT | STORED);
+    let timestamp = schema.add_i64_field("timestamp", STORED);
+
+    std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    let directory = MmapDirectory::open(dir).map_err(|e| e.to_string())?;
+    let index = Index::open_or_create(directory, schema.build()).map_err(|e| e.to_string())?;
+    let writer = index
+        .writer_with_num_threads(1, WRITER_MEMORY)
+        .map_err(|e| e.to_string())?;
+    let reader = index
+        .reader_builder()
+        .reload_policy(ReloadPolicy::Manual)
+        .try_into()
+        .map_err(|e| e.to_string())?;
+    Ok(MessageIndex {
+        dir: dir.to_path_buf(),
+        index,
+        reader,
+        writer: Mutex::new(writer),
+        id,
+        channel_id,
+        text,
+        timestamp,
+    })
+}
+
+fn utf16_offset(text: &str, byte_offset: usize) -> usize {
+    text[..byte_offset].encode_utf16().count()
+}
@@ -0,0 +1,81 @@
+use std::fs;
+use std::path::PathBuf;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use tauri::AppHandle;
+use tauri_plugin_store::StoreExt;
+
+/// Store file for settings owned by the native side.
+/// Kept separate from the frontend's `settings.json` so clearing one doesn't wipe the other.
+pub const STORE_FILE: &str = "desktop.json";
+
+/// Store file used by the web app's key-value storage
+pub const FRONTEND_STORE_FILE: &str = "settings.json";
+
+/// Read a setting, returning None if it's missing or has an unexpected shape
+pub fn get<T: DeserializeOwned>(app: &AppHandle, key: &str) -> Option<T> {
+    let store = app.store(STORE_FILE).ok()?;
+    let value = store.get(key)?;
+    serde_json::from_value(value).ok()
+}
+
+/// Read a setting straight from disk, before the app and its store plugin are running.
+/// Only for flags that must be applied before the webview is created.
+pub fn get_at_startup<T: DeserializeOwned>(identifier: &str, key: &str) -> Option<T> {
+    let contents = fs::read(path_at_startup(identifier)?).ok()?;
+    let mut values: serde_json::Map<String, serde_json::Value> =
+        serde_json::from_slice(&contents).ok()?;
+    serde_json::from_value(values.remove(key)?).ok()
+}
+
+/// Where the settings store is, before the app is running
+pub fn path_at_startup(identifier: &str) -> Option<PathBuf> {
+    // The store plugin resolves store files against the app data dir
+    Some(dirs::data_dir()?.join(identifier).join(STORE_FILE))
+}
+
+/// Write a setting and save the store to disk
+pub fn set<T: Serialize>(app: &AppHandle, key: &str, value: T) -> Result<(), String> {
+    let store = app.store(STORE_FILE).map_err(|e| e.to_string())?;
+    let value = serde_json::to_value(value).map_err(|e| e.to_string())?;
+    store.set(key, value);
+    store.save().map_err(|e| e.to_string())
+}
+
+/// Remove a setting and save the store to disk
+pub fn delete(app: &AppHandle, key: &str) -> Result<(), String> {
+    let store = app.store(STORE_FILE).map_err(|e| e.to_string())?;
+    store.delete(key);
+    store.save().map_err(|e| e.to_string())
+}
+
+/// Remove every setting from both stores, the frontend's included, and save them
+pub fn clear_all(app: &AppHandle) -> Result<(), String> {
+    for file in [STORE_FILE, FRONTEND_STORE_FILE] {
+        let store = app.store(file).map_err(|e| e.to_string())?;
+        store.clear();
+        store.save().map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+/// Every setting, e.g. for diagnostics
+pub fn entries(app: &AppHandle) -> Vec<(String, serde_json::Value)> {
+    match app.store(STORE_FILE) {
+        Ok(store) => store.entries(),
+        Err(_) => Vec::new(),
+    }
+}
+
+/// Save every loaded store to disk, e.g. before the process goes away
+pub fn flush(app: &AppHandle) {
+    for file in [STORE_FILE, FRONTEND_STORE_FILE] {
+        let Some(store) = app.get_store(file) else {
+            continue;
+        };
+        if let Err(e) = store.save() {
+            log::warn!("Failed to flush store {}: {}", file, e);
+        }
+    }
+}
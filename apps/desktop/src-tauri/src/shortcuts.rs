@@ -0,0 +1,286 @@
+use std::collections::BTreeMap;
+
+use muda::accelerator::Accelerator;
+use serde::Serialize;
+use tauri::menu::{MenuItem, Submenu};
+use tauri::{command, App, AppHandle, Emitter, Wry};
+
+use crate::{menu, settings, window};
+
+const CUSTOM_SHORTCUTS_KEY: &str = "shortcuts.custom";
+
+/// Id of the View submenu holding one item per custom shortcut
+const SUBMENU_ID: &str = "custom_shortcuts";
+
+/// Menu item ids for custom shortcuts are this prefix followed by the action id
+pub const ITEM_PREFIX: &str = "shortcut:";
+
+/// Built-in menu accelerators, keyed by menu item id
+pub const DEFAULTS: &[(&str, &str)] = &[
+    ("settings", "CmdOrCtrl+,"),
+    ("quit", "CmdOrCtrl+Q"),
+    ("new_channel", "CmdOrCtrl+Alt+N"),
+    ("invite", "CmdOrCtrl+Alt+I"),
+    ("find", "CmdOrCtrl+F"),
+    ("focus_composer", window::DEFAULT_FOCUS_COMPOSER_ACCELERATOR),
+];
+
+/// Accelerators of the predefined menu items and the one macOS adds, keyed by item label.
+/// They can't be changed, but nothing else can take them either.
+const PREDEFINED: &[(&str, &str)] = &[
+    #[cfg(target_os = "macos")]
+    ("Hide Hazel", "CmdOrCtrl+H"),
+    #[cfg(target_os = "macos")]
+    ("Undo", "CmdOrCtrl+Z"),
+    #[cfg(target_os = "macos")]
+    ("Redo", "CmdOrCtrl+Shift+Z"),
+    ("Cut", "CmdOrCtrl+X"),
+    ("Copy", "CmdOrCtrl+C"),
+    ("Paste", "CmdOrCtrl+V"),
+    ("Select All", "CmdOrCtrl+A"),
+    #[cfg(target_os = "macos")]
+    ("Emoji & Symbols", "Cmd+Ctrl+Space"),
+    #[cfg(target_os = "macos")]
+    ("Minimize", "CmdOrCtrl+M"),
+];
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ShortcutTriggered<'a> {
+    action_id: &'a str,
+}
+
+/// Bind an accelerator to a frontend action. Pressing it while Hazel is focused emits
+/// `shortcut-triggered` with the action id; registering an action again rebinds it.
+/// Fails if the accelerator is invalid or already used by a menu item or another action.
+#[command]
+pub fn register_shortcut(
+    app: AppHandle,
+    action_id: String,
+    accelerator: String,
+) -> Result<(), String> {
+    let item_id = format!("{}{}", ITEM_PREFIX, action_id);
+    check_conflicts(&app, &item_id, &accelerator)?;
+
+    let mut shortcuts = custom_shortcuts(&app);
+    sync_menu_item(&app, &action_id, Some(&accelerator))?;
+    shortcuts.insert(action_id, accelerator);
+    settings::set(&app, CUSTOM_SHORTCUTS_KEY, shortcuts)
+}
+
+/// Remove a custom shortcut. Returns false if the action had none.
+#[command]
+pub fn unregister_shortcut(app: AppHandle, action_id: String) -> Result<bool, String> {
+    let mut shortcuts = custom_shortcuts(&app);
+    if shortcuts.remove(&action_id).is_none() {
+        return Ok(false);
+    }
+    sync_menu_item(&app, &action_id, None)?;
+    settings::set(&app, CUSTOM_SHORTCUTS_KEY, shortcuts)?;
+    Ok(true)
+}
+
+/// The custom action→accelerator mapping, e.g. to save as a backup file
+#[command]
+pub fn export_shortcuts(app: AppHandle) -> BTreeMap<String, String> {
+    custom_shortcuts(&app)
+}
+
+/// Replace every custom shortcut with an exported mapping. Nothing is applied if any
+/// accelerator is invalid or conflicts with a menu item or another entry in the mapping;
+/// the error lists every problem found.
+#[command]
+pub fn import_shortcuts(app: AppHandle, shortcuts: BTreeMap<String, String>) -> Result<(), String> {
+    let mut problems = Vec::new();
+    let mut taken = builtin_shortcuts(&app)
+        .into_iter()
+        .filter_map(|(id, accelerator)| Some((accelerator.parse::<Accelerator>().ok()?, id)))
+        .collect::<Vec<_>>();
+    for (action_id, accelerator) in &shortcuts {
+        let parsed = match parse(accelerator) {
+            Ok(parsed) => parsed,
+            Err(e) => {
+                problems.push(e);
+                continue;
+            }
+        };
+        match taken.iter().find(|(existing, _)| *existing == parsed) {
+            Some((_, owner)) => problems.push(format!(
+                "\"{}\" for \"{}\" is already used by \"{}\"",
+                accelerator, action_id, owner
+            )),
+            None => taken.push((parsed, action_id.clone())),
+        }
+    }
+    if !problems.is_empty() {
+        return Err(problems.join("; "));
+    }
+
+    for action_id in custom_shortcuts(&app).keys() {
+        if !shortcuts.contains_key(action_id) {
+            sync_menu_item(&app, action_id, None)?;
+        }
+    }
+    for (action_id, accelerator) in &shortcuts {
+        sync_menu_item(&app, action_id, Some(accelerator))?;
+    }
+    settings::set(&app, CUSTOM_SHORTCUTS_KEY, shortcuts)
+}
+
+/// The built-in menu accelerators, keyed by menu item id
+#[command]
+pub fn default_shortcuts() -> BTreeMap<&'static str, &'static str> {
+    DEFAULTS.iter().copied().collect()
+}
+
+/// Remove every custom shortcut and restore the built-in menu accelerators, then emit
+/// `shortcuts-reset`
+#[command]
+pub fn reset_shortcuts(app: AppHandle) -> Result<(), String> {
+    for action_id in custom_shortcuts(&app).keys() {
+        sync_menu_item(&app, action_id, None)?;
+    }
+    settings::delete(&app, CUSTOM_SHORTCUTS_KEY)?;
+    window::set_focus_composer_shortcut(app.clone(), None)?;
+
+    for (id, accelerator) in DEFAULTS {
+        let item = menu::find_item(&app, id);
+        if let Some(item) = item.as_ref().and_then(|item| item.as_menuitem()) {
+            item.set_accelerator(Some(*accelerator))
+                .map_err(|e| e.to_string())?;
+        }
+    }
+
+    let _ = app.emit("shortcuts-reset", ());
+    Ok(())
+}
+
+/// Accelerator for a built-in menu item
+pub fn default_accelerator(id: &str) -> Option<&'static str> {
+    DEFAULTS
+        .iter()
+        .find(|(item, _)| *item == id)
+        .map(|(_, accelerator)| *accelerator)
+}
+
+/// Parse an accelerator, with an error message fit for the frontend
+fn parse(accelerator: &str) -> Result<Accelerator, String> {
+    accelerator
+        .parse()
+        .map_err(|e| format!("Invalid accelerator \"{}\": {}", accelerator, e))
+}
+
+/// Fail if `accelerator` is invalid or bound to any menu item other than `item_id`,
+/// predefined ones like Copy included
+pub fn check_conflicts(app: &AppHandle, item_id: &str, accelerator: &str) -> Result<(), String> {
+    let wanted = parse(accelerator)?;
+
+    let builtin = builtin_shortcuts(app)
+        .into_iter()
+        .map(|(id, accelerator)| (id.clone(), id, accelerator));
+    let custom = custom_shortcuts(app)
+        .into_iter()
+        .map(|(action_id, accelerator)| {
+            (
+                format!("{}{}", ITEM_PREFIX, action_id),
+                action_id,
+                accelerator,
+            )
+        });
+
+    for (id, name, accelerator) in builtin.chain(custom) {
+        if id != item_id && accelerator.parse::<Accelerator>().ok() == Some(wanted) {
+            return Err(format!(
+                "\"{}\" is already used by \"{}\"",
+                accelerator, name
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Build the submenu of custom shortcuts from the persisted mapping
+pub fn submenu(app: &App) -> tauri::Result<Submenu<Wry>> {
+    let shortcuts = custom_shortcuts(app.handle());
+    let submenu = Submenu::with_id(app, SUBMENU_ID, "Custom Shortcuts", !shortcuts.is_empty())?;
+    for (action_id, accelerator) in shortcuts {
+        let id = format!("{}{}", ITEM_PREFIX, action_id);
+        submenu.append(&MenuItem::with_id(
+            app,
+            id,
+            &action_id,
+            true,
+            Some(accelerator),
+        )?)?;
+    }
+    Ok(submenu)
+}
+
+/// Emit `shortcut-triggered` for a custom shortcut's menu item
+pub fn trigger(app: &AppHandle, action_id: &str) {
+    let _ = app.emit("shortcut-triggered", ShortcutTriggered { action_id });
+}
+
+// Current accelerators of the built-in menu items, keyed by menu item id, followed by
+// the predefined ones keyed by label
+fn builtin_shortcuts(app: &AppHandle) -> Vec<(String, String)> {
+    let predefined = PREDEFINED
+        .iter()
+        .map(|(label, accelerator)| (label.to_string(), accelerator.to_string()));
+    DEFAULTS
+        .iter()
+        .map(|(id, accelerator)| {
+            let accelerator = match *id {
+                "focus_composer" => window::focus_composer_accelerator(app),
+                _ => accelerator.to_string(),
+            };
+            (id.to_string(), accelerator)
+        })
+        .chain(predefined)
+        .collect()
+}
+
+fn custom_shortcuts(app: &AppHandle) -> BTreeMap<String, String> {
+    settings::get(app, CUSTOM_SHORTCUTS_KEY).unwrap_or_default()
+}
+
+// Add, update or (with no accelerator) remove the menu item backing a custom shortcut
+fn sync_menu_item(
+    app: &AppHandle,
+    action_id: &str,
+    accelerator: Option<&str>,
+) -> Result<(), String> {
+    let submenu = menu::find_item(app, SUBMENU_ID);
+    let Some(submenu) = submenu.as_ref().and_then(|item| item.as_submenu()) else {
+        return Ok(());
+    };
+
+    let id = format!("{}{}", ITEM_PREFIX, action_id);
+    let existing = submenu.get(&id);
+    let result = match (
+        existing.as_ref().and_then(|item| item.as_menuitem()),
+        accelerator,
+    ) {
+        (Some(item), Some(accelerator)) => item.set_accelerator(Some(accelerator)),
+        (Some(item), None) => submenu.remove(item),
+        (None, Some(accelerator)) => MenuItem::with_id(app, id, action_id, true, Some(accelerator))
+            .and_then(|item| submenu.append(&item)),
+        (None, None) => Ok(()),
+    };
+    result.map_err(|e| e.to_string())?;
+
+    let empty = submenu.items().map_err(|e| e.to_string())?.is_empty();
+    submenu.set_enabled(!empty).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builtin_accelerators_parse() {
+        for (name, accelerator) in DEFAULTS.iter().chain(PREDEFINED) {
+            assert!(parse(accelerator).is_ok(), "{}: {}", name, accelerator);
+        }
+    }
+}
@@ -0,0 +1,184 @@
+use std::sync::{Mutex, OnceLock};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use tauri::{command, AppHandle, Emitter};
+
+use crate::settings;
+
+const STATUS_KEY: &str = "status.custom";
+
+/// Longest status text, in characters
+const MAX_TEXT_CHARS: usize = 100;
+
+/// Longest emoji, in characters, which leaves room for sequences and `:shortcodes:`
+const MAX_EMOJI_CHARS: usize = 32;
+
+/// The expiry timer wakes this often to compare against the clock, so time spent asleep
+/// still counts and a status doesn't outlive its expiry by more than this
+const CHECK_INTERVAL: Duration = Duration::from_secs(15);
+
+// Bumped whenever the status changes so stale expiry timers know to do nothing; held
+// while the status is changed so an expiry can't clear a status set at the same moment
+fn generation() -> &'static Mutex<u64> {
+    static GENERATION: OnceLock<Mutex<u64>> = OnceLock::new();
+    GENERATION.get_or_init(|| Mutex::new(0))
+}
+
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CustomStatus {
+    emoji: Option<String>,
+    text: Option<String>,
+    /// When it clears itself, as unix millis
+    expires_at: Option<u64>,
+}
+
+impl CustomStatus {
+    /// A status that doesn't expire
+    pub fn new(emoji: &str, text: &str) -> CustomStatus {
+        CustomStatus {
+            emoji: Some(emoji.to_string()),
+            text: Some(text.to_string()),
+            expires_at: None,
+        }
+    }
+}
+
+/// Set the user's custom status, e.g. 📅 "In a meeting, back at 3pm" until 15:00, and
+/// emit `status-changed` with it. With `expires_at` (unix millis) it clears itself then,
+/// emitting `status-expired` with the old status and `status-changed` with null, including
+/// after a restart. An empty emoji and text clear the status. The frontend syncs it to the
+/// server.
+#[command]
+pub fn set_status(
+    app: AppHandle,
+    emoji: Option<String>,
+    text: Option<String>,
+    expires_at: Option<u64>,
+) -> Result<Option<CustomStatus>, String> {
+    let emoji = emoji
+        .map(|emoji| emoji.trim().to_string())
+        .filter(|emoji| !emoji.is_empty());
+    let text = text
+        .map(|text| text.trim().to_string())
+        .filter(|text| !text.is_empty());
+    if emoji.is_none() && text.is_none() {
+        clear_status(app)?;
+        return Ok(None);
+    }
+    if emoji
+        .as_ref()
+        .is_some_and(|emoji| emoji.chars().count() > MAX_EMOJI_CHARS)
+    {
+        return Err("The emoji is too long".to_string());
+    }
+    if text
+        .as_ref()
+        .is_some_and(|text| text.chars().count() > MAX_TEXT_CHARS)
+    {
+        return Err(format!(
+            "Statuses can be at most {} characters",
+            MAX_TEXT_CHARS
+        ));
+    }
+    if expires_at.is_some_and(|expires_at| expires_at <= now_ms()) {
+        return Err("The status would already have expired".to_string());
+    }
+
+    let status = CustomStatus {
+        emoji,
+        text,
+        expires_at,
+    };
+    let mut generation = generation().lock().unwrap();
+    settings::set(&app, STATUS_KEY, &status)?;
+    *generation += 1;
+    let _ = app.emit("status-changed", &status);
+    schedule_expiry(&app, *generation, expires_at);
+    Ok(Some(status))
+}
+
+/// Clear the custom status, emitting `status-changed` with null if there was one
+#[command]
+pub fn clear_status(app: AppHandle) -> Result<(), String> {
+    let mut generation = generation().lock().unwrap();
+    *generation += 1;
+    if current(&app).is_some() {
+        settings::delete(&app, STATUS_KEY)?;
+        let _ = app.emit("status-changed", None::<CustomStatus>);
+    }
+    Ok(())
+}
+
+#[command]
+pub fn custom_status(app: AppHandle) -> Option<CustomStatus> {
+    current(&app)
+}
+
+/// Re-arm the expiry of a status set before the app last quit, or clear it if it ran
+/// out while the app was closed
+pub fn restore_status(app: &AppHandle) {
+    let generation = generation().lock().unwrap();
+    if let Some(status) = current(app) {
+        schedule_expiry(app, *generation, status.expires_at);
+    }
+}
+
+/// Set a status saved earlier, or clear it with None or if it has expired since
+pub fn replace(app: &AppHandle, status: Option<CustomStatus>) -> Result<(), String> {
+    match status.filter(|status| status.expires_at.map_or(true, |at| at > now_ms())) {
+        Some(status) => {
+            set_status(app.clone(), status.emoji, status.text, status.expires_at).map(|_| ())
+        }
+        None => clear_status(app.clone()),
+    }
+}
+
+fn current(app: &AppHandle) -> Option<CustomStatus> {
+    settings::get(app, STATUS_KEY)
+}
+
+fn schedule_expiry(app: &AppHandle, scheduled: u64, expires_at: Option<u64>) {
+    let Some(expires_at) = expires_at else {
+        return;
+    };
+    let app = app.clone();
+    thread::spawn(move || {
+        loop {
+            let remaining = expires_at.saturating_sub(now_ms());
+            if remaining == 0 {
+                break;
+            }
+            thread::sleep(CHECK_INTERVAL.min(Duration::from_millis(remaining)));
+            if *generation().lock().unwrap() != scheduled {
+                return;
+            }
+        }
+        expire(&app, scheduled);
+    });
+}
+
+fn expire(app: &AppHandle, scheduled: u64) {
+    let mut generation = generation().lock().unwrap();
+    if *generation != scheduled {
+        return;
+    }
+    *generation += 1;
+    let Some(status) = current(app) else {
+        return;
+    };
+    if let Err(e) = settings::delete(app, STATUS_KEY) {
+        log::warn!("Failed to clear the expired status: {}", e);
+    }
+    let _ = app.emit("status-expired", &status);
+    let _ = app.emit("status-changed", None::<CustomStatus>);
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64
+}
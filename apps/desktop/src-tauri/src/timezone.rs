@@ -0,0 +1,150 @@
+use std::sync::{Mutex, OnceLock};
+use std::thread;
+use std::time::Duration;
+
+use chrono::{DateTime, Local, LocalResult, NaiveDate, NaiveTime, TimeDelta, TimeZone};
+use tauri::{command, AppHandle, Emitter};
+
+/// How often the time zone is checked while the window is in the background
+const POLL_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Longest gap a daylight saving change leaves in a day; some zones skip a whole day
+const MAX_GAP_MINUTES: i64 = 24 * 60;
+
+// Last time zone seen, for change detection
+fn last_timezone() -> &'static Mutex<Option<String>> {
+    static LAST: OnceLock<Mutex<Option<String>>> = OnceLock::new();
+    LAST.get_or_init(|| Mutex::new(None))
+}
+
+/// The OS time zone's IANA name, e.g. "Europe/Berlin", or "UTC" if it can't be read
+#[command]
+pub fn system_timezone() -> String {
+    let timezone = detect();
+    *last_timezone().lock().unwrap() = Some(timezone.clone());
+    timezone
+}
+
+/// Emit `timezone-changed` with the new name if the OS time zone changed since it was
+/// last read. Called when the window regains focus, and by `watch_timezone`.
+pub fn check_for_change(app: &AppHandle) {
+    let current = detect();
+    let mut last = last_timezone().lock().unwrap();
+    if last.as_ref().is_some_and(|last| *last != current) {
+        log::info!("Time zone changed to {}", current);
+        let _ = app.emit("timezone-changed", &current);
+    }
+    *last = Some(current);
+}
+
+/// Check for time zone changes in the background too, e.g. after travelling with the
+/// laptop asleep
+pub fn watch_timezone(app: &AppHandle) {
+    check_for_change(app);
+    let app = app.clone();
+    thread::spawn(move || loop {
+        thread::sleep(POLL_INTERVAL);
+        check_for_change(&app);
+    });
+}
+
+/// The current time in the OS time zone as it is now; schedules go through this and
+/// `at` rather than the clock directly
+pub fn now() -> DateTime<Local> {
+    Local::now()
+}
+
+/// When `time` on `date` happens in the OS time zone. Daylight saving makes some times
+/// happen twice, which gives the first, and some not at all, which gives the moment the
+/// clocks skip to, so a schedule neither fires twice nor gets skipped.
+pub fn at(date: NaiveDate, time: NaiveTime) -> DateTime<Local> {
+    at_in(&Local, date, time)
+}
+
+fn at_in<Tz: TimeZone>(zone: &Tz, date: NaiveDate, time: NaiveTime) -> DateTime<Tz> {
+    let wanted = date.and_time(time);
+    // Gaps start and end on the minute, so the first minute that exists is where it ends
+    for minutes in 0..=MAX_GAP_MINUTES {
+        match zone.from_local_datetime(&(wanted + TimeDelta::minutes(minutes))) {
+            LocalResult::Single(instant) => return instant,
+            // Not always in order
+            LocalResult::Ambiguous(a, b) => return a.min(b),
+            LocalResult::None => {}
+        }
+    }
+    zone.from_utc_datetime(&wanted)
+}
+
+fn detect() -> String {
+    iana_time_zone::get_timezone().unwrap_or_else(|_| "UTC".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+    use chrono_tz::America::New_York;
+    use chrono_tz::Europe::Berlin;
+    use chrono_tz::Pacific::Apia;
+
+    use super::*;
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    fn time(hour: u32, minute: u32) -> NaiveTime {
+        NaiveTime::from_hms_opt(hour, minute, 0).unwrap()
+    }
+
+    fn utc(date: NaiveDate, time: NaiveTime) -> DateTime<Utc> {
+        Utc.from_utc_datetime(&date.and_time(time))
+    }
+
+    #[test]
+    fn ordinary_times() {
+        let instant = at_in(&Berlin, date(2025, 6, 1), time(9, 0));
+        assert_eq!(instant, utc(date(2025, 6, 1), time(7, 0)));
+    }
+
+    #[test]
+    fn skipped_times_move_to_the_end_of_the_gap() {
+        // Berlin skips from 02:00 to 03:00 on March 30, 2025
+        let march = date(2025, 3, 30);
+        assert_eq!(at_in(&Berlin, march, time(2, 0)), utc(march, time(1, 0)));
+        assert_eq!(at_in(&Berlin, march, time(2, 30)), utc(march, time(1, 0)));
+        assert_eq!(at_in(&Berlin, march, time(3, 0)), utc(march, time(1, 0)));
+        assert_eq!(at_in(&Berlin, march, time(1, 59)), utc(march, time(0, 59)));
+
+        // New York skips from 02:00 to 03:00 on March 9, 2025
+        let march = date(2025, 3, 9);
+        assert_eq!(at_in(&New_York, march, time(2, 15)), utc(march, time(7, 0)));
+    }
+
+    #[test]
+    fn repeated_times_give_the_first() {
+        // Berlin goes back from 03:00 to 02:00 on October 26, 2025
+        let october = date(2025, 10, 26);
+        assert_eq!(
+            at_in(&Berlin, october, time(2, 30)),
+            utc(october, time(0, 30))
+        );
+        assert_eq!(
+            at_in(&Berlin, october, time(3, 0)),
+            utc(october, time(2, 0))
+        );
+
+        // New York goes back from 02:00 to 01:00 on November 2, 2025
+        let november = date(2025, 11, 2);
+        assert_eq!(
+            at_in(&New_York, november, time(1, 30)),
+            utc(november, time(5, 30))
+        );
+    }
+
+    #[test]
+    fn skipped_days_move_to_the_next() {
+        // Samoa skipped December 30, 2011 when it crossed the date line
+        let instant = at_in(&Apia, date(2011, 12, 30), time(9, 0));
+        assert_eq!(instant, utc(date(2011, 12, 30), time(10, 0)));
+    }
+}
@@ -0,0 +1,191 @@
+use std::ffi::OsStr;
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use tauri::{command, AppHandle, Emitter};
+
+use crate::cache;
+
+/// Longer recordings are cut off at this length
+pub const MAX_DURATION_SECS: u64 = 15 * 60;
+
+#[derive(Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AudioFormat {
+    /// Opus in Ogg, the default for voice messages
+    Ogg,
+    /// AAC in MP4, for clients that can't play Opus
+    M4a,
+    Mp3,
+    Wav,
+}
+
+impl AudioFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            AudioFormat::Ogg => "ogg",
+            AudioFormat::M4a => "m4a",
+            AudioFormat::Mp3 => "mp3",
+            AudioFormat::Wav => "wav",
+        }
+    }
+
+    // Encoder settings tuned for mono speech
+    fn codec_args(self) -> &'static [&'static str] {
+        match self {
+            AudioFormat::Ogg => &["-c:a", "libopus", "-b:a", "32k", "-application", "voip"],
+            AudioFormat::M4a => &["-c:a", "aac", "-b:a", "64k"],
+            AudioFormat::Mp3 => &["-c:a", "libmp3lame", "-b:a", "64k"],
+            AudioFormat::Wav => &["-c:a", "pcm_s16le", "-ar", "16000"],
+        }
+    }
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct TranscodeProgress<'a> {
+    input_path: &'a Path,
+    /// From 0 to 1
+    progress: f64,
+}
+
+/// Convert a recorded voice message to `output_format` (mono, speech bitrate) for upload,
+/// emitting `transcode-progress` with the input path as it goes. Recordings longer than
+/// 15 minutes are cut off. Uses the `ffmpeg` and `ffprobe` binaries on the PATH; the
+/// result lands in the attachment cache.
+#[command]
+pub async fn transcode_audio(
+    app: AppHandle,
+    input_path: PathBuf,
+    output_format: AudioFormat,
+) -> Result<PathBuf, String> {
+    let dir = cache::dir(&app, "attachments")?.join("voice");
+    tauri::async_runtime::spawn_blocking(move || {
+        let duration = probe(&input_path)?;
+        std::fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+        let output = dir.join(output_name(&input_path, output_format));
+        transcode(&app, &input_path, &output, output_format, duration)?;
+        Ok(output)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+// Duration of the input in seconds, failing if it has no audio ffmpeg can decode
+fn probe(input: &Path) -> Result<f64, String> {
+    if !input.is_file() {
+        return Err(format!("{} doesn't exist", input.display()));
+    }
+    let output = tool("ffprobe")
+        .args(["-v", "error", "-select_streams", "a:0"])
+        .args(["-show_entries", "stream=codec_name:format=duration"])
+        .args(["-of", "default=noprint_wrappers=1"])
+        .arg(input)
+        .output()
+        .map_err(|e| format!("ffprobe is required to transcode audio: {}", e))?;
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let field = |name: &str| {
+        stdout
+            .lines()
+            .find_map(|line| line.strip_prefix(name)?.strip_prefix('='))
+            .map(str::trim)
+    };
+    if !output.status.success() || field("codec_name").is_none() {
+        return Err(format!("Unsupported audio file {}", input.display()));
+    }
+    // Streamed WebM recordings often carry no duration, which only costs us progress events
+    Ok(field("duration")
+        .and_then(|duration| duration.parse().ok())
+        .unwrap_or(0.0))
+}
+
+fn transcode(
+    app: &AppHandle,
+    input: &Path,
+    output: &Path,
+    format: AudioFormat,
+    duration: f64,
+) -> Result<(), String> {
+    let mut child = tool("ffmpeg")
+        .args(["-nostdin", "-hide_banner", "-loglevel", "error", "-y", "-i"])
+        .arg(input)
+        .args(["-t", &MAX_DURATION_SECS.to_string()])
+        .args(["-vn", "-map_metadata", "-1", "-ac", "1"])
+        .args(format.codec_args())
+        .args(["-progress", "pipe:1", "-nostats"])
+        .arg(output)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("ffmpeg is required to transcode audio: {}", e))?;
+
+    // `-progress` writes key=value blocks; out_time_us is how far encoding has got
+    let total_us = duration.min(MAX_DURATION_SECS as f64) * 1_000_000.0;
+    let mut reported = -1.0;
+    if let Some(stdout) = child.stdout.take() {
+        for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+            let progress = match line.split_once('=') {
+                Some(("out_time_us", us)) if total_us > 0.0 => match us.parse::<f64>() {
+                    Ok(us) => (us / total_us).clamp(0.0, 1.0),
+                    Err(_) => continue,
+                },
+                Some(("progress", "end")) => 1.0,
+                _ => continue,
+            };
+            // Whole percents are plenty for a progress bar
+            let progress = (progress * 100.0).floor() / 100.0;
+            if progress > reported {
+                reported = progress;
+                let _ = app.emit(
+                    "transcode-progress",
+                    TranscodeProgress {
+                        input_path: input,
+                        progress,
+                    },
+                );
+            }
+        }
+    }
+
+    let result = child.wait_with_output().map_err(|e| e.to_string())?;
+    if !result.status.success() {
+        let _ = std::fs::remove_file(output);
+        let stderr = String::from_utf8_lossy(&result.stderr);
+        return Err(format!(
+            "Failed to transcode {}: {}",
+            input.display(),
+            stderr.lines().last().unwrap_or("ffmpeg failed")
+        ));
+    }
+    Ok(())
+}
+
+// The input's name with the new extension, made unique so repeated recordings don't clash
+fn output_name(input: &Path, format: AudioFormat) -> String {
+    let stem = input
+        .file_stem()
+        .and_then(OsStr::to_str)
+        .unwrap_or("voice-message");
+    let millis = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_millis())
+        .unwrap_or_default();
+    format!("{}-{}.{}", stem, millis, format.extension())
+}
+
+/// Command for one of the ffmpeg tools, without a console window on Windows
+pub fn tool(name: &str) -> Command {
+    #[allow(unused_mut)]
+    let mut command = Command::new(name);
+    #[cfg(windows)]
+    {
+        use std::os::windows::process::CommandExt;
+        const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+        command.creation_flags(CREATE_NO_WINDOW);
+    }
+    command
+}
@@ -0,0 +1,277 @@
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::sync::{Mutex, OnceLock};
+use std::thread;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tauri::image::Image;
+use tauri::menu::{CheckMenuItem, Menu, MenuEvent, MenuItem, PredefinedMenuItem};
+use tauri::tray::{MouseButton, MouseButtonState, TrayIconBuilder, TrayIconEvent};
+use tauri::{command, include_image, App, AppHandle, Emitter, Manager, Wry};
+
+use crate::{lifecycle, window};
+
+const TRAY_ID: &str = "main";
+
+/// Tray menu item ids for recent channels are this prefix followed by the channel id
+const CHANNEL_ITEM_PREFIX: &str = "tray:channel:";
+
+/// Recent channels listed in the tray menu
+const MAX_RECENT_CHANNELS: usize = 5;
+
+/// How long each frame of the mention blink lasts
+const BLINK_INTERVAL: Duration = Duration::from_millis(600);
+
+#[derive(Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RecentChannel {
+    id: String,
+    name: String,
+}
+
+// Presence set by `set_tray_status`, which blinking alternates with the mention icon
+fn tray_state() -> &'static Mutex<TrayState> {
+    static STATE: OnceLock<Mutex<TrayState>> = OnceLock::new();
+    STATE.get_or_init(|| Mutex::new(TrayState::default()))
+}
+
+// Stops the blink timer when dropped
+fn blink_timer() -> &'static Mutex<Option<Sender<()>>> {
+    static TIMER: OnceLock<Mutex<Option<Sender<()>>>> = OnceLock::new();
+    TIMER.get_or_init(|| Mutex::new(None))
+}
+
+// What the tray menu shows, as last reported by the frontend
+#[derive(Default)]
+struct TrayMenu {
+    unread: u32,
+    dnd: bool,
+    recent_channels: Vec<RecentChannel>,
+}
+
+fn tray_menu() -> &'static Mutex<TrayMenu> {
+    static MENU: OnceLock<Mutex<TrayMenu>> = OnceLock::new();
+    MENU.get_or_init(|| Mutex::new(TrayMenu::default()))
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct OpenChannel<'a> {
+    channel_id: &'a str,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ToggleDnd {
+    enabled: bool,
+}
+
+/// Presence shown by the tray icon's status dot
+#[derive(Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TrayState {
+    #[default]
+    Online,
+    Away,
+    Dnd,
+    Offline,
+}
+
+impl TrayState {
+    fn icon(self) -> Image<'static> {
+        match self {
+            TrayState::Online => include_image!("icons/tray/online.png"),
+            TrayState::Away => include_image!("icons/tray/away.png"),
+            TrayState::Dnd => include_image!("icons/tray/dnd.png"),
+            TrayState::Offline => include_image!("icons/tray/offline.png"),
+        }
+    }
+}
+
+/// Swap the tray icon for the one showing `state` and update its tooltip.
+/// Does nothing where there's no tray; Linux trays don't show tooltips.
+#[command]
+pub fn set_tray_status(app: AppHandle, tooltip: String, state: TrayState) -> Result<(), String> {
+    *tray_state().lock().unwrap() = state;
+    let Some(tray) = app.tray_by_id(TRAY_ID) else {
+        return Ok(());
+    };
+    // A blinking icon picks up the new state on its next frame
+    if blink_timer().lock().unwrap().is_none() {
+        tray.set_icon(Some(state.icon()))
+            .map_err(|e| e.to_string())?;
+    }
+    tray.set_tooltip(Some(tooltip)).map_err(|e| e.to_string())
+}
+
+/// Show the unread count in the tray menu. While there are unread `mentions` the tray
+/// icon blinks, until they're read or a Hazel window gains focus.
+#[command]
+pub fn update_unread(app: AppHandle, count: u32, mentions: Option<u32>) -> Result<(), String> {
+    tray_menu().lock().unwrap().unread = count;
+    if mentions.unwrap_or(0) > 0 {
+        start_blink(&app);
+    } else {
+        stop_blink(&app);
+    }
+    rebuild_menu(&app)
+}
+
+/// Check or uncheck "Do Not Disturb" in the tray menu. Clicking it emits `menu-toggle-dnd`
+/// with the new state for the frontend to apply.
+#[command]
+pub fn set_dnd(app: AppHandle, enabled: bool) -> Result<(), String> {
+    tray_menu().lock().unwrap().dnd = enabled;
+    rebuild_menu(&app)
+}
+
+/// List channels in the tray menu, most recent first (up to 5). Clicking one emits
+/// `menu-open-channel` with its id.
+#[command]
+pub fn set_recent_channels(app: AppHandle, channels: Vec<RecentChannel>) -> Result<(), String> {
+    let mut channels = channels;
+    channels.truncate(MAX_RECENT_CHANNELS);
+    tray_menu().lock().unwrap().recent_channels = channels;
+    rebuild_menu(&app)
+}
+
+/// Add the tray icon; clicking it brings the main window forward and right-clicking
+/// opens its menu
+pub fn setup(app: &App) -> tauri::Result<()> {
+    let menu = build_menu(app.handle(), &tray_menu().lock().unwrap())?;
+    TrayIconBuilder::with_id(TRAY_ID)
+        .icon(TrayState::Online.icon())
+        .tooltip("Hazel")
+        .menu(&menu)
+        .show_menu_on_left_click(false)
+        .on_menu_event(handle_menu_event)
+        .on_tray_icon_event(|tray, event| {
+            if let TrayIconEvent::Click {
+                button: MouseButton::Left,
+                button_state: MouseButtonState::Up,
+                ..
+            } = event
+            {
+                show_main_window(tray.app_handle());
+            }
+        })
+        .build(app)?;
+    Ok(())
+}
+
+/// Stop blinking for mentions and show the presence icon again
+pub fn stop_blink(app: &AppHandle) {
+    // Dropping the sender ends the timer thread
+    if blink_timer().lock().unwrap().take().is_none() {
+        return;
+    }
+    if let Some(tray) = app.tray_by_id(TRAY_ID) {
+        let _ = tray.set_icon(Some(tray_state().lock().unwrap().icon()));
+    }
+}
+
+fn start_blink(app: &AppHandle) {
+    let mut timer = blink_timer().lock().unwrap();
+    if timer.is_some() || app.tray_by_id(TRAY_ID).is_none() {
+        return;
+    }
+    let (stop_tx, stop_rx) = mpsc::channel::<()>();
+    *timer = Some(stop_tx);
+
+    let app = app.clone();
+    thread::spawn(move || {
+        let mut highlighted = false;
+        while let Err(RecvTimeoutError::Timeout) = stop_rx.recv_timeout(BLINK_INTERVAL) {
+            highlighted = !highlighted;
+            let icon = if highlighted {
+                include_image!("icons/tray/mention.png")
+            } else {
+                tray_state().lock().unwrap().icon()
+            };
+            if let Some(tray) = app.tray_by_id(TRAY_ID) {
+                let _ = tray.set_icon(Some(icon));
+            }
+        }
+    });
+}
+
+// The menu is replaced as a whole, and the tray drops the old one along with its items
+fn rebuild_menu(app: &AppHandle) -> Result<(), String> {
+    let Some(tray) = app.tray_by_id(TRAY_ID) else {
+        return Ok(());
+    };
+    let menu = build_menu(app, &tray_menu().lock().unwrap()).map_err(|e| e.to_string())?;
+    tray.set_menu(Some(menu)).map_err(|e| e.to_string())
+}
+
+fn build_menu(app: &AppHandle, state: &TrayMenu) -> tauri::Result<Menu<Wry>> {
+    let unread = match state.unread {
+        0 => "No unread messages".to_string(),
+        1 => "1 unread message".to_string(),
+        count => format!("{} unread messages", count),
+    };
+    let menu = Menu::with_items(
+        app,
+        &[
+            &MenuItem::with_id(app, "tray:unread", unread, false, None::<&str>)?,
+            &PredefinedMenuItem::separator(app)?,
+        ],
+    )?;
+
+    if !state.recent_channels.is_empty() {
+        for channel in &state.recent_channels {
+            let id = format!("{}{}", CHANNEL_ITEM_PREFIX, channel.id);
+            menu.append(&MenuItem::with_id(
+                app,
+                id,
+                &channel.name,
+                true,
+                None::<&str>,
+            )?)?;
+        }
+        menu.append(&PredefinedMenuItem::separator(app)?)?;
+    }
+
+    menu.append_items(&[
+        &CheckMenuItem::with_id(
+            app,
+            "tray:dnd",
+            "Do Not Disturb",
+            true,
+            state.dnd,
+            None::<&str>,
+        )?,
+        &PredefinedMenuItem::separator(app)?,
+        &MenuItem::with_id(app, "tray:show", "Show Hazel", true, None::<&str>)?,
+        &MenuItem::with_id(app, "tray:quit", "Quit Hazel", true, None::<&str>)?,
+    ])?;
+    Ok(menu)
+}
+
+fn handle_menu_event(app: &AppHandle, event: MenuEvent) {
+    match event.id().as_ref() {
+        "tray:dnd" => {
+            let enabled = {
+                let mut state = tray_menu().lock().unwrap();
+                state.dnd = !state.dnd;
+                state.dnd
+            };
+            let _ = rebuild_menu(app);
+            let _ = app.emit("menu-toggle-dnd", ToggleDnd { enabled });
+        }
+        "tray:show" => show_main_window(app),
+        "tray:quit" => lifecycle::quit(app, true),
+        id => {
+            if let Some(channel_id) = id.strip_prefix(CHANNEL_ITEM_PREFIX) {
+                show_main_window(app);
+                let _ = app.emit("menu-open-channel", OpenChannel { channel_id });
+            }
+        }
+    }
+}
+
+fn show_main_window(app: &AppHandle) {
+    if let Some(main) = app.get_webview_window("main") {
+        let _ = window::show_and_focus(&main);
+    }
+}
@@ -0,0 +1,772 @@
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+
+use base64::Engine;
+use minisign_verify::{Error as MinisignError, PublicKey, Signature};
+use semver::Version;
+use serde::{Deserialize, Serialize};
+use tauri::{command, AppHandle, Emitter};
+
+use crate::{bandwidth, cache, disk, http, settings};
+
+const VERSION_PIN_KEY: &str = "updates.version_pin";
+const STAGED_KEY: &str = "updates.staged";
+const PARTIAL_KEY: &str = "updates.partial";
+
+/// Subfolders of the updates cache for the downloaded update, the installer of the
+/// running version, and a download in progress
+const STAGED_DIR: &str = "staged";
+const BASE_DIR: &str = "base";
+const PARTIAL_DIR: &str = "partial";
+
+// Highest version updates may go to. The updater's version check can't reach the store,
+// so the pin is mirrored here.
+fn version_pin_state() -> &'static Mutex<Option<Version>> {
+    static PIN: OnceLock<Mutex<Option<Version>>> = OnceLock::new();
+    PIN.get_or_init(|| Mutex::new(None))
+}
+
+/// Result of `check_for_update`
+#[derive(Serialize)]
+#[serde(
+    tag = "status",
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase"
+)]
+pub enum UpdateCheck {
+    Available {
+        version: String,
+        notes: Option<String>,
+    },
+    UpToDate,
+    /// A newer version exists, but it's past the pinned one
+    Pinned {
+        pinned_version: String,
+        latest_version: String,
+    },
+}
+
+// An update downloaded by `download_update`, waiting to be installed
+#[derive(Serialize, Deserialize)]
+struct StagedUpdate {
+    version: String,
+    path: PathBuf,
+    signature: String,
+}
+
+// A full download that was paused or interrupted, resumable if the update is unchanged
+#[derive(Serialize, Deserialize, PartialEq)]
+struct PartialDownload {
+    version: String,
+    url: String,
+}
+
+#[derive(Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+enum DownloadState {
+    Idle,
+    Downloading,
+    Paused,
+    Cancelled,
+}
+
+// Where `download_update` is at; the download loop checks it between chunks
+fn download_state() -> &'static Mutex<DownloadState> {
+    static STATE: OnceLock<Mutex<DownloadState>> = OnceLock::new();
+    STATE.get_or_init(|| Mutex::new(DownloadState::Idle))
+}
+
+// "paused" or "cancelled" once the download has been, for the download loops to stop with
+fn interruption() -> Option<String> {
+    match *download_state().lock().unwrap() {
+        DownloadState::Paused => Some("paused".to_string()),
+        DownloadState::Cancelled => Some("cancelled".to_string()),
+        DownloadState::Idle | DownloadState::Downloading => None,
+    }
+}
+
+// Emits `update-download-state` with the new state
+fn set_download_state(app: &AppHandle, state: DownloadState) {
+    *download_state().lock().unwrap() = state;
+    let _ = app.emit("update-download-state", state);
+}
+
+// The updater only installs through the `Update` it downloaded, so the last one is kept
+// for `install_downloaded_update`. After a restart the update is looked up again.
+#[cfg(desktop)]
+fn downloaded_update() -> &'static Mutex<Option<tauri_plugin_updater::Update>> {
+    static UPDATE: OnceLock<Mutex<Option<tauri_plugin_updater::Update>>> = OnceLock::new();
+    UPDATE.get_or_init(|| Mutex::new(None))
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct DownloadProgress {
+    downloaded: u64,
+    total: Option<u64>,
+}
+
+/// Outcome of `verify_update_signature`. Anything short of a good signature from the
+/// updater's key comes back invalid with the reason.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SignatureCheck {
+    valid: bool,
+    /// Id of the updater's public key, as minisign prints it
+    key_id: Option<String>,
+    /// Id of the key the signature was made with
+    signer_key_id: Option<String>,
+    /// Signed metadata, usually the signing time and file name
+    trusted_comment: Option<String>,
+    error: Option<String>,
+}
+
+/// Keep updates at or below `version` (e.g. "1.4.2"), for managed deployments; None
+/// lets them through again. Applies to both `check_for_update` and the updater's own
+/// checks from the web app.
+#[command]
+pub fn set_version_pin(app: AppHandle, version: Option<String>) -> Result<(), String> {
+    let pin = match version {
+        Some(version) => {
+            let parsed = parse_version(&version)?;
+            settings::set(&app, VERSION_PIN_KEY, parsed.to_string())?;
+            Some(parsed)
+        }
+        None => {
+            settings::delete(&app, VERSION_PIN_KEY)?;
+            None
+        }
+    };
+    *version_pin_state().lock().unwrap() = pin;
+    Ok(())
+}
+
+/// The pinned version, if any
+pub fn version_pin() -> Option<String> {
+    version_pin_state()
+        .lock()
+        .unwrap()
+        .as_ref()
+        .map(Version::to_string)
+}
+
+/// Load the pin before the updater starts checking. Runs before the app is built,
+/// so it reads the setting straight from disk.
+pub fn restore_version_pin(identifier: &str) {
+    let pin = settings::get_at_startup::<String>(identifier, VERSION_PIN_KEY)
+        .and_then(|version| parse_version(&version).ok());
+    *version_pin_state().lock().unwrap() = pin;
+}
+
+/// Version comparator for the updater: newer than the running version and not past the pin
+#[cfg(desktop)]
+pub fn allows_update(current: Version, release: tauri_plugin_updater::RemoteRelease) -> bool {
+    let pinned = version_pin_state().lock().unwrap().clone();
+    release.version > current && pinned.map_or(true, |pin| release.version <= pin)
+}
+
+/// Look for an update, reporting `pinned` when the only newer versions are past the pin
+#[cfg(desktop)]
+#[command]
+pub async fn check_for_update(app: AppHandle) -> Result<UpdateCheck, String> {
+    use tauri_plugin_updater::UpdaterExt;
+
+    // Ask for the latest version regardless of the pin, to tell the two cases apart
+    let update = app
+        .updater_builder()
+        .version_comparator(|current, release| release.version > current)
+        .build()
+        .map_err(|e| e.to_string())?
+        .check()
+        .await
+        .map_err(|e| e.to_string())?;
+    let Some(update) = update else {
+        return Ok(UpdateCheck::UpToDate);
+    };
+
+    let pinned = version_pin_state().lock().unwrap().clone();
+    let latest = parse_version(&update.version)?;
+    match pinned {
+        Some(pin) if latest > pin => Ok(UpdateCheck::Pinned {
+            pinned_version: pin.to_string(),
+            latest_version: latest.to_string(),
+        }),
+        _ => Ok(UpdateCheck::Available {
+            version: update.version,
+            notes: update.body,
+        }),
+    }
+}
+
+/// Download and verify the available update without installing it, emitting
+/// `update-download-progress` as it goes, and return where it was saved. Respects the
+/// version pin. A later `install_downloaded_update` applies it, e.g. once the user is
+/// ready to restart.
+///
+/// When the manifest offers a delta from the running version and the installer it was
+/// installed from is still cached, only the delta is downloaded and patched onto it.
+/// Otherwise, or if patching fails, the full installer is downloaded. Either way the
+/// result must match the update's signature, and `update-download-method` says which
+/// was used.
+///
+/// Downloads can be paused, which makes this fail with "paused". Full downloads pick up
+/// where they left off on the next call, even after a restart; deltas start over.
+#[cfg(desktop)]
+#[command]
+pub async fn download_update(app: AppHandle) -> Result<PathBuf, String> {
+    {
+        let mut state = download_state().lock().unwrap();
+        if *state == DownloadState::Downloading {
+            return Err("An update is already downloading".to_string());
+        }
+        *state = DownloadState::Downloading;
+    }
+    let _ = app.emit("update-download-state", DownloadState::Downloading);
+
+    let result = stage_update(&app).await;
+    // Pausing and cancelling set their own state
+    if *download_state().lock().unwrap() == DownloadState::Downloading {
+        set_download_state(&app, DownloadState::Idle);
+    }
+    result
+}
+
+/// Pause the update download, keeping what was downloaded. Returns false if
+/// nothing was downloading.
+#[cfg(desktop)]
+#[command]
+pub fn pause_update_download(app: AppHandle) -> bool {
+    if *download_state().lock().unwrap() != DownloadState::Downloading {
+        return false;
+    }
+    set_download_state(&app, DownloadState::Paused);
+    true
+}
+
+/// Carry on with a paused or interrupted update download; the same as `download_update`
+#[cfg(desktop)]
+#[command]
+pub async fn resume_update_download(app: AppHandle) -> Result<PathBuf, String> {
+    download_update(app).await
+}
+
+/// Stop the update download and delete what was downloaded so far
+#[cfg(desktop)]
+#[command]
+pub fn cancel_update_download(app: AppHandle) -> Result<(), String> {
+    let downloading = *download_state().lock().unwrap() == DownloadState::Downloading;
+    set_download_state(&app, DownloadState::Cancelled);
+    // A running download cleans up after itself when it sees the state
+    if !downloading {
+        discard_partial(&app)?;
+    }
+    Ok(())
+}
+
+#[cfg(desktop)]
+async fn stage_update(app: &AppHandle) -> Result<PathBuf, String> {
+    use tauri_plugin_updater::UpdaterExt;
+
+    let update = app
+        .updater()
+        .map_err(|e| e.to_string())?
+        .check()
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| "No update is available".to_string())?;
+
+    prune_bases(&base_dir(app)?.join(&update.current_version));
+    let resuming = settings::get::<PartialDownload>(app, PARTIAL_KEY)
+        .is_some_and(|partial| partial.version == update.version);
+    let delta = match resuming {
+        true => Err("resuming a paused download".to_string()),
+        false => download_delta(app, &update).await,
+    };
+    // A delta stopped by pausing or cancelling isn't a reason to fall back to the full one
+    if let Some(stopped) = interruption() {
+        if stopped == "cancelled" {
+            discard_partial(app)?;
+        }
+        return Err(stopped);
+    }
+    let bytes = match delta {
+        Ok(bytes) => {
+            let _ = app.emit("update-download-method", "delta");
+            bytes
+        }
+        Err(e) => {
+            log::info!("Downloading the full update: {}", e);
+            let _ = app.emit("update-download-method", "full");
+            download_full(app, &update).await?
+        }
+    };
+
+    let dir = cache::dir(app, "updates")?.join(STAGED_DIR);
+    let name = update
+        .download_url
+        .path_segments()
+        .and_then(|mut segments| segments.next_back())
+        .filter(|name| !name.is_empty())
+        .map_or_else(
+            || format!("hazel-{}.update", update.version),
+            str::to_string,
+        );
+    let path = dir.join(name);
+    // Only one staged update is kept
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir)
+        .and_then(|_| std::fs::write(&path, &bytes))
+        .map_err(|e| format!("Failed to save the update: {}", e))?;
+
+    let staged = StagedUpdate {
+        version: update.version.clone(),
+        path: path.clone(),
+        signature: update.signature.clone(),
+    };
+    settings::set(app, STAGED_KEY, staged)?;
+    *downloaded_update().lock().unwrap() = Some(update);
+    Ok(path)
+}
+
+// Emits `update-download-progress` every percent, or every megabyte if the size isn't known
+struct Progress<'a> {
+    app: &'a AppHandle,
+    downloaded: u64,
+    reported: u64,
+}
+
+impl<'a> Progress<'a> {
+    fn new(app: &'a AppHandle) -> Self {
+        Progress {
+            app,
+            downloaded: 0,
+            reported: 0,
+        }
+    }
+
+    fn advance(&mut self, chunk: usize, total: Option<u64>) {
+        self.downloaded += chunk as u64;
+        let step = total.map_or(1024 * 1024, |total| (total / 100).max(1));
+        if self.downloaded - self.reported >= step || total == Some(self.downloaded) {
+            self.reported = self.downloaded;
+            let _ = self.app.emit(
+                "update-download-progress",
+                DownloadProgress {
+                    downloaded: self.downloaded,
+                    total,
+                },
+            );
+        }
+    }
+}
+
+// Download the full installer into a partial file, resuming it with a range request if
+// an earlier download of the same update was cut short, then check its signature
+#[cfg(desktop)]
+async fn download_full(
+    app: &AppHandle,
+    update: &tauri_plugin_updater::Update,
+) -> Result<Vec<u8>, String> {
+    let dir = cache::dir(app, "updates")?.join(PARTIAL_DIR);
+    let path = dir.join("update.part");
+    let partial = PartialDownload {
+        version: update.version.clone(),
+        url: update.download_url.to_string(),
+    };
+    let mut have = match settings::get::<PartialDownload>(app, PARTIAL_KEY) {
+        Some(existing) if existing == partial => std::fs::metadata(&path).map_or(0, |m| m.len()),
+        _ => 0,
+    };
+    std::fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+    settings::set(app, PARTIAL_KEY, &partial)?;
+
+    let mut response = request_installer(app, update, have).await?;
+    // Nothing past the end means the partial file is complete or stale; start over
+    if response.status() == reqwest::StatusCode::RANGE_NOT_SATISFIABLE {
+        have = 0;
+        response = request_installer(app, update, 0).await?;
+    }
+    let mut response = response
+        .error_for_status()
+        .map_err(|e| format!("Failed to download the update: {}", e))?;
+    // Servers that ignore the range send the whole file again
+    if response.status() != reqwest::StatusCode::PARTIAL_CONTENT {
+        have = 0;
+    }
+    if let Some(remaining) = response.content_length() {
+        disk::ensure_space(&dir, remaining)?;
+    }
+    let total = response.content_length().map(|length| length + have);
+
+    let mut file = OpenOptions::new()
+        .create(true)
+        .write(true)
+        .append(have > 0)
+        .truncate(have == 0)
+        .open(&path)
+        .map_err(|e| e.to_string())?;
+    let mut progress = Progress::new(app);
+    progress.advance(have as usize, total);
+    loop {
+        if let Some(stopped) = interruption() {
+            if stopped == "cancelled" {
+                drop(file);
+                discard_partial(app)?;
+            }
+            return Err(stopped);
+        }
+        let chunk = response
+            .chunk()
+            .await
+            .map_err(|e| format!("Failed to download the update: {}", e))?;
+        let Some(chunk) = chunk else {
+            break;
+        };
+        file.write_all(&chunk).map_err(|e| e.to_string())?;
+        progress.advance(chunk.len(), total);
+        bandwidth::throttle_download(chunk.len()).await;
+    }
+    drop(file);
+
+    let bytes = std::fs::read(&path).map_err(|e| e.to_string())?;
+    discard_partial(app)?;
+    let verified = check(
+        updater_pubkey(app),
+        &mut bytes.as_slice(),
+        &update.signature,
+    );
+    if !verified.valid {
+        return Err(format!(
+            "The update failed verification: {}",
+            verified.error.unwrap_or_default()
+        ));
+    }
+    Ok(bytes)
+}
+
+#[cfg(desktop)]
+async fn request_installer(
+    app: &AppHandle,
+    update: &tauri_plugin_updater::Update,
+    from: u64,
+) -> Result<reqwest::Response, String> {
+    let url = http::check_url(app, update.download_url.as_str(), true).await?;
+    let mut request = http::client(app)?
+        .get(url)
+        .header("Accept", "application/octet-stream");
+    if from > 0 {
+        request = request.header("Range", format!("bytes={}-", from));
+    }
+    request
+        .send()
+        .await
+        .map_err(|e| format!("Failed to download the update: {}", e))
+}
+
+fn discard_partial(app: &AppHandle) -> Result<(), String> {
+    let _ = std::fs::remove_dir_all(cache::dir(app, "updates")?.join(PARTIAL_DIR));
+    settings::delete(app, PARTIAL_KEY)
+}
+
+// Rebuild the new installer from the cached one and a bsdiff patch (in the format of the
+// `bsdiff` crate). The manifest lists patches by the version they apply to, under
+// `deltas` next to the platform's `url` and `signature`:
+// `"deltas": { "1.0.3": { "url": "https://..." } }`
+#[cfg(desktop)]
+async fn download_delta(
+    app: &AppHandle,
+    update: &tauri_plugin_updater::Update,
+) -> Result<Vec<u8>, String> {
+    let platform = match update.raw_json.get("platforms") {
+        Some(platforms) => platforms.get(&update.target),
+        None => Some(&update.raw_json),
+    };
+    let url = platform
+        .and_then(|platform| platform.get("deltas"))
+        .and_then(|deltas| deltas.get(&update.current_version))
+        .and_then(|delta| delta.get("url"))
+        .and_then(|url| url.as_str())
+        .ok_or_else(|| format!("no delta from {}", update.current_version))?;
+
+    let base_path = base_dir(app)?.join(&update.current_version);
+    let base = std::fs::read(&base_path)
+        .map_err(|e| format!("the installed version's installer isn't cached: {}", e))?;
+    // The patched installer comes out about the size of the old one
+    disk::ensure_space(&base_path, base.len() as u64)?;
+
+    let url = http::check_url(app, url, true).await?;
+    let mut response = http::client(app)?
+        .get(url)
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| format!("failed to download the delta: {}", e))?;
+    let total = response.content_length();
+    let mut progress = Progress::new(app);
+    let mut patch = Vec::new();
+    while let Some(chunk) = response
+        .chunk()
+        .await
+        .map_err(|e| format!("failed to download the delta: {}", e))?
+    {
+        // Kept in memory only, so a paused delta is downloaded again
+        if let Some(stopped) = interruption() {
+            return Err(stopped);
+        }
+        progress.advance(chunk.len(), total);
+        patch.extend_from_slice(&chunk);
+        bandwidth::throttle_download(chunk.len()).await;
+    }
+
+    let patched = tauri::async_runtime::spawn_blocking(move || {
+        let mut patched = Vec::new();
+        bsdiff::patch(&base, &mut patch.as_slice(), &mut patched)
+            .map_err(|e| format!("failed to apply the delta: {}", e))?;
+        Ok::<_, String>(patched)
+    })
+    .await
+    .map_err(|e| e.to_string())??;
+
+    let verified = check(
+        updater_pubkey(app),
+        &mut patched.as_slice(),
+        &update.signature,
+    );
+    if !verified.valid {
+        return Err(format!(
+            "the patched installer failed verification: {}",
+            verified.error.unwrap_or_default()
+        ));
+    }
+    Ok(patched)
+}
+
+// Installers of installed versions, by version, for applying deltas to
+fn base_dir(app: &AppHandle) -> Result<PathBuf, String> {
+    Ok(cache::dir(app, "updates")?.join(BASE_DIR))
+}
+
+// Only the running version's installer is ever patched
+fn prune_bases(keep: &Path) {
+    let Some(entries) = keep.parent().and_then(|dir| std::fs::read_dir(dir).ok()) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        if entry.path() != keep {
+            let _ = std::fs::remove_file(entry.path());
+        }
+    }
+}
+
+/// Install the update saved by `download_update`, checking its signature again first.
+/// Fails if nothing was downloaded or the file has gone missing. After a restart this
+/// needs the network once more, to look the update up.
+#[cfg(desktop)]
+#[command]
+pub async fn install_downloaded_update(app: AppHandle) -> Result<(), String> {
+    use tauri_plugin_updater::UpdaterExt;
+
+    let Some(staged) = settings::get::<StagedUpdate>(&app, STAGED_KEY) else {
+        return Err("No update has been downloaded".to_string());
+    };
+    if !staged.path.is_file() {
+        settings::delete(&app, STAGED_KEY)?;
+        return Err("The downloaded update is missing; download it again".to_string());
+    }
+
+    // Verify the very bytes that get installed, since the file sat on disk
+    let bytes = std::fs::read(&staged.path)
+        .map_err(|e| format!("Failed to read the downloaded update: {}", e))?;
+    let verified = check(
+        updater_pubkey(&app),
+        &mut bytes.as_slice(),
+        &staged.signature,
+    );
+    if !verified.valid {
+        let _ = std::fs::remove_file(&staged.path);
+        settings::delete(&app, STAGED_KEY)?;
+        return Err(format!(
+            "The downloaded update failed verification: {}",
+            verified.error.unwrap_or_default()
+        ));
+    }
+
+    let kept = downloaded_update().lock().unwrap().take();
+    let update = match kept {
+        Some(update) if update.version == staged.version => update,
+        _ => app
+            .updater()
+            .map_err(|e| e.to_string())?
+            .check()
+            .await
+            .map_err(|e| e.to_string())?
+            .filter(|update| update.version == staged.version)
+            .ok_or_else(|| {
+                format!(
+                    "Version {} is no longer offered as an update",
+                    staged.version
+                )
+            })?,
+    };
+
+    // Keep the installer as the base for the next delta. This has to happen first, since
+    // installing exits the app on Windows.
+    let base = base_dir(&app)?.join(&staged.version);
+    if let Err(e) = base
+        .parent()
+        .map_or(Ok(()), std::fs::create_dir_all)
+        .and_then(|_| std::fs::write(&base, &bytes))
+    {
+        log::warn!("Failed to keep the installer for delta updates: {}", e);
+    }
+    if let Err(e) = update.install(bytes) {
+        let _ = std::fs::remove_file(&base);
+        return Err(e.to_string());
+    }
+
+    let _ = std::fs::remove_file(&staged.path);
+    settings::delete(&app, STAGED_KEY)
+}
+
+/// Check an update artifact against a signature with the updater's public key, the
+/// same way the updater does before installing, so sideloaded artifacts can be vetted
+/// first. `signature` is what goes in the update manifest (the base64 `.sig` file),
+/// or the `.sig` file's contents.
+#[command]
+pub async fn verify_update_signature(
+    app: AppHandle,
+    artifact_path: PathBuf,
+    signature: String,
+) -> Result<SignatureCheck, String> {
+    let pubkey = updater_pubkey(&app);
+    tauri::async_runtime::spawn_blocking(move || {
+        let mut artifact = File::open(&artifact_path)
+            .map_err(|e| format!("Failed to open {}: {}", artifact_path.display(), e))?;
+        Ok(check(pubkey, &mut artifact, &signature))
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+fn updater_pubkey(app: &AppHandle) -> Option<String> {
+    app.config()
+        .plugins
+        .0
+        .get("updater")
+        .and_then(|updater| updater.get("pubkey"))
+        .and_then(|pubkey| pubkey.as_str())
+        .map(str::to_string)
+}
+
+fn check(pubkey: Option<String>, artifact: &mut dyn Read, signature: &str) -> SignatureCheck {
+    let mut result = SignatureCheck {
+        valid: false,
+        key_id: None,
+        signer_key_id: None,
+        trusted_comment: None,
+        error: None,
+    };
+
+    let Some(pubkey) = pubkey.as_deref().and_then(decode_base64) else {
+        result.error = Some("The updater has no valid public key".to_string());
+        return result;
+    };
+    result.key_id = key_id(&pubkey);
+    let public_key = match PublicKey::decode(&pubkey) {
+        Ok(key) => key,
+        Err(e) => {
+            result.error = Some(format!("Invalid updater public key: {}", e));
+            return result;
+        }
+    };
+
+    let signature = if signature.trim_start().starts_with("untrusted comment:") {
+        signature.to_string()
+    } else {
+        match decode_base64(signature) {
+            Some(signature) => signature,
+            None => {
+                result.error = Some("The signature isn't valid base64".to_string());
+                return result;
+            }
+        }
+    };
+    result.signer_key_id = key_id(&signature);
+    let signature = match Signature::decode(&signature) {
+        Ok(signature) => signature,
+        Err(e) => {
+            result.error = Some(format!("Invalid signature: {}", e));
+            return result;
+        }
+    };
+    result.trusted_comment = Some(signature.trusted_comment().to_string());
+
+    match verify(&public_key, &signature, artifact) {
+        Ok(()) => result.valid = true,
+        Err(e) => result.error = Some(e),
+    }
+    result
+}
+
+// Stream the artifact through the verifier, which only legacy signatures can't do
+fn verify(
+    public_key: &PublicKey,
+    signature: &Signature,
+    artifact: &mut dyn Read,
+) -> Result<(), String> {
+    let read_error = |e: std::io::Error| format!("Failed to read the artifact: {}", e);
+
+    let result = match public_key.verify_stream(signature) {
+        Ok(mut verifier) => {
+            let mut buffer = vec![0; 64 * 1024];
+            loop {
+                let read = artifact.read(&mut buffer).map_err(read_error)?;
+                if read == 0 {
+                    break;
+                }
+                verifier.update(&buffer[..read]);
+            }
+            verifier.finalize()
+        }
+        Err(MinisignError::UnsupportedLegacyMode) => {
+            let mut contents = Vec::new();
+            artifact.read_to_end(&mut contents).map_err(read_error)?;
+            public_key.verify(&contents, signature, true)
+        }
+        Err(e) => Err(e),
+    };
+    result.map_err(|e| match e {
+        MinisignError::UnexpectedKeyId => {
+            "The artifact was signed with a different key".to_string()
+        }
+        MinisignError::InvalidSignature => "The signature doesn't match the artifact".to_string(),
+        e => e.to_string(),
+    })
+}
+
+// Semver, with an optional leading "v"
+fn parse_version(version: &str) -> Result<Version, String> {
+    let trimmed = version.trim();
+    Version::parse(trimmed.strip_prefix('v').unwrap_or(trimmed))
+        .map_err(|e| format!("Invalid version \"{}\": {}", version, e))
+}
+
+fn decode_base64(text: &str) -> Option<String> {
+    let bytes = base64::engine::general_purpose::STANDARD
+        .decode(text.trim())
+        .ok()?;
+    String::from_utf8(bytes).ok()
+}
+
+// Key id from the base64 line of a minisign key or signature file. It sits after the
+// two-byte algorithm and is printed as a little-endian number.
+fn key_id(file: &str) -> Option<String> {
+    let encoded = file.lines().nth(1)?;
+    let bytes = base64::engine::general_purpose::STANDARD
+        .decode(encoded.trim())
+        .ok()?;
+    let id: [u8; 8] = bytes.get(2..10)?.try_into().ok()?;
+    Some(format!("{:016X}", u64::from_le_bytes(id)))
+}
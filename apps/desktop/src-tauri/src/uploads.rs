@@ -0,0 +1,245 @@
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use tauri::{command, AppHandle, Emitter};
+
+use crate::{backoff, bandwidth, http, settings};
+
+const PENDING_KEY: &str = "uploads.pending";
+
+/// Bytes sent per request; a dropped connection loses at most this much
+const CHUNK_SIZE: usize = 4 * 1024 * 1024;
+
+/// Failed chunks are retried this many times, with the shared backoff, before the upload
+/// is left for `resume_upload`
+const MAX_RETRIES: u32 = 5;
+
+/// Version of the tus protocol spoken to the server
+const TUS_VERSION: &str = "1.0.0";
+
+static NEXT_UPLOAD: AtomicU64 = AtomicU64::new(0);
+
+// Ids of uploads with a task sending them; cancelling removes the id, which stops it
+fn running() -> &'static Mutex<HashSet<String>> {
+    static RUNNING: OnceLock<Mutex<HashSet<String>>> = OnceLock::new();
+    RUNNING.get_or_init(|| Mutex::new(HashSet::new()))
+}
+
+/// An upload that hasn't finished, kept across launches
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PendingUpload {
+    id: String,
+    path: PathBuf,
+    url: String,
+    size: u64,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct UploadProgress<'a> {
+    id: &'a str,
+    uploaded: u64,
+    total: u64,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct UploadFailed<'a> {
+    id: &'a str,
+    error: &'a str,
+}
+
+/// Upload a file in chunks to `url`, an upload the server has already created with the
+/// tus protocol (`PATCH` with `Upload-Offset`, `HEAD` to ask how much arrived). Returns
+/// the upload's id straight away; `upload-progress`, then `upload-finished` or
+/// `upload-failed`, follow. Interrupted uploads are kept, including across restarts,
+/// and `resume_upload` carries on from what the server has.
+#[command]
+pub fn start_upload(app: AppHandle, path: PathBuf, url: String) -> Result<String, String> {
+    let parsed = reqwest::Url::parse(&url).map_err(|e| format!("Invalid URL: {}", e))?;
+    if parsed.scheme() != "https" {
+        return Err("Files can only be uploaded over https".to_string());
+    }
+    let size = std::fs::metadata(&path)
+        .map_err(|e| format!("Failed to open {}: {}", path.display(), e))?
+        .len();
+    let millis = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis();
+    let upload = PendingUpload {
+        id: format!("{}-{}", millis, NEXT_UPLOAD.fetch_add(1, Ordering::Relaxed)),
+        path,
+        url,
+        size,
+    };
+    let mut pending = pending(&app);
+    pending.insert(upload.id.clone(), upload.clone());
+    settings::set(&app, PENDING_KEY, pending)?;
+
+    let id = upload.id.clone();
+    spawn(app, upload);
+    Ok(id)
+}
+
+/// Carry on with an interrupted upload from the offset the server reports
+#[command]
+pub fn resume_upload(app: AppHandle, id: String) -> Result<(), String> {
+    let upload = pending(&app)
+        .remove(&id)
+        .ok_or_else(|| format!("No upload with id {}", id))?;
+    if running().lock().unwrap().contains(&id) {
+        return Err("The upload is already running".to_string());
+    }
+    spawn(app, upload);
+    Ok(())
+}
+
+/// Uploads that haven't finished, whether running or interrupted
+#[command]
+pub fn pending_uploads(app: AppHandle) -> Vec<PendingUpload> {
+    pending(&app).into_values().collect()
+}
+
+/// Stop an upload and forget it. What the server already received is left to it.
+#[command]
+pub fn cancel_upload(app: AppHandle, id: String) -> Result<(), String> {
+    running().lock().unwrap().remove(&id);
+    let mut pending = pending(&app);
+    if pending.remove(&id).is_none() {
+        return Err(format!("No upload with id {}", id));
+    }
+    settings::set(&app, PENDING_KEY, pending)
+}
+
+fn pending(app: &AppHandle) -> HashMap<String, PendingUpload> {
+    settings::get(app, PENDING_KEY).unwrap_or_default()
+}
+
+fn spawn(app: AppHandle, upload: PendingUpload) {
+    running().lock().unwrap().insert(upload.id.clone());
+    tauri::async_runtime::spawn(async move {
+        let result = send(&app, &upload).await;
+        // Cancelled uploads have already been removed
+        if !running().lock().unwrap().remove(&upload.id) {
+            return;
+        }
+        match result {
+            Ok(()) => {
+                let mut pending = pending(&app);
+                pending.remove(&upload.id);
+                if let Err(e) = settings::set(&app, PENDING_KEY, pending) {
+                    log::warn!("Failed to forget finished upload {}: {}", upload.id, e);
+                }
+                let _ = app.emit("upload-finished", &upload.id);
+            }
+            Err(e) => {
+                log::warn!("Upload {} failed: {}", upload.id, e);
+                let failed = UploadFailed {
+                    id: &upload.id,
+                    error: &e,
+                };
+                let _ = app.emit("upload-failed", failed);
+            }
+        }
+    });
+}
+
+async fn send(app: &AppHandle, upload: &PendingUpload) -> Result<(), String> {
+    let mut file = File::open(&upload.path)
+        .map_err(|e| format!("Failed to open {}: {}", upload.path.display(), e))?;
+    let size = file.metadata().map_err(|e| e.to_string())?.len();
+    if size != upload.size {
+        return Err(format!(
+            "{} changed since the upload started",
+            upload.path.display()
+        ));
+    }
+
+    http::check_url(app, &upload.url, true).await?;
+    let client = http::client(app)?;
+    let mut offset = server_offset(&client, upload).await?;
+    let mut failures = 0;
+    let mut chunk = vec![0; CHUNK_SIZE];
+    while offset < upload.size {
+        if !running().lock().unwrap().contains(&upload.id) {
+            return Err("cancelled".to_string());
+        }
+        let length = (upload.size - offset).min(CHUNK_SIZE as u64) as usize;
+        file.seek(SeekFrom::Start(offset))
+            .and_then(|_| file.read_exact(&mut chunk[..length]))
+            .map_err(|e| format!("Failed to read {}: {}", upload.path.display(), e))?;
+
+        bandwidth::throttle_upload(length).await;
+        match send_chunk(&client, upload, offset, chunk[..length].to_vec()).await {
+            Ok(next) => {
+                offset = next;
+                failures = 0;
+                let progress = UploadProgress {
+                    id: &upload.id,
+                    uploaded: offset,
+                    total: upload.size,
+                };
+                let _ = app.emit("upload-progress", progress);
+            }
+            Err(e) if failures < MAX_RETRIES => {
+                log::info!("Retrying upload {}: {}", upload.id, e);
+                tokio::time::sleep(Duration::from_millis(backoff::delay_ms(failures))).await;
+                failures += 1;
+                // Part of the chunk may have arrived before the connection dropped
+                offset = server_offset(&client, upload).await.unwrap_or(offset);
+            }
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(())
+}
+
+// How much of the file the server has
+async fn server_offset(client: &reqwest::Client, upload: &PendingUpload) -> Result<u64, String> {
+    let response = client
+        .head(&upload.url)
+        .header("Tus-Resumable", TUS_VERSION)
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| format!("Failed to reach the upload server: {}", e))?;
+    upload_offset(&response)
+}
+
+// Returns the offset the server acknowledged
+async fn send_chunk(
+    client: &reqwest::Client,
+    upload: &PendingUpload,
+    offset: u64,
+    chunk: Vec<u8>,
+) -> Result<u64, String> {
+    let response = client
+        .patch(&upload.url)
+        .header("Tus-Resumable", TUS_VERSION)
+        .header("Content-Type", "application/offset+octet-stream")
+        .header("Upload-Offset", offset)
+        .header("Upload-Length", upload.size)
+        .body(chunk)
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| format!("Failed to upload: {}", e))?;
+    upload_offset(&response)
+}
+
+fn upload_offset(response: &reqwest::Response) -> Result<u64, String> {
+    response
+        .headers()
+        .get("Upload-Offset")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse().ok())
+        .ok_or_else(|| "The upload server didn't say how much it received".to_string())
+}
@@ -0,0 +1,122 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use sha2::{Digest, Sha256};
+use tauri::{command, AppHandle};
+
+use crate::{cache, transcode};
+
+/// Most buckets a waveform can have, which is roughly the waveform's width in bars
+const MAX_BUCKETS: usize = 2000;
+
+/// Audio is decoded to mono at this rate, which is plenty to find peaks
+const SAMPLE_RATE: u32 = 8000;
+
+/// Files larger than this aren't voice messages
+const MAX_FILE_SIZE: u64 = 200 * 1024 * 1024;
+
+/// Peak amplitude of each of `buckets` equal slices of an audio file, scaled so the
+/// loudest is 1.0, for drawing a voice message's waveform. Only the first 15 minutes
+/// are read. Results are cached by the file's path, size and modification time, so a
+/// file is only decoded again once it changes.
+#[command]
+pub async fn waveform_peaks(
+    app: AppHandle,
+    path: PathBuf,
+    buckets: usize,
+) -> Result<Vec<f32>, String> {
+    if buckets == 0 || buckets > MAX_BUCKETS {
+        return Err(format!("buckets must be between 1 and {}", MAX_BUCKETS));
+    }
+    // Kept with the attachments so clearing those clears their waveforms too
+    let dir = cache::dir(&app, "attachments")?.join("waveforms");
+    tauri::async_runtime::spawn_blocking(move || {
+        let cached = dir.join(format!("{}-{}.json", cache_key(&path)?, buckets));
+
+        if let Some(peaks) = fs::read(&cached)
+            .ok()
+            .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+        {
+            return Ok(peaks);
+        }
+
+        let peaks = peaks(&decode(&path)?, buckets);
+        let saved = fs::create_dir_all(&dir)
+            .and_then(|_| fs::write(&cached, serde_json::to_vec(&peaks).unwrap_or_default()));
+        if let Err(e) = saved {
+            log::warn!("Failed to cache waveform for {}: {}", path.display(), e);
+        }
+        Ok(peaks)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+// SHA-256 rather than std's hasher, whose output can change between Rust releases and
+// would throw the cache away with it
+fn cache_key(path: &Path) -> Result<String, String> {
+    let metadata =
+        fs::metadata(path).map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+    if metadata.len() > MAX_FILE_SIZE {
+        return Err(format!("{} is too large", path.display()));
+    }
+    let modified = metadata
+        .modified()
+        .ok()
+        .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |modified| modified.as_nanos());
+
+    let mut hasher = Sha256::new();
+    hasher.update(path.as_os_str().as_encoded_bytes());
+    hasher.update(metadata.len().to_le_bytes());
+    hasher.update(modified.to_le_bytes());
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect())
+}
+
+// Mono 16-bit samples, going through ffmpeg so Opus and WebM recordings decode too
+fn decode(path: &Path) -> Result<Vec<i16>, String> {
+    let output = transcode::tool("ffmpeg")
+        .args(["-nostdin", "-hide_banner", "-loglevel", "error", "-i"])
+        .arg(path)
+        .args(["-t", &transcode::MAX_DURATION_SECS.to_string()])
+        .args(["-vn", "-ac", "1", "-ar", &SAMPLE_RATE.to_string()])
+        .args(["-f", "s16le", "pipe:1"])
+        .output()
+        .map_err(|e| format!("ffmpeg is required to read audio: {}", e))?;
+    if !output.status.success() || output.stdout.len() < 2 {
+        return Err(format!("Unsupported audio file {}", path.display()));
+    }
+    Ok(output
+        .stdout
+        .chunks_exact(2)
+        .map(|pair| i16::from_le_bytes([pair[0], pair[1]]))
+        .collect())
+}
+
+// Short files get zeros in the buckets past their last sample
+fn peaks(samples: &[i16], buckets: usize) -> Vec<f32> {
+    let mut peaks: Vec<f32> = (0..buckets)
+        .map(|bucket| {
+            let start = bucket * samples.len() / buckets;
+            let end = (bucket + 1) * samples.len() / buckets;
+            samples[start..end]
+                .iter()
+                .map(|sample| sample.unsigned_abs())
+                .max()
+                .unwrap_or(0) as f32
+        })
+        .collect();
+
+    let loudest = peaks.iter().copied().fold(0.0, f32::max);
+    if loudest > 0.0 {
+        for peak in &mut peaks {
+            *peak /= loudest;
+        }
+    }
+    peaks
+}
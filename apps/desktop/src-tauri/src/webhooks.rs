@@ -0,0 +1,402 @@
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::OsRng;
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sha2::Sha256;
+use tauri::{command, AppHandle, Emitter, EventId, Listener};
+
+use crate::{backoff, http, settings};
+
+const WEBHOOKS_KEY: &str = "webhooks";
+
+/// Keychain account of a webhook's secret is this prefix followed by its id, under the
+/// app's identifier as the service
+const SECRET_ACCOUNT_PREFIX: &str = "webhook-secret:";
+
+/// Deliveries are tried this many times, with the shared backoff, before they're dropped
+const MAX_ATTEMPTS: u32 = 5;
+
+/// How long each delivery attempt may take
+const DELIVERY_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Events webhooks can be sent for. Anything else could leak what shouldn't leave the
+/// machine (clipboard contents, log lines, the local API) or loop, like `log-appended`
+/// firing for the log lines deliveries write themselves.
+const EVENTS: &[&str] = &[
+    "auto-status-changed",
+    "download-batch-finished",
+    "download-corrupt",
+    "download-verified",
+    "locked",
+    "presenting-changed",
+    "quiet-hours-ended",
+    "quiet-hours-started",
+    "snooze-ended",
+    "snooze-started",
+    "status-changed",
+    "status-expired",
+    "system-resume",
+    "system-suspend",
+    "unlocked",
+    "update-download-state",
+    "upload-failed",
+    "upload-finished",
+];
+
+// Listeners forwarding events to each webhook, by its id
+fn listeners() -> &'static Mutex<HashMap<String, Vec<EventId>>> {
+    static LISTENERS: OnceLock<Mutex<HashMap<String, Vec<EventId>>>> = OnceLock::new();
+    LISTENERS.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Webhook {
+    id: String,
+    url: String,
+    events: Vec<String>,
+    /// Only in webhooks saved before secrets moved to the keychain
+    #[serde(default, rename = "secret", skip_serializing)]
+    legacy_secret: Option<String>,
+}
+
+/// What `register_webhook` returns: the only time the secret leaves the keychain
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NewWebhook {
+    #[serde(flatten)]
+    webhook: Webhook,
+    /// Key for the `X-Hazel-Signature` HMAC, used as the text it is
+    secret: String,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct WebhookDelivered<'a> {
+    webhook_id: &'a str,
+    delivery_id: &'a str,
+    event: &'a str,
+    status: u16,
+    attempts: u32,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct WebhookFailed<'a> {
+    webhook_id: &'a str,
+    delivery_id: &'a str,
+    event: &'a str,
+    attempts: u32,
+    error: &'a str,
+}
+
+/// POST to an https `url` whenever one of `events` is emitted, with a JSON body of
+/// `{ id, event, payload, timestamp }` signed by `X-Hazel-Signature: sha256=<hex HMAC>`
+/// using the returned webhook's secret. Failed deliveries are retried with the shared
+/// backoff; each ends with `webhook-delivered` or `webhook-failed`. Webhooks are kept
+/// across launches. Only the events in `webhook_events` can be sent.
+///
+/// The secret is kept in the OS keychain and only returned here, so save it now.
+#[command]
+pub async fn register_webhook(
+    app: AppHandle,
+    url: String,
+    events: Vec<String>,
+) -> Result<NewWebhook, String> {
+    let url = http::check_url(&app, &url, true).await?.to_string();
+    let mut events: Vec<String> = events
+        .iter()
+        .map(|event| event.trim().to_string())
+        .collect();
+    events.sort();
+    events.dedup();
+    if events.is_empty() {
+        return Err("A webhook needs at least one event".to_string());
+    }
+    if let Some(event) = events
+        .iter()
+        .find(|event| !EVENTS.contains(&event.as_str()))
+    {
+        return Err(format!("Webhooks can't be sent for \"{}\"", event));
+    }
+
+    let webhook = Webhook {
+        id: random_hex(8),
+        url,
+        events,
+        legacy_secret: None,
+    };
+    let secret = random_hex(32);
+    let saved = {
+        let (app, id, secret) = (app.clone(), webhook.id.clone(), secret.clone());
+        tauri::async_runtime::spawn_blocking(move || save_secret(&app, &id, &secret))
+            .await
+            .map_err(|e| e.to_string())?
+    };
+    saved?;
+    let mut webhooks = stored(&app);
+    webhooks.insert(webhook.id.clone(), webhook.clone());
+    if let Err(e) = settings::set(&app, WEBHOOKS_KEY, webhooks) {
+        let _ = forget_secret(&app.config().identifier, &webhook.id);
+        return Err(e);
+    }
+    listen(&app, &webhook, &secret);
+    Ok(NewWebhook { webhook, secret })
+}
+
+/// The events `register_webhook` accepts
+#[command]
+pub fn webhook_events() -> Vec<&'static str> {
+    EVENTS.to_vec()
+}
+
+/// The registered webhooks, without their secrets
+#[command]
+pub fn list_webhooks(app: AppHandle) -> Vec<Webhook> {
+    let mut webhooks: Vec<Webhook> = stored(&app).into_values().collect();
+    webhooks.sort_by(|a, b| a.url.cmp(&b.url).then_with(|| a.id.cmp(&b.id)));
+    webhooks
+}
+
+/// Stop sending events to a webhook, including retries of deliveries already underway
+#[command]
+pub fn delete_webhook(app: AppHandle, id: String) -> Result<(), String> {
+    let mut webhooks = stored(&app);
+    if webhooks.remove(&id).is_none() {
+        return Err(format!("No webhook with id {}", id));
+    }
+    settings::set(&app, WEBHOOKS_KEY, webhooks)?;
+    for listener in listeners().lock().unwrap().remove(&id).unwrap_or_default() {
+        app.unlisten(listener);
+    }
+    if let Err(e) = forget_secret(&app.config().identifier, &id) {
+        log::warn!("Failed to delete the secret of webhook {}: {}", id, e);
+    }
+    Ok(())
+}
+
+/// Start forwarding events to the webhooks registered in earlier launches, moving
+/// secrets still in the settings store into the keychain
+pub fn restore_webhooks(app: &AppHandle) {
+    // The keychain can ask the user for access, so not on the main thread
+    let app = app.clone();
+    thread::spawn(move || {
+        let mut webhooks = stored(&app);
+        // Saving drops the secrets from the store, so only once they're all moved
+        let mut all_moved = true;
+        let mut any_moved = false;
+        for webhook in webhooks.values_mut() {
+            let secret = match webhook.legacy_secret.take() {
+                Some(secret) => {
+                    match save_secret(&app, &webhook.id, &secret) {
+                        Ok(()) => any_moved = true,
+                        Err(e) => {
+                            log::warn!("Failed to move webhook {}'s secret: {}", webhook.id, e);
+                            all_moved = false;
+                        }
+                    }
+                    secret
+                }
+                None => match load_secret(&app, &webhook.id) {
+                    Ok(secret) => secret,
+                    Err(e) => {
+                        log::warn!("Not sending webhook {}: {}", webhook.id, e);
+                        continue;
+                    }
+                },
+            };
+            listen(&app, webhook, &secret);
+        }
+        // Read again in case one was registered meanwhile
+        if any_moved && all_moved {
+            if let Err(e) = settings::set(&app, WEBHOOKS_KEY, stored(&app)) {
+                log::warn!("Failed to save webhooks: {}", e);
+            }
+        }
+    });
+}
+
+/// Delete every webhook's secret from the keychain, e.g. when wiping local data
+pub fn forget_secrets(app: &AppHandle) {
+    for id in stored(app).keys() {
+        if let Err(e) = forget_secret(&app.config().identifier, id) {
+            log::warn!("Failed to delete the secret of webhook {}: {}", id, e);
+        }
+    }
+}
+
+fn stored(app: &AppHandle) -> HashMap<String, Webhook> {
+    settings::get(app, WEBHOOKS_KEY).unwrap_or_default()
+}
+
+fn secret_entry(identifier: &str, id: &str) -> Result<keyring::Entry, String> {
+    keyring::Entry::new(identifier, &format!("{}{}", SECRET_ACCOUNT_PREFIX, id))
+        .map_err(|e| e.to_string())
+}
+
+fn save_secret(app: &AppHandle, id: &str, secret: &str) -> Result<(), String> {
+    secret_entry(&app.config().identifier, id)?
+        .set_password(secret)
+        .map_err(|e| format!("Failed to save the webhook secret to the keychain: {}", e))
+}
+
+fn load_secret(app: &AppHandle, id: &str) -> Result<String, String> {
+    secret_entry(&app.config().identifier, id)?
+        .get_password()
+        .map_err(|e| format!("Failed to read the webhook secret from the keychain: {}", e))
+}
+
+fn forget_secret(identifier: &str, id: &str) -> Result<(), String> {
+    match secret_entry(identifier, id)?.delete_credential() {
+        Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+// Webhooks saved before an event was taken off the list don't get it any more
+fn listen(app: &AppHandle, webhook: &Webhook, secret: &str) {
+    let ids = webhook
+        .events
+        .iter()
+        .filter(|event| EVENTS.contains(&event.as_str()))
+        .map(|event| {
+            let app_handle = app.clone();
+            let webhook = webhook.clone();
+            let secret = secret.to_string();
+            let name = event.clone();
+            app.listen_any(event, move |event| {
+                let payload = serde_json::from_str(event.payload()).unwrap_or(Value::Null);
+                let app = app_handle.clone();
+                let webhook = webhook.clone();
+                let secret = secret.clone();
+                let name = name.clone();
+                tauri::async_runtime::spawn(async move {
+                    deliver(&app, &webhook, &secret, &name, payload).await;
+                });
+            })
+        })
+        .collect();
+    listeners().lock().unwrap().insert(webhook.id.clone(), ids);
+}
+
+async fn deliver(app: &AppHandle, webhook: &Webhook, secret: &str, event: &str, payload: Value) {
+    let delivery_id = random_hex(8);
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64;
+    let body = json!({
+        "id": delivery_id,
+        "event": event,
+        "payload": payload,
+        "timestamp": timestamp,
+    })
+    .to_string();
+    let signature = match sign(secret, &body) {
+        Ok(signature) => signature,
+        Err(e) => return failed(app, webhook, &delivery_id, event, 0, &e),
+    };
+
+    let mut attempts = 0;
+    let error = loop {
+        attempts += 1;
+        match send(app, webhook, event, &delivery_id, &signature, &body).await {
+            Ok(status) => {
+                let delivered = WebhookDelivered {
+                    webhook_id: &webhook.id,
+                    delivery_id: &delivery_id,
+                    event,
+                    status,
+                    attempts,
+                };
+                let _ = app.emit("webhook-delivered", delivered);
+                return;
+            }
+            Err((e, retry)) if retry && attempts < MAX_ATTEMPTS => {
+                log::info!("Retrying webhook delivery {}: {}", delivery_id, e);
+                tokio::time::sleep(Duration::from_millis(backoff::delay_ms(attempts - 1))).await;
+                if !stored(app).contains_key(&webhook.id) {
+                    break "The webhook was deleted".to_string();
+                }
+            }
+            Err((e, _)) => break e,
+        }
+    };
+    failed(app, webhook, &delivery_id, event, attempts, &error);
+}
+
+// The status on success; on failure, whether it's worth trying again
+async fn send(
+    app: &AppHandle,
+    webhook: &Webhook,
+    event: &str,
+    delivery_id: &str,
+    signature: &str,
+    body: &str,
+) -> Result<u16, (String, bool)> {
+    let response = http::client(app)
+        .map_err(|e| (e, false))?
+        .post(&webhook.url)
+        .timeout(DELIVERY_TIMEOUT)
+        .header("Content-Type", "application/json")
+        .header("X-Hazel-Event", event)
+        .header("X-Hazel-Delivery", delivery_id)
+        .header("X-Hazel-Signature", format!("sha256={}", signature))
+        .body(body.to_string())
+        .send()
+        .await
+        .map_err(|e| (format!("Failed to reach {}: {}", webhook.url, e), true))?;
+    let status = response.status();
+    if status.is_success() {
+        return Ok(status.as_u16());
+    }
+    // Anything else the server turned down won't go through on a retry
+    let retry = status.is_server_error() || status.as_u16() == 429;
+    Err((format!("{} answered {}", webhook.url, status), retry))
+}
+
+fn failed(
+    app: &AppHandle,
+    webhook: &Webhook,
+    delivery_id: &str,
+    event: &str,
+    attempts: u32,
+    error: &str,
+) {
+    log::warn!(
+        "Webhook delivery {} for {} failed: {}",
+        delivery_id,
+        event,
+        error
+    );
+    let failed = WebhookFailed {
+        webhook_id: &webhook.id,
+        delivery_id,
+        event,
+        attempts,
+        error,
+    };
+    let _ = app.emit("webhook-failed", failed);
+}
+
+fn sign(secret: &str, body: &str) -> Result<String, String> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).map_err(|e| e.to_string())?;
+    mac.update(body.as_bytes());
+    Ok(hex(&mac.finalize().into_bytes()))
+}
+
+fn random_hex(bytes: usize) -> String {
+    let mut random = vec![0; bytes];
+    OsRng.fill_bytes(&mut random);
+    hex(&random)
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
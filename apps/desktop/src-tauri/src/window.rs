@@ -0,0 +1,458 @@
+use serde::{Deserialize, Serialize};
+use tauri::{
+    command, AppHandle, Emitter, LogicalSize, Manager, Monitor, PhysicalPosition, PhysicalSize,
+    WebviewWindow,
+};
+#[cfg(target_os = "macos")]
+use tauri_plugin_decorum::WebviewWindowExt;
+
+use crate::settings;
+
+const SIZE_CONSTRAINTS_KEY: &str = "window.size_constraints";
+const NATIVE_DECORATIONS_KEY: &str = "window.native_decorations";
+const FOCUS_COMPOSER_SHORTCUT_KEY: &str = "window.focus_composer_shortcut";
+const TITLEBAR_COLOR_KEY: &str = "window.titlebar_color";
+
+pub const DEFAULT_FOCUS_COMPOSER_ACCELERATOR: &str = "CmdOrCtrl+Shift+K";
+
+/// Traffic light position for the overlay titlebar on macOS
+#[cfg(target_os = "macos")]
+pub const TRAFFIC_LIGHTS_INSET: (f32, f32) = (16.0, 20.0);
+
+/// Matches minWidth/minHeight in tauri.conf.json
+const DEFAULT_MIN_WIDTH: f64 = 940.0;
+const DEFAULT_MIN_HEIGHT: f64 = 500.0;
+
+/// Stand-in for "no max" on one axis, since the window API only takes both dimensions
+const UNBOUNDED: f64 = 100_000.0;
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SizeConstraints {
+    pub min_width: f64,
+    pub min_height: f64,
+    pub max_width: Option<f64>,
+    pub max_height: Option<f64>,
+}
+
+impl Default for SizeConstraints {
+    fn default() -> Self {
+        Self {
+            min_width: DEFAULT_MIN_WIDTH,
+            min_height: DEFAULT_MIN_HEIGHT,
+            max_width: None,
+            max_height: None,
+        }
+    }
+}
+
+impl SizeConstraints {
+    fn validate(&self) -> Result<(), String> {
+        let sizes = [self.min_width, self.min_height];
+        let maxes = self.max_width.into_iter().chain(self.max_height);
+        if !sizes.into_iter().chain(maxes).all(f64::is_finite) {
+            return Err("Sizes must be finite numbers".into());
+        }
+        if self.min_width <= 0.0 || self.min_height <= 0.0 {
+            return Err("Minimum size must be positive".into());
+        }
+        if self.max_width.is_some_and(|max| self.min_width > max) {
+            return Err("Minimum width is greater than maximum width".into());
+        }
+        if self.max_height.is_some_and(|max| self.min_height > max) {
+            return Err("Minimum height is greater than maximum height".into());
+        }
+        Ok(())
+    }
+
+    fn apply(&self, window: &WebviewWindow) -> Result<(), String> {
+        window
+            .set_min_size(Some(LogicalSize::new(self.min_width, self.min_height)))
+            .map_err(|e| e.to_string())?;
+        let max = match (self.max_width, self.max_height) {
+            (None, None) => None,
+            (w, h) => Some(LogicalSize::new(
+                w.unwrap_or(UNBOUNDED),
+                h.unwrap_or(UNBOUNDED),
+            )),
+        };
+        window.set_max_size(max).map_err(|e| e.to_string())
+    }
+}
+
+pub fn main_window(app: &AppHandle) -> Result<WebviewWindow, String> {
+    app.get_webview_window("main")
+        .ok_or_else(|| "Main window not found".to_string())
+}
+
+/// Unminimize, show and focus a window, e.g. when it has been hidden to the tray
+pub fn show_and_focus(window: &WebviewWindow) -> Result<(), String> {
+    window.unminimize().map_err(|e| e.to_string())?;
+    window.show().map_err(|e| e.to_string())?;
+    window.set_focus().map_err(|e| e.to_string())
+}
+
+/// Set the main window's minimum and maximum size. Pass null for either max to leave it unbounded.
+#[command]
+pub fn set_size_constraints(
+    app: AppHandle,
+    min_w: f64,
+    min_h: f64,
+    max_w: Option<f64>,
+    max_h: Option<f64>,
+) -> Result<(), String> {
+    let constraints = SizeConstraints {
+        min_width: min_w,
+        min_height: min_h,
+        max_width: max_w,
+        max_height: max_h,
+    };
+    constraints.validate()?;
+    constraints.apply(&main_window(&app)?)?;
+    settings::set(&app, SIZE_CONSTRAINTS_KEY, constraints)
+}
+
+/// Apply the persisted size constraints (or the defaults) at startup
+pub fn restore_size_constraints(app: &AppHandle) {
+    let constraints = settings::get::<SizeConstraints>(app, SIZE_CONSTRAINTS_KEY)
+        .filter(|c| c.validate().is_ok())
+        .unwrap_or_default();
+    let result = main_window(app).and_then(|window| constraints.apply(&window));
+    if let Err(e) = result {
+        log::warn!("Failed to apply window size constraints: {}", e);
+    }
+}
+
+/// Switch between the native titlebar (`enabled: true`) and the frameless decorum overlay
+/// (`enabled: false`, the default). Emits `decorations-changed` so the frontend can hide its
+/// own titlebar controls while native ones are showing.
+///
+/// - **Windows:** toggles the OS frame. Decorum draws its own controls in the webview, so the
+///   frontend must hide them when native decorations are on to avoid duplicates.
+/// - **macOS:** the window stays decorated so the traffic lights never disappear; this switches
+///   the titlebar between the visible and overlay styles instead.
+/// - **Linux:** toggles the WM frame; decorum's webview controls apply as on Windows.
+#[command]
+pub fn set_decorations(app: AppHandle, enabled: bool) -> Result<(), String> {
+    apply_decorations(&main_window(&app)?, enabled)?;
+    settings::set(&app, NATIVE_DECORATIONS_KEY, enabled)?;
+    let _ = app.emit("decorations-changed", enabled);
+    Ok(())
+}
+
+#[cfg(target_os = "macos")]
+fn apply_decorations(window: &WebviewWindow, enabled: bool) -> Result<(), String> {
+    let style = if enabled {
+        tauri::TitleBarStyle::Visible
+    } else {
+        tauri::TitleBarStyle::Overlay
+    };
+    window
+        .set_title_bar_style(style)
+        .map_err(|e| e.to_string())?;
+    // Changing the style resets the traffic lights to their default position
+    if !enabled {
+        let (x, y) = TRAFFIC_LIGHTS_INSET;
+        window
+            .set_traffic_lights_inset(x, y)
+            .map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "macos"))]
+fn apply_decorations(window: &WebviewWindow, enabled: bool) -> Result<(), String> {
+    window.set_decorations(enabled).map_err(|e| e.to_string())
+}
+
+/// Re-apply the persisted titlebar choice at startup, after decorum has set up the overlay
+pub fn restore_decorations(app: &AppHandle) {
+    if !settings::get::<bool>(app, NATIVE_DECORATIONS_KEY).unwrap_or(false) {
+        return;
+    }
+    let result = main_window(app).and_then(|window| apply_decorations(&window, true));
+    if let Err(e) = result {
+        log::warn!("Failed to restore native decorations: {}", e);
+    }
+}
+
+/// A rectangle in physical pixels
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Rect {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Rect {
+    fn contains(&self, x: i32, y: i32) -> bool {
+        x >= self.x
+            && y >= self.y
+            && i64::from(x) < i64::from(self.x) + i64::from(self.width)
+            && i64::from(y) < i64::from(self.y) + i64::from(self.height)
+    }
+
+    /// Shrink and move this rect so it lies entirely within `bounds`
+    fn clamp_to(&self, bounds: &Rect) -> Rect {
+        let width = self.width.min(bounds.width);
+        let height = self.height.min(bounds.height);
+        let max_x = bounds.x.saturating_add((bounds.width - width) as i32);
+        let max_y = bounds.y.saturating_add((bounds.height - height) as i32);
+        Rect {
+            x: self.x.clamp(bounds.x, max_x),
+            y: self.y.clamp(bounds.y, max_y),
+            width,
+            height,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MonitorInfo {
+    pub name: Option<String>,
+    pub work_area: Rect,
+    pub scale_factor: f64,
+}
+
+impl From<&Monitor> for MonitorInfo {
+    fn from(monitor: &Monitor) -> Self {
+        let area = monitor.work_area();
+        Self {
+            name: monitor.name().cloned(),
+            work_area: Rect {
+                x: area.position.x,
+                y: area.position.y,
+                width: area.size.width,
+                height: area.size.height,
+            },
+            scale_factor: monitor.scale_factor(),
+        }
+    }
+}
+
+/// Window geometry and monitor layout, captured for reproducing layout bugs
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WindowSnapshot {
+    /// Outer position and inner size in physical pixels
+    pub bounds: Rect,
+    pub maximized: bool,
+    pub fullscreen: bool,
+    /// The monitor the window was on
+    pub monitor: Option<MonitorInfo>,
+    /// Every monitor connected at the time, for context in support tickets
+    pub monitors: Vec<MonitorInfo>,
+}
+
+/// Capture the main window's geometry and the current monitor configuration
+#[command]
+pub fn snapshot_window_state(app: AppHandle) -> Result<WindowSnapshot, String> {
+    let window = main_window(&app)?;
+    let position = window.outer_position().map_err(|e| e.to_string())?;
+    let size = window.inner_size().map_err(|e| e.to_string())?;
+    let monitor = window.current_monitor().map_err(|e| e.to_string())?;
+    let monitors = window.available_monitors().map_err(|e| e.to_string())?;
+
+    Ok(WindowSnapshot {
+        bounds: Rect {
+            x: position.x,
+            y: position.y,
+            width: size.width,
+            height: size.height,
+        },
+        maximized: window.is_maximized().map_err(|e| e.to_string())?,
+        fullscreen: window.is_fullscreen().map_err(|e| e.to_string())?,
+        monitor: monitor.as_ref().map(MonitorInfo::from),
+        monitors: monitors.iter().map(MonitorInfo::from).collect(),
+    })
+}
+
+/// Restore a snapshot taken with `snapshot_window_state`. The bounds are clamped to the
+/// matching monitor if it's still connected, otherwise to whichever monitor now holds the
+/// saved position (or the primary), so a snapshot from another setup can't strand the window.
+#[command]
+pub fn restore_window_state(app: AppHandle, snapshot: WindowSnapshot) -> Result<(), String> {
+    let window = main_window(&app)?;
+    let monitors: Vec<MonitorInfo> = window
+        .available_monitors()
+        .map_err(|e| e.to_string())?
+        .iter()
+        .map(MonitorInfo::from)
+        .collect();
+
+    let target = target_monitor(&snapshot, &monitors)
+        .or_else(|| {
+            window
+                .primary_monitor()
+                .ok()
+                .flatten()
+                .map(|m| MonitorInfo::from(&m))
+        })
+        .ok_or("No monitors available")?;
+    let bounds = snapshot.bounds.clamp_to(&target.work_area);
+
+    // Geometry can't be changed while maximized or fullscreen
+    window.set_fullscreen(false).map_err(|e| e.to_string())?;
+    window.unmaximize().map_err(|e| e.to_string())?;
+    window
+        .set_position(PhysicalPosition::new(bounds.x, bounds.y))
+        .map_err(|e| e.to_string())?;
+    window
+        .set_size(PhysicalSize::new(bounds.width, bounds.height))
+        .map_err(|e| e.to_string())?;
+
+    if snapshot.maximized {
+        window.maximize().map_err(|e| e.to_string())?;
+    }
+    if snapshot.fullscreen {
+        window.set_fullscreen(true).map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+fn target_monitor(snapshot: &WindowSnapshot, monitors: &[MonitorInfo]) -> Option<MonitorInfo> {
+    let by_name = snapshot
+        .monitor
+        .as_ref()
+        .and_then(|saved| saved.name.as_ref())
+        .and_then(|name| monitors.iter().find(|m| m.name.as_ref() == Some(name)));
+    let by_position = || {
+        monitors
+            .iter()
+            .find(|m| m.work_area.contains(snapshot.bounds.x, snapshot.bounds.y))
+    };
+    by_name.or_else(by_position).cloned()
+}
+
+/// Raise the main window (showing it first if hidden) and emit `focus-composer`
+/// so the frontend selects the message input
+#[command]
+pub fn focus_composer(app: AppHandle) -> Result<(), String> {
+    show_and_focus(&main_window(&app)?)?;
+    let _ = app.emit("focus-composer", ());
+    Ok(())
+}
+
+/// Accelerator for the "Focus Message Input" menu item
+pub fn focus_composer_accelerator(app: &AppHandle) -> String {
+    settings::get(app, FOCUS_COMPOSER_SHORTCUT_KEY)
+        .unwrap_or_else(|| DEFAULT_FOCUS_COMPOSER_ACCELERATOR.to_string())
+}
+
+/// Change the "Focus Message Input" accelerator. Pass null to restore the default.
+#[cfg(desktop)]
+#[command]
+pub fn set_focus_composer_shortcut(
+    app: AppHandle,
+    accelerator: Option<String>,
+) -> Result<(), String> {
+    let effective = accelerator
+        .clone()
+        .unwrap_or_else(|| DEFAULT_FOCUS_COMPOSER_ACCELERATOR.to_string());
+    crate::shortcuts::check_conflicts(&app, "focus_composer", &effective)?;
+
+    let item = crate::menu::find_item(&app, "focus_composer");
+    if let Some(item) = item.as_ref().and_then(|item| item.as_menuitem()) {
+        item.set_accelerator(Some(&effective))
+            .map_err(|e| e.to_string())?;
+    }
+    match accelerator {
+        Some(accelerator) => settings::set(&app, FOCUS_COMPOSER_SHORTCUT_KEY, accelerator),
+        None => settings::delete(&app, FOCUS_COMPOSER_SHORTCUT_KEY),
+    }
+}
+
+/// Tint the titlebar region with a `#rgb`, `#rrggbb` or `#rrggbbaa` color, e.g. per workspace
+/// in whitelabel deployments. Pass null to revert to the default.
+///
+/// The color is applied to decorum's titlebar element inside the webview, so it only shows
+/// while the overlay titlebar is in use:
+/// - **macOS:** the traffic lights sit on top of the tint; the native titlebar style set by
+///   `set_decorations(true)` is not tinted.
+/// - **Windows/Linux:** the tint sits behind decorum's webview controls; the OS frame shown
+///   with native decorations is not tinted.
+#[command]
+pub fn set_titlebar_color(app: AppHandle, hex: Option<String>) -> Result<(), String> {
+    let color = hex
+        .filter(|hex| !hex.is_empty())
+        .map(|hex| normalize_hex_color(&hex))
+        .transpose()?;
+    apply_titlebar_color(&main_window(&app)?, color.as_deref())?;
+    match color {
+        Some(color) => settings::set(&app, TITLEBAR_COLOR_KEY, color),
+        None => settings::delete(&app, TITLEBAR_COLOR_KEY),
+    }
+}
+
+/// Re-apply the persisted titlebar color, e.g. after the page reloads
+pub fn restore_titlebar_color(window: &WebviewWindow) {
+    let Some(color) = settings::get::<String>(window.app_handle(), TITLEBAR_COLOR_KEY) else {
+        return;
+    };
+    if let Err(e) = apply_titlebar_color(window, Some(&color)) {
+        log::warn!("Failed to restore titlebar color: {}", e);
+    }
+}
+
+fn normalize_hex_color(hex: &str) -> Result<String, String> {
+    let digits = hex
+        .strip_prefix('#')
+        .filter(|d| matches!(d.len(), 3 | 6 | 8) && d.chars().all(|c| c.is_ascii_hexdigit()))
+        .ok_or_else(|| format!("Invalid hex color \"{}\"", hex))?;
+    Ok(format!("#{}", digits.to_ascii_lowercase()))
+}
+
+fn apply_titlebar_color(window: &WebviewWindow, color: Option<&str>) -> Result<(), String> {
+    // `!important` because decorum sets an inline transparent background on the element
+    let css = color
+        .map(|c| {
+            format!(
+                "[data-tauri-decorum-tb] {{ background-color: {} !important; }}",
+                c
+            )
+        })
+        .unwrap_or_default();
+    let css = serde_json::to_string(&css).map_err(|e| e.to_string())?;
+    let script = format!(
+        r#"(function (css) {{
+            let style = document.getElementById("hazel-titlebar-color");
+            if (!style) {{
+                style = document.createElement("style");
+                style.id = "hazel-titlebar-color";
+                document.head.appendChild(style);
+            }}
+            style.textContent = css;
+        }})({})"#,
+        css
+    );
+    window.eval(&script).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn constraints(min: f64, max: Option<f64>) -> SizeConstraints {
+        SizeConstraints {
+            min_width: min,
+            min_height: min,
+            max_width: max,
+            max_height: max,
+        }
+    }
+
+    #[test]
+    fn validate_rejects_bad_sizes() {
+        assert!(constraints(400.0, None).validate().is_ok());
+        assert!(constraints(400.0, Some(800.0)).validate().is_ok());
+        assert!(constraints(0.0, None).validate().is_err());
+        assert!(constraints(800.0, Some(400.0)).validate().is_err());
+        assert!(constraints(f64::NAN, None).validate().is_err());
+        assert!(constraints(f64::INFINITY, None).validate().is_err());
+        assert!(constraints(400.0, Some(f64::NAN)).validate().is_err());
+        assert!(constraints(400.0, Some(f64::INFINITY)).validate().is_err());
+    }
+}
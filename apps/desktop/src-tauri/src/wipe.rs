@@ -0,0 +1,143 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use tauri::{command, AppHandle, Context, Emitter, Manager};
+
+use crate::{automation, data_dir, encryption, lifecycle, settings, webhooks};
+
+/// What `wipe_local_data` has to be called with, so it can't run by accident
+const CONFIRMATION: &str = "WIPE LOCAL DATA";
+
+/// Left in the settings store while a wipe is unfinished, listing the folders to delete
+const PENDING_KEY: &str = "wipe.pending";
+
+// Why the wipe resumed by `prepare` didn't finish, reported once logging is up
+static INCOMPLETE: OnceLock<String> = OnceLock::new();
+
+/// Delete everything Hazel keeps on this machine, e.g. when it's shared or lost: the
+/// message store, search index, caches, settings (the frontend's too), webview storage
+/// and the keychain entries, then restart as a fresh install. `confirmation` must be
+/// "WIPE LOCAL DATA". Emits `data-wiped` just before restarting.
+///
+/// Files in use can't be deleted while the app runs, so the folders go on the next
+/// launch, before anything opens them. Until that's done the settings store keeps only a
+/// marker listing them, so a wipe cut short carries on at the following launch, and
+/// calling this again is harmless.
+#[command]
+pub fn wipe_local_data(app: AppHandle, confirmation: String) -> Result<(), String> {
+    if confirmation != CONFIRMATION {
+        return Err(format!("Pass \"{}\" to confirm the wipe", CONFIRMATION));
+    }
+    let mut dirs = settings::get::<Vec<PathBuf>>(&app, PENDING_KEY).unwrap_or_default();
+    let path = app.path();
+    for dir in [
+        data_dir::data_dir(&app),
+        data_dir::cache_dir(&app),
+        path.app_data_dir().map_err(|e| e.to_string()),
+        path.app_local_data_dir().map_err(|e| e.to_string()),
+        path.app_cache_dir().map_err(|e| e.to_string()),
+        path.app_config_dir().map_err(|e| e.to_string()),
+    ] {
+        let dir = dir?;
+        if !dirs.contains(&dir) {
+            dirs.push(dir);
+        }
+    }
+    // Found through the webhooks in the settings, so before those go
+    webhooks::forget_secrets(&app);
+    settings::clear_all(&app)?;
+    settings::set(&app, PENDING_KEY, &dirs)?;
+
+    if let Err(e) = encryption::forget_key(&app.config().identifier) {
+        log::warn!("Failed to delete the cache encryption key: {}", e);
+    }
+    if let Err(e) = automation::forget_token(&app.config().identifier) {
+        log::warn!("Failed to delete the local API token: {}", e);
+    }
+    // WKWebView keeps its storage outside Hazel's folders, so it's cleared here
+    for (label, window) in app.webview_windows() {
+        if let Err(e) = window.clear_all_browsing_data() {
+            log::warn!("Failed to clear browsing data of {}: {}", label, e);
+        }
+    }
+
+    let _ = app.emit("data-wiped", ());
+    lifecycle::restart_app(app);
+    Ok(())
+}
+
+/// Finish a wipe started by `wipe_local_data`. Must run before the app is built, so
+/// nothing has the files open yet.
+pub fn prepare(context: &Context) {
+    let identifier = &context.config().identifier;
+    let Some(dirs) = settings::get_at_startup::<Vec<PathBuf>>(identifier, PENDING_KEY) else {
+        return;
+    };
+    let Some(store) = settings::path_at_startup(identifier) else {
+        return;
+    };
+
+    // Deleted again in case the app stopped before it got that far
+    let mut failures = Vec::new();
+    if let Err(e) = encryption::forget_key(identifier) {
+        failures.push(format!("the cache encryption key: {}", e));
+    }
+    if let Err(e) = automation::forget_token(identifier) {
+        failures.push(format!("the local API token: {}", e));
+    }
+    for dir in &dirs {
+        if let Err(e) = remove_contents(dir, &store) {
+            failures.push(format!("{}: {}", dir.display(), e));
+        }
+    }
+
+    // The marker goes last, so anything left is retried at the next launch
+    if failures.is_empty() {
+        let _ = fs::remove_file(&store);
+    } else {
+        let _ = INCOMPLETE.set(failures.join(", "));
+    }
+    for dir in &dirs {
+        let _ = fs::remove_dir(dir);
+    }
+}
+
+/// Report a wipe that couldn't finish at startup
+pub fn setup() {
+    if let Some(failures) = INCOMPLETE.get() {
+        log::warn!(
+            "Failed to finish wiping local data, will retry: {}",
+            failures
+        );
+    }
+}
+
+// Everything in `dir` except `keep`; what's already gone counts as deleted
+fn remove_contents(dir: &Path, keep: &Path) -> io::Result<()> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e),
+    };
+    for entry in entries {
+        let entry = entry?;
+        let path = entry.path();
+        if path == keep {
+            continue;
+        }
+        let removed = if keep.starts_with(&path) {
+            remove_contents(&path, keep)
+        } else if entry.file_type()?.is_dir() {
+            fs::remove_dir_all(&path)
+        } else {
+            fs::remove_file(&path)
+        };
+        match removed {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+            _ => {}
+        }
+    }
+    Ok(())
+}